@@ -0,0 +1,349 @@
+use flate2::read::MultiGzDecoder;
+use indicatif::ProgressBar;
+use sevenz_rust::{Archive, Password, SevenZReader};
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::atomic::AtomicBool,
+};
+use zip::ZipArchive;
+
+use crate::archive::extract::{copy_with_progress, write_entry, write_out};
+use crate::io::file::dest_path;
+use crate::io::sanitize::dest_parts;
+
+/// Archive formats resource bundles ship in, told apart by their first bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Gzip,
+    Zstd,
+    SevenZip,
+}
+
+const EXTENSIONS: [(&str, ArchiveFormat); 4] = [
+    (".zip", ArchiveFormat::Zip),
+    (".gz", ArchiveFormat::Gzip),
+    (".zst", ArchiveFormat::Zstd),
+    (".7z", ArchiveFormat::SevenZip),
+];
+
+const MAGIC: [(&[u8], ArchiveFormat); 5] = [
+    (b"PK\x03\x04", ArchiveFormat::Zip),
+    // An empty zip is only its end of central directory record.
+    (b"PK\x05\x06", ArchiveFormat::Zip),
+    (b"\x1f\x8b", ArchiveFormat::Gzip),
+    (b"\x28\xb5\x2f\xfd", ArchiveFormat::Zstd),
+    (b"7z\xbc\xaf\x27\x1c", ArchiveFormat::SevenZip),
+];
+
+impl ArchiveFormat {
+    /// The format `dest` names by its extension, if it is an archive at all.
+    pub fn from_name(dest: &str) -> Option<Self> {
+        let dest = dest.to_ascii_lowercase();
+        EXTENSIONS
+            .iter()
+            .find(|(extension, _)| dest.ends_with(extension))
+            .map(|(_, format)| *format)
+    }
+
+    /// The format of the file at `path` by its magic bytes, whatever its name.
+    pub fn detect(path: &Path) -> io::Result<Option<Self>> {
+        let mut header = Vec::with_capacity(6);
+        fs::File::open(path)?.take(6).read_to_end(&mut header)?;
+        Ok(MAGIC
+            .iter()
+            .find(|(magic, _)| header.starts_with(magic))
+            .map(|(_, format)| *format))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+            Self::SevenZip => "7z",
+        }
+    }
+
+    /// The decoder that unpacks this format, or `None` when this build has none.
+    pub fn decoder(self) -> Option<&'static dyn Decoder> {
+        match self {
+            Self::Zip => Some(&ZipDecoder),
+            Self::Gzip => Some(&GzipDecoder),
+            Self::Zstd => Some(&ZstdDecoder),
+            Self::SevenZip => Some(&SevenZipDecoder),
+        }
+    }
+}
+
+/// Unpacks one archive format. Adding a format means a new [`ArchiveFormat`]
+/// with its extension and magic bytes, and a decoder for it.
+pub trait Decoder: Send + Sync {
+    /// Bytes the archive unpacks to, for the progress bar.
+    fn unpacked_size(&self, path: &Path) -> io::Result<u64>;
+
+    /// Unpacks the archive at `path` below `target`, counting written bytes in `bar`.
+    fn extract(
+        &self,
+        path: &Path,
+        target: &Path,
+        bar: &ProgressBar,
+        should_stop: &AtomicBool,
+    ) -> io::Result<()>;
+}
+
+/// The decoder for the archive at `path`, chosen by its content.
+pub fn decoder_for(path: &Path) -> io::Result<&'static dyn Decoder> {
+    let Some(format) = ArchiveFormat::detect(path)? else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a zip, gzip, zstd or 7z archive",
+        ));
+    };
+    format.decoder().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} archives are not supported yet", format.name()),
+        )
+    })
+}
+
+pub struct ZipDecoder;
+
+impl Decoder for ZipDecoder {
+    fn unpacked_size(&self, path: &Path) -> io::Result<u64> {
+        let mut archive = ZipArchive::new(fs::File::open(path)?).map_err(io::Error::other)?;
+        let mut total = 0_u64;
+        for index in 0..archive.len() {
+            let entry = archive.by_index_raw(index).map_err(io::Error::other)?;
+            total = total.saturating_add(entry.size());
+        }
+        Ok(total)
+    }
+
+    fn extract(
+        &self,
+        path: &Path,
+        target: &Path,
+        bar: &ProgressBar,
+        should_stop: &AtomicBool,
+    ) -> io::Result<()> {
+        let mut archive = ZipArchive::new(fs::File::open(path)?).map_err(io::Error::other)?;
+        let mut buffer = vec![0_u8; 262_144];
+
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+            write_entry(&mut entry, target, &mut buffer, bar, should_stop)?;
+        }
+        Ok(())
+    }
+}
+
+/// A single gzip-compressed file, unpacked under its name without `.gz`.
+pub struct GzipDecoder;
+
+impl Decoder for GzipDecoder {
+    /// The size the gzip trailer records; it wraps at 4 GiB, which only
+    /// affects the progress bar.
+    fn unpacked_size(&self, path: &Path) -> io::Result<u64> {
+        let mut file = fs::File::open(path)?;
+        file.seek(SeekFrom::End(-4))?;
+        let mut size = [0_u8; 4];
+        file.read_exact(&mut size)?;
+        Ok(u64::from(u32::from_le_bytes(size)))
+    }
+
+    fn extract(
+        &self,
+        path: &Path,
+        target: &Path,
+        bar: &ProgressBar,
+        should_stop: &AtomicBool,
+    ) -> io::Result<()> {
+        let Some(name) = path.file_stem() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No file name in {}", path.display()),
+            ));
+        };
+        let mut reader = MultiGzDecoder::new(io::BufReader::new(fs::File::open(path)?));
+        let mut out = io::BufWriter::new(fs::File::create(target.join(name))?);
+        let mut buffer = vec![0_u8; 262_144];
+        copy_with_progress(&mut reader, &mut out, &mut buffer, bar, should_stop)
+    }
+}
+
+/// A single zstd-compressed file, unpacked under its name without `.zst`.
+pub struct ZstdDecoder;
+
+impl Decoder for ZstdDecoder {
+    /// The size the first frame header records. Frames written without one
+    /// count their compressed size, which only affects the progress bar.
+    fn unpacked_size(&self, path: &Path) -> io::Result<u64> {
+        let mut header = Vec::with_capacity(18);
+        fs::File::open(path)?.take(18).read_to_end(&mut header)?;
+        match zstd::zstd_safe::get_frame_content_size(&header) {
+            Ok(Some(size)) => Ok(size),
+            _ => Ok(fs::metadata(path)?.len()),
+        }
+    }
+
+    fn extract(
+        &self,
+        path: &Path,
+        target: &Path,
+        bar: &ProgressBar,
+        should_stop: &AtomicBool,
+    ) -> io::Result<()> {
+        let Some(name) = path.file_stem() else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("No file name in {}", path.display()),
+            ));
+        };
+        let mut reader = zstd::Decoder::new(fs::File::open(path)?)?;
+        let mut out = io::BufWriter::new(fs::File::create(target.join(name))?);
+        let mut buffer = vec![0_u8; 262_144];
+        copy_with_progress(&mut reader, &mut out, &mut buffer, bar, should_stop)
+    }
+}
+
+pub struct SevenZipDecoder;
+
+impl Decoder for SevenZipDecoder {
+    fn unpacked_size(&self, path: &Path) -> io::Result<u64> {
+        let archive = Archive::open(path).map_err(io::Error::other)?;
+        Ok(archive
+            .files
+            .iter()
+            .fold(0_u64, |total, entry| total.saturating_add(entry.size())))
+    }
+
+    fn extract(
+        &self,
+        path: &Path,
+        target: &Path,
+        bar: &ProgressBar,
+        should_stop: &AtomicBool,
+    ) -> io::Result<()> {
+        let mut reader = SevenZReader::open(path, Password::empty()).map_err(io::Error::other)?;
+        let mut buffer = vec![0_u8; 262_144];
+        // Kept out of the 7z error so a cancellation still reads as one.
+        let mut failure = None;
+        let decoded = reader.for_each_entries(|entry, data| {
+            let written = if dest_parts(entry.name()).any(|part| part == "..") {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Unsafe entry path in archive: {}", entry.name()),
+                ))
+            } else {
+                let out_path = dest_path(target, entry.name());
+                write_out(
+                    data,
+                    &out_path,
+                    entry.is_directory(),
+                    &mut buffer,
+                    bar,
+                    should_stop,
+                )
+            };
+            match written {
+                Ok(()) => Ok(true),
+                Err(e) => {
+                    failure = Some(e);
+                    Err(sevenz_rust::Error::other("entry failed"))
+                }
+            }
+        });
+        match (failure, decoded) {
+            (Some(e), _) => Err(e),
+            (None, decoded) => decoded.map_err(io::Error::other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveFormat, decoder_for};
+    use crate::io::file::unique_path;
+    use flate2::{Compression, write::GzEncoder};
+    use indicatif::ProgressBar;
+    use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+    use std::fs;
+    use std::io::Write;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn picks_the_decoder_by_magic_bytes() {
+        let dir = unique_path("decoder");
+        fs::create_dir_all(&dir).unwrap();
+
+        let gzip = dir.join("a.pak.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&b"abc".repeat(1000)).unwrap();
+        fs::write(&gzip, encoder.finish().unwrap()).unwrap();
+        assert_eq!(
+            ArchiveFormat::detect(&gzip).unwrap(),
+            Some(ArchiveFormat::Gzip)
+        );
+        let decoder = decoder_for(&gzip).unwrap();
+        assert_eq!(decoder.unpacked_size(&gzip).unwrap(), 3000);
+        decoder
+            .extract(&gzip, &dir, &ProgressBar::hidden(), &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!(fs::read(dir.join("a.pak")).unwrap(), b"abc".repeat(1000));
+
+        // Named like a zip, but a zstd frame: unpacked as zstd.
+        let zstd = dir.join("b.zip");
+        fs::write(&zstd, zstd::bulk::compress(&b"xyz".repeat(500), 3).unwrap()).unwrap();
+        let decoder = decoder_for(&zstd).unwrap();
+        assert_eq!(decoder.unpacked_size(&zstd).unwrap(), 1500);
+        decoder
+            .extract(&zstd, &dir, &ProgressBar::hidden(), &AtomicBool::new(false))
+            .unwrap();
+        assert_eq!(fs::read(dir.join("b")).unwrap(), b"xyz".repeat(500));
+        assert_eq!(
+            ArchiveFormat::from_name("Client/Paks/c.7Z"),
+            Some(ArchiveFormat::SevenZip)
+        );
+        assert_eq!(ArchiveFormat::from_name("Client/Paks/c.pak"), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn unpacks_7z_archives_with_their_folders() {
+        let dir = unique_path("7z");
+        fs::create_dir_all(&dir).unwrap();
+
+        let archive = dir.join("paks.7z");
+        let mut writer = SevenZWriter::create(&archive).unwrap();
+        for (name, contents) in [("Client/Paks/a.pak", &b"aaaa"[..]), ("b.txt", &b"bb"[..])] {
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = name.to_string();
+            entry.has_stream = true;
+            writer.push_archive_entry(entry, Some(contents)).unwrap();
+        }
+        writer.finish().unwrap();
+
+        assert_eq!(
+            ArchiveFormat::detect(&archive).unwrap(),
+            Some(ArchiveFormat::SevenZip)
+        );
+        let decoder = decoder_for(&archive).unwrap();
+        assert_eq!(decoder.unpacked_size(&archive).unwrap(), 6);
+        let target = dir.join("out");
+        decoder
+            .extract(
+                &archive,
+                &target,
+                &ProgressBar::hidden(),
+                &AtomicBool::new(false),
+            )
+            .unwrap();
+        assert_eq!(fs::read(target.join("Client/Paks/a.pak")).unwrap(), b"aaaa");
+        assert_eq!(fs::read(target.join("b.txt")).unwrap(), b"bb");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,260 @@
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use zip::read::ZipFile;
+
+use crate::archive::decoder::{ArchiveFormat, decoder_for};
+use crate::config::{status::Status, style};
+use crate::io::file::{dest_path, get_filename};
+use crate::io::i18n::Msg;
+use crate::io::logging::{SharedLogFile, log_error};
+use crate::sys;
+
+pub(crate) const EXTRACT_CANCELLATION_ERROR: &str = "Extraction cancelled";
+
+pub struct ExtractResult {
+    pub extracted: usize,
+    pub failed: usize,
+    pub removed: usize,
+}
+
+pub fn is_archive(dest: &str) -> bool {
+    ArchiveFormat::from_name(dest).is_some()
+}
+
+fn archive_uncompressed_size(path: &Path) -> io::Result<u64> {
+    decoder_for(path)?.unpacked_size(path)
+}
+
+fn extract_archive_sync(
+    path: &Path,
+    target: &Path,
+    bar: &ProgressBar,
+    should_stop: &AtomicBool,
+) -> io::Result<()> {
+    decoder_for(path)?.extract(path, target, bar, should_stop)
+}
+
+/// Writes one archive entry below `target`, refusing paths that leave it.
+pub(crate) fn write_entry<R: Read>(
+    entry: &mut ZipFile<'_, R>,
+    target: &Path,
+    buffer: &mut [u8],
+    bar: &ProgressBar,
+    should_stop: &AtomicBool,
+) -> io::Result<()> {
+    let Some(relative) = entry.enclosed_name() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Unsafe entry path in archive: {}", entry.name()),
+        ));
+    };
+    let out_path = dest_path(target, &relative.to_string_lossy());
+    let is_dir = entry.is_dir();
+    write_out(entry, &out_path, is_dir, buffer, bar, should_stop)
+}
+
+/// Writes `reader` to `out_path`, or only creates it when it is a directory.
+pub(crate) fn write_out<R: Read + ?Sized>(
+    reader: &mut R,
+    out_path: &Path,
+    is_dir: bool,
+    buffer: &mut [u8],
+    bar: &ProgressBar,
+    should_stop: &AtomicBool,
+) -> io::Result<()> {
+    if is_dir {
+        return fs::create_dir_all(out_path);
+    }
+
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut out = io::BufWriter::new(fs::File::create(out_path)?);
+    copy_with_progress(reader, &mut out, buffer, bar, should_stop)
+}
+
+/// Copies `reader` into `out`, counting bytes in `bar` and stopping with
+/// [`EXTRACT_CANCELLATION_ERROR`] once `should_stop` is set.
+pub(crate) fn copy_with_progress<R: Read + ?Sized, W: Write>(
+    reader: &mut R,
+    out: &mut W,
+    buffer: &mut [u8],
+    bar: &ProgressBar,
+    should_stop: &AtomicBool,
+) -> io::Result<()> {
+    loop {
+        if should_stop.load(Ordering::SeqCst) {
+            return Err(io::Error::other(EXTRACT_CANCELLATION_ERROR));
+        }
+
+        let read = match reader.read(buffer) {
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if read == 0 {
+            break;
+        }
+        sys::throttle_blocking(read);
+        out.write_all(&buffer[..read])?;
+        bar.inc(read as u64);
+    }
+    out.flush()
+}
+
+pub(crate) fn extract_bar(total_bytes: u64) -> ProgressBar {
+    let bar = ProgressBar::new(total_bytes);
+    bar.set_style(style::ticks(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [EXTRACT] [{wide_bar:.green/blue}] {bytes}/{total_bytes} ({eta}) {msg}")
+            .unwrap()
+            .progress_chars("#>-"),
+    ));
+    bar
+}
+
+/// Unpacks every downloaded archive in `dests` next to itself, so the manifest's
+/// directory layout is preserved. With `cleanup`, archives are removed after a
+/// successful extraction.
+pub async fn extract_archives(
+    folder: PathBuf,
+    dests: Vec<String>,
+    cleanup: bool,
+    log_file: SharedLogFile,
+    should_stop: Arc<AtomicBool>,
+) -> ExtractResult {
+    tokio::task::spawn_blocking(move || {
+        let archives: Vec<PathBuf> = dests
+            .iter()
+            .map(|dest| dest_path(&folder, dest))
+            .filter(|path| path.is_file())
+            .collect();
+
+        let total_bytes = archives
+            .iter()
+            .filter_map(|path| archive_uncompressed_size(path).ok())
+            .sum();
+
+        let bar = extract_bar(total_bytes);
+
+        let mut result = ExtractResult {
+            extracted: 0,
+            failed: 0,
+            removed: 0,
+        };
+
+        for path in &archives {
+            if should_stop.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let filename = get_filename(&path.to_string_lossy());
+            bar.set_message(format!("extracting {}", filename));
+            let target = path.parent().unwrap_or(&folder);
+
+            match extract_archive_sync(path, target, &bar, &should_stop) {
+                Ok(()) => {
+                    result.extracted += 1;
+                    if cleanup {
+                        match fs::remove_file(path) {
+                            Ok(()) => result.removed += 1,
+                            Err(e) => log_error(
+                                &log_file,
+                                &format!("Failed to remove archive {}: {}", path.display(), e),
+                            ),
+                        }
+                    }
+                }
+                Err(e) if e.to_string() == EXTRACT_CANCELLATION_ERROR => break,
+                Err(e) => {
+                    result.failed += 1;
+                    log_error(
+                        &log_file,
+                        &format!("Failed to extract {}: {}", path.display(), e),
+                    );
+                }
+            }
+        }
+
+        if should_stop.load(Ordering::SeqCst) {
+            bar.finish_with_message("extraction stopped");
+        } else {
+            bar.finish_with_message("extraction complete");
+        }
+
+        result
+    })
+    .await
+    .unwrap_or(ExtractResult {
+        extracted: 0,
+        failed: 0,
+        removed: 0,
+    })
+}
+
+pub fn print_extract_results(result: &ExtractResult) {
+    println!(
+        "{} {}",
+        Status::success(),
+        Msg::ArchivesExtracted.fill(&[&result.extracted.to_string().green()])
+    );
+    if result.failed > 0 {
+        println!(
+            "{} {}",
+            Status::error(),
+            Msg::ArchivesFailed.fill(&[&result.failed.to_string().red()])
+        );
+    }
+    if result.removed > 0 {
+        println!(
+            "{} {}",
+            Status::info(),
+            Msg::ArchivesRemoved.fill(&[&result.removed.to_string().cyan()])
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::extract_archive_sync;
+    use crate::io::file::unique_path;
+    use indicatif::ProgressBar;
+    use std::fs;
+    use std::io::Write;
+    use std::sync::atomic::AtomicBool;
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    #[test]
+    fn extract_archive_sync_preserves_nested_layout() {
+        let dir = unique_path("extract");
+        fs::create_dir_all(&dir).unwrap();
+        let archive_path = dir.join("pack.zip");
+
+        let mut writer = ZipWriter::new(fs::File::create(&archive_path).unwrap());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("Paks/a.pak", options).unwrap();
+        writer.write_all(b"abc").unwrap();
+        writer.finish().unwrap();
+
+        extract_archive_sync(
+            &archive_path,
+            &dir,
+            &ProgressBar::hidden(),
+            &AtomicBool::new(false),
+        )
+        .unwrap();
+
+        assert_eq!(fs::read(dir.join("Paks/a.pak")).unwrap(), b"abc");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,316 @@
+use indicatif::ProgressBar;
+use reqwest::Client;
+use std::{
+    io::{self, Read},
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use tokio::sync::mpsc;
+use zip::{read::read_zipfile_from_stream, result::ZipError};
+
+use crate::archive::extract::{
+    EXTRACT_CANCELLATION_ERROR, ExtractResult, extract_bar, write_entry,
+};
+use crate::config::cfg::{Config, ResourceItem};
+use crate::io::file::{Checksum, Hasher, dest_path, get_filename};
+use crate::io::logging::{SharedLogFile, log_error, log_warn};
+use crate::network::client::build_download_url;
+
+/// Downloaded chunks buffered between the HTTP body and the extractor.
+const STREAM_BUFFER_CHUNKS: usize = 16;
+
+enum StreamError {
+    /// The archive needs its central directory (e.g. entries with a data
+    /// descriptor), so it has to be downloaded first.
+    Unsupported(String),
+    Failed(String),
+    Interrupted,
+}
+
+/// `--stream-extract`: unpacks each archive in `archives` while it downloads,
+/// so it never lands on disk, trying the CDNs in order. The archive's hash is
+/// checked over the stream. Returns the archives that cannot be read as a
+/// stream, to be downloaded and extracted the usual way.
+pub async fn stream_extract_archives(
+    client: &Client,
+    config: &Config,
+    folder: &Path,
+    archives: Vec<ResourceItem>,
+    log_file: &SharedLogFile,
+    should_stop: &Arc<AtomicBool>,
+) -> (ExtractResult, Vec<ResourceItem>) {
+    let mut result = ExtractResult {
+        extracted: 0,
+        failed: 0,
+        removed: 0,
+    };
+    let mut unsupported = Vec::new();
+    let bar = extract_bar(archives.iter().filter_map(|item| item.size).sum());
+
+    'archives: for item in archives {
+        if should_stop.load(Ordering::SeqCst) {
+            break;
+        }
+        bar.set_message(format!("streaming {}", get_filename(&item.dest)));
+        let path = dest_path(folder, &item.dest);
+        let target = path.parent().unwrap_or(folder).to_path_buf();
+        let dest = item.dest.replace('\\', "/");
+        let mut last_error = "No CDN available".to_string();
+
+        for base_url in &config.zip_bases {
+            let url = build_download_url(base_url, &dest);
+            let position = bar.position();
+            match stream_archive(
+                client,
+                &url,
+                &target,
+                item.checksum.as_ref(),
+                &bar,
+                should_stop,
+            )
+            .await
+            {
+                Ok(()) => {
+                    result.extracted += 1;
+                    continue 'archives;
+                }
+                Err(StreamError::Interrupted) => break 'archives,
+                Err(StreamError::Unsupported(e)) => {
+                    log_warn(
+                        log_file,
+                        &format!(
+                            "{} cannot be extracted while streaming ({}), downloading it first",
+                            dest, e
+                        ),
+                    );
+                    bar.set_position(position);
+                    unsupported.push(item);
+                    continue 'archives;
+                }
+                Err(StreamError::Failed(e)) => {
+                    bar.set_position(position);
+                    last_error = format!("{}: {}", url, e);
+                }
+            }
+        }
+        result.failed += 1;
+        log_error(
+            log_file,
+            &format!("Failed to stream-extract {}: {}", dest, last_error),
+        );
+    }
+
+    if should_stop.load(Ordering::SeqCst) {
+        bar.finish_with_message("extraction stopped");
+    } else {
+        bar.finish_with_message("extraction complete");
+    }
+    (result, unsupported)
+}
+
+async fn stream_archive(
+    client: &Client,
+    url: &str,
+    target: &Path,
+    checksum: Option<&Checksum>,
+    bar: &ProgressBar,
+    should_stop: &Arc<AtomicBool>,
+) -> Result<(), StreamError> {
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| StreamError::Failed(e.to_string()))?;
+
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER_CHUNKS);
+    let extractor = {
+        let target = target.to_path_buf();
+        let hasher = checksum.map(|checksum| Hasher::new(checksum.algorithm));
+        let should_stop = should_stop.clone();
+        tokio::task::spawn_blocking(move || {
+            extract_stream(ChannelReader::new(receiver), hasher, &target, &should_stop)
+        })
+    };
+
+    loop {
+        let chunk = tokio::select! {
+            chunk = response.chunk() => chunk,
+            _ = wait_for_stop(should_stop) => break,
+        };
+        let chunk = match chunk {
+            Ok(Some(chunk)) => Ok(chunk.to_vec()),
+            Ok(None) => break,
+            Err(e) => Err(io::Error::other(e)),
+        };
+        let failed = chunk.is_err();
+        if let Ok(chunk) = &chunk {
+            bar.inc(chunk.len() as u64);
+        }
+        // A closed channel means the extractor already stopped with an error.
+        if sender.send(chunk).await.is_err() || failed {
+            break;
+        }
+    }
+    drop(sender);
+
+    let digest = extractor
+        .await
+        .map_err(|e| StreamError::Failed(e.to_string()))?;
+    if should_stop.load(Ordering::SeqCst) {
+        return Err(StreamError::Interrupted);
+    }
+    let digest = digest?;
+    match (checksum, digest) {
+        (Some(checksum), Some(digest)) if !digest.eq_ignore_ascii_case(&checksum.digest) => {
+            Err(StreamError::Failed(format!(
+                "checksum mismatch (expected {}, got {})",
+                checksum.digest, digest
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+async fn wait_for_stop(should_stop: &AtomicBool) {
+    while !should_stop.load(Ordering::SeqCst) {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
+
+/// Extracts every entry of the zip read from `reader`, then hashes what
+/// follows them (the central directory) so the digest covers the whole file.
+fn extract_stream(
+    reader: ChannelReader,
+    hasher: Option<Hasher>,
+    target: &Path,
+    should_stop: &AtomicBool,
+) -> Result<Option<String>, StreamError> {
+    let mut reader = HashingReader {
+        inner: reader,
+        hasher,
+    };
+    let mut buffer = vec![0_u8; 262_144];
+    let hidden = ProgressBar::hidden();
+    loop {
+        let entry = read_zipfile_from_stream(&mut reader);
+        let mut entry = match entry {
+            Ok(Some(entry)) => entry,
+            Ok(None) => break,
+            Err(ZipError::UnsupportedArchive(e)) => {
+                return Err(StreamError::Unsupported(e.to_string()));
+            }
+            Err(e) => return Err(StreamError::Failed(e.to_string())),
+        };
+        write_entry(&mut entry, target, &mut buffer, &hidden, should_stop).map_err(|e| {
+            if e.to_string() == EXTRACT_CANCELLATION_ERROR {
+                StreamError::Interrupted
+            } else {
+                StreamError::Failed(e.to_string())
+            }
+        })?;
+    }
+    if should_stop.load(Ordering::SeqCst) {
+        return Err(StreamError::Interrupted);
+    }
+    io::copy(&mut reader, &mut io::sink()).map_err(|e| StreamError::Failed(e.to_string()))?;
+    Ok(reader.hasher.map(Hasher::finalize_hex))
+}
+
+/// Blocking reader over the chunks the download task sends.
+struct ChannelReader {
+    receiver: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    offset: usize,
+}
+
+impl ChannelReader {
+    fn new(receiver: mpsc::Receiver<io::Result<Vec<u8>>>) -> Self {
+        Self {
+            receiver,
+            chunk: Vec::new(),
+            offset: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk?;
+                    self.offset = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let read = buf.len().min(self.chunk.len() - self.offset);
+        buf[..read].copy_from_slice(&self.chunk[self.offset..self.offset + read]);
+        self.offset += read;
+        Ok(read)
+    }
+}
+
+struct HashingReader<R> {
+    inner: R,
+    hasher: Option<Hasher>,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelReader, extract_stream};
+    use crate::io::file::{HashAlgorithm, Hasher, unique_path};
+    use std::io::Write;
+    use std::sync::atomic::AtomicBool;
+    use tokio::sync::mpsc;
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    #[tokio::test]
+    async fn extracts_and_hashes_a_zip_as_it_arrives() {
+        let mut writer = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        writer.start_file("Paks/a.pak", options).unwrap();
+        writer.write_all(&b"abc".repeat(1000)).unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        let mut expected = Hasher::new(HashAlgorithm::Md5);
+        expected.update(&archive);
+
+        let dir = unique_path("stream");
+        let (sender, receiver) = mpsc::channel(4);
+        let target = dir.clone();
+        let extractor = tokio::task::spawn_blocking(move || {
+            extract_stream(
+                ChannelReader::new(receiver),
+                Some(Hasher::new(HashAlgorithm::Md5)),
+                &target,
+                &AtomicBool::new(false),
+            )
+        });
+        for chunk in archive.chunks(7) {
+            sender.send(Ok(chunk.to_vec())).await.unwrap();
+        }
+        drop(sender);
+
+        let digest = extractor.await.unwrap().ok().unwrap();
+        assert_eq!(digest, Some(expected.finalize_hex()));
+        assert_eq!(
+            std::fs::read(dir.join("Paks/a.pak")).unwrap(),
+            b"abc".repeat(1000)
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,38 @@
+use colored::Colorize;
+use log::info;
+
+use crate::commands::Context;
+use crate::config::outcome::Outcome;
+use crate::io::{console::print_cdn_reports, logging::log_error};
+use crate::network::health::{SAMPLE_BYTES, check_cdns, sample_resource};
+
+pub async fn run(ctx: &Context) -> Outcome {
+    let config = ctx.config().await;
+    let resources = ctx.resources(&config).await;
+    let Some(sample) = sample_resource(&resources) else {
+        ctx.fail("Index lists no resources to test the CDNs with");
+    };
+
+    info!(
+        "Checking {} CDNs with {}...",
+        config.zip_bases.len().to_string().cyan(),
+        sample.dest.cyan()
+    );
+    let reports = check_cdns(&ctx.client, &config, sample, SAMPLE_BYTES).await;
+    for report in &reports {
+        if let Err(e) = &report.status {
+            log_error(
+                &ctx.log_file,
+                &format!("CDN {} unreachable: {}", report.base_url, e),
+            );
+        }
+    }
+    print_cdn_reports(&reports);
+
+    let healthy = reports.iter().filter(|report| report.is_healthy()).count();
+    match healthy {
+        0 => Outcome::Error,
+        n if n < reports.len() => Outcome::Partial,
+        _ => Outcome::Success,
+    }
+}
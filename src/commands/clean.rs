@@ -0,0 +1,280 @@
+use colored::Colorize;
+use indicatif::HumanBytes;
+use log::info;
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::commands::{Context, self_update::UPDATE_CHECK_CACHE};
+use crate::config::{cli::CleanArgs, outcome::Outcome, status::Status};
+use crate::download::report::{FAILED_REPORT, SESSION_REPORT};
+use crate::io::{
+    checksum_list::is_checksum_list,
+    hash_cache::HASH_CACHE,
+    i18n::Msg,
+    journal::WRITE_JOURNAL,
+    logging::{log_error, log_info},
+    manifest_archive::MANIFEST_DIR,
+    sanitize::sanitize_dest,
+    session_lock::SESSION_LOCK,
+    size_cache::SIZE_CACHE,
+    util::read_line,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Leftover {
+    /// A `.part` file from an unfinished download.
+    Partial,
+    /// A file the index does not list.
+    Orphan,
+}
+
+pub(crate) struct Entry {
+    pub(crate) path: PathBuf,
+    pub(crate) relative: String,
+    pub(crate) size: u64,
+}
+
+pub async fn run(ctx: &Context, args: &CleanArgs) -> Outcome {
+    let folder = ctx.folder(&args.folder);
+    let _lock = ctx.lock_folder(&folder);
+    let known = if args.orphans {
+        let config = ctx.index_config().await;
+        let resources = ctx.resources(&config).await;
+        Some(
+            resources
+                .iter()
+                .map(|item| sanitize_dest(&item.dest))
+                .collect::<HashSet<_>>(),
+        )
+    } else {
+        None
+    };
+
+    let files = match list_files(&folder) {
+        Ok(files) => files,
+        Err(e) => ctx.fail(&format!("Failed to read {}: {}", folder.display(), e)),
+    };
+    let leftovers: Vec<(Entry, Leftover)> = files
+        .into_iter()
+        .filter_map(|entry| classify(&entry.relative, known.as_ref()).map(|kind| (entry, kind)))
+        .collect();
+
+    if leftovers.is_empty() {
+        info!(
+            "Nothing to clean in {}",
+            folder.display().to_string().cyan()
+        );
+        return Outcome::Success;
+    }
+
+    for (entry, kind) in &leftovers {
+        let label = match kind {
+            Leftover::Partial => "partial",
+            Leftover::Orphan => "not in index",
+        };
+        println!(
+            "{} {} ({}, {})",
+            Status::warning(),
+            entry.relative,
+            label.yellow(),
+            HumanBytes(entry.size)
+        );
+    }
+
+    let bytes: u64 = leftovers.iter().map(|(entry, _)| entry.size).sum();
+    if args.dry_run {
+        println!(
+            "\n{} Would remove {} files ({})",
+            Status::info(),
+            leftovers.len().to_string().cyan(),
+            HumanBytes(bytes).to_string().cyan()
+        );
+        return Outcome::Success;
+    }
+
+    let orphans = leftovers
+        .iter()
+        .filter(|(_, kind)| *kind == Leftover::Orphan)
+        .count();
+    let orphan_bytes: u64 = leftovers
+        .iter()
+        .filter(|(_, kind)| *kind == Leftover::Orphan)
+        .map(|(entry, _)| entry.size)
+        .sum();
+    if orphans > 0 && !args.yes {
+        if ctx.cli.headless() {
+            info!(
+                "Keeping {} orphaned files, pass --yes to delete them",
+                orphans
+            );
+            return Outcome::Success;
+        }
+        if !confirm_orphans(orphans, orphan_bytes) {
+            info!("Clean cancelled");
+            return Outcome::Success;
+        }
+    }
+
+    let mut removed = 0;
+    let mut freed = 0;
+    for (entry, _) in &leftovers {
+        match fs::remove_file(&entry.path) {
+            Ok(()) => {
+                removed += 1;
+                freed += entry.size;
+                remove_empty_parents(&folder, &entry.path);
+            }
+            Err(e) => log_error(
+                &ctx.log_file,
+                &format!("Failed to remove {}: {}", entry.path.display(), e),
+            ),
+        }
+    }
+
+    log_info(
+        &ctx.log_file,
+        &format!("Cleaned {}: removed {} files", folder.display(), removed),
+    );
+    println!(
+        "\n{} Removed {} files ({})",
+        Status::success(),
+        removed.to_string().green(),
+        HumanBytes(freed).to_string().green()
+    );
+    if removed < leftovers.len() {
+        Outcome::Partial
+    } else {
+        Outcome::Success
+    }
+}
+
+fn confirm_orphans(count: usize, bytes: u64) -> bool {
+    print!(
+        "\n{} {}",
+        Status::question(),
+        Msg::ConfirmOrphans.fill(&[&count, &HumanBytes(bytes)])
+    );
+    let _ = io::stdout().flush();
+    read_line().is_ok_and(|input| input.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Decides whether a file (relative to the download folder, `/`-separated) is
+/// a leftover. Orphans are only reported when the index is known; the failure
+/// report, hash cache, write journal, update check cache, checksum lists and
+/// log files are never touched.
+fn classify(relative: &str, known: Option<&HashSet<String>>) -> Option<Leftover> {
+    if relative.ends_with(".part") {
+        return Some(Leftover::Partial);
+    }
+    if is_bookkeeping(relative) {
+        return None;
+    }
+    match known {
+        Some(known) if !known.contains(relative) => Some(Leftover::Orphan),
+        _ => None,
+    }
+}
+
+/// Whether `relative` is one of the files this tool keeps next to the game.
+pub(crate) fn is_bookkeeping(relative: &str) -> bool {
+    relative == FAILED_REPORT
+        || relative == SESSION_REPORT
+        || relative == HASH_CACHE
+        || relative == WRITE_JOURNAL
+        || relative == SESSION_LOCK
+        || relative == SIZE_CACHE
+        || relative == UPDATE_CHECK_CACHE
+        || is_checksum_list(relative)
+        || Path::new(relative).starts_with(MANIFEST_DIR)
+        || (!relative.contains('/') && relative.ends_with(".log"))
+}
+
+/// Removes the folders of an old version that deleting `path` left empty,
+/// stopping at the first one that still has content and never at `folder`.
+fn remove_empty_parents(folder: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == folder || !current.starts_with(folder) || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+/// Every regular file below `folder`; symlinked directories are not followed.
+pub(crate) fn list_files(folder: &Path) -> io::Result<Vec<Entry>> {
+    let mut files = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = fs::symlink_metadata(&path)?;
+            if metadata.is_dir() {
+                pending.push(path);
+            } else if metadata.is_file() {
+                let relative = path
+                    .strip_prefix(folder)
+                    .unwrap_or(&path)
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.push(Entry {
+                    path,
+                    relative,
+                    size: metadata.len(),
+                });
+            }
+        }
+    }
+
+    files.sort_by(|a, b| a.relative.cmp(&b.relative));
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Leftover, classify, remove_empty_parents};
+    use crate::io::file::unique_path;
+    use crate::io::sanitize::sanitize_dest;
+    use std::collections::HashSet;
+
+    #[test]
+    fn removes_only_the_folders_left_empty() {
+        let root = unique_path("clean");
+        std::fs::create_dir_all(root.join("Client/Old/Paks")).unwrap();
+        std::fs::write(root.join("Client/keep.pak"), b"x").unwrap();
+
+        remove_empty_parents(&root, &root.join("Client/Old/Paks/old.pak"));
+        assert!(!root.join("Client/Old").exists());
+        assert!(root.join("Client/keep.pak").is_file());
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn classify_finds_partials_and_orphans_but_keeps_reports() {
+        let known: HashSet<String> = [sanitize_dest("\\Client\\Paks\\main.pak")].into();
+
+        assert_eq!(
+            classify("Client/Paks/main.pak.part", None),
+            Some(Leftover::Partial)
+        );
+        assert_eq!(classify("Client/Paks/main.pak", Some(&known)), None);
+        assert_eq!(
+            classify("Client/Paks/old.pak", Some(&known)),
+            Some(Leftover::Orphan)
+        );
+        assert_eq!(classify("Client/Paks/old.pak", None), None);
+        assert_eq!(classify("failed.json", Some(&known)), None);
+        assert_eq!(classify(".hash-cache.json", Some(&known)), None);
+        assert_eq!(classify(".write-journal.json", Some(&known)), None);
+        assert_eq!(classify(".update-check.json", Some(&known)), None);
+        assert_eq!(classify("logs.1.log", Some(&known)), None);
+    }
+}
@@ -0,0 +1,196 @@
+use colored::Colorize;
+use indicatif::HumanBytes;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::commands::Context;
+use crate::config::{
+    cfg::{Config, ResourceItem},
+    cli::DiffArgs,
+    outcome::Outcome,
+    status::Status,
+};
+use crate::io::util::parse_resources;
+use crate::network::client::{fetch_index, load_index_file};
+
+pub async fn run(ctx: &Context, args: &DiffArgs) -> Outcome {
+    let old = load_resources(ctx, &args.old).await;
+    let new = load_resources(ctx, &args.new).await;
+    let diff = diff_indexes(&old, &new);
+
+    for item in &diff.added {
+        println!("{} {} ({})", "+".green(), item.dest, size(item.size));
+    }
+    for item in &diff.removed {
+        println!("{} {} ({})", "-".red(), item.dest, size(item.size));
+    }
+    for (old, new) in &diff.changed {
+        println!(
+            "{} {} ({} -> {}, {})",
+            "~".yellow(),
+            new.dest,
+            size(old.size),
+            size(new.size),
+            delta(old.size, new.size)
+        );
+    }
+
+    println!(
+        "\n{} {} added, {} removed, {} changed, {} unchanged",
+        Status::info(),
+        diff.added.len().to_string().green(),
+        diff.removed.len().to_string().red(),
+        diff.changed.len().to_string().yellow(),
+        diff.unchanged
+    );
+    println!(
+        "{} Bytes to update: {} (total size {} -> {})",
+        Status::info(),
+        HumanBytes(diff.update_bytes()).to_string().cyan(),
+        HumanBytes(total(&old)),
+        HumanBytes(total(&new))
+    );
+    Outcome::Success
+}
+
+/// Resources of the index at `source`, a file or an http(s):// URL.
+async fn load_resources(ctx: &Context, source: &str) -> Vec<ResourceItem> {
+    let data = if source.starts_with("http://") || source.starts_with("https://") {
+        let config = Config {
+            index_url: source.to_string(),
+            zip_bases: Vec::new(),
+        };
+        fetch_index(&ctx.client, &config, &ctx.log_file).await
+    } else {
+        load_index_file(Path::new(source), &ctx.log_file)
+    };
+    match data.and_then(|data: Value| parse_resources(&data)) {
+        Ok(resources) => resources,
+        Err(e) => ctx.fail(&format!("{}: {}", source, e)),
+    }
+}
+
+struct IndexDiff<'a> {
+    added: Vec<&'a ResourceItem>,
+    removed: Vec<&'a ResourceItem>,
+    /// Old and new entry of every file whose size or hash differs.
+    changed: Vec<(&'a ResourceItem, &'a ResourceItem)>,
+    unchanged: usize,
+}
+
+impl IndexDiff<'_> {
+    /// What an update from the old index downloads: every added and changed file.
+    fn update_bytes(&self) -> u64 {
+        self.added
+            .iter()
+            .copied()
+            .chain(self.changed.iter().map(|(_, new)| *new))
+            .filter_map(|item| item.size)
+            .sum()
+    }
+}
+
+/// Compares two indexes by path; a file changed if its size or its hash
+/// (when both sides have one of the same algorithm) differs.
+fn diff_indexes<'a>(old: &'a [ResourceItem], new: &'a [ResourceItem]) -> IndexDiff<'a> {
+    let key = |item: &ResourceItem| {
+        item.dest
+            .replace('\\', "/")
+            .trim_start_matches('/')
+            .to_string()
+    };
+    let old_by_dest: HashMap<String, &ResourceItem> =
+        old.iter().map(|item| (key(item), item)).collect();
+    let new_dests: HashSet<String> = new.iter().map(key).collect();
+
+    let mut diff = IndexDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        changed: Vec::new(),
+        unchanged: 0,
+    };
+    for item in new {
+        match old_by_dest.get(&key(item)) {
+            None => diff.added.push(item),
+            Some(previous) if is_changed(previous, item) => diff.changed.push((previous, item)),
+            Some(_) => diff.unchanged += 1,
+        }
+    }
+    diff.removed = old
+        .iter()
+        .filter(|item| !new_dests.contains(&key(item)))
+        .collect();
+    diff.added.sort_by(|a, b| a.dest.cmp(&b.dest));
+    diff.removed.sort_by(|a, b| a.dest.cmp(&b.dest));
+    diff.changed.sort_by(|a, b| a.1.dest.cmp(&b.1.dest));
+    diff
+}
+
+fn is_changed(old: &ResourceItem, new: &ResourceItem) -> bool {
+    let hash_changed = match (&old.checksum, &new.checksum) {
+        (Some(old), Some(new)) if old.algorithm == new.algorithm => old.digest != new.digest,
+        _ => false,
+    };
+    hash_changed || old.size != new.size
+}
+
+fn total(resources: &[ResourceItem]) -> u64 {
+    resources.iter().filter_map(|item| item.size).sum()
+}
+
+fn size(size: Option<u64>) -> String {
+    size.map_or_else(
+        || "size unknown".to_string(),
+        |size| HumanBytes(size).to_string(),
+    )
+}
+
+fn delta(old: Option<u64>, new: Option<u64>) -> String {
+    match (old, new) {
+        (Some(old), Some(new)) if new >= old => format!("+{}", HumanBytes(new - old)),
+        (Some(old), Some(new)) => format!("-{}", HumanBytes(old - new)),
+        _ => "size unknown".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_indexes;
+    use crate::config::cfg::ResourceItem;
+    use crate::io::file::{Checksum, HashAlgorithm};
+
+    fn item(dest: &str, digest: &str, size: u64) -> ResourceItem {
+        ResourceItem {
+            dest: dest.to_string(),
+            checksum: Some(Checksum::new(HashAlgorithm::Md5, digest)),
+            size: Some(size),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }
+    }
+
+    #[test]
+    fn sorts_files_into_added_removed_and_changed() {
+        let old = vec![
+            item("/Client/a.pak", "aa", 10),
+            item("Client/b.pak", "bb", 20),
+            item("Client/gone.pak", "cc", 5),
+        ];
+        let new = vec![
+            item("Client/a.pak", "AA", 10),
+            item("Client\\b.pak", "b2", 20),
+            item("Client/new.pak", "dd", 7),
+        ];
+
+        let diff = diff_indexes(&old, &new);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.added[0].dest, "Client/new.pak");
+        assert_eq!(diff.removed[0].dest, "Client/gone.pak");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].0.dest, "Client/b.pak");
+        assert_eq!(diff.update_bytes(), 27);
+    }
+}
@@ -0,0 +1,837 @@
+use chrono::Local;
+use colored::Colorize;
+use indicatif::HumanBytes;
+use log::{info, warn};
+use std::{
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::archive::{
+    decoder::ArchiveFormat,
+    extract::{ExtractResult, extract_archives, is_archive},
+    stream::stream_extract_archives,
+};
+use crate::commands::{Context, watch::wait_for_predownload};
+use crate::config::{
+    cfg::{Config, DownloadOptions, ResourceItem},
+    cli::{DownloadArgs, FilterArgs, TransferArgs},
+    outcome::Outcome,
+    status::Status,
+};
+use crate::download::{
+    control::DownloadControl,
+    dedupe::{Duplicates, split_duplicates},
+    events::{EventSink, ProgressFormat},
+    mirror::Mirror,
+    pipeline::{PipelineResult, run_pipeline},
+    report::{
+        FAILED_REPORT, SESSION_REPORT, SessionReport, load_failed_report, write_failed_report,
+        write_session_report, write_stats_csv,
+    },
+};
+use crate::io::{
+    checksum_list::write_checksum_lists,
+    console::{clear_screen, print_results},
+    file::{available_space, bytes_still_needed, dest_path},
+    i18n::Msg,
+    journal::WriteJournal,
+    keys::KeyListener,
+    logging::{SUCCESS, log_error, log_info, log_warn},
+    notify::{notify, session_message},
+    progress_log::ProgressLog,
+    prompt::{Prompter, TerminalPrompter},
+    reuse::ReuseSource,
+    size_cache::{SIZE_CACHE, SizeCache},
+    url_log::{URL_LOG, UrlLog},
+    util::{
+        ask_concurrency, check_disk_space, index_keys, merge_groups, parse_resource_groups,
+        parse_resources, setup_ctrlc,
+    },
+};
+use crate::network::{
+    cdn_history::CdnHistory, cdn_tracker::CdnTracker, client::probe_missing_sizes,
+    metrics::Metrics, url_map::UrlMap, web_ui::WebUi,
+};
+use crate::sys::{self, PowerAction};
+
+/// Seconds `--shutdown-on-complete` and `--hibernate-on-complete` wait for a Ctrl-C.
+const POWER_COUNTDOWN: u64 = 60;
+
+pub async fn run(ctx: &Context, args: &DownloadArgs) -> Outcome {
+    if args.list_groups {
+        return list_groups(ctx, &args.filter).await;
+    }
+    // Watching asks for the folder first so nothing is left to prompt for
+    // when the predownload shows up hours later.
+    let (config, folder) = if args.watch.watch {
+        let folder = ctx.folder(&args.folder);
+        let mut config = wait_for_predownload(ctx, &args.watch).await;
+        if !ctx.cli.source.base_url.is_empty() {
+            config.zip_bases = ctx.cli.source.base_url.clone();
+        } else {
+            ctx.cli
+                .source
+                .order_cdns(&mut config.zip_bases, &ctx.cli.log_dir);
+        }
+        (config, folder)
+    } else {
+        let config = ctx.config().await;
+        (config, ctx.folder(&args.folder))
+    };
+    let _lock = ctx.lock_folder(&folder);
+    let prompt = !args.watch.watch
+        && !ctx.cli.headless()
+        && args.concurrency.is_none()
+        && args.verify_jobs.is_none();
+    let mut options = if prompt {
+        match ask_concurrency(&TerminalPrompter) {
+            Ok(options) => options,
+            Err(e) => ctx.fail(&e),
+        }
+    } else {
+        DownloadOptions::default()
+    };
+    if let Some(concurrency) = args.concurrency {
+        options.download_concurrency = concurrency;
+    }
+    if let Some(jobs) = args.verify_jobs {
+        options.verify_concurrency = jobs;
+    }
+    let events = event_sink(ctx, &args.transfer);
+    apply_transfer_args(ctx, &args.transfer, &mut options);
+    options.skip_existing = args.skip_existing;
+
+    if !ctx.cli.headless() {
+        clear_screen();
+    }
+    print_options(&folder, &options);
+    if args.mirror {
+        return mirror(ctx, args, &config, &folder, options, events).await;
+    }
+
+    let resources = if args.retry_failed {
+        let report = match load_failed_report(&folder) {
+            Ok(report) => report,
+            Err(e) => ctx.fail(&e),
+        };
+        if report
+            .index_url
+            .as_deref()
+            .is_some_and(|url| url != config.index_url)
+        {
+            warn!("failed.json was written for a different game version");
+        }
+        info!(
+            "Retrying {} files from the previous session",
+            report.resources.len().to_string().cyan()
+        );
+        report.resources
+    } else {
+        ctx.session_resources(&config, &args.filter, &folder).await
+    };
+
+    let should_stop = Arc::new(AtomicBool::new(false));
+    setup_ctrlc(should_stop.clone(), &ctx.log_file);
+    let (resources, streamed) = if args.stream_extract {
+        stream_extract(ctx, &config, &folder, resources, &should_stop).await
+    } else {
+        (resources, None)
+    };
+
+    let archives: Vec<String> = if args.extract {
+        resources
+            .iter()
+            .filter(|item| is_archive(&item.dest))
+            .map(|item| item.dest.clone())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let result = transfer(
+        ctx,
+        &config,
+        &folder,
+        resources,
+        options,
+        &args.transfer,
+        args.extract.then_some(args.cleanup),
+        events,
+        should_stop.clone(),
+    )
+    .await;
+
+    let extract_result = if args.extract && !should_stop.load(Ordering::SeqCst) {
+        info!("{}", Msg::ExtractingArchives.text());
+        let mut extracted = extract_archives(
+            folder.clone(),
+            archives,
+            args.cleanup,
+            ctx.log_file.clone(),
+            should_stop.clone(),
+        )
+        .await;
+        if let Some(streamed) = &streamed {
+            extracted.extracted += streamed.extracted;
+            extracted.failed += streamed.failed;
+        }
+        Some(extracted)
+    } else {
+        streamed
+    };
+
+    finish(
+        ctx,
+        &folder,
+        &result,
+        extract_result.as_ref(),
+        &args.transfer,
+        &should_stop,
+    )
+    .await
+}
+
+/// `--stream-extract`: unpacks the archives not on disk yet while they
+/// download. Returns what is left for the usual transfer, including archives
+/// that cannot be read as a stream, and how the streamed ones went.
+async fn stream_extract(
+    ctx: &Context,
+    config: &Config,
+    folder: &Path,
+    resources: Vec<ResourceItem>,
+    should_stop: &Arc<AtomicBool>,
+) -> (Vec<ResourceItem>, Option<ExtractResult>) {
+    let (mut archives, mut rest): (Vec<_>, Vec<_>) = resources.into_iter().partition(|item| {
+        ArchiveFormat::from_name(&item.dest) == Some(ArchiveFormat::Zip)
+            && item.chunks.is_empty()
+            && !dest_path(folder, &item.dest).exists()
+    });
+    ctx.drop_unsafe_dests(folder, &mut archives);
+    if archives.is_empty() {
+        return (rest, None);
+    }
+    info!(
+        "Extracting {} archives while they download",
+        archives.len().to_string().cyan()
+    );
+    let (result, unsupported) = stream_extract_archives(
+        &ctx.client,
+        config,
+        folder,
+        archives,
+        &ctx.log_file,
+        should_stop,
+    )
+    .await;
+    rest.extend(unsupported);
+    (rest, Some(result))
+}
+
+/// `--mirror`: downloads what the mirror lacks through its staging folder,
+/// stores it by checksum and publishes the index once nothing is missing.
+async fn mirror(
+    ctx: &Context,
+    args: &DownloadArgs,
+    config: &Config,
+    folder: &Path,
+    options: DownloadOptions,
+    events: EventSink,
+) -> Outcome {
+    let index = ctx.index_data(config).await;
+    let resources = match parse_resource_groups(&index) {
+        Ok(groups) => merge_groups(groups),
+        Err(e) => ctx.fail(&e),
+    };
+    let unaddressable = resources
+        .iter()
+        .filter(|item| item.checksum.is_none())
+        .count();
+    if unaddressable > 0 {
+        warn!(
+            "Skipping {} resources without a checksum, which the mirror cannot store",
+            unaddressable.to_string().yellow()
+        );
+    }
+    let mirror = Mirror::new(folder);
+    let pending = mirror.pending(&resources);
+    info!(
+        "{}",
+        Msg::MirrorUpToDate.fill(&[&(resources.len() - unaddressable - pending.len())
+            .to_string()
+            .cyan()])
+    );
+
+    let staging = mirror.staging();
+    let should_stop = Arc::new(AtomicBool::new(false));
+    setup_ctrlc(should_stop.clone(), &ctx.log_file);
+    let result = transfer(
+        ctx,
+        config,
+        &staging,
+        pending.clone(),
+        options,
+        &args.transfer,
+        None,
+        events,
+        should_stop.clone(),
+    )
+    .await;
+    for error in mirror.store(&pending, &result) {
+        log_error(&ctx.log_file, &error);
+    }
+
+    if !should_stop.load(Ordering::SeqCst) {
+        match mirror.publish(&index, &resources) {
+            Ok(()) => info!(
+                "{}",
+                Msg::MirrorReady.fill(&[&folder.display().to_string().cyan()])
+            ),
+            Err(e) => log_warn(&ctx.log_file, &e),
+        }
+    }
+    finish(ctx, &staging, &result, None, &args.transfer, &should_stop).await
+}
+
+pub fn event_sink(ctx: &Context, args: &TransferArgs) -> EventSink {
+    match (args.progress_format, &args.progress_output) {
+        (ProgressFormat::Human, _) => EventSink::disabled(),
+        (ProgressFormat::Json, None) => EventSink::stdout(),
+        (ProgressFormat::Json, Some(path)) => match EventSink::file(path) {
+            Ok(sink) => sink,
+            Err(e) => ctx.fail(&format!(
+                "Failed to open progress output {}: {}",
+                path.display(),
+                e
+            )),
+        },
+    }
+}
+
+pub fn apply_transfer_args(ctx: &Context, args: &TransferArgs, options: &mut DownloadOptions) {
+    if let Some(path) = &args.progress_log {
+        match ProgressLog::open(path, args.progress_log_interval) {
+            Ok(progress_log) => options.progress_log = Some(progress_log),
+            Err(e) => ctx.fail(&format!(
+                "Failed to open progress log {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+    if let Some(port) = args.metrics_port {
+        match Metrics::serve(port) {
+            Ok(metrics) => options.metrics = Some(metrics),
+            Err(e) => ctx.fail(&format!("Failed to serve metrics on port {}: {}", port, e)),
+        }
+    }
+    if let Some(addr) = args.web_ui {
+        match WebUi::serve(addr) {
+            Ok(web_ui) => {
+                info!("Web UI at http://{}/", addr);
+                options.web_ui = Some(web_ui);
+            }
+            Err(e) => ctx.fail(&format!("Failed to serve the web UI on {}: {}", addr, e)),
+        }
+    }
+    if let Some(dir) = &args.reuse_from {
+        if !dir.is_dir() {
+            ctx.fail(&format!("--reuse-from {} is not a folder", dir.display()));
+        }
+        options.reuse_from = Some(ReuseSource {
+            dir: dir.clone(),
+            hardlink: args.reuse_hardlink,
+        });
+    }
+    options.retry = args.retry_policy();
+    options.cdns = CdnTracker::new(args.cdn_cooldown, &ctx.log_file);
+    if let Some(path) = &args.url_map {
+        options.url_map = match UrlMap::load(path) {
+            Ok(url_map) => url_map,
+            Err(e) => ctx.fail(&e),
+        };
+        info!(
+            "Using {} URL overrides from {}",
+            options.url_map.len().to_string().cyan(),
+            path.display()
+        );
+    }
+    if args.log_urls {
+        options.url_log = match UrlLog::open(&ctx.cli.log_dir) {
+            Ok(url_log) => url_log,
+            Err(e) => ctx.fail(&format!(
+                "Failed to open {}: {}",
+                ctx.cli.log_dir.join(URL_LOG).display(),
+                e
+            )),
+        };
+    }
+    options.preallocate = !args.no_preallocate;
+    options.order = args.order;
+    options.delete_corrupt = args.delete_corrupt;
+    options.verify_partials = args.verify_partials.then_some(args.verify_partials_tail);
+    options.on_file_complete = args.on_file_complete.clone();
+    options.webhook = args.webhook();
+    options.backend = args.backend();
+    options.show_progress = !ctx.cli.quiet && !ctx.cli.headless() && !args.json_to_stdout();
+}
+
+pub fn print_options(folder: &Path, options: &DownloadOptions) {
+    info!(
+        "{}",
+        Msg::DownloadFolder.fill(&[&folder.display().to_string().cyan()])
+    );
+    info!(
+        "{}",
+        Msg::DownloadConcurrency.fill(&[&options.download_concurrency.to_string().cyan()])
+    );
+    info!(
+        "{}",
+        Msg::VerifyConcurrency.fill(&[&options.verify_concurrency.to_string().cyan()])
+    );
+}
+
+/// Verifies and downloads `resources` into `folder`, then records failures in
+/// `failed.json`, every file's outcome in `session-report.json` and the
+/// session summary in the log. `extract` is `Some(cleanup)` when archives are
+/// unpacked afterwards, which the space check then accounts for.
+#[allow(clippy::too_many_arguments)]
+pub async fn transfer(
+    ctx: &Context,
+    config: &Config,
+    folder: &Path,
+    mut resources: Vec<ResourceItem>,
+    mut options: DownloadOptions,
+    args: &TransferArgs,
+    extract: Option<bool>,
+    events: EventSink,
+    should_stop: Arc<AtomicBool>,
+) -> PipelineResult {
+    ctx.drop_unsafe_dests(folder, &mut resources);
+    options.hash_cache = ctx.hash_cache(folder);
+    options.journal = WriteJournal::open(folder, args.fsync_interval);
+
+    let missing_sizes = resources.iter().filter(|item| item.size.is_none()).count();
+    if missing_sizes > 0 {
+        info!(
+            "Probing sizes for {} files missing from the index...",
+            missing_sizes.to_string().cyan()
+        );
+        let mut sizes = SizeCache::load(folder, &config.index_url);
+        let resolved = probe_missing_sizes(
+            &ctx.client,
+            config,
+            &mut resources,
+            &mut sizes,
+            &options.cdns,
+            ctx.cli.http.head_timeout,
+            &ctx.log_file,
+        )
+        .await;
+        if let Err(e) = sizes.save() {
+            log_warn(
+                &ctx.log_file,
+                &format!("Failed to save {}: {}", SIZE_CACHE, e),
+            );
+        }
+        if resolved < missing_sizes {
+            warn!(
+                "Could not determine size of {} files",
+                (missing_sizes - resolved).to_string().yellow()
+            );
+        }
+    }
+
+    info!(
+        "{}",
+        Msg::FilesToDownload.fill(&[&resources.len().to_string().cyan()])
+    );
+    log_info(
+        &ctx.log_file,
+        &format!(
+            "Downloading {} files to {}",
+            resources.len(),
+            folder.display()
+        ),
+    );
+
+    let download_bytes: u64 = resources.iter().filter_map(|item| item.size).sum();
+    let fetch_bytes = bytes_still_needed(folder, &resources).await;
+    info!(
+        "{}",
+        Msg::DownloadSize.fill(&[
+            &HumanBytes(download_bytes).to_string().cyan(),
+            &HumanBytes(fetch_bytes).to_string().cyan()
+        ])
+    );
+    let mut required = fetch_bytes;
+    if let Some(cleanup) = extract {
+        let archives = resources.iter().filter(|item| is_archive(&item.dest));
+        let unpacked: u64 = archives.clone().filter_map(|item| item.unpacked_size).sum();
+        required += unpacked;
+        if archives.clone().all(|item| item.unpacked_size.is_some()) && unpacked > 0 {
+            let removed: u64 = if cleanup {
+                archives.filter_map(|item| item.size).sum()
+            } else {
+                0
+            };
+            let on_disk = (download_bytes + unpacked).saturating_sub(removed);
+            info!(
+                "{}",
+                Msg::SizeAfterExtraction.fill(&[&HumanBytes(on_disk).to_string().cyan()])
+            );
+        }
+    }
+    if let Ok(available) = available_space(folder) {
+        info!(
+            "{}",
+            Msg::FreeSpace.fill(&[&HumanBytes(available).to_string().cyan()])
+        );
+    }
+    // JSON events on stdout leave no room for a prompt.
+    let prompter: Option<&dyn Prompter> =
+        (!ctx.cli.headless() && !ctx.cli.json_to_stdout()).then_some(&TerminalPrompter);
+    match check_disk_space(prompter, folder, required, args.space_margin) {
+        Ok(true) => {}
+        Ok(false) => ctx.fail(Msg::SpaceCancelled.text()),
+        Err(e) => ctx.fail(&e),
+    }
+
+    let listed = (!args.write_checksums.is_empty()).then(|| resources.clone());
+    let (resources, duplicates) = if args.no_dedupe {
+        (resources, Duplicates::default())
+    } else {
+        split_duplicates(resources)
+    };
+    if !duplicates.is_empty() {
+        info!(
+            "{}",
+            Msg::DuplicatesCopied.fill(&[&duplicates.len().to_string().cyan()])
+        );
+    }
+
+    let control = DownloadControl::default();
+    let key_listener = if options.show_progress {
+        KeyListener::spawn(control.clone(), should_stop.clone())
+    } else {
+        None
+    };
+    if key_listener.is_some() {
+        info!("{}", Msg::KeysHint.text());
+    }
+
+    let started = Local::now();
+    let mut result = run_pipeline(
+        Arc::new(ctx.client.clone()),
+        Arc::new(config.clone()),
+        resources,
+        folder.to_path_buf(),
+        ctx.log_file.clone(),
+        should_stop.clone(),
+        options,
+        events.clone(),
+        control,
+    )
+    .await;
+
+    if let Some(listener) = key_listener {
+        listener.stop();
+    }
+    duplicates
+        .copy_into(folder, &mut result, &ctx.log_file)
+        .await;
+
+    let interrupted = should_stop.load(Ordering::SeqCst);
+    events.summary(&result, interrupted);
+    if let Err(e) = write_failed_report(
+        folder,
+        &config.index_url,
+        &result.failures,
+        &result.unfinished,
+    ) {
+        log_error(
+            &ctx.log_file,
+            &format!("Failed to write {}: {}", FAILED_REPORT, e),
+        );
+    }
+    let report = SessionReport {
+        index_url: &config.index_url,
+        started,
+        finished: Local::now(),
+        interrupted,
+        result: &result,
+    };
+    if let Err(e) = write_session_report(folder, &report) {
+        log_error(
+            &ctx.log_file,
+            &format!("Failed to write {}: {}", SESSION_REPORT, e),
+        );
+    }
+    if let Some(path) = &args.stats_csv {
+        match write_stats_csv(path, &result) {
+            Ok(()) => info!(target: SUCCESS, "Wrote {}", path.display().to_string().cyan()),
+            Err(e) => log_error(
+                &ctx.log_file,
+                &format!("Failed to write {}: {}", path.display(), e),
+            ),
+        }
+    }
+    if let Some(listed) = listed
+        && !interrupted
+    {
+        let algorithms: Vec<_> = args
+            .write_checksums
+            .iter()
+            .map(|format| format.algorithm())
+            .collect();
+        match write_checksum_lists(
+            folder,
+            &listed,
+            &result.completed,
+            &algorithms,
+            &should_stop,
+        )
+        .await
+        {
+            Ok(written) => {
+                for path in written {
+                    info!(target: SUCCESS, "Wrote {}", path.display().to_string().cyan());
+                }
+            }
+            Err(e) => log_error(
+                &ctx.log_file,
+                &format!("Failed to write checksum lists: {}", e),
+            ),
+        }
+    }
+    log_info(
+        &ctx.log_file,
+        &format!(
+            "Session finished{}: {} verified, {} downloaded ({} hashed after download), {} failed of {} files",
+            if interrupted { " (interrupted)" } else { "" },
+            result.verified_ok,
+            result.downloaded_ok,
+            result.hashed_after_download,
+            result.failed,
+            result.total
+        ),
+    );
+
+    result
+}
+
+/// Runs the completion hook, then shows the results screen and reports how
+/// the session ended.
+pub async fn finish(
+    ctx: &Context,
+    folder: &Path,
+    result: &PipelineResult,
+    extract_result: Option<&ExtractResult>,
+    args: &TransferArgs,
+    should_stop: &AtomicBool,
+) -> Outcome {
+    let interrupted = should_stop.load(Ordering::SeqCst);
+    let outcome = if interrupted {
+        Outcome::Interrupted
+    } else if result.failed > 0 || extract_result.is_some_and(|extract| extract.failed > 0) {
+        Outcome::Partial
+    } else {
+        Outcome::Success
+    };
+    if !result.cdns.is_empty() {
+        let mut history = CdnHistory::load(&ctx.cli.log_dir);
+        history.record(&result.cdns);
+        if let Err(e) = history.save(&ctx.cli.log_dir) {
+            log_warn(
+                &ctx.log_file,
+                &format!("Could not save the CDN history: {}", e),
+            );
+        }
+    }
+    if let Some(hook) = &args.on_complete
+        && !interrupted
+    {
+        let folder = folder.display().to_string();
+        let success = (result.verified_ok + result.downloaded_ok).to_string();
+        let failed = result.failed.to_string();
+        let total = result.total.to_string();
+        hook.run(
+            &[
+                ("folder", &folder),
+                ("success", &success),
+                ("failed", &failed),
+                ("total", &total),
+            ],
+            &ctx.log_file,
+        )
+        .await;
+    }
+    if args.notify || args.webhook_url.is_some() {
+        let (title, body) = session_message(result, interrupted);
+        if args.notify {
+            notify(title, &body);
+        }
+        if let Some(webhook) = args.webhook() {
+            webhook
+                .post(&ctx.client, &format!("{}: {}", title, body), &ctx.log_file)
+                .await;
+        }
+    }
+    let power_action = args.power_action().filter(|_| outcome == Outcome::Success);
+    if !args.json_to_stdout() {
+        print_session(
+            ctx,
+            folder,
+            result,
+            extract_result,
+            interrupted,
+            power_action,
+        )
+        .await;
+    }
+    if let Some(action) = power_action {
+        power_down(ctx, action, should_stop).await;
+    }
+    outcome
+}
+
+/// Whether the results wait for Enter. A pending shutdown or hibernate must
+/// not, or an unattended run would never reach its countdown.
+fn waits_for_enter(headless: bool, power_action: Option<PowerAction>) -> bool {
+    !headless && power_action.is_none()
+}
+
+/// `--list-groups`: every top-level key of the index and what the parser
+/// takes from it, for file counts that differ from the launcher's.
+async fn list_groups(ctx: &Context, filter: &FilterArgs) -> Outcome {
+    let config = ctx.index_config().await;
+    let data = ctx.index_data(&config).await;
+    let keys = index_keys(&data);
+    if keys.is_empty() {
+        ctx.fail("The index is not a JSON object");
+    }
+
+    println!("{} Top-level keys of {}", Status::info(), config.index_url);
+    for key in &keys {
+        let detail = match key.kind {
+            "array" if key.files == 0 => {
+                format!("{} entries, none with a dest: not a file list", key.entries)
+            }
+            "array" if key.files < key.entries => format!(
+                "{} files, {} entries without a dest skipped",
+                key.files,
+                key.entries - key.files
+            )
+            .yellow()
+            .to_string(),
+            "array" => format!("{} files", key.files).green().to_string(),
+            "object" => format!("{} keys: not a file list", key.entries),
+            _ => "not a file list".to_string(),
+        };
+        let left_out =
+            key.files > 0 && !filter.groups.is_empty() && !filter.groups.contains(&key.name);
+        println!(
+            "    {:<24} {:<7} {}{}",
+            key.name,
+            key.kind,
+            detail,
+            if left_out {
+                " (left out by --group)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    let listed: usize = keys.iter().map(|key| key.files).sum();
+    let unique = parse_resources(&data).map_or(0, |resources| resources.len());
+    println!(
+        "{} {} files listed across all groups, {} different ones",
+        Status::info(),
+        listed.to_string().cyan(),
+        unique.to_string().cyan()
+    );
+    Outcome::Success
+}
+
+async fn print_session(
+    ctx: &Context,
+    folder: &Path,
+    result: &PipelineResult,
+    extract_result: Option<&ExtractResult>,
+    interrupted: bool,
+    power_action: Option<PowerAction>,
+) {
+    let remaining_bytes = if interrupted {
+        let pending: Vec<_> = result
+            .failures
+            .iter()
+            .map(|failure| failure.item.clone())
+            .chain(result.unfinished.iter().cloned())
+            .collect();
+        Some(bytes_still_needed(folder, &pending).await)
+    } else {
+        None
+    };
+
+    #[cfg(windows)]
+    if !ctx.cli.headless() {
+        let _ = winconsole::console::clear();
+    }
+
+    print_results(
+        result,
+        folder,
+        extract_result,
+        remaining_bytes,
+        waits_for_enter(ctx.cli.headless(), power_action),
+    );
+}
+
+/// Counts down for [`POWER_COUNTDOWN`] seconds, which Ctrl-C cancels, then
+/// shuts down or hibernates the machine.
+async fn power_down(ctx: &Context, action: PowerAction, should_stop: &AtomicBool) {
+    for left in (1..=POWER_COUNTDOWN).rev() {
+        if should_stop.load(Ordering::SeqCst) {
+            info!("{} cancelled", action.label());
+            return;
+        }
+        if left % 10 == 0 || left <= 5 {
+            warn!(
+                "{} in {} s, press Ctrl-C to cancel",
+                action.label(),
+                left.to_string().yellow()
+            );
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    if should_stop.load(Ordering::SeqCst) {
+        info!("{} cancelled", action.label());
+        return;
+    }
+    log_info(
+        &ctx.log_file,
+        &format!("{} after a complete download", action.label()),
+    );
+    if let Err(e) = sys::power_down(action) {
+        log_error(&ctx.log_file, &e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::waits_for_enter;
+    use crate::sys::PowerAction;
+
+    #[test]
+    fn a_pending_power_action_skips_the_enter_prompt() {
+        assert!(waits_for_enter(false, None));
+        assert!(!waits_for_enter(false, Some(PowerAction::Shutdown)));
+        assert!(!waits_for_enter(false, Some(PowerAction::Hibernate)));
+        assert!(!waits_for_enter(true, None));
+    }
+}
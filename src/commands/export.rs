@@ -0,0 +1,288 @@
+use colored::Colorize;
+use log::info;
+use serde_json::{Value, json};
+use std::fs;
+
+use crate::commands::Context;
+use crate::config::cfg::{Config, ResourceItem};
+use crate::config::{
+    cli::{ExportArgs, ExportFormat},
+    outcome::Outcome,
+};
+use crate::io::logging::log_warn;
+use crate::io::util::{parse_resource_groups, resource_to_json};
+use crate::network::client::{
+    build_download_url, channel_config, config_version, fetch_index, fetch_versions,
+};
+
+pub async fn run(ctx: &Context, args: &ExportArgs) -> Outcome {
+    if args.all_regions {
+        return export_all_regions(ctx, args).await;
+    }
+
+    let config = match args.format {
+        ExportFormat::Json => ctx.index_config().await,
+        ExportFormat::Urls | ExportFormat::Metalink | ExportFormat::Aria2 => ctx.config().await,
+    };
+    let resources = ctx.selected_resources(&config, &args.filter).await;
+
+    let contents = match render(args.format, &resources, &config) {
+        Ok(contents) => contents,
+        Err(e) => ctx.fail(&e),
+    };
+
+    match &args.output {
+        Some(path) => {
+            if let Err(e) = fs::write(path, contents) {
+                ctx.fail(&format!("Failed to write {}: {}", path.display(), e));
+            }
+            info!(
+                "Exported {} resources to {}",
+                resources.len().to_string().cyan(),
+                path.display().to_string().cyan()
+            );
+        }
+        None => print!("{}", contents),
+    }
+    Outcome::Success
+}
+
+/// `--all-regions`: one export per channel of the version list, written to
+/// the `--output` folder. A channel that fails is reported and skipped.
+async fn export_all_regions(ctx: &Context, args: &ExportArgs) -> Outcome {
+    let Some(folder) = &args.output else {
+        ctx.fail("--all-regions needs --output <FOLDER>");
+    };
+    if let Err(e) = fs::create_dir_all(folder) {
+        ctx.fail(&format!("Failed to create {}: {}", folder.display(), e));
+    }
+    let entries = match fetch_versions(&ctx.client, ctx.cli.source.source_url.as_deref()).await {
+        Ok(entries) => entries,
+        Err(e) => ctx.fail(&e),
+    };
+
+    let selected = ctx.cli.source.selected_config();
+    let mut outcome = Outcome::Success;
+    for entry in entries {
+        let label = entry.label();
+        let Some(version_json) = &entry.json else {
+            log_warn(
+                &ctx.log_file,
+                &format!("{}: version file unavailable", label),
+            );
+            outcome = Outcome::Partial;
+            continue;
+        };
+        info!("Exporting {}...", label.cyan());
+        let version = config_version(version_json, selected);
+        let exported = async {
+            let config = channel_config(version_json, selected)?;
+            let index = fetch_index(&ctx.client, &config, &ctx.log_file).await?;
+            let resources = ctx.select_groups(parse_resource_groups(&index)?, &args.filter);
+            let contents = render(args.format, &resources, &config)?;
+            let path = folder.join(export_file_name(
+                entry.category,
+                entry.region,
+                version,
+                args.format,
+            ));
+            fs::write(&path, contents)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            Ok::<_, String>((resources.len(), path))
+        }
+        .await;
+        match exported {
+            Ok((count, path)) => info!(
+                "Exported {} resources to {}",
+                count.to_string().cyan(),
+                path.display().to_string().cyan()
+            ),
+            Err(e) => {
+                log_warn(&ctx.log_file, &format!("{}: {}", label, e));
+                outcome = Outcome::Partial;
+            }
+        }
+    }
+    outcome
+}
+
+/// Contents of the export of `resources` in `format`.
+fn render(
+    format: ExportFormat,
+    resources: &[ResourceItem],
+    config: &Config,
+) -> Result<String, String> {
+    if format != ExportFormat::Json && config.zip_bases.is_empty() {
+        return Err("No CDN available to build download URLs".to_string());
+    }
+    Ok(match format {
+        ExportFormat::Json => {
+            let index = json!({
+                "resource": resources
+                    .iter()
+                    .map(|item| Value::Object(resource_to_json(item)))
+                    .collect::<Vec<_>>(),
+            });
+            serde_json::to_string_pretty(&index)
+                .map_err(|e| format!("Failed to serialize index: {}", e))?
+                + "\n"
+        }
+        ExportFormat::Urls => resources
+            .iter()
+            .map(|item| build_download_url(&config.zip_bases[0], &item.dest) + "\n")
+            .collect(),
+        ExportFormat::Metalink => metalink(resources, &config.zip_bases),
+        ExportFormat::Aria2 => aria2_input(resources, &config.zip_bases),
+    })
+}
+
+/// File `--all-regions` writes one channel's export to, e.g. `live-os-2.5.0.json`.
+fn export_file_name(category: &str, region: &str, version: &str, format: ExportFormat) -> String {
+    let version: String = version
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let extension = match format {
+        ExportFormat::Json => "json",
+        ExportFormat::Urls => "txt",
+        ExportFormat::Metalink => "meta4",
+        ExportFormat::Aria2 => "aria2",
+    };
+    format!("{}-{}-{}.{}", category, region, version, extension)
+}
+
+fn mirror_urls<'a>(
+    item: &'a ResourceItem,
+    bases: &'a [String],
+) -> impl Iterator<Item = String> + 'a {
+    bases
+        .iter()
+        .map(move |base_url| build_download_url(base_url, &item.dest))
+}
+
+/// Hash an external tool can check, if the index has one it knows.
+fn portable_checksum(item: &ResourceItem) -> Option<(&'static str, &str)> {
+    let checksum = item.checksum.as_ref()?;
+    Some((checksum.algorithm.iana_name()?, &checksum.digest))
+}
+
+/// Metalink 4 (RFC 5854) document with every CDN as a mirror, in order of preference.
+fn metalink(resources: &[ResourceItem], bases: &[String]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<metalink xmlns=\"urn:ietf:params:xml:ns:metalink\">\n",
+    );
+    for item in resources {
+        let name = item.dest.replace('\\', "/");
+        let name = name.trim_start_matches('/');
+        out += &format!("  <file name=\"{}\">\n", xml_escape(name));
+        if let Some(size) = item.size {
+            out += &format!("    <size>{}</size>\n", size);
+        }
+        if let Some((algorithm, digest)) = portable_checksum(item) {
+            out += &format!("    <hash type=\"{}\">{}</hash>\n", algorithm, digest);
+        }
+        for (priority, url) in mirror_urls(item, bases).enumerate() {
+            out += &format!(
+                "    <url priority=\"{}\">{}</url>\n",
+                priority + 1,
+                xml_escape(&url)
+            );
+        }
+        out += "  </file>\n";
+    }
+    out + "</metalink>\n"
+}
+
+/// aria2c input file: tab-separated mirrors for one file, then its options.
+fn aria2_input(resources: &[ResourceItem], bases: &[String]) -> String {
+    let mut out = String::new();
+    for item in resources {
+        let name = item.dest.replace('\\', "/");
+        out += &mirror_urls(item, bases).collect::<Vec<_>>().join("\t");
+        out += &format!("\n  out={}\n", name.trim_start_matches('/'));
+        if let Some((algorithm, digest)) = portable_checksum(item) {
+            out += &format!("  checksum={}={}\n", algorithm, digest);
+        }
+    }
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aria2_input, export_file_name, metalink};
+    use crate::config::cfg::ResourceItem;
+    use crate::config::cli::ExportFormat;
+    use crate::io::file::{Checksum, HashAlgorithm};
+
+    fn resources() -> Vec<ResourceItem> {
+        vec![
+            ResourceItem {
+                dest: "/Client/Paks/a&b.pak".to_string(),
+                checksum: Some(Checksum::new(HashAlgorithm::Md5, "ABC")),
+                size: Some(42),
+                chunks: Vec::new(),
+                unpacked_size: None,
+            },
+            ResourceItem {
+                dest: "Client\\game.exe".to_string(),
+                checksum: Some(Checksum::new(HashAlgorithm::Xxh3, "DEF")),
+                size: None,
+                chunks: Vec::new(),
+                unpacked_size: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn exports_list_every_mirror_with_portable_hashes() {
+        let bases = vec![
+            "https://cdn1/zip/".to_string(),
+            "https://cdn2/zip/".to_string(),
+        ];
+
+        let meta4 = metalink(&resources(), &bases);
+        assert!(meta4.contains("<file name=\"Client/Paks/a&amp;b.pak\">"));
+        assert!(meta4.contains("<size>42</size>"));
+        assert!(meta4.contains("<hash type=\"md5\">abc</hash>"));
+        assert!(
+            meta4.contains("<url priority=\"2\">https://cdn2/zip/Client/Paks/a&amp;b.pak</url>")
+        );
+        assert!(!meta4.contains("def"));
+
+        let aria2 = aria2_input(&resources(), &bases);
+        let lines: Vec<&str> = aria2.lines().collect();
+        assert_eq!(
+            lines[0],
+            "https://cdn1/zip/Client/Paks/a&b.pak\thttps://cdn2/zip/Client/Paks/a&b.pak"
+        );
+        assert_eq!(lines[1], "  out=Client/Paks/a&b.pak");
+        assert_eq!(lines[2], "  checksum=md5=abc");
+        assert_eq!(lines[4], "  out=Client/game.exe");
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn names_region_exports_by_channel_and_version() {
+        assert_eq!(
+            export_file_name("live", "os", "2.5.0", ExportFormat::Json),
+            "live-os-2.5.0.json"
+        );
+        assert_eq!(
+            export_file_name("beta", "cn", "2.6/rc 1", ExportFormat::Metalink),
+            "beta-cn-2.6_rc_1.meta4"
+        );
+    }
+}
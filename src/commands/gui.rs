@@ -0,0 +1,318 @@
+use async_channel::Receiver;
+use eframe::egui;
+use indicatif::HumanBytes;
+use std::{cell::Cell, path::PathBuf, rc::Rc, time::Duration};
+
+use crate::commands::Context;
+use crate::config::{cfg::DownloadOptions, outcome::Outcome};
+use crate::download::{
+    events::Event,
+    session::{DownloadSession, Release, SessionCommand, Setup, SetupCommand, SetupEvent},
+};
+
+const TITLE: &str = "Wuthering Waves Downloader";
+/// How often the window redraws to pick up new events.
+const REFRESH: Duration = Duration::from_millis(100);
+/// Failed files listed under the progress bar; older ones are in the log.
+const SHOWN_ERRORS: usize = 5;
+
+/// `gui`: picks a release and folder in a window and follows the download
+/// there, driving the same [`Setup`] and [`DownloadSession`] channels a
+/// front-end embedding the crate would.
+pub async fn run(ctx: &Context) -> Outcome {
+    let setup = Setup::start(
+        ctx.client.clone(),
+        ctx.cli.source.source_url.clone(),
+        ctx.log_file.clone(),
+        DownloadOptions::default(),
+    );
+    setup.send(SetupCommand::ListReleases);
+    let outcome = Rc::new(Cell::new(Outcome::Success));
+    let app = App::new(setup, outcome.clone());
+
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title(TITLE)
+            .with_inner_size([560.0, 360.0]),
+        ..Default::default()
+    };
+    if let Err(e) = eframe::run_native(TITLE, options, Box::new(|_| Ok(Box::new(app)))) {
+        ctx.fail(&format!("Could not open the window: {}", e));
+    }
+    outcome.get()
+}
+
+struct App {
+    setup: Setup,
+    setup_events: Receiver<SetupEvent>,
+    releases: Option<Result<Vec<Release>, String>>,
+    picked: usize,
+    folder: String,
+    /// A start is on its way, so the form stays locked.
+    starting: bool,
+    error: Option<String>,
+    download: Option<Download>,
+    outcome: Rc<Cell<Outcome>>,
+}
+
+/// The running session and what its events said so far.
+struct Download {
+    session: DownloadSession,
+    events: Receiver<Event>,
+    paused: bool,
+    current: Option<String>,
+    downloaded: u64,
+    total: u64,
+    bytes_per_sec: u64,
+    files_done: usize,
+    errors: Vec<String>,
+    summary: Option<Event>,
+}
+
+impl App {
+    fn new(setup: Setup, outcome: Rc<Cell<Outcome>>) -> Self {
+        Self {
+            setup_events: setup.events(),
+            setup,
+            releases: None,
+            picked: 0,
+            folder: String::new(),
+            starting: false,
+            error: None,
+            download: None,
+            outcome,
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Ok(event) = self.setup_events.try_recv() {
+            match event {
+                SetupEvent::Releases(releases) => self.releases = Some(releases),
+                SetupEvent::Started(Ok(session)) => {
+                    self.starting = false;
+                    self.download = Some(Download::new(session));
+                }
+                SetupEvent::Started(Err(e)) => {
+                    self.starting = false;
+                    self.error = Some(e);
+                }
+            }
+        }
+        if let Some(download) = &mut self.download {
+            download.receive();
+            if let Some(Event::Summary {
+                failed,
+                interrupted,
+                ..
+            }) = download.summary
+            {
+                self.outcome.set(if interrupted {
+                    Outcome::Interrupted
+                } else if failed > 0 {
+                    Outcome::Partial
+                } else {
+                    Outcome::Success
+                });
+            }
+        }
+    }
+
+    fn pickers(&mut self, ui: &mut egui::Ui) {
+        let releases = match &self.releases {
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Loading the version list...");
+                });
+                return;
+            }
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+                return;
+            }
+            Some(Ok(releases)) => releases,
+        };
+
+        let locked = self.starting;
+        ui.add_enabled_ui(!locked, |ui| {
+            egui::ComboBox::from_label("Version")
+                .selected_text(release_text(&releases[self.picked]))
+                .show_ui(ui, |ui| {
+                    for (i, release) in releases.iter().enumerate() {
+                        ui.selectable_value(&mut self.picked, i, release_text(release));
+                    }
+                });
+            ui.horizontal(|ui| {
+                ui.label("Folder");
+                ui.text_edit_singleline(&mut self.folder);
+                if ui.button("Browse...").clicked()
+                    && let Some(folder) = rfd::FileDialog::new()
+                        .set_title("Select the Wuthering Waves download folder")
+                        .pick_folder()
+                {
+                    self.folder = folder.display().to_string();
+                }
+            });
+            let ready = !self.folder.trim().is_empty();
+            if ui
+                .add_enabled(ready, egui::Button::new("Download"))
+                .clicked()
+            {
+                self.error = None;
+                self.starting = true;
+                self.setup.send(SetupCommand::Start {
+                    release: releases[self.picked].clone(),
+                    folder: PathBuf::from(self.folder.trim()),
+                });
+            }
+        });
+        if locked {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Fetching the index...");
+            });
+        }
+        if let Some(e) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.receive();
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(TITLE);
+            ui.separator();
+            match &mut self.download {
+                Some(download) => download.show(ui),
+                None => self.pickers(ui),
+            }
+        });
+        ctx.request_repaint_after(REFRESH);
+    }
+
+    /// Closing the window stops after the files in flight, which stay resumable.
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Some(download) = &self.download
+            && download.summary.is_none()
+        {
+            download.session.send(SessionCommand::Cancel);
+            self.outcome.set(Outcome::Interrupted);
+        }
+    }
+}
+
+impl Download {
+    fn new(session: DownloadSession) -> Self {
+        Self {
+            events: session.events(),
+            session,
+            paused: false,
+            current: None,
+            downloaded: 0,
+            total: 0,
+            bytes_per_sec: 0,
+            files_done: 0,
+            errors: Vec::new(),
+            summary: None,
+        }
+    }
+
+    fn receive(&mut self) {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::FileStarted { dest, .. } => self.current = Some(dest),
+                Event::Bytes {
+                    downloaded,
+                    total,
+                    bytes_per_sec,
+                } => {
+                    self.downloaded = downloaded;
+                    self.total = total;
+                    self.bytes_per_sec = bytes_per_sec;
+                }
+                Event::FileDone { .. } => self.files_done += 1,
+                Event::Error { dest, message } => {
+                    self.errors.push(format!("{}: {}", dest, message));
+                }
+                summary @ Event::Summary { .. } => self.summary = Some(summary),
+            }
+        }
+    }
+
+    fn show(&mut self, ui: &mut egui::Ui) {
+        if let Some(Event::Summary {
+            verified,
+            downloaded,
+            failed,
+            total,
+            interrupted,
+        }) = &self.summary
+        {
+            let title = if *interrupted {
+                "Download interrupted"
+            } else if *failed > 0 {
+                "Download finished with failures"
+            } else {
+                "Download complete"
+            };
+            ui.strong(title);
+            ui.label(format!(
+                "{} verified, {} downloaded, {} failed of {} files",
+                verified, downloaded, failed, total
+            ));
+            self.show_errors(ui);
+            return;
+        }
+
+        let fraction = if self.total > 0 {
+            self.downloaded as f32 / self.total as f32
+        } else {
+            0.0
+        };
+        ui.add(egui::ProgressBar::new(fraction).show_percentage());
+        ui.label(format!(
+            "{} / {} at {}/s, {} files done",
+            HumanBytes(self.downloaded),
+            HumanBytes(self.total),
+            HumanBytes(self.bytes_per_sec),
+            self.files_done
+        ));
+        if let Some(current) = &self.current {
+            ui.label(format!("Current: {}", current));
+        }
+        ui.horizontal(|ui| {
+            let pause = if self.paused { "Resume" } else { "Pause" };
+            if ui.button(pause).clicked() {
+                self.paused = !self.paused;
+                self.session.send(if self.paused {
+                    SessionCommand::Pause
+                } else {
+                    SessionCommand::Resume
+                });
+            }
+            if ui.button("Skip current").clicked() {
+                self.session.send(SessionCommand::SkipCurrent);
+            }
+            if ui.button("Cancel").clicked() {
+                self.session.send(SessionCommand::Cancel);
+            }
+        });
+        self.show_errors(ui);
+    }
+
+    fn show_errors(&self, ui: &mut egui::Ui) {
+        let skipped = self.errors.len().saturating_sub(SHOWN_ERRORS);
+        for error in &self.errors[skipped..] {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        if skipped > 0 {
+            ui.label(format!("{} more errors in the log", skipped));
+        }
+    }
+}
+
+fn release_text(release: &Release) -> String {
+    format!("{} ({})", release.label, release.version)
+}
@@ -0,0 +1,107 @@
+use colored::Colorize;
+use indicatif::HumanBytes;
+use log::warn;
+
+use crate::commands::Context;
+use crate::config::{cli::InfoArgs, outcome::Outcome, status::Status};
+use crate::io::util::{VERSION_CHANNELS, get_version, parse_resources};
+use crate::network::client::{channel_config, config_version, fetch_json, fetch_version_list};
+
+const CONFIGS: [&str; 2] = ["default", "predownload"];
+
+pub async fn run(ctx: &Context, args: &InfoArgs) -> Outcome {
+    let versions = match fetch_version_list(&ctx.client, ctx.cli.source.source_url.as_deref()).await
+    {
+        Ok(versions) => versions,
+        Err(e) => ctx.fail(&e),
+    };
+
+    let mut outcome = Outcome::Success;
+    let source = &ctx.cli.source;
+    let channels = VERSION_CHANNELS.iter().filter(|(category, region)| {
+        source
+            .channel
+            .as_deref()
+            .is_none_or(|wanted| wanted == *category)
+            && source
+                .region
+                .as_deref()
+                .is_none_or(|wanted| wanted == *region)
+    });
+    for (category, region) in channels {
+        println!(
+            "\n{} {} - {}",
+            Status::info(),
+            capitalize(category).bold(),
+            region.to_uppercase().bold()
+        );
+
+        let version_json = match get_version(&versions, category, region) {
+            Ok(url) => fetch_json(&ctx.client, &url).await,
+            Err(e) => Err(e),
+        };
+        let version_json = match version_json {
+            Ok(json) => json,
+            Err(e) => {
+                warn!("{}", e);
+                outcome = Outcome::Partial;
+                continue;
+            }
+        };
+
+        for selected in CONFIGS {
+            if version_json.get(selected).is_none() {
+                continue;
+            }
+            let version = config_version(&version_json, selected);
+            if args.no_sizes {
+                println!("    {:<12} {}", selected, version.cyan());
+                continue;
+            }
+
+            let size = match index_size(ctx, &version_json, selected).await {
+                Ok(size) if size.unknown_size > 0 => format!(
+                    "{} files, {} + {} files of unknown size",
+                    size.files,
+                    HumanBytes(size.bytes),
+                    size.unknown_size
+                ),
+                Ok(size) => format!("{} files, {}", size.files, HumanBytes(size.bytes)),
+                Err(e) => format!("size unavailable: {}", e).yellow().to_string(),
+            };
+            println!("    {:<12} {} ({})", selected, version.cyan(), size);
+        }
+    }
+    outcome
+}
+
+struct IndexSize {
+    files: usize,
+    bytes: u64,
+    /// Entries without a `size` field; not probed, so `info` stays a single request per index.
+    unknown_size: usize,
+}
+
+/// Totals the manifest of one config from the sizes it lists.
+async fn index_size(
+    ctx: &Context,
+    version_json: &serde_json::Value,
+    selected: &str,
+) -> Result<IndexSize, String> {
+    let config = channel_config(version_json, selected)?;
+    let index = fetch_json(&ctx.client, &config.index_url).await?;
+    let resources = parse_resources(&index)?;
+    Ok(IndexSize {
+        files: resources.len(),
+        bytes: resources.iter().filter_map(|item| item.size).sum(),
+        unknown_size: resources.iter().filter(|item| item.size.is_none()).count(),
+    })
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
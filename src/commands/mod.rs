@@ -0,0 +1,410 @@
+pub mod check_cdn;
+pub mod clean;
+pub mod diff;
+pub mod download;
+pub mod export;
+#[cfg(feature = "gui")]
+pub mod gui;
+pub mod info;
+pub mod repair;
+pub mod self_update;
+pub mod serve;
+pub mod speedtest;
+pub mod verify;
+pub mod watch;
+
+use colored::Colorize;
+use indicatif::HumanBytes;
+use log::{debug, info, warn};
+use reqwest::Client;
+use serde_json::Value;
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use crate::config::{
+    cfg::{Config, ResourceItem},
+    cli::{Cli, Commands, FilterArgs, FolderArgs},
+    outcome::Outcome,
+    status::Status,
+};
+use crate::download::selection::{audio_groups, retain_audio_languages, unmatched_languages};
+use crate::io::{
+    browser::browse,
+    file::{get_dir, resolve_dest},
+    hash_cache::HashCache,
+    logging::{SharedLogFile, log_error, log_warn},
+    manifest_archive::save_manifest,
+    notify::notify,
+    prompt::{Prompter, TerminalPrompter},
+    sanitize::{is_renamed, sanitize_dest},
+    session_lock::{LockOwner, SessionLock},
+    util::{
+        CONFIRM_ENV, MAIN_GROUP, ResourceGroup, ask_audio_languages, exit_with_error, merge_groups,
+        parse_resource_groups,
+    },
+};
+use crate::network::client::{fetch_channel_config, fetch_index, get_config, load_index_file};
+
+/// State shared by every subcommand: parsed arguments, the HTTP client and the log file.
+pub struct Context {
+    pub cli: Cli,
+    pub client: Client,
+    pub log_file: SharedLogFile,
+}
+
+impl Context {
+    pub fn fail(&self, error: &str) -> ! {
+        if self.notifications_enabled() {
+            notify("Download failed", error);
+        }
+        exit_with_error(&self.log_file, error, !self.cli.headless())
+    }
+
+    /// Whether `--notify` was given to a command that downloads.
+    pub fn notifications_enabled(&self) -> bool {
+        match self.cli.command() {
+            Commands::Download(args) => args.transfer.notify,
+            Commands::Repair(args) => args.transfer.notify,
+            _ => false,
+        }
+    }
+
+    /// The folder's checksum cache, or a disabled one under `--no-hash-cache`.
+    pub fn hash_cache(&self, folder: &Path) -> HashCache {
+        if self.cli.no_hash_cache {
+            HashCache::disabled()
+        } else {
+            HashCache::load(folder)
+        }
+    }
+
+    /// Index URL and CDNs to use. `--index-file` together with `--base-url`
+    /// needs no network or prompts; `--channel`, `--region`, `--predownload`
+    /// or headless mode pick the version from flags; otherwise it is prompted for.
+    pub async fn config(&self) -> Config {
+        let source = &self.cli.source;
+        if let Some(path) = &source.index_file
+            && !source.base_url.is_empty()
+        {
+            return Config {
+                index_url: path.display().to_string(),
+                zip_bases: source.base_url.clone(),
+            };
+        }
+
+        let from_flags = self.cli.headless()
+            || source.channel.is_some()
+            || source.region.is_some()
+            || source.predownload;
+        let config = if from_flags {
+            fetch_channel_config(
+                &self.client,
+                source.source_url.as_deref(),
+                source.channel(),
+                source.region(),
+                source.selected_config(),
+            )
+            .await
+        } else {
+            get_config(
+                &self.client,
+                &TerminalPrompter,
+                source.source_url.as_deref(),
+                &source.base_url,
+            )
+            .await
+        };
+        let mut config = match config {
+            Ok(config) => config,
+            Err(e) => self.fail(&e),
+        };
+        if !source.base_url.is_empty() {
+            config.zip_bases = source.base_url.clone();
+        } else {
+            source.order_cdns(&mut config.zip_bases, &self.cli.log_dir);
+        }
+        config
+    }
+
+    /// Like [`Context::config`], but skips the version prompts when the index
+    /// comes from `--index-file` and no CDNs are needed.
+    pub async fn index_config(&self) -> Config {
+        match &self.cli.source.index_file {
+            Some(path) => Config {
+                index_url: path.display().to_string(),
+                zip_bases: self.cli.source.base_url.clone(),
+            },
+            None => self.config().await,
+        }
+    }
+
+    /// Loads the index from `--index-file`, or downloads it from `config`.
+    pub async fn index_data(&self, config: &Config) -> Value {
+        let url = self
+            .cli
+            .source
+            .index_file
+            .as_ref()
+            .and_then(|path| path.to_str())
+            .filter(|path| path.starts_with("http://") || path.starts_with("https://"));
+        let data = match (&self.cli.source.index_file, url) {
+            (Some(_), Some(url)) => {
+                let config = Config {
+                    index_url: url.to_string(),
+                    zip_bases: Vec::new(),
+                };
+                fetch_index(&self.client, &config, &self.log_file).await
+            }
+            (Some(path), None) => load_index_file(path, &self.log_file),
+            (None, _) => fetch_index(&self.client, config, &self.log_file).await,
+        };
+        match data {
+            Ok(data) => data,
+            Err(e) => self.fail(&e),
+        }
+    }
+
+    async fn resource_groups(&self, config: &Config) -> Vec<ResourceGroup> {
+        match parse_resource_groups(&self.index_data(config).await) {
+            Ok(groups) => groups,
+            Err(e) => self.fail(&e),
+        }
+    }
+
+    /// Every resource of the index, across all groups.
+    pub async fn resources(&self, config: &Config) -> Vec<ResourceItem> {
+        merge_groups(self.resource_groups(config).await)
+    }
+
+    /// Resources of the groups picked with `--group`, narrowed down by [`Context::select`].
+    pub async fn selected_resources(
+        &self,
+        config: &Config,
+        filter: &FilterArgs,
+    ) -> Vec<ResourceItem> {
+        let groups = self.resource_groups(config).await;
+        self.select_groups(groups, filter)
+    }
+
+    /// [`Context::selected_resources`] for a session downloading to `folder`,
+    /// which first keeps a copy of the index and config in `.wuwa/manifests`.
+    pub async fn session_resources(
+        &self,
+        config: &Config,
+        filter: &FilterArgs,
+        folder: &Path,
+    ) -> Vec<ResourceItem> {
+        let index = self.index_data(config).await;
+        match save_manifest(folder, &index, config) {
+            Ok(Some(path)) => debug!("Saved the index to {}", path.display()),
+            Ok(None) => {}
+            Err(e) => log_warn(&self.log_file, &format!("Could not save the index: {}", e)),
+        }
+        match parse_resource_groups(&index) {
+            Ok(groups) => self.select_groups(groups, filter),
+            Err(e) => self.fail(&e),
+        }
+    }
+
+    /// [`Context::selected_resources`] for groups parsed from an index already at hand.
+    pub fn select_groups(
+        &self,
+        mut groups: Vec<ResourceGroup>,
+        filter: &FilterArgs,
+    ) -> Vec<ResourceItem> {
+        if let Some(unknown) = filter
+            .groups
+            .iter()
+            .find(|name| !groups.iter().any(|group| &group.name == *name))
+        {
+            let available: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+            self.fail(&format!(
+                "Unknown resource group \"{}\" (index has: {})",
+                unknown,
+                available.join(", ")
+            ));
+        }
+        if !filter.groups.is_empty() {
+            groups.retain(|group| filter.groups.contains(&group.name));
+        }
+        if groups.len() > 1 || groups.iter().any(|group| group.name != MAIN_GROUP) {
+            let summary: Vec<String> = groups
+                .iter()
+                .map(|group| format!("{} ({} files)", group.name, group.resources.len()))
+                .collect();
+            info!("Resource groups: {}", summary.join(", ").cyan());
+        }
+
+        let mut resources = merge_groups(groups);
+        self.select(filter, &mut resources);
+        resources
+    }
+
+    pub fn folder(&self, args: &FolderArgs) -> PathBuf {
+        if args.dir.is_none() && self.cli.headless() {
+            self.fail("--dir is required in headless mode");
+        }
+        let picker = !args.no_picker && std::io::stdin().is_terminal();
+        match get_dir(args.dir.as_deref(), picker, &TerminalPrompter) {
+            Ok(folder) => folder,
+            Err(e) => self.fail(&format!("Download directory error: {}", e)),
+        }
+    }
+
+    /// Keeps other sessions out of `folder` while the guard lives. A lock a
+    /// crashed session left behind is taken over after asking, or right away
+    /// in headless mode.
+    pub fn lock_folder(&self, folder: &Path) -> SessionLock {
+        let take_over = |owner: &LockOwner| {
+            if self.cli.headless() {
+                log_warn(
+                    &self.log_file,
+                    &format!("Taking over the stale lock of {}", owner.describe()),
+                );
+                return true;
+            }
+            let question = format!(
+                "{} A previous session ({}) did not release {}. Take it over? [Y/n]: ",
+                Status::warning(),
+                owner.describe(),
+                folder.display()
+            );
+            TerminalPrompter
+                .ask(&question, Some(CONFIRM_ENV))
+                .is_ok_and(|(input, _)| {
+                    matches!(input.trim().to_ascii_lowercase().as_str(), "y" | "yes" | "")
+                })
+        };
+        match SessionLock::acquire(folder, take_over) {
+            Ok(lock) => lock,
+            Err(e) => self.fail(&e),
+        }
+    }
+
+    /// Applies `--include`/`--exclude`, then the `--browse` tree or the voice-over language choice.
+    pub fn select(&self, filter: &FilterArgs, resources: &mut Vec<ResourceItem>) {
+        let filtered = filter.resource_filter().apply(resources);
+        if filtered.files > 0 {
+            info!(
+                "Filtered out {} files ({})",
+                filtered.files.to_string().cyan(),
+                HumanBytes(filtered.bytes).to_string().cyan()
+            );
+        }
+
+        if filter.browse {
+            self.browse(resources);
+            return;
+        }
+
+        let groups = audio_groups(resources);
+        let all_languages = filter
+            .audio_lang
+            .iter()
+            .any(|language| language.eq_ignore_ascii_case("all"));
+        if groups.len() <= 1 || all_languages {
+            return;
+        }
+
+        if filter.audio_lang.is_empty() && self.cli.headless() {
+            info!("Keeping every voice-over language, pick some with --audio-lang");
+            return;
+        }
+        let languages = if filter.audio_lang.is_empty() {
+            match ask_audio_languages(&TerminalPrompter, &groups) {
+                Ok(languages) => languages,
+                Err(e) => self.fail(&e),
+            }
+        } else {
+            let unmatched = unmatched_languages(&filter.audio_lang, &groups);
+            let requested = filter
+                .audio_lang
+                .iter()
+                .filter(|language| !language.trim().is_empty())
+                .count();
+            if unmatched.len() == requested {
+                self.fail(&format!(
+                    "--audio-lang {} matches no voice-over pack in this version",
+                    unmatched.join(",")
+                ));
+            }
+            if !unmatched.is_empty() {
+                warn!(
+                    "No voice-over pack for --audio-lang {}",
+                    unmatched.join(",").yellow()
+                );
+            }
+            filter.audio_lang.clone()
+        };
+        let skipped = retain_audio_languages(resources, &languages);
+        if skipped.files > 0 {
+            info!(
+                "Skipping {} voice-over files ({})",
+                skipped.files.to_string().cyan(),
+                HumanBytes(skipped.bytes).to_string().cyan()
+            );
+        }
+    }
+
+    fn browse(&self, resources: &mut Vec<ResourceItem>) {
+        if self.cli.headless() || !std::io::stdin().is_terminal() {
+            self.fail("--browse needs an interactive terminal");
+        }
+        let selection = match browse(resources) {
+            Ok(Some(selection)) => selection,
+            Ok(None) => {
+                info!("Selection cancelled");
+                Outcome::Success.exit();
+            }
+            Err(e) => self.fail(&format!("File browser failed: {}", e)),
+        };
+
+        let mut keep = selection.into_iter();
+        resources.retain(|_| keep.next().unwrap_or(false));
+        info!(
+            "Selected {} files ({})",
+            resources.len().to_string().cyan(),
+            HumanBytes(resources.iter().filter_map(|item| item.size).sum())
+                .to_string()
+                .cyan()
+        );
+    }
+
+    /// Drops index entries that would land outside `folder` and logs every
+    /// file stored under a sanitized name.
+    pub fn drop_unsafe_dests(&self, folder: &Path, resources: &mut Vec<ResourceItem>) {
+        let mut unsafe_dests = 0;
+        let mut renamed = 0;
+        resources.retain(|item| match resolve_dest(folder, &item.dest) {
+            Ok(_) => {
+                if is_renamed(&item.dest) {
+                    log_warn(
+                        &self.log_file,
+                        &format!("Saving {} as {}", item.dest, sanitize_dest(&item.dest)),
+                    );
+                    renamed += 1;
+                }
+                true
+            }
+            Err(e) => {
+                log_error(&self.log_file, &e);
+                unsafe_dests += 1;
+                false
+            }
+        });
+        if unsafe_dests > 0 {
+            warn!(
+                "Skipping {} index entries that point outside the download folder",
+                unsafe_dests.to_string().yellow()
+            );
+        }
+        if renamed > 0 {
+            warn!(
+                "Saving {} files under escaped names that Windows accepts (see the log)",
+                renamed.to_string().yellow()
+            );
+        }
+    }
+}
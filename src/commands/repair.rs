@@ -0,0 +1,69 @@
+use colored::Colorize;
+use log::info;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use crate::commands::{
+    Context,
+    download::{apply_transfer_args, event_sink, finish, print_options, transfer},
+    verify::inspect,
+};
+use crate::config::{cfg::DownloadOptions, cli::RepairArgs, outcome::Outcome};
+use crate::io::util::setup_ctrlc;
+
+pub async fn run(ctx: &Context, args: &RepairArgs) -> Outcome {
+    if args.verify.checksums.is_some() {
+        ctx.fail("--checksums only works with verify, as repair needs the index to download from");
+    }
+    let config = ctx.config().await;
+    let folder = ctx.folder(&args.verify.folder);
+    let _lock = ctx.lock_folder(&folder);
+    let mut resources = ctx
+        .session_resources(&config, &args.verify.filter, &folder)
+        .await;
+    ctx.drop_unsafe_dests(&folder, &mut resources);
+
+    let mut options = DownloadOptions {
+        verify_concurrency: args.verify.jobs,
+        ..DownloadOptions::default()
+    };
+    let events = event_sink(ctx, &args.transfer);
+    apply_transfer_args(ctx, &args.transfer, &mut options);
+    print_options(&folder, &options);
+
+    let should_stop = Arc::new(AtomicBool::new(false));
+    setup_ctrlc(should_stop.clone(), &ctx.log_file);
+    let report = inspect(ctx, &folder, &resources, &args.verify, should_stop.clone()).await;
+    if should_stop.load(Ordering::SeqCst) {
+        return Outcome::Interrupted;
+    }
+    if report.problems.is_empty() {
+        info!(
+            "All {} files are intact, nothing to repair",
+            report.intact.to_string().cyan()
+        );
+        return Outcome::Success;
+    }
+
+    info!(
+        "Repairing {} missing or damaged files",
+        report.problems.len().to_string().cyan()
+    );
+    let damaged = report.problems.into_iter().map(|(item, _)| item).collect();
+    let result = transfer(
+        ctx,
+        &config,
+        &folder,
+        damaged,
+        options,
+        &args.transfer,
+        None,
+        events,
+        should_stop.clone(),
+    )
+    .await;
+
+    finish(ctx, &folder, &result, None, &args.transfer, &should_stop).await
+}
@@ -0,0 +1,464 @@
+use colored::Colorize;
+use indicatif::HumanBytes;
+use log::{info, warn};
+use reqwest::Client;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::Ordering,
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{io::AsyncWriteExt, task::JoinHandle};
+
+use crate::commands::Context;
+use crate::config::{cli::SelfUpdateArgs, outcome::Outcome, status::Status};
+use crate::io::{i18n::Msg, logging::log_info, util::read_line};
+use crate::network::client::fetch_json;
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/yuhkix/wuwa-downloader/releases/latest";
+
+/// Latest release tag seen by the startup check, kept in `--log-dir`.
+pub const UPDATE_CHECK_CACHE: &str = ".update-check.json";
+/// How long a cached release tag is trusted before GitHub is asked again.
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long the end of a run waits for a startup check still in flight.
+const UPDATE_CHECK_GRACE: Duration = Duration::from_secs(1);
+
+/// Release assets that list SHA-256 sums for the other assets.
+const CHECKSUM_LISTS: [&str; 3] = ["SHA256SUMS", "sha256sums.txt", "checksums.txt"];
+
+/// Suffixes of assets that are not a bare executable.
+const NON_BINARY_SUFFIXES: [&str; 8] = [
+    ".sha256", ".txt", ".sig", ".asc", ".zip", ".tar.gz", ".tgz", ".7z",
+];
+
+/// Name keywords of release binaries built for this platform.
+#[cfg(windows)]
+const PLATFORM_KEYWORDS: &[&str] = &["windows", "win64", "win32"];
+#[cfg(target_os = "macos")]
+const PLATFORM_KEYWORDS: &[&str] = &["macos", "darwin", "apple"];
+#[cfg(not(any(windows, target_os = "macos")))]
+const PLATFORM_KEYWORDS: &[&str] = &["linux"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Asset {
+    name: String,
+    url: String,
+    size: u64,
+}
+
+pub async fn run(ctx: &Context, args: &SelfUpdateArgs) -> Outcome {
+    let release = match fetch_json(&ctx.client, LATEST_RELEASE_URL).await {
+        Ok(release) => release,
+        Err(e) => ctx.fail(&format!("Failed to check for updates: {}", e)),
+    };
+    let Some(tag) = release.get("tag_name").and_then(Value::as_str) else {
+        ctx.fail("Latest release has no tag");
+    };
+    let current = env!("CARGO_PKG_VERSION");
+    if compare_versions(tag, current) != Some(Ordering::Greater) {
+        info!("Already up to date (v{})", current.cyan());
+        return Outcome::Success;
+    }
+
+    let assets = release_assets(&release);
+    let Some(binary) = pick_binary(&assets, PLATFORM_KEYWORDS) else {
+        ctx.fail(&format!(
+            "Release {} has no binary for this platform; download it from GitHub instead",
+            tag
+        ));
+    };
+    let expected = match published_checksum(ctx, &assets, &binary.name).await {
+        Ok(Some(expected)) => expected,
+        Ok(None) => ctx.fail(&format!(
+            "Release {} publishes no SHA-256 checksum for {}; refusing to install it",
+            tag, binary.name
+        )),
+        Err(e) => ctx.fail(&e),
+    };
+
+    println!(
+        "{} Update available: v{} -> {} ({}, {})",
+        Status::info(),
+        current,
+        tag.green(),
+        binary.name,
+        HumanBytes(binary.size)
+    );
+    if args.check {
+        return Outcome::Success;
+    }
+    if !args.yes {
+        if ctx.cli.headless() {
+            info!("Not updating, pass --yes to install {}", tag);
+            return Outcome::Success;
+        }
+        if !confirm_update(tag) {
+            info!("Update cancelled");
+            return Outcome::Success;
+        }
+    }
+
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => ctx.fail(&format!("Failed to locate the running executable: {}", e)),
+    };
+    let staged = staged_path(&exe);
+    if let Err(e) = download_binary(ctx, binary, &staged, &expected).await {
+        let _ = fs::remove_file(&staged);
+        ctx.fail(&e);
+    }
+    if let Err(e) = replace_executable(&exe, &staged) {
+        let _ = fs::remove_file(&staged);
+        ctx.fail(&format!("Failed to replace {}: {}", exe.display(), e));
+    }
+
+    log_info(
+        &ctx.log_file,
+        &format!("Updated {} from v{} to {}", exe.display(), current, tag),
+    );
+    println!(
+        "{} Updated to {}; the new version runs from the next start",
+        Status::success(),
+        tag.green()
+    );
+    Outcome::Success
+}
+
+/// Deletes the executable a Windows update renamed aside, which could not be
+/// removed while it was still running. Harmless when there is none.
+pub fn remove_replaced_executable() {
+    if let Ok(exe) = env::current_exe() {
+        let _ = fs::remove_file(replaced_path(&exe));
+    }
+}
+
+/// Starts the startup release check. A tag cached within the last day is
+/// reported right away; otherwise GitHub is asked in the background and the
+/// returned task is handed to [`finish_update_check`] once the command is done.
+pub fn start_update_check(client: &Client, log_dir: &Path) -> Option<JoinHandle<()>> {
+    let cache = log_dir.join(UPDATE_CHECK_CACHE);
+    if let Some(tag) = cached_tag(&cache, unix_now()) {
+        notify_if_newer(&tag);
+        return None;
+    }
+    let client = client.clone();
+    Some(tokio::spawn(async move {
+        let Ok(release) = fetch_json(&client, LATEST_RELEASE_URL).await else {
+            return;
+        };
+        let Some(tag) = release.get("tag_name").and_then(Value::as_str) else {
+            return;
+        };
+        let entry = json!({ "checked": unix_now(), "latest": tag });
+        let _ = fs::write(&cache, entry.to_string());
+        notify_if_newer(tag);
+    }))
+}
+
+/// Gives a background check a moment to report, then abandons it.
+pub async fn finish_update_check(check: JoinHandle<()>) {
+    let _ = tokio::time::timeout(UPDATE_CHECK_GRACE, check).await;
+}
+
+fn notify_if_newer(tag: &str) {
+    let current = env!("CARGO_PKG_VERSION");
+    if compare_versions(tag, current) == Some(Ordering::Greater) {
+        warn!("{}", Msg::UpdateAvailable.fill(&[&tag, &current]));
+    }
+}
+
+/// The tag in `cache` when it was written less than a day before `now`.
+fn cached_tag(cache: &Path, now: u64) -> Option<String> {
+    let entry: Value = serde_json::from_str(&fs::read_to_string(cache).ok()?).ok()?;
+    let checked = entry.get("checked")?.as_u64()?;
+    let fresh = now.saturating_sub(checked) < UPDATE_CHECK_INTERVAL.as_secs();
+    fresh.then(|| entry.get("latest")?.as_str().map(str::to_string))?
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn confirm_update(tag: &str) -> bool {
+    print!(
+        "\n{} {}",
+        Status::question(),
+        Msg::ConfirmUpdate.fill(&[&tag])
+    );
+    let _ = io::stdout().flush();
+    read_line().is_ok_and(|input| input.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Orders two `vMAJOR.MINOR.PATCH` tags, ignoring a leading `v` and any
+/// pre-release or build suffix; `None` when either is not a version.
+fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let parse = |version: &str| -> Option<Vec<u64>> {
+        let core = version.trim().trim_start_matches(['v', 'V']);
+        let core = core.split(['-', '+']).next().unwrap_or(core);
+        core.split('.').map(|part| part.parse().ok()).collect()
+    };
+    let (mut a, mut b) = (parse(a)?, parse(b)?);
+    let len = a.len().max(b.len());
+    a.resize(len, 0);
+    b.resize(len, 0);
+    Some(a.cmp(&b))
+}
+
+fn release_assets(release: &Value) -> Vec<Asset> {
+    release
+        .get("assets")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|asset| {
+            Some(Asset {
+                name: asset.get("name")?.as_str()?.to_string(),
+                url: asset.get("browser_download_url")?.as_str()?.to_string(),
+                size: asset.get("size").and_then(Value::as_u64).unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// The bare executable built for a platform named by one of `keywords`. On
+/// Windows a lone `.exe` is taken even when its name carries no platform.
+fn pick_binary<'a>(assets: &'a [Asset], keywords: &[&str]) -> Option<&'a Asset> {
+    let binaries: Vec<&Asset> = assets
+        .iter()
+        .filter(|asset| {
+            let name = asset.name.to_ascii_lowercase();
+            !NON_BINARY_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+                && !CHECKSUM_LISTS
+                    .iter()
+                    .any(|list| asset.name.eq_ignore_ascii_case(list))
+        })
+        .collect();
+    binaries
+        .iter()
+        .find(|asset| {
+            let name = asset.name.to_ascii_lowercase();
+            keywords.iter().any(|keyword| name.contains(keyword))
+        })
+        .or_else(|| {
+            let mut exes = binaries
+                .iter()
+                .filter(|asset| cfg!(windows) && asset.name.to_ascii_lowercase().ends_with(".exe"));
+            exes.next().filter(|_| exes.next().is_none())
+        })
+        .copied()
+}
+
+/// The SHA-256 of `name` from its `<name>.sha256` asset or a checksum list.
+async fn published_checksum(
+    ctx: &Context,
+    assets: &[Asset],
+    name: &str,
+) -> Result<Option<String>, String> {
+    let sidecar = format!("{}.sha256", name);
+    let sources = assets.iter().filter(|asset| {
+        asset.name.eq_ignore_ascii_case(&sidecar)
+            || CHECKSUM_LISTS
+                .iter()
+                .any(|list| asset.name.eq_ignore_ascii_case(list))
+    });
+    for source in sources {
+        let listing = fetch_text(ctx, &source.url).await?;
+        if let Some(sum) = checksum_for(&listing, name) {
+            return Ok(Some(sum));
+        }
+    }
+    Ok(None)
+}
+
+/// Finds `name` in `sha256sum` output. A line without a file name, as in a
+/// single-file `.sha256` asset, matches any name.
+fn checksum_for(listing: &str, name: &str) -> Option<String> {
+    listing.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let sum = fields.next()?;
+        let listed = fields.next().map(|file| file.trim_start_matches('*'));
+        let is_sum = sum.len() == 64 && sum.chars().all(|c| c.is_ascii_hexdigit());
+        (is_sum && listed.is_none_or(|file| file == name)).then(|| sum.to_ascii_lowercase())
+    })
+}
+
+async fn fetch_text(ctx: &Context, url: &str) -> Result<String, String> {
+    let response = ctx
+        .client
+        .get(url)
+        .timeout(Duration::from_secs(30))
+        .send()
+        .await
+        .map_err(|e| format!("Network error fetching {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Server error fetching {}: HTTP {}",
+            url,
+            response.status()
+        ));
+    }
+    response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", url, e))
+}
+
+/// Streams `asset` to `path` and checks it against `expected`.
+async fn download_binary(
+    ctx: &Context,
+    asset: &Asset,
+    path: &Path,
+    expected: &str,
+) -> Result<(), String> {
+    info!("Downloading {}", asset.name.cyan());
+    let mut response = ctx
+        .client
+        .get(&asset.url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to download {}: {}", asset.name, e))?;
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download {}: {}", asset.name, e))?
+    {
+        hasher.update(&chunk);
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    let actual: String = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    if actual != expected {
+        return Err(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            asset.name, expected, actual
+        ));
+    }
+    Ok(())
+}
+
+/// Where the new binary is written before it replaces `exe`.
+fn staged_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    exe.with_file_name(name)
+}
+
+/// Where Windows moves the running `exe` until the next start removes it.
+fn replaced_path(exe: &Path) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".old");
+    exe.with_file_name(name)
+}
+
+/// Renames `staged` over the running executable in one step.
+#[cfg(not(windows))]
+fn replace_executable(exe: &Path, staged: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(exe).map_or(0o755, |meta| meta.permissions().mode());
+    fs::set_permissions(staged, fs::Permissions::from_mode(mode))?;
+    fs::rename(staged, exe)
+}
+
+/// A running executable cannot be overwritten on Windows, but it can be
+/// renamed: it moves aside and the next start deletes it.
+#[cfg(windows)]
+fn replace_executable(exe: &Path, staged: &Path) -> io::Result<()> {
+    let replaced = replaced_path(exe);
+    let _ = fs::remove_file(&replaced);
+    fs::rename(exe, &replaced)?;
+    if let Err(e) = fs::rename(staged, exe) {
+        let _ = fs::rename(&replaced, exe);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Asset, cached_tag, checksum_for, compare_versions, pick_binary};
+    use crate::io::file::unique_path;
+    use std::cmp::Ordering;
+
+    fn asset(name: &str) -> Asset {
+        Asset {
+            name: name.to_string(),
+            url: format!("https://example.com/{}", name),
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn compares_release_tags_with_the_package_version() {
+        assert_eq!(compare_versions("v1.4.0", "1.3.9"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("v1.4", "1.4.0"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("1.10.0", "1.9.0"), Some(Ordering::Greater));
+        assert_eq!(
+            compare_versions("v2.0.0-rc1", "2.0.0"),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(compare_versions("nightly", "1.0.0"), None);
+    }
+
+    #[test]
+    fn picks_the_platform_binary_and_its_checksum() {
+        let assets = [
+            asset("wuwa-downloader-windows.exe"),
+            asset("wuwa-downloader-linux"),
+            asset("wuwa-downloader-linux.sha256"),
+            asset("SHA256SUMS"),
+            asset("source.zip"),
+        ];
+        assert_eq!(
+            pick_binary(&assets, &["linux"]).map(|a| a.name.as_str()),
+            Some("wuwa-downloader-linux")
+        );
+        assert_eq!(pick_binary(&assets, &["macos"]), None);
+
+        let sum = "ab".repeat(32);
+        let listing = format!(
+            "{}  wuwa-downloader-windows.exe\n{} *wuwa-downloader-linux\n",
+            "cd".repeat(32),
+            sum.to_uppercase()
+        );
+        assert_eq!(
+            checksum_for(&listing, "wuwa-downloader-linux"),
+            Some(sum.clone())
+        );
+        assert_eq!(checksum_for(&format!("{}\n", sum), "anything"), Some(sum));
+        assert_eq!(checksum_for(&listing, "missing"), None);
+    }
+
+    #[test]
+    fn trusts_a_cached_tag_for_a_day() {
+        let path = unique_path("update-check");
+        std::fs::write(&path, r#"{"checked": 1000, "latest": "v9.9.9"}"#).unwrap();
+
+        assert_eq!(cached_tag(&path, 1000 + 3600).as_deref(), Some("v9.9.9"));
+        assert_eq!(cached_tag(&path, 1000 + 24 * 3600), None);
+        assert_eq!(cached_tag(&path.with_extension("missing"), 1000), None);
+    }
+}
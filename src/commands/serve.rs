@@ -0,0 +1,39 @@
+use colored::Colorize;
+use log::info;
+use std::net::TcpListener;
+
+use crate::commands::Context;
+use crate::config::{cli::ServeArgs, outcome::Outcome};
+use crate::download::mirror::FILES_DIR;
+use crate::io::i18n::Msg;
+use crate::network::file_server::serve_folder;
+
+pub async fn run(ctx: &Context, args: &ServeArgs) -> Outcome {
+    if !args.dir.is_dir() {
+        ctx.fail(&format!("{} is not a folder", args.dir.display()));
+    }
+    let listener = match TcpListener::bind(("0.0.0.0", args.port)) {
+        Ok(listener) => listener,
+        Err(e) => ctx.fail(&format!("Failed to listen on port {}: {}", args.port, e)),
+    };
+
+    info!(
+        "{}",
+        Msg::ServingFolder.fill(&[
+            &args.dir.display().to_string().cyan(),
+            &args.port.to_string().cyan()
+        ])
+    );
+    if args.dir.join(FILES_DIR).is_dir() {
+        info!("{}", Msg::InstallFromMirror.fill(&[&args.port, &args.port]));
+    } else {
+        info!("{}", Msg::InstallFromFolder.fill(&[&args.port]));
+    }
+
+    let root = args.dir.clone();
+    match tokio::task::spawn_blocking(move || serve_folder(listener, root)).await {
+        Ok(Ok(())) => Outcome::Success,
+        Ok(Err(e)) => ctx.fail(&format!("Server stopped: {}", e)),
+        Err(e) => ctx.fail(&format!("Server stopped: {}", e)),
+    }
+}
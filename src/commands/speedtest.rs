@@ -0,0 +1,50 @@
+use colored::Colorize;
+use indicatif::HumanBytes;
+use log::info;
+
+use crate::commands::Context;
+use crate::config::{cli::SpeedtestArgs, outcome::Outcome};
+use crate::io::{
+    console::{print_cdn_reports, print_download_estimate},
+    logging::log_error,
+};
+use crate::network::health::{check_cdns, estimate_download_time, sample_resource};
+
+pub async fn run(ctx: &Context, args: &SpeedtestArgs) -> Outcome {
+    if args.sample_size == 0 {
+        ctx.fail("--sample-size must be larger than zero");
+    }
+    let config = ctx.config().await;
+    let resources = ctx.selected_resources(&config, &args.filter).await;
+    let Some(sample) = sample_resource(&resources) else {
+        ctx.fail("No resources selected to test the CDNs with");
+    };
+
+    info!(
+        "Reading {} of {} from {} CDNs...",
+        HumanBytes(args.sample_size).to_string().cyan(),
+        sample.dest.cyan(),
+        config.zip_bases.len().to_string().cyan()
+    );
+    let reports = check_cdns(&ctx.client, &config, sample, args.sample_size).await;
+    for report in &reports {
+        if let Err(e) = &report.status {
+            log_error(
+                &ctx.log_file,
+                &format!("CDN {} unreachable: {}", report.base_url, e),
+            );
+        }
+    }
+    print_cdn_reports(&reports);
+
+    let total_bytes: u64 = resources.iter().filter_map(|item| item.size).sum();
+    let without_size = resources.iter().filter(|item| item.size.is_none()).count();
+    let estimate = estimate_download_time(&reports, total_bytes);
+    print_download_estimate(total_bytes, resources.len(), without_size, estimate);
+
+    match estimate {
+        None => Outcome::Error,
+        Some(_) if reports.iter().any(|report| !report.is_healthy()) => Outcome::Partial,
+        Some(_) => Outcome::Success,
+    }
+}
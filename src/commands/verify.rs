@@ -0,0 +1,245 @@
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::info;
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::commands::{
+    Context,
+    clean::{is_bookkeeping, list_files},
+};
+use crate::config::{cfg::ResourceItem, cli::VerifyArgs, outcome::Outcome, style};
+use crate::download::{filter::ResourceFilter, hash_pool::HashPool};
+use crate::io::{
+    checksum_list::read_checksum_list,
+    console::print_verify_results,
+    file::{FileState, VerificationError, inspect_file, resolve_dest},
+    hash_cache::HASH_CACHE,
+    logging::{log_error, log_info},
+    sanitize::sanitize_dest,
+    util::setup_ctrlc,
+};
+
+pub struct VerifyReport {
+    pub intact: usize,
+    /// Missing or damaged files, in index order.
+    pub problems: Vec<(ResourceItem, FileState)>,
+    /// Files that could not be read, or were not reached before an interrupt.
+    pub unchecked: usize,
+    /// Files on disk a `--checksums` list does not name.
+    pub extra: Vec<String>,
+}
+
+pub async fn run(ctx: &Context, args: &VerifyArgs) -> Outcome {
+    let Some(list) = &args.checksums else {
+        let config = ctx.index_config().await;
+        let folder = ctx.folder(&args.folder);
+        let mut resources = ctx.selected_resources(&config, &args.filter).await;
+        ctx.drop_unsafe_dests(&folder, &mut resources);
+        return check(ctx, &folder, &resources, args, None).await;
+    };
+
+    let folder = ctx.folder(&args.folder);
+    let mut resources = match read_checksum_list(list) {
+        Ok(resources) => resources,
+        Err(e) => ctx.fail(&e),
+    };
+    let filter = args.filter.resource_filter();
+    filter.apply(&mut resources);
+    ctx.drop_unsafe_dests(&folder, &mut resources);
+    check(ctx, &folder, &resources, args, Some((list, &filter))).await
+}
+
+/// Verifies `resources`, and with a checksum list also looks for files on
+/// disk that it does not name.
+async fn check(
+    ctx: &Context,
+    folder: &Path,
+    resources: &[ResourceItem],
+    args: &VerifyArgs,
+    list: Option<(&PathBuf, &ResourceFilter)>,
+) -> Outcome {
+    // Mapped files must not be truncated under the hash threads.
+    let _lock = args.hash_threads.is_some().then(|| ctx.lock_folder(folder));
+    let should_stop = Arc::new(AtomicBool::new(false));
+    setup_ctrlc(should_stop.clone(), &ctx.log_file);
+    let mut report = inspect(ctx, folder, resources, args, should_stop.clone()).await;
+    if let Some((list, filter)) = list {
+        report.extra = match extra_files(folder, resources, list, filter) {
+            Ok(extra) => extra,
+            Err(e) => ctx.fail(&format!("Failed to read {}: {}", folder.display(), e)),
+        };
+    }
+    print_verify_results(&report, folder, list.is_none());
+
+    if should_stop.load(Ordering::SeqCst) {
+        Outcome::Interrupted
+    } else if !report.problems.is_empty() || report.unchecked > 0 || !report.extra.is_empty() {
+        Outcome::Partial
+    } else {
+        Outcome::Success
+    }
+}
+
+/// Files below `folder` that `resources` does not name, leaving out this
+/// tool's own files and the checksum list itself.
+fn extra_files(
+    folder: &Path,
+    resources: &[ResourceItem],
+    list: &Path,
+    filter: &ResourceFilter,
+) -> io::Result<Vec<String>> {
+    let listed: HashSet<String> = resources
+        .iter()
+        .map(|item| sanitize_dest(&item.dest))
+        .collect();
+    let list = fs::canonicalize(list).ok();
+    Ok(list_files(folder)?
+        .into_iter()
+        .filter(|entry| {
+            !listed.contains(&entry.relative)
+                && !is_bookkeeping(&entry.relative)
+                && filter.matches(&entry.relative)
+                && fs::canonicalize(&entry.path).ok() != list
+        })
+        .map(|entry| entry.relative)
+        .collect())
+}
+
+/// Checks every resource against its size and checksum without modifying the folder.
+pub async fn inspect(
+    ctx: &Context,
+    folder: &Path,
+    resources: &[ResourceItem],
+    args: &VerifyArgs,
+    should_stop: Arc<AtomicBool>,
+) -> VerifyReport {
+    info!(
+        "Verifying {} files in {}",
+        resources.len().to_string().cyan(),
+        folder.display().to_string().cyan()
+    );
+
+    let bar = if ctx.cli.quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(resources.len() as u64)
+    };
+    bar.set_style(style::ticks(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [VERIFY] [{wide_bar:.green/blue}] {pos}/{len} ({eta})")
+            .unwrap()
+            .progress_chars("#>-"),
+    ));
+
+    let hash_cache = ctx.hash_cache(folder);
+    let pool = args.hash_threads.map(HashPool::mapped);
+    let jobs = args.jobs.max(args.hash_threads.unwrap_or(0));
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, item) in resources.iter().enumerate() {
+        let Ok(path) = resolve_dest(folder, &item.dest) else {
+            continue;
+        };
+        let item = item.clone();
+        let semaphore = semaphore.clone();
+        let should_stop = should_stop.clone();
+        let hash_cache = hash_cache.clone();
+        let pool = pool.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok();
+            if should_stop.load(Ordering::SeqCst) {
+                return (index, Err(VerificationError::Interrupted));
+            }
+            if hash_cache.is_cached(&path, &item).await {
+                return (index, Ok(FileState::Intact));
+            }
+            let state = match &pool {
+                Some(pool) => inspect_on_pool(pool, &path, &item, should_stop).await,
+                None => inspect_file(&path, item.checksum.as_ref(), item.size, should_stop).await,
+            };
+            if let (Ok(FileState::Intact), Some(checksum)) = (&state, &item.checksum) {
+                hash_cache.record(&path, checksum);
+            }
+            (index, state)
+        });
+    }
+
+    drop(pool);
+
+    let mut states = vec![None; resources.len()];
+    while let Some(joined) = tasks.join_next().await {
+        bar.inc(1);
+        match joined {
+            Ok((index, Ok(state))) => states[index] = Some(state),
+            Ok((_, Err(VerificationError::Interrupted))) => {}
+            Ok((index, Err(VerificationError::Io(e)))) => log_error(
+                &ctx.log_file,
+                &format!("Failed to verify {}: {}", resources[index].dest, e),
+            ),
+            Err(e) => log_error(&ctx.log_file, &format!("Verify task failed: {}", e)),
+        }
+    }
+    bar.finish_and_clear();
+    if let Err(e) = hash_cache.save() {
+        log_error(
+            &ctx.log_file,
+            &format!("Failed to save {}: {}", HASH_CACHE, e),
+        );
+    }
+
+    let mut report = VerifyReport {
+        intact: 0,
+        problems: Vec::new(),
+        unchecked: 0,
+        extra: Vec::new(),
+    };
+    for (item, state) in resources.iter().zip(states) {
+        match state {
+            Some(FileState::Intact) => report.intact += 1,
+            Some(state) => report.problems.push((item.clone(), state)),
+            None => report.unchecked += 1,
+        }
+    }
+
+    log_info(
+        &ctx.log_file,
+        &format!(
+            "Verified {}: {} intact, {} missing or damaged, {} unchecked",
+            folder.display(),
+            report.intact,
+            report.problems.len(),
+            report.unchecked
+        ),
+    );
+    report
+}
+
+/// `--hash-threads`: checks the size as usual, then hashes on the pool's threads.
+async fn inspect_on_pool(
+    pool: &HashPool,
+    path: &Path,
+    item: &ResourceItem,
+    should_stop: Arc<AtomicBool>,
+) -> Result<FileState, VerificationError> {
+    let state = inspect_file(path, None, item.size, should_stop.clone()).await?;
+    match (&state, &item.checksum) {
+        (FileState::Intact, Some(checksum)) => {
+            let actual = pool.hash(path, checksum.algorithm, should_stop).await?;
+            Ok(if checksum.matches(&actual) {
+                FileState::Intact
+            } else {
+                FileState::Corrupt
+            })
+        }
+        _ => Ok(state),
+    }
+}
@@ -0,0 +1,66 @@
+use colored::Colorize;
+use indicatif::HumanDuration;
+use log::{info, warn};
+use tokio::time::sleep;
+
+use crate::commands::Context;
+use crate::config::{cfg::Config, cli::WatchArgs};
+use crate::io::{logging::log_info, notify::notify, util::get_version};
+use crate::network::client::{channel_config, config_version, fetch_json, fetch_version_list};
+
+/// Polls the version list until the watched channel publishes a `predownload`
+/// config, then returns it. Network errors are reported and retried on the
+/// next poll rather than ending the watch.
+pub async fn wait_for_predownload(ctx: &Context, args: &WatchArgs) -> Config {
+    let source = &ctx.cli.source;
+    let label = format!("{} - {}", source.channel(), source.region().to_uppercase());
+    info!(
+        "Watching {} for a predownload, checking every {}",
+        label.cyan(),
+        HumanDuration(args.watch_interval).to_string().cyan()
+    );
+
+    loop {
+        match check_once(ctx).await {
+            Ok(Ok(config)) => {
+                log_info(
+                    &ctx.log_file,
+                    &format!("Predownload published for {}", label),
+                );
+                if ctx.notifications_enabled() {
+                    notify(
+                        "Predownload available",
+                        &format!("Starting the {} predownload", label),
+                    );
+                }
+                return config;
+            }
+            Ok(Err(version)) => info!(
+                "No predownload for {} yet (current version {})",
+                label,
+                version.cyan()
+            ),
+            Err(e) => warn!("Version check failed, retrying next time: {}", e),
+        }
+        sleep(args.watch_interval).await;
+    }
+}
+
+/// `Ok(Ok(config))` once the predownload exists, `Ok(Err(version))` with the
+/// current live version while it does not.
+async fn check_once(ctx: &Context) -> Result<Result<Config, String>, String> {
+    let source = &ctx.cli.source;
+    let versions = fetch_version_list(&ctx.client, source.source_url.as_deref()).await?;
+    let url = get_version(&versions, source.channel(), source.region())?;
+    let version_json = fetch_json(&ctx.client, &url).await?;
+
+    if version_json.get("predownload").is_none() {
+        return Ok(Err(config_version(&version_json, "default").to_string()));
+    }
+
+    info!(
+        "Predownload {} is available",
+        config_version(&version_json, "predownload").cyan()
+    );
+    channel_config(&version_json, "predownload").map(Ok)
+}
@@ -1,13 +1,69 @@
+use std::sync::Arc;
+
+use crate::download::backend::{DownloadBackend, NativeBackend};
+use crate::download::queue::DownloadOrder;
+use crate::io::{
+    file::Checksum, hash_cache::HashCache, hooks::Hook, journal::WriteJournal,
+    progress_log::ProgressLog, reuse::ReuseSource, url_log::UrlLog,
+};
+use crate::network::{
+    cdn_tracker::CdnTracker, metrics::Metrics, retry::RetryPolicy, url_map::UrlMap, web_ui::WebUi,
+    webhook::Webhook,
+};
+
 #[derive(Clone)]
 pub struct Config {
     pub index_url: String,
     pub zip_bases: Vec<String>,
 }
 
+/// How files already in the download folder are checked before being skipped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SkipExisting {
+    /// Size and checksum must match.
+    #[default]
+    Hash,
+    /// A matching size is enough; corruption that keeps the size goes unnoticed.
+    Size,
+}
+
 #[derive(Clone)]
 pub struct DownloadOptions {
     pub verify_concurrency: usize,
     pub download_concurrency: usize,
+    pub retry: RetryPolicy,
+    pub show_progress: bool,
+    pub preallocate: bool,
+    pub skip_existing: SkipExisting,
+    pub order: DownloadOrder,
+    /// Remove files that fail their checksum rather than quarantining them.
+    pub delete_corrupt: bool,
+    /// Installation whose matching files are taken over instead of downloaded.
+    pub reuse_from: Option<ReuseSource>,
+    /// Bytes at the end of a partial fetched again and compared before resuming.
+    pub verify_partials: Option<u64>,
+    /// Run after each file is downloaded and verified.
+    pub on_file_complete: Option<Hook>,
+    /// Receives a progress update every `Webhook::interval`.
+    pub webhook: Option<Webhook>,
+    /// Gets a progress line every `ProgressLog::interval`.
+    pub progress_log: Option<ProgressLog>,
+    /// Kept up to date for the `--metrics-port` endpoint.
+    pub metrics: Option<Metrics>,
+    /// Shows progress and takes pause/cancel presses for `--web-ui`.
+    pub web_ui: Option<WebUi>,
+    /// Lets files verified in an earlier session skip re-hashing.
+    pub hash_cache: HashCache,
+    /// Records how much of each partial is synced, for recovery after a crash.
+    pub journal: WriteJournal,
+    /// Counts each CDN's responses and benches the ones that keep refusing.
+    pub cdns: CdnTracker,
+    /// `--url-map` URLs tried before the CDNs for single files.
+    pub url_map: UrlMap,
+    /// `--log-urls`: where every requested URL is recorded.
+    pub url_log: UrlLog,
+    /// Fetches each file; the built-in HTTP client unless `--backend` says otherwise.
+    pub backend: Arc<dyn DownloadBackend>,
 }
 
 impl Default for DownloadOptions {
@@ -15,6 +71,25 @@ impl Default for DownloadOptions {
         Self {
             verify_concurrency: 8,
             download_concurrency: 4,
+            retry: RetryPolicy::default(),
+            show_progress: true,
+            preallocate: true,
+            skip_existing: SkipExisting::Hash,
+            order: DownloadOrder::Manifest,
+            delete_corrupt: false,
+            reuse_from: None,
+            verify_partials: None,
+            on_file_complete: None,
+            webhook: None,
+            progress_log: None,
+            metrics: None,
+            web_ui: None,
+            hash_cache: HashCache::disabled(),
+            journal: WriteJournal::disabled(),
+            cdns: CdnTracker::default(),
+            url_map: UrlMap::default(),
+            url_log: UrlLog::disabled(),
+            backend: Arc::new(NativeBackend),
         }
     }
 }
@@ -22,6 +97,24 @@ impl Default for DownloadOptions {
 #[derive(Clone, Debug)]
 pub struct ResourceItem {
     pub dest: String,
-    pub md5: Option<String>,
+    pub checksum: Option<Checksum>,
     pub size: Option<u64>,
+    /// Independently checked byte ranges, from the index's `chunkInfos`.
+    pub chunks: Vec<FileChunk>,
+    /// What an archive takes once extracted, from the index's `uncompressedSize`.
+    pub unpacked_size: Option<u64>,
+}
+
+/// One entry of a resource's `chunkInfos`; `end` is inclusive, as in a Range header.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileChunk {
+    pub start: u64,
+    pub end: u64,
+    pub checksum: Checksum,
+}
+
+impl FileChunk {
+    pub fn size(&self) -> u64 {
+        self.end - self.start + 1
+    }
 }
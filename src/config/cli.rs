@@ -0,0 +1,1029 @@
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum, builder::RangedU64ValueParser};
+use log::LevelFilter;
+use std::{
+    io::IsTerminal,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::config::cfg::SkipExisting;
+use crate::download::{
+    backend::{BackendKind, DownloadBackend, NativeBackend},
+    events::ProgressFormat,
+    filter::ResourceFilter,
+    queue::DownloadOrder,
+};
+use crate::io::{
+    file::HashAlgorithm,
+    hooks::{Hook, parse_hook},
+    i18n::Lang,
+};
+use crate::network::{
+    aria2::{Aria2Backend, DEFAULT_RPC_URL},
+    cdn_history::{CdnHistory, order_cdns},
+    http::{HttpOptions, IpFamily, parse_header, parse_proxy_map, parse_resolve},
+    retry::RetryPolicy,
+    webhook::Webhook,
+};
+use crate::sys::PowerAction;
+use reqwest::{
+    Url,
+    header::{HeaderName, HeaderValue},
+};
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    name = "wuwa-downloader",
+    version,
+    about = "Wuthering Waves Downloader",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+
+    /// Only print errors and the final summary
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print HTTP statuses and CDN decisions (-vv adds per-chunk traces)
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Directory for logs.log and its rotated predecessors
+    #[arg(long, global = true, value_name = "DIR", default_value = ".")]
+    pub log_dir: PathBuf,
+
+    /// Re-hash every existing file instead of trusting digests cached from earlier runs
+    #[arg(long, global = true)]
+    pub no_hash_cache: bool,
+
+    /// Do not ask GitHub whether a newer release exists
+    #[arg(long, global = true)]
+    pub no_update_check: bool,
+
+    /// Print without ANSI colors (also set by a non-empty NO_COLOR)
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    /// Use only ASCII in status glyphs, spinners and the file browser
+    #[arg(long, global = true)]
+    pub ascii: bool,
+
+    /// Language of prompts and result screens (default: system locale)
+    #[arg(long, global = true, value_enum, env = "WUWA_LANG")]
+    pub lang: Option<Lang>,
+
+    /// Never prompt, draw progress bars or wait for Enter; every choice comes from
+    /// flags. Implied when stdout is not a terminal
+    #[arg(long, global = true)]
+    pub headless: bool,
+
+    /// Run at low CPU and disk priority and cap disk throughput, to keep games smooth
+    #[arg(long, global = true)]
+    pub background: bool,
+
+    /// Keep the system from going to sleep until the run ends
+    #[arg(long, global = true)]
+    pub keep_awake: bool,
+
+    #[command(flatten)]
+    pub source: SourceArgs,
+
+    #[command(flatten)]
+    pub http: HttpArgs,
+
+    /// Options for running without a subcommand, which downloads
+    #[command(flatten)]
+    pub download: DownloadArgs,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Commands {
+    /// Download a game version (the default when no subcommand is given)
+    Download(DownloadArgs),
+    /// Check files on disk against the index without changing anything
+    Verify(VerifyArgs),
+    /// Verify files on disk and re-download only the missing or damaged ones
+    Repair(RepairArgs),
+    /// Save the selected resources as an index file or a URL list
+    Export(ExportArgs),
+    /// List the files added, removed and changed between two indexes
+    Diff(DiffArgs),
+    /// Print the version and download size of every channel
+    Info(InfoArgs),
+    /// Remove partial downloads and other leftovers from a download folder
+    Clean(CleanArgs),
+    /// Report reachability, latency and throughput of every CDN, then exit
+    CheckCdn,
+    /// Measure every CDN's speed and estimate how long the selected files take to download
+    Speedtest(SpeedtestArgs),
+    /// Replace this program with the latest GitHub release
+    SelfUpdate(SelfUpdateArgs),
+    /// Serve a --mirror or download folder over HTTP to other PCs
+    Serve(ServeArgs),
+    /// Pick a version and folder in a window and follow the download there
+    #[cfg(feature = "gui")]
+    Gui,
+}
+
+/// Where the version list, index and resources come from.
+#[derive(Args, Debug, Clone)]
+pub struct SourceArgs {
+    /// Version list to pick game versions from, instead of the built-in gist
+    #[arg(long, global = true, value_name = "URL", env = "WUWA_SOURCE_URL")]
+    pub source_url: Option<String>,
+
+    /// Load the resource index from this file (or http:// URL) instead of the version's CDN
+    #[arg(long, global = true, value_name = "PATH")]
+    pub index_file: Option<PathBuf>,
+
+    /// Resource base URL to download from (repeatable, tried in order); with
+    /// --index-file this skips the version and CDN prompts entirely
+    #[arg(long, global = true, value_name = "URL", value_parser = parse_base_url)]
+    pub base_url: Vec<String>,
+
+    /// File of resource base URLs, one per line with an optional weight
+    /// ("URL 10"); higher weights are tried first. Replaces --base-url
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "base_url")]
+    pub cdn_list: Option<PathBuf>,
+
+    /// Priority of a CDN host (repeatable, e.g. "cdn.example.com=10"); higher
+    /// is tried first, negative only as a last resort, unlisted hosts are 0
+    #[arg(long, global = true, value_name = "HOST=N", value_parser = parse_cdn_priority)]
+    pub cdn_priority: Vec<(String, i32)>,
+
+    /// Release channel to use instead of the version prompt (info: only show this channel)
+    #[arg(long, global = true, value_parser = ["live", "beta"])]
+    pub channel: Option<String>,
+
+    /// Region to use instead of the version prompt (info: only show this region)
+    #[arg(long, global = true, value_parser = ["os", "cn"])]
+    pub region: Option<String>,
+
+    /// Use the channel's predownload instead of its current version
+    #[arg(long, global = true)]
+    pub predownload: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct HttpArgs {
+    /// Idle keep-alive connections to keep open per CDN host
+    #[arg(long, global = true, value_name = "N", default_value_t = HttpOptions::default().pool_size)]
+    pub pool_size: usize,
+
+    /// Disable TCP_NODELAY on CDN connections
+    #[arg(long, global = true)]
+    pub no_tcp_nodelay: bool,
+
+    /// Force HTTP/1.1 even when a CDN offers HTTP/2
+    #[arg(long, global = true)]
+    pub http1: bool,
+
+    /// User-Agent sent with every request
+    #[arg(long, global = true, value_name = "UA")]
+    pub user_agent: Option<String>,
+
+    /// Extra request header sent with every request (repeatable, e.g. "Referer: https://...")
+    #[arg(long = "header", global = true, value_name = "KEY: VALUE", value_parser = parse_header)]
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+
+    /// Time allowed to open a connection to a CDN (e.g. 15s)
+    #[arg(long, global = true, value_parser = parse_duration, default_value = "15s")]
+    pub connect_timeout: Duration,
+
+    /// Give up on a response that sends nothing for this long; slow transfers are never cut off
+    #[arg(long, global = true, value_parser = parse_duration, default_value = "60s")]
+    pub read_timeout: Duration,
+
+    /// Time allowed for each HEAD request that probes a missing file size
+    #[arg(long, global = true, value_parser = parse_duration, default_value = "15s")]
+    pub head_timeout: Duration,
+
+    /// Connect to CDNs over IPv4 only
+    #[arg(long, global = true, conflicts_with = "ipv6")]
+    pub ipv4: bool,
+
+    /// Connect to CDNs over IPv6 only
+    #[arg(long, global = true)]
+    pub ipv6: bool,
+
+    /// Pause between index, version list and HEAD requests (e.g. 500ms)
+    #[arg(long, global = true, value_parser = parse_duration, default_value = "0s")]
+    pub request_delay: Duration,
+
+    /// Most HEAD requests per second when probing missing file sizes
+    #[arg(long, global = true, value_name = "N", value_parser = RangedU64ValueParser::<u32>::new().range(1..))]
+    pub max_head_rps: Option<u32>,
+
+    /// Connect to HOST at ADDR instead of looking it up (repeatable, e.g. cdn.example.com:443:1.2.3.4)
+    #[arg(long, global = true, value_name = "HOST:PORT:ADDR", value_parser = parse_resolve)]
+    pub resolve: Vec<(String, SocketAddr)>,
+
+    /// Look CDN hosts up through this DNS-over-HTTPS JSON endpoint (e.g. https://cloudflare-dns.com/dns-query)
+    #[arg(long, global = true, value_name = "URL", value_parser = Url::parse)]
+    pub doh_url: Option<Url>,
+
+    /// Reach HOST through its own proxy (repeatable, e.g. cdn.example.com=socks5h://127.0.0.1:9050);
+    /// unlisted hosts connect directly
+    #[arg(long, global = true, value_name = "HOST=PROXY", value_parser = parse_proxy_map)]
+    pub proxy_map: Vec<(String, Url)>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct FolderArgs {
+    /// Download folder; skips the folder picker and prompt
+    #[arg(long, value_name = "PATH", env = "WUWA_DIR")]
+    pub dir: Option<PathBuf>,
+
+    /// Type the download folder instead of opening the folder picker (Windows)
+    #[arg(long)]
+    pub no_picker: bool,
+}
+
+/// Which resources of the index a command works on.
+#[derive(Args, Debug, Clone)]
+pub struct FilterArgs {
+    /// Only use resources whose path matches this glob (repeatable, e.g. "*.pak")
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip resources whose path matches this glob (repeatable, e.g. "zip/audio/*")
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Voice-over languages to keep (comma-separated: zh,en,ja,ko or all); prompts when omitted
+    #[arg(long, value_name = "LANGS", value_delimiter = ',')]
+    pub audio_lang: Vec<String>,
+
+    /// Index resource groups to include (comma-separated, e.g. resource,patchResource); all by default
+    #[arg(long = "group", value_name = "GROUPS", value_delimiter = ',')]
+    pub groups: Vec<String>,
+
+    /// Pick files and folders in an interactive tree before starting (replaces the voice-over prompt)
+    #[arg(long)]
+    pub browse: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct TransferArgs {
+    /// Attempts per CDN for transient failures (timeouts, 5xx), the first included
+    #[arg(long, default_value_t = RetryPolicy::default().max_attempts)]
+    pub attempts: usize,
+
+    /// Base delay between retries, doubled on every attempt (e.g. 500ms, 2s, 1m)
+    #[arg(long, value_parser = parse_duration, default_value = "1s")]
+    pub retry_backoff: Duration,
+
+    /// Free space to keep on the target drive on top of the download (e.g. 512MB, 2GiB)
+    #[arg(long, value_parser = parse_size, default_value = "1GiB")]
+    pub space_margin: u64,
+
+    /// Do not reserve disk space for each file before downloading it
+    #[arg(long)]
+    pub no_preallocate: bool,
+
+    /// Order to process files in
+    #[arg(long, value_enum, default_value_t = DownloadOrder::Manifest)]
+    pub order: DownloadOrder,
+
+    /// Take files that match the index from this existing installation
+    /// instead of downloading them (cloned where the filesystem allows, else copied)
+    #[arg(long, value_name = "DIR")]
+    pub reuse_from: Option<PathBuf>,
+
+    /// Hardlink files taken from --reuse-from; both installs then share them
+    #[arg(long, requires = "reuse_from")]
+    pub reuse_hardlink: bool,
+
+    /// Write checksums.md5 (or =md5,sha256 etc.) listing every intact file, for md5sum -c
+    #[arg(
+        long,
+        value_enum,
+        value_name = "ALGORITHMS",
+        value_delimiter = ',',
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "md5"
+    )]
+    pub write_checksums: Vec<ChecksumFormat>,
+
+    /// Download files with identical content separately instead of copying one download
+    #[arg(long)]
+    pub no_dedupe: bool,
+
+    /// How long to stop using a CDN that answered 429, 403 or 5xx three times
+    /// in a row (e.g. 1m); 0 never stops using one
+    #[arg(long, value_parser = parse_duration, default_value = "60s")]
+    pub cdn_cooldown: Duration,
+
+    /// JSON object mapping index paths to full URLs that are tried before the
+    /// CDNs, for single files rehosted elsewhere
+    #[arg(long, value_name = "PATH")]
+    pub url_map: Option<PathBuf>,
+
+    /// Delete files that fail their checksum instead of moving them to quarantine/
+    #[arg(long)]
+    pub delete_corrupt: bool,
+
+    /// Progress output: colored terminal UI or newline-delimited JSON events
+    #[arg(long, value_enum, default_value_t = ProgressFormat::Human)]
+    pub progress_format: ProgressFormat,
+
+    /// Write JSON events to this file or named pipe instead of stdout
+    #[arg(long, value_name = "PATH")]
+    pub progress_output: Option<PathBuf>,
+
+    /// Show a desktop notification when the download finishes, fails or is interrupted
+    #[arg(long)]
+    pub notify: bool,
+
+    /// Power the machine off once every file downloaded and verified
+    #[arg(long, conflicts_with = "hibernate_on_complete")]
+    pub shutdown_on_complete: bool,
+
+    /// Hibernate the machine once every file downloaded and verified
+    #[arg(long)]
+    pub hibernate_on_complete: bool,
+
+    /// Command to run when the session completes; placeholders: {folder} {success} {failed} {total}
+    #[arg(long, value_name = "COMMAND", value_parser = parse_hook)]
+    pub on_complete: Option<Hook>,
+
+    /// Command to run after each downloaded file; placeholders: {folder} {file} {dest}
+    #[arg(long, value_name = "COMMAND", value_parser = parse_hook)]
+    pub on_file_complete: Option<Hook>,
+
+    /// Post progress updates and the final summary to this Discord, Slack or Telegram webhook
+    #[arg(long, value_name = "URL")]
+    pub webhook_url: Option<String>,
+
+    /// How often to post progress to --webhook-url (e.g. 10m, 1h)
+    #[arg(long, value_parser = parse_duration, default_value = "10m", requires = "webhook_url")]
+    pub webhook_interval: Duration,
+
+    /// Record every URL files are requested from, once each, with its dest and
+    /// MD5 as tab-separated lines in urls.txt in --log-dir
+    #[arg(long)]
+    pub log_urls: bool,
+
+    /// Write each file's outcome, bytes, duration, speed, requests and CDN to this CSV file
+    #[arg(long, value_name = "PATH")]
+    pub stats_csv: Option<PathBuf>,
+
+    /// Append a progress line (bytes, speed, ETA, current file) to this file
+    /// every --progress-log-interval, for runs without a terminal
+    #[arg(long, value_name = "PATH")]
+    pub progress_log: Option<PathBuf>,
+
+    /// How often to write to --progress-log (e.g. 30s, 5m)
+    #[arg(long, value_parser = parse_duration, default_value = "10s", requires = "progress_log")]
+    pub progress_log_interval: Duration,
+
+    /// Before resuming a partial file, download its last --verify-partials-tail
+    /// bytes again and restart it if they differ
+    #[arg(long)]
+    pub verify_partials: bool,
+
+    /// How much of a partial's end --verify-partials compares, up to 1MiB (e.g. 512KiB)
+    #[arg(long, value_parser = parse_size, default_value = "1MiB", requires = "verify_partials")]
+    pub verify_partials_tail: u64,
+
+    /// How often in-flight files are synced to disk and journaled, so a resume
+    /// after a power loss only trusts synced bytes; 0 turns this off
+    #[arg(long, value_parser = parse_duration, default_value = "5s")]
+    pub fsync_interval: Duration,
+
+    /// Serve Prometheus metrics on this port while downloading
+    #[arg(long, value_name = "PORT")]
+    pub metrics_port: Option<u16>,
+
+    /// Serve a status page with pause/cancel buttons at this address (e.g. 127.0.0.1:8080)
+    #[arg(long, value_name = "ADDR")]
+    pub web_ui: Option<SocketAddr>,
+
+    /// Download with the built-in client or a running aria2c daemon
+    #[arg(long, value_enum, default_value_t = BackendKind::Native)]
+    pub backend: BackendKind,
+
+    /// aria2 JSON-RPC endpoint used by --backend aria2
+    #[arg(long, value_name = "URL", default_value = DEFAULT_RPC_URL)]
+    pub aria2_rpc: String,
+
+    /// aria2 --rpc-secret, if the daemon was started with one
+    #[arg(
+        long,
+        value_name = "SECRET",
+        env = "ARIA2_SECRET",
+        hide_env_values = true
+    )]
+    pub aria2_secret: Option<String>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DownloadArgs {
+    #[command(flatten)]
+    pub folder: FolderArgs,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+
+    #[command(flatten)]
+    pub transfer: TransferArgs,
+
+    /// Parallel downloads; skips the concurrency prompt
+    #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub concurrency: Option<usize>,
+
+    /// Files hashed in parallel while checking existing files; skips the concurrency prompt
+    #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub verify_jobs: Option<usize>,
+
+    /// Unpack downloaded .zip resources next to where they were downloaded
+    #[arg(long)]
+    pub extract: bool,
+
+    /// Delete archives after they were extracted successfully (re-downloaded on the next run)
+    #[arg(long, requires = "extract")]
+    pub cleanup: bool,
+
+    /// Unpack archives while they download instead of saving them first, halving
+    /// the disk space needed; archives that cannot be read as a stream are saved as usual
+    #[arg(long, requires = "extract", conflicts_with = "retry_failed")]
+    pub stream_extract: bool,
+
+    /// Only re-download the resources listed in failed.json from the previous session
+    #[arg(long)]
+    pub retry_failed: bool,
+
+    /// How to check files already on disk: `hash` re-verifies checksums, `size` trusts a matching size
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = SkipExisting::Hash)]
+    pub skip_existing: SkipExisting,
+
+    /// Store every file of the index by checksum, with a copy of the index and
+    /// a files/ tree that other PCs can download from with --base-url
+    #[arg(
+        long,
+        conflicts_with_all = ["include", "exclude", "audio_lang", "groups", "browse", "retry_failed", "extract"]
+    )]
+    pub mirror: bool,
+
+    /// Print every top-level key of the index with its entry count and which
+    /// of them are read as files, then exit
+    #[arg(long, conflicts_with_all = ["mirror", "retry_failed", "watch"])]
+    pub list_groups: bool,
+
+    #[command(flatten)]
+    pub watch: WatchArgs,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WatchArgs {
+    /// Wait for a predownload to be published, then download it without prompting
+    #[arg(long, conflicts_with = "retry_failed")]
+    pub watch: bool,
+
+    /// How often --watch checks the version list (e.g. 5m, 1h)
+    #[arg(long, value_parser = parse_duration, default_value = "10m", requires = "watch")]
+    pub watch_interval: Duration,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    pub folder: FolderArgs,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+
+    /// Files hashed in parallel
+    #[arg(long, value_name = "N", default_value_t = 8)]
+    pub jobs: usize,
+
+    /// Hash on N dedicated threads over memory-mapped files in large slices, for fast drives
+    #[arg(long, value_name = "N", value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub hash_threads: Option<usize>,
+
+    /// Check the folder against this md5sum/sha256sum list instead of the index, offline
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["groups", "audio_lang", "browse"])]
+    pub checksums: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct RepairArgs {
+    #[command(flatten)]
+    pub verify: VerifyArgs,
+
+    #[command(flatten)]
+    pub transfer: TransferArgs,
+}
+
+/// Digest lists `--write-checksums` can write; XXH3 is left out, as
+/// coreutils cannot check it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ChecksumFormat {
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl ChecksumFormat {
+    pub fn algorithm(self) -> HashAlgorithm {
+        match self {
+            ChecksumFormat::Md5 => HashAlgorithm::Md5,
+            ChecksumFormat::Sha1 => HashAlgorithm::Sha1,
+            ChecksumFormat::Sha256 => HashAlgorithm::Sha256,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Index JSON that --index-file can load
+    Json,
+    /// One download URL per line, for external download managers
+    Urls,
+    /// Metalink 4 (.meta4) listing every CDN mirror, size and hash per file
+    Metalink,
+    /// aria2c input file (aria2c -i) with every CDN mirror and hash per file
+    Aria2,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub filter: FilterArgs,
+
+    #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+    pub format: ExportFormat,
+
+    /// File to write; prints to stdout when omitted. With --all-regions, the
+    /// folder to write one file per channel to
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Export every channel (live/beta × os/cn) in one run, one file per channel in --output
+    #[arg(
+        long,
+        requires = "output",
+        conflicts_with_all = ["channel", "region", "index_file", "browse"]
+    )]
+    pub all_regions: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DiffArgs {
+    /// Index of the older version: a file or http(s):// URL
+    #[arg(long, value_name = "PATH|URL")]
+    pub old: String,
+
+    /// Index of the newer version: a file or http(s):// URL
+    #[arg(long, value_name = "PATH|URL")]
+    pub new: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct InfoArgs {
+    /// Only list versions, without downloading every index to total its size
+    #[arg(long)]
+    pub no_sizes: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CleanArgs {
+    #[command(flatten)]
+    pub folder: FolderArgs,
+
+    /// Also delete files the index does not list
+    #[arg(long)]
+    pub orphans: bool,
+
+    /// Delete orphans without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+
+    /// List what would be deleted without deleting it
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SelfUpdateArgs {
+    /// Only report whether a newer release exists
+    #[arg(long)]
+    pub check: bool,
+
+    /// Install the update without asking for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SpeedtestArgs {
+    #[command(flatten)]
+    pub filter: FilterArgs,
+
+    /// Bytes to read from every CDN (e.g. 16MiB); larger samples even out slow starts
+    #[arg(long, value_parser = parse_size, default_value = "16MiB")]
+    pub sample_size: u64,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ServeArgs {
+    /// Folder to serve: a --mirror folder or any download folder
+    #[arg(long, value_name = "PATH")]
+    pub dir: PathBuf,
+
+    /// Port to listen on, on every interface
+    #[arg(long, default_value_t = 8081)]
+    pub port: u16,
+}
+
+impl Cli {
+    /// The subcommand to run; a bare invocation downloads.
+    pub fn command(&self) -> Commands {
+        self.command
+            .clone()
+            .unwrap_or_else(|| Commands::Download(self.download.clone()))
+    }
+
+    pub fn http_options(&self) -> HttpOptions {
+        HttpOptions {
+            pool_size: self.http.pool_size,
+            tcp_nodelay: !self.http.no_tcp_nodelay,
+            http1_only: self.http.http1,
+            user_agent: self.http.user_agent.clone(),
+            headers: self.http.headers.clone(),
+            connect_timeout: self.http.connect_timeout,
+            read_timeout: self.http.read_timeout,
+            ip_family: if self.http.ipv4 {
+                Some(IpFamily::V4)
+            } else if self.http.ipv6 {
+                Some(IpFamily::V6)
+            } else {
+                None
+            },
+            resolve: self.http.resolve.clone(),
+            doh_url: self.http.doh_url.clone(),
+            proxy_map: self.http.proxy_map.clone(),
+        }
+    }
+
+    pub fn log_level(&self) -> LevelFilter {
+        match (self.quiet, self.verbose) {
+            (true, _) => LevelFilter::Error,
+            (false, 0) => LevelFilter::Info,
+            (false, 1) => LevelFilter::Debug,
+            (false, _) => LevelFilter::Trace,
+        }
+    }
+
+    /// Whether prompts and other interactive screens must be avoided.
+    pub fn headless(&self) -> bool {
+        self.headless || !std::io::stdout().is_terminal()
+    }
+
+    /// JSON events (and exported files without `--output`) own stdout, so
+    /// everything else has to go to stderr.
+    pub fn json_to_stdout(&self) -> bool {
+        match self.command() {
+            Commands::Download(args) => args.transfer.json_to_stdout(),
+            Commands::Repair(args) => args.transfer.json_to_stdout(),
+            Commands::Export(args) => args.output.is_none(),
+            _ => false,
+        }
+    }
+}
+
+impl TransferArgs {
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.attempts,
+            base_delay: self.retry_backoff,
+            ..RetryPolicy::default()
+        }
+    }
+
+    pub fn json_to_stdout(&self) -> bool {
+        self.progress_format == ProgressFormat::Json && self.progress_output.is_none()
+    }
+
+    pub fn backend(&self) -> Arc<dyn DownloadBackend> {
+        match self.backend {
+            BackendKind::Native => Arc::new(NativeBackend),
+            BackendKind::Aria2 => Arc::new(Aria2Backend {
+                rpc_url: self.aria2_rpc.clone(),
+                secret: self.aria2_secret.clone(),
+            }),
+        }
+    }
+
+    pub fn power_action(&self) -> Option<PowerAction> {
+        if self.shutdown_on_complete {
+            Some(PowerAction::Shutdown)
+        } else if self.hibernate_on_complete {
+            Some(PowerAction::Hibernate)
+        } else {
+            None
+        }
+    }
+
+    pub fn webhook(&self) -> Option<Webhook> {
+        self.webhook_url.as_ref().map(|url| Webhook {
+            url: url.clone(),
+            interval: self.webhook_interval,
+        })
+    }
+}
+
+impl SourceArgs {
+    /// Reads `--cdn-list` into `base_url`, so it works wherever `--base-url`
+    /// does, and orders the URLs by `--cdn-priority`.
+    pub fn prepare_base_urls(&mut self) -> Result<(), String> {
+        if let Some(path) = &self.cdn_list {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            self.base_url =
+                parse_cdn_list(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        order_cdns(&mut self.base_url, &self.cdn_priority, None);
+        Ok(())
+    }
+
+    /// Orders the CDNs of a remote config by `--cdn-priority`, then by how
+    /// they fared in past sessions.
+    pub fn order_cdns(&self, zip_bases: &mut [String], log_dir: &Path) {
+        let history = CdnHistory::load(log_dir);
+        order_cdns(zip_bases, &self.cdn_priority, Some(&history));
+    }
+
+    pub fn channel(&self) -> &str {
+        self.channel.as_deref().unwrap_or("live")
+    }
+
+    pub fn region(&self) -> &str {
+        self.region.as_deref().unwrap_or("os")
+    }
+
+    /// The config to read from the channel's version file.
+    pub fn selected_config(&self) -> &'static str {
+        if self.predownload {
+            "predownload"
+        } else {
+            "default"
+        }
+    }
+}
+
+impl FilterArgs {
+    pub fn resource_filter(&self) -> ResourceFilter {
+        ResourceFilter {
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+        }
+    }
+}
+
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid duration: {}", value))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        other => return Err(format!("Unknown duration unit '{}' in {}", other, value)),
+    };
+
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!("Invalid duration: {}", value));
+    }
+
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Accepts a base URL with or without a scheme; `https://` is assumed.
+pub fn parse_base_url(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("Base URL cannot be empty".to_string());
+    }
+
+    let url = if value.starts_with("http://") || value.starts_with("https://") {
+        value.to_string()
+    } else {
+        format!("https://{}", value)
+    };
+    Ok(format!("{}/", url.trim_end_matches('/')))
+}
+
+/// Base URLs of a `--cdn-list` file, highest weight first; a line without a
+/// weight counts as 1 and equal weights keep the file's order.
+fn parse_cdn_list(contents: &str) -> Result<Vec<String>, String> {
+    let mut bases = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let url = parse_base_url(fields.next().unwrap_or_default())?;
+        let weight = match fields.next() {
+            Some(weight) => weight
+                .parse::<u32>()
+                .map_err(|_| format!("line {}: invalid weight \"{}\"", number + 1, weight))?,
+            None => 1,
+        };
+        if fields.next().is_some() {
+            return Err(format!("line {} is not \"<URL> [weight]\"", number + 1));
+        }
+        bases.push((weight, url));
+    }
+    if bases.is_empty() {
+        return Err("no base URLs listed".to_string());
+    }
+    bases.sort_by_key(|(weight, _)| std::cmp::Reverse(*weight));
+    Ok(bases.into_iter().map(|(_, url)| url).collect())
+}
+
+fn parse_cdn_priority(value: &str) -> Result<(String, i32), String> {
+    let (host, priority) = value
+        .split_once('=')
+        .ok_or_else(|| "expected HOST=N".to_string())?;
+    let host = host.trim();
+    if host.is_empty() {
+        return Err("expected HOST=N".to_string());
+    }
+    let priority = priority
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid priority \"{}\"", priority.trim()))?;
+    Ok((host.to_string(), priority))
+}
+
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size: {}", value))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        "t" | "tb" => 1_000_000_000_000,
+        "tib" => 1 << 40,
+        other => return Err(format!("Unknown size unit '{}' in {}", other, value)),
+    };
+
+    if !number.is_finite() || number < 0.0 {
+        return Err(format!("Invalid size: {}", value));
+    }
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Cli, Commands, parse_base_url, parse_cdn_list, parse_cdn_priority, parse_duration,
+        parse_size,
+    };
+    use crate::sys::PowerAction;
+    use clap::Parser;
+    use std::{path::PathBuf, time::Duration};
+
+    #[test]
+    fn parse_duration_accepts_units() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn parse_size_accepts_decimal_and_binary_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("2MB").unwrap(), 2_000_000);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        assert_eq!(parse_size("1.5k").unwrap(), 1_500);
+        assert!(parse_size("3 parsecs").is_err());
+    }
+
+    #[test]
+    fn parse_base_url_adds_scheme_and_trailing_slash() {
+        assert_eq!(
+            parse_base_url("cdn.example.com/zip").unwrap(),
+            "https://cdn.example.com/zip/"
+        );
+        assert_eq!(
+            parse_base_url("http://mirror.local/zip//").unwrap(),
+            "http://mirror.local/zip/"
+        );
+        assert!(parse_base_url("  ").is_err());
+    }
+
+    #[test]
+    fn cdn_list_orders_by_weight() {
+        let list = "# mirrors\n\
+                    cdn-a.example.com/zip\n\
+                    https://fast.example.com/zip/ 10\n\
+                    \n\
+                    http://slow.example.com/zip 0\n\
+                    cdn-b.example.com/zip 1\n";
+        assert_eq!(
+            parse_cdn_list(list).unwrap(),
+            [
+                "https://fast.example.com/zip/",
+                "https://cdn-a.example.com/zip/",
+                "https://cdn-b.example.com/zip/",
+                "http://slow.example.com/zip/",
+            ]
+        );
+        assert!(parse_cdn_list("cdn.example.com fast").is_err());
+        assert!(parse_cdn_list("# nothing\n").is_err());
+        assert_eq!(
+            parse_cdn_priority("cdn.example.com=-5").unwrap(),
+            ("cdn.example.com".to_string(), -5)
+        );
+        assert!(parse_cdn_priority("cdn.example.com").is_err());
+    }
+
+    #[test]
+    fn bare_invocation_downloads_and_subcommands_take_their_own_options() {
+        let cli = Cli::try_parse_from(["wuwa-downloader", "--dir", "games", "--extract"]).unwrap();
+        let Commands::Download(args) = cli.command() else {
+            panic!("expected download");
+        };
+        assert_eq!(args.folder.dir, Some(PathBuf::from("games")));
+        assert!(args.extract);
+
+        let cli = Cli::try_parse_from(["wuwa-downloader", "verify", "--jobs", "2", "-q"]).unwrap();
+        assert!(matches!(cli.command(), Commands::Verify(args) if args.jobs == 2));
+        assert!(cli.quiet);
+
+        assert!(Cli::try_parse_from(["wuwa-downloader", "info", "--extract"]).is_err());
+    }
+
+    #[test]
+    fn headless_version_flags_are_global() {
+        let cli = Cli::try_parse_from([
+            "wuwa-downloader",
+            "repair",
+            "--headless",
+            "--channel",
+            "beta",
+            "--predownload",
+        ])
+        .unwrap();
+        assert!(cli.headless());
+        assert_eq!(cli.source.channel(), "beta");
+        assert_eq!(cli.source.region(), "os");
+        assert_eq!(cli.source.selected_config(), "predownload");
+
+        let cli = Cli::try_parse_from(["wuwa-downloader", "--concurrency", "8"]).unwrap();
+        assert!(matches!(cli.command(), Commands::Download(args) if args.concurrency == Some(8)));
+        assert!(Cli::try_parse_from(["wuwa-downloader", "--concurrency", "0"]).is_err());
+    }
+
+    #[test]
+    fn one_power_action_at_a_time() {
+        let cli =
+            Cli::try_parse_from(["wuwa-downloader", "repair", "--hibernate-on-complete"]).unwrap();
+        assert!(
+            matches!(cli.command(), Commands::Repair(args) if args.transfer.power_action() == Some(PowerAction::Hibernate))
+        );
+        assert!(
+            Cli::try_parse_from([
+                "wuwa-downloader",
+                "--shutdown-on-complete",
+                "--hibernate-on-complete"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn parse_duration_rejects_garbage() {
+        assert!(parse_duration("fast").is_err());
+        assert!(parse_duration("5d").is_err());
+    }
+}
@@ -1,2 +1,5 @@
 pub mod cfg;
+pub mod cli;
+pub mod outcome;
 pub mod status;
+pub mod style;
@@ -0,0 +1,32 @@
+use std::process::ExitCode;
+
+/// How a run ended. `main` reports it as the process exit code so wrapper
+/// scripts can react without parsing the output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Everything requested was done.
+    Success = 0,
+    /// The run finished, but some files failed or are still missing or damaged.
+    Partial = 2,
+    /// Nothing could be done: network, config, index, argument or folder error.
+    Error = 3,
+    /// Stopped with Ctrl-C.
+    Interrupted = 130,
+}
+
+impl Outcome {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    /// Ends the process right away, for paths that cannot return to `main`.
+    pub fn exit(self) -> ! {
+        std::process::exit(self.code().into())
+    }
+}
+
+impl From<Outcome> for ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        ExitCode::from(outcome.code())
+    }
+}
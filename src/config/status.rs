@@ -1,5 +1,7 @@
 use colored::*;
 
+use crate::config::style;
+
 #[derive(Clone, Copy)]
 pub struct Status;
 
@@ -20,9 +22,9 @@ impl Status {
         "[?]".blue()
     }
     pub fn progress() -> ColoredString {
-        "[→]".purple()
+        style::glyph("[→]", "[>]").purple()
     }
     pub fn matched() -> ColoredString {
-        "[↓]".bright_purple()
+        style::glyph("[↓]", "[v]").bright_purple()
     }
 }
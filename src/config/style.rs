@@ -0,0 +1,50 @@
+//! Output styling switches: ANSI colors and non-ASCII glyphs. Everything that
+//! colors text or draws symbols asks here, so `--no-color` and `--ascii` reach
+//! status glyphs, progress bars and the file browser alike.
+
+use indicatif::ProgressStyle;
+use std::{
+    env,
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+/// Settles styling for the process. Color is off with `no_color`, when
+/// `NO_COLOR` is set to anything, or when stdout is not a terminal unless
+/// `CLICOLOR_FORCE` asks for it.
+pub fn init(no_color: bool, ascii: bool) {
+    let env_set = |name: &str| env::var_os(name).is_some_and(|value| !value.is_empty());
+    let forced = env::var_os("CLICOLOR_FORCE").is_some_and(|value| value != "0");
+    let color = !no_color && !env_set("NO_COLOR") && (forced || io::stdout().is_terminal());
+
+    COLOR.store(color, Ordering::SeqCst);
+    ASCII.store(ascii, Ordering::SeqCst);
+    colored::control::set_override(color);
+    console::set_colors_enabled(color);
+    console::set_colors_enabled_stderr(color);
+}
+
+pub fn color_enabled() -> bool {
+    COLOR.load(Ordering::SeqCst)
+}
+
+pub fn ascii() -> bool {
+    ASCII.load(Ordering::SeqCst)
+}
+
+/// `fancy`, or `plain` under `--ascii`.
+pub fn glyph(fancy: &'static str, plain: &'static str) -> &'static str {
+    if ascii() { plain } else { fancy }
+}
+
+/// Swaps a progress style's braille spinner for ASCII frames under `--ascii`.
+pub fn ticks(style: ProgressStyle) -> ProgressStyle {
+    if ascii() {
+        style.tick_chars("-\\|/ ")
+    } else {
+        style
+    }
+}
@@ -0,0 +1,54 @@
+use indicatif::ProgressBar;
+use reqwest::Client;
+use std::{future::Future, path::Path, pin::Pin, sync::atomic::AtomicBool};
+
+use crate::config::cfg::{Config, DownloadOptions, ResourceItem};
+use crate::download::{control::DownloadControl, progress::DownloadProgress};
+use crate::io::logging::SharedLogFile;
+use crate::network::client::{DownloadedFile, download_file};
+
+/// Which [`DownloadBackend`] fetches files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum BackendKind {
+    /// Built-in HTTP client with resume, chunk repair and CDN fallback
+    #[default]
+    Native,
+    /// Hand every file to a running aria2c daemon over JSON-RPC
+    Aria2,
+}
+
+/// One resource to fetch into its `.part` file below `folder`, with everything
+/// a backend needs to report progress and notice cancellation.
+pub struct DownloadJob<'a> {
+    pub client: &'a Client,
+    pub config: &'a Config,
+    pub item: &'a ResourceItem,
+    pub expected_size: Option<u64>,
+    pub folder: &'a Path,
+    pub log_file: &'a SharedLogFile,
+    pub should_stop: &'a AtomicBool,
+    pub control: &'a DownloadControl,
+    pub progress: &'a DownloadProgress,
+    pub total_pb: &'a ProgressBar,
+    pub task_pb: &'a ProgressBar,
+    pub options: &'a DownloadOptions,
+}
+
+pub type FetchFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<DownloadedFile, String>> + Send + 'a>>;
+
+/// Fetches files for the pipeline. Verifying the result, retrying after a
+/// checksum mismatch and renaming the `.part` file stay with the pipeline;
+/// a backend only writes the `.part` file and counts its bytes in `progress`.
+pub trait DownloadBackend: Send + Sync {
+    fn fetch<'a>(&'a self, job: &'a DownloadJob<'a>) -> FetchFuture<'a>;
+}
+
+/// The built-in HTTP downloader.
+pub struct NativeBackend;
+
+impl DownloadBackend for NativeBackend {
+    fn fetch<'a>(&'a self, job: &'a DownloadJob<'a>) -> FetchFuture<'a> {
+        Box::pin(download_file(job))
+    }
+}
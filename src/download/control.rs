@@ -0,0 +1,125 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::time::sleep;
+
+/// Runtime switches flipped by the hotkey listener and polled by download workers.
+#[derive(Clone, Default)]
+pub struct DownloadControl {
+    paused: Arc<AtomicBool>,
+    skip_epoch: Arc<AtomicU64>,
+    reconnect_epoch: Arc<AtomicU64>,
+}
+
+impl DownloadControl {
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Skips every file that is downloading right now; files started later are unaffected.
+    pub fn skip_current(&self) {
+        self.skip_epoch.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn skip_epoch(&self) -> u64 {
+        self.skip_epoch.load(Ordering::SeqCst)
+    }
+
+    pub fn skipped_since(&self, epoch: u64) -> bool {
+        self.skip_epoch() != epoch
+    }
+
+    /// Drops every open connection, which then resumes where it stopped,
+    /// e.g. after the system woke from sleep and sockets may be dead.
+    pub fn reconnect(&self) {
+        self.reconnect_epoch.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn reconnect_epoch(&self) -> u64 {
+        self.reconnect_epoch.load(Ordering::SeqCst)
+    }
+
+    /// Returns once [`DownloadControl::reconnect`] is called after `epoch`.
+    pub async fn wait_for_reconnect(&self, epoch: u64) {
+        while self.reconnect_epoch() == epoch {
+            sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    pub async fn wait_while_paused(&self, should_stop: &AtomicBool) {
+        while self.is_paused() && !should_stop.load(Ordering::SeqCst) {
+            sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+/// Wall-clock gap between two checks that means the system was asleep.
+const SLEEP_GAP: Duration = Duration::from_secs(30);
+
+/// Notices that the system was suspended: the pipeline checks several times
+/// a second, so a long wall-clock gap between two checks means the process
+/// was not running. The wall clock is used because the monotonic clock
+/// stops during suspend on some systems.
+pub struct WakeDetector {
+    last: SystemTime,
+}
+
+impl WakeDetector {
+    pub fn new() -> Self {
+        Self {
+            last: SystemTime::now(),
+        }
+    }
+
+    /// Whether the system slept since the previous check.
+    pub fn woke(&mut self, now: SystemTime) -> bool {
+        let gap = now.duration_since(self.last).unwrap_or_default();
+        self.last = now;
+        gap >= SLEEP_GAP
+    }
+}
+
+impl Default for WakeDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadControl, WakeDetector};
+    use std::time::Duration;
+
+    #[test]
+    fn skip_only_affects_files_started_before_it() {
+        let control = DownloadControl::default();
+        let before = control.skip_epoch();
+
+        control.skip_current();
+
+        assert!(control.skipped_since(before));
+        assert!(!control.skipped_since(control.skip_epoch()));
+    }
+
+    #[test]
+    fn long_wall_clock_gaps_count_as_sleep() {
+        let mut detector = WakeDetector::new();
+        let start = detector.last;
+        assert!(!detector.woke(start + Duration::from_secs(1)));
+        assert!(detector.woke(start + Duration::from_secs(120)));
+        // A clock set back is not a wake-up.
+        assert!(!detector.woke(start));
+    }
+}
@@ -0,0 +1,196 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::config::cfg::ResourceItem;
+use crate::download::pipeline::{CompletedResource, FailedResource, PipelineResult};
+use crate::io::file::{HashAlgorithm, part_path, resolve_dest};
+use crate::io::logging::{SharedLogFile, log_warn};
+
+/// Resources whose content another resource in the session already has,
+/// keyed by the `dest` that is downloaded for them.
+#[derive(Default)]
+pub struct Duplicates {
+    by_source: HashMap<String, Vec<ResourceItem>>,
+}
+
+/// Keeps the first resource of every checksum and size, and sets the others
+/// aside to be copied from it. Resources without a checksum are always kept.
+pub fn split_duplicates(resources: Vec<ResourceItem>) -> (Vec<ResourceItem>, Duplicates) {
+    let mut sources: HashMap<(HashAlgorithm, String, Option<u64>), String> = HashMap::new();
+    let mut unique = Vec::with_capacity(resources.len());
+    let mut duplicates = Duplicates::default();
+    for item in resources {
+        let Some(checksum) = &item.checksum else {
+            unique.push(item);
+            continue;
+        };
+        let key = (checksum.algorithm, checksum.digest.clone(), item.size);
+        match sources.get(&key) {
+            Some(source) if *source != item.dest => duplicates
+                .by_source
+                .entry(source.clone())
+                .or_default()
+                .push(item),
+            Some(_) => unique.push(item),
+            None => {
+                sources.insert(key, item.dest.clone());
+                unique.push(item);
+            }
+        }
+    }
+    (unique, duplicates)
+}
+
+impl Duplicates {
+    pub fn len(&self) -> usize {
+        self.by_source.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_source.is_empty()
+    }
+
+    /// Copies every finished source to its duplicates and folds them into
+    /// `result`: a duplicate fails with its source and stays unfinished with
+    /// it, so `failed.json` and resuming cover it too. Copies are independent
+    /// files, so patching one later never touches the other.
+    pub async fn copy_into(
+        self,
+        folder: &Path,
+        result: &mut PipelineResult,
+        log_file: &SharedLogFile,
+    ) {
+        result.total += self.len();
+        let mut by_source = self.by_source;
+
+        let failed: Vec<(String, String)> = result
+            .failures
+            .iter()
+            .filter(|failure| by_source.contains_key(&failure.item.dest))
+            .map(|failure| (failure.item.dest.clone(), failure.reason.clone()))
+            .collect();
+        for (source, reason) in failed {
+            for item in by_source.remove(&source).unwrap_or_default() {
+                result.failed += 1;
+                result.failures.push(FailedResource {
+                    item,
+                    reason: format!("Copy of {} failed: {}", source, reason),
+                });
+            }
+        }
+        let unfinished: Vec<ResourceItem> = result
+            .unfinished
+            .iter()
+            .filter_map(|item| by_source.remove(&item.dest))
+            .flatten()
+            .collect();
+        result.unfinished.extend(unfinished);
+
+        let finished: Vec<String> = result
+            .completed
+            .iter()
+            .filter(|file| by_source.contains_key(&file.dest))
+            .map(|file| file.dest.clone())
+            .collect();
+        for source in finished {
+            for item in by_source.remove(&source).unwrap_or_default() {
+                match copy_file(folder, &source, &item.dest).await {
+                    Ok(()) => {
+                        result.downloaded_ok += 1;
+                        result.completed.push(CompletedResource {
+                            dest: item.dest,
+                            downloaded: true,
+                            retries: 0,
+                            stats: None,
+                        });
+                    }
+                    Err(e) => {
+                        let reason = format!("Copy from {} failed: {}", source, e);
+                        log_warn(log_file, &format!("{}: {}", item.dest, reason));
+                        result.failed += 1;
+                        result.failures.push(FailedResource { item, reason });
+                    }
+                }
+            }
+        }
+
+        // Sources neither finished nor failed were never reached, e.g. after
+        // a stop before the queue drained.
+        result.unfinished.extend(by_source.into_values().flatten());
+    }
+}
+
+/// Copies through a `.part` file, so an interrupted copy is never mistaken
+/// for a finished file.
+async fn copy_file(folder: &Path, source: &str, dest: &str) -> Result<(), String> {
+    let from = resolve_dest(folder, source)?;
+    let to = resolve_dest(folder, dest)?;
+    if let Some(parent) = to.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let part = part_path(&to);
+    tokio::fs::copy(&from, &part)
+        .await
+        .map_err(|e| e.to_string())?;
+    tokio::fs::rename(&part, &to)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_duplicates;
+    use crate::config::cfg::ResourceItem;
+    use crate::download::pipeline::{CompletedResource, PipelineResult};
+    use crate::io::file::{Checksum, HashAlgorithm, unique_path};
+    use crate::io::logging::setup_logging;
+
+    fn item(dest: &str, digest: Option<&str>, size: u64) -> ResourceItem {
+        ResourceItem {
+            dest: dest.to_string(),
+            checksum: digest.map(|digest| Checksum::new(HashAlgorithm::Md5, digest)),
+            size: Some(size),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn downloads_each_hash_once_and_copies_the_rest() {
+        let (unique, duplicates) = split_duplicates(vec![
+            item("en/a.wem", Some("AA"), 3),
+            item("ja/a.wem", Some("aa"), 3),
+            item("ko/a.wem", Some("aa"), 3),
+            item("en/b.wem", Some("aa"), 4),
+            item("en/c.wem", None, 3),
+            item("ja/c.wem", None, 3),
+        ]);
+        let dests: Vec<_> = unique.iter().map(|item| item.dest.as_str()).collect();
+        assert_eq!(dests, ["en/a.wem", "en/b.wem", "en/c.wem", "ja/c.wem"]);
+        assert_eq!(duplicates.len(), 2);
+
+        let folder = unique_path("dedupe");
+        std::fs::create_dir_all(folder.join("en")).unwrap();
+        std::fs::write(folder.join("en/a.wem"), b"abc").unwrap();
+        let log_file = setup_logging(&folder.join("logs")).unwrap();
+        let mut result = PipelineResult {
+            total: 4,
+            downloaded_ok: 1,
+            completed: vec![CompletedResource {
+                dest: "en/a.wem".to_string(),
+                downloaded: true,
+                retries: 0,
+                stats: None,
+            }],
+            ..PipelineResult::default()
+        };
+
+        duplicates.copy_into(&folder, &mut result, &log_file).await;
+
+        assert_eq!((result.total, result.downloaded_ok), (6, 3));
+        assert_eq!(std::fs::read(folder.join("ko/a.wem")).unwrap(), b"abc");
+        assert!(!folder.join("ko/a.wem.part").exists());
+        let _ = std::fs::remove_dir_all(folder);
+    }
+}
@@ -0,0 +1,208 @@
+use async_channel::{Receiver, Sender};
+use serde_json::{Value, json};
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::download::pipeline::PipelineResult;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressFormat {
+    Human,
+    Json,
+}
+
+/// One progress event, as written to `--progress-format json` or sent to a
+/// [`EventSink::channel`] receiver.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    FileStarted {
+        dest: String,
+        size: Option<u64>,
+        attempt: usize,
+    },
+    Bytes {
+        downloaded: u64,
+        total: u64,
+        bytes_per_sec: u64,
+    },
+    FileDone {
+        dest: String,
+        outcome: String,
+    },
+    Error {
+        dest: String,
+        message: String,
+    },
+    Summary {
+        verified: usize,
+        downloaded: usize,
+        failed: usize,
+        total: usize,
+        interrupted: bool,
+    },
+}
+
+impl Event {
+    /// The `event` field of the JSON form.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::FileStarted { .. } => "file_started",
+            Event::Bytes { .. } => "bytes",
+            Event::FileDone { .. } => "file_done",
+            Event::Error { .. } => "error",
+            Event::Summary { .. } => "summary",
+        }
+    }
+
+    /// The JSON line for this event, stamped with `timestamp_ms`.
+    pub fn to_json(&self, timestamp_ms: u64) -> Value {
+        let mut payload = match self {
+            Event::FileStarted {
+                dest,
+                size,
+                attempt,
+            } => json!({ "dest": dest, "size": size, "attempt": attempt }),
+            Event::Bytes {
+                downloaded,
+                total,
+                bytes_per_sec,
+            } => {
+                json!({ "downloaded": downloaded, "total": total, "bytes_per_sec": bytes_per_sec })
+            }
+            Event::FileDone { dest, outcome } => json!({ "dest": dest, "outcome": outcome }),
+            Event::Error { dest, message } => json!({ "dest": dest, "message": message }),
+            Event::Summary {
+                verified,
+                downloaded,
+                failed,
+                total,
+                interrupted,
+            } => json!({
+                "verified": verified,
+                "downloaded": downloaded,
+                "failed": failed,
+                "total": total,
+                "interrupted": interrupted,
+            }),
+        };
+        if let Value::Object(map) = &mut payload {
+            map.insert("event".to_string(), json!(self.name()));
+            map.insert("timestamp_ms".to_string(), json!(timestamp_ms));
+        }
+        payload
+    }
+}
+
+#[derive(Clone)]
+enum Target {
+    Writer(Arc<Mutex<Box<dyn Write + Send>>>),
+    Channel(Sender<Event>),
+}
+
+/// Destination for machine-readable progress events: one JSON object per line
+/// for `--progress-format json`, or typed [`Event`]s on a channel for front-ends
+/// embedding the pipeline. A disabled sink drops everything, so callers can
+/// emit unconditionally.
+#[derive(Clone, Default)]
+pub struct EventSink {
+    target: Option<Target>,
+}
+
+impl EventSink {
+    pub fn disabled() -> Self {
+        Self { target: None }
+    }
+
+    pub fn stdout() -> Self {
+        Self::from_writer(Box::new(io::stdout()))
+    }
+
+    pub fn file(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::from_writer(Box::new(file)))
+    }
+
+    /// A sink that sends every event to the returned receiver. Events are
+    /// dropped once the receiver is gone.
+    pub fn channel() -> (Self, Receiver<Event>) {
+        let (sender, receiver) = async_channel::unbounded();
+        let sink = Self {
+            target: Some(Target::Channel(sender)),
+        };
+        (sink, receiver)
+    }
+
+    fn from_writer(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            target: Some(Target::Writer(Arc::new(Mutex::new(writer)))),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.target.is_some()
+    }
+
+    fn emit(&self, event: Event) {
+        match &self.target {
+            None => {}
+            Some(Target::Channel(sender)) => {
+                let _ = sender.try_send(event);
+            }
+            Some(Target::Writer(writer)) => {
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                if let Ok(mut writer) = writer.lock() {
+                    let _ = writeln!(writer, "{}", event.to_json(timestamp));
+                    let _ = writer.flush();
+                }
+            }
+        }
+    }
+
+    pub fn file_started(&self, dest: &str, size: Option<u64>, attempt: usize) {
+        self.emit(Event::FileStarted {
+            dest: dest.to_string(),
+            size,
+            attempt,
+        });
+    }
+
+    pub fn bytes(&self, downloaded: u64, total: u64, bytes_per_sec: u64) {
+        self.emit(Event::Bytes {
+            downloaded,
+            total,
+            bytes_per_sec,
+        });
+    }
+
+    pub fn file_done(&self, dest: &str, outcome: &str) {
+        self.emit(Event::FileDone {
+            dest: dest.to_string(),
+            outcome: outcome.to_string(),
+        });
+    }
+
+    pub fn error(&self, dest: &str, message: &str) {
+        self.emit(Event::Error {
+            dest: dest.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    pub fn summary(&self, result: &PipelineResult, interrupted: bool) {
+        self.emit(Event::Summary {
+            verified: result.verified_ok,
+            downloaded: result.downloaded_ok,
+            failed: result.failed,
+            total: result.total,
+            interrupted,
+        });
+    }
+}
@@ -0,0 +1,104 @@
+use crate::config::cfg::ResourceItem;
+
+#[derive(Clone, Debug, Default)]
+pub struct ResourceFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+pub struct FilterStats {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+impl ResourceFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, dest: &str) -> bool {
+        let dest = dest.replace('\\', "/");
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| glob_match(pattern, &dest));
+        included
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| glob_match(pattern, &dest))
+    }
+
+    /// Drops every resource the filter rejects and reports what was dropped.
+    pub fn apply(&self, resources: &mut Vec<ResourceItem>) -> FilterStats {
+        let mut stats = FilterStats { files: 0, bytes: 0 };
+        if self.is_empty() {
+            return stats;
+        }
+
+        resources.retain(|item| {
+            let keep = self.matches(&item.dest);
+            if !keep {
+                stats.files += 1;
+                stats.bytes += item.size.unwrap_or(0);
+            }
+            keep
+        });
+        stats
+    }
+}
+
+/// Case-insensitive wildcard match where `*` spans any characters (including `/`)
+/// and `?` matches exactly one.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.replace('\\', "/").to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ResourceFilter, glob_match};
+
+    #[test]
+    fn glob_match_handles_wildcards() {
+        assert!(glob_match("*.pak", "Client/Content/Paks/pakchunk0.pak"));
+        assert!(glob_match("zip/audio/*", "zip/audio/en/vo.pck"));
+        assert!(glob_match("pak?.PAK", "pak1.pak"));
+        assert!(!glob_match("*.pak", "Client/readme.txt"));
+        assert!(!glob_match("audio/*", "zip/audio/en/vo.pck"));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let filter = ResourceFilter {
+            include: vec!["*.pak".to_string()],
+            exclude: vec!["*optional*".to_string()],
+        };
+
+        assert!(filter.matches("Paks/main.pak"));
+        assert!(!filter.matches("Paks/optional.pak"));
+        assert!(!filter.matches("Binaries/game.exe"));
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use crate::config::cfg::ResourceItem;
+use crate::io::sanitize::dest_parts;
+
+/// Directory levels below the common root that name a group.
+const GROUP_DEPTH: usize = 2;
+
+/// One directory's share of the session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirGroup {
+    pub name: String,
+    pub files: usize,
+    pub files_done: usize,
+    pub bytes: u64,
+    pub bytes_done: u64,
+}
+
+impl DirGroup {
+    /// Completion by size, or by file count when no sizes are known.
+    pub fn percent(&self) -> u64 {
+        (self.bytes_done * 100)
+            .checked_div(self.bytes)
+            .unwrap_or_else(|| {
+                (self.files_done * 100)
+                    .checked_div(self.files)
+                    .unwrap_or(100) as u64
+            })
+    }
+}
+
+/// Per-directory progress, so users can see what the remaining bulk is made
+/// of. Directories every resource shares (such as `Client/`) are skipped and
+/// the next [`GROUP_DEPTH`] levels name the group.
+pub struct GroupProgress {
+    groups: Vec<DirGroup>,
+    pending: HashMap<String, (usize, u64)>,
+}
+
+impl GroupProgress {
+    pub fn new(items: &[ResourceItem]) -> Self {
+        let dirs: Vec<Vec<&str>> = items
+            .iter()
+            .map(|item| {
+                let mut parts: Vec<&str> = dest_parts(&item.dest).collect();
+                parts.pop();
+                parts
+            })
+            .collect();
+        let root = common_prefix(&dirs);
+
+        let mut groups: Vec<DirGroup> = Vec::new();
+        let mut index = HashMap::new();
+        let mut pending = HashMap::new();
+        for (item, parts) in items.iter().zip(&dirs) {
+            let below = &parts[root..parts.len().min(root + GROUP_DEPTH)];
+            let name = if !below.is_empty() {
+                below.join("/")
+            } else if root > 0 {
+                parts[..root].join("/")
+            } else {
+                ".".to_string()
+            };
+            let slot = *index.entry(name.clone()).or_insert_with(|| {
+                groups.push(DirGroup {
+                    name,
+                    files: 0,
+                    files_done: 0,
+                    bytes: 0,
+                    bytes_done: 0,
+                });
+                groups.len() - 1
+            });
+            let size = item.size.unwrap_or(0);
+            groups[slot].files += 1;
+            groups[slot].bytes += size;
+            pending.insert(item.dest.clone(), (slot, size));
+        }
+        Self { groups, pending }
+    }
+
+    /// Counts `dest` as finished; later calls for the same file are ignored.
+    pub fn complete(&mut self, dest: &str) {
+        if let Some((slot, size)) = self.pending.remove(dest) {
+            self.groups[slot].files_done += 1;
+            self.groups[slot].bytes_done += size;
+        }
+    }
+
+    /// The `limit` groups with the most left to do, e.g.
+    /// `Content/Paks 80% | Content/Aki 3% | +2 more`.
+    pub fn render(&self, limit: usize) -> String {
+        let mut open: Vec<&DirGroup> = self
+            .groups
+            .iter()
+            .filter(|group| group.files_done < group.files)
+            .collect();
+        if open.is_empty() {
+            return "all directories complete".to_string();
+        }
+        open.sort_by_key(|group| {
+            (
+                std::cmp::Reverse(group.bytes - group.bytes_done),
+                std::cmp::Reverse(group.files - group.files_done),
+            )
+        });
+        let mut line = open
+            .iter()
+            .take(limit)
+            .map(|group| format!("{} {}%", group.name, group.percent()))
+            .collect::<Vec<_>>()
+            .join(" | ");
+        if open.len() > limit {
+            line.push_str(&format!(" | +{} more", open.len() - limit));
+        }
+        line
+    }
+
+    /// Every group, largest first.
+    pub fn into_groups(mut self) -> Vec<DirGroup> {
+        self.groups
+            .sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+        self.groups
+    }
+}
+
+/// How many leading directories every entry shares.
+fn common_prefix(dirs: &[Vec<&str>]) -> usize {
+    let Some((first, rest)) = dirs.split_first() else {
+        return 0;
+    };
+    let mut len = first.len();
+    for parts in rest {
+        len = first
+            .iter()
+            .zip(parts)
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count();
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GroupProgress;
+    use crate::config::cfg::ResourceItem;
+
+    fn item(dest: &str, size: u64) -> ResourceItem {
+        ResourceItem {
+            dest: dest.to_string(),
+            checksum: None,
+            size: Some(size),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }
+    }
+
+    #[test]
+    fn groups_below_the_shared_root() {
+        let items = [
+            item("Client/Content/Paks/a.pak", 800),
+            item("Client/Content/Paks/b.pak", 200),
+            item("Client/Content/Aki/WwiseAudio/en/1.wem", 50),
+            item("Client\\Content\\Aki\\WwiseAudio\\ja\\2.wem", 50),
+            item("Client/Binaries/Client.exe", 150),
+        ];
+        let mut groups = GroupProgress::new(&items);
+        groups.complete("Client/Content/Paks/a.pak");
+        groups.complete("Client/Content/Paks/a.pak");
+
+        assert_eq!(groups.render(2), "Content/Paks 80% | Binaries 0% | +1 more");
+        let groups = groups.into_groups();
+        let names: Vec<_> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["Content/Paks", "Binaries", "Content/Aki"]);
+        assert_eq!((groups[0].files_done, groups[0].bytes_done), (1, 800));
+    }
+
+    #[test]
+    fn names_a_single_directory_after_itself() {
+        let items = [item("Client/Paks/a.pak", 1), item("Client/Paks/b.pak", 1)];
+        let mut groups = GroupProgress::new(&items);
+        groups.complete("Client/Paks/a.pak");
+        groups.complete("Client/Paks/b.pak");
+
+        assert_eq!(groups.render(3), "all directories complete");
+        assert_eq!(groups.into_groups()[0].name, "Client/Paks");
+    }
+}
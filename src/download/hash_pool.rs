@@ -0,0 +1,111 @@
+use async_channel::{Receiver, Sender};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::AtomicBool},
+    thread,
+};
+use tokio::sync::oneshot;
+
+use crate::io::file::{HashAlgorithm, VerificationError, hash_file_blocking, hash_file_mapped};
+
+type HashFn = fn(&Path, HashAlgorithm, Arc<AtomicBool>) -> Result<String, VerificationError>;
+
+struct HashJob {
+    path: PathBuf,
+    algorithm: HashAlgorithm,
+    should_stop: Arc<AtomicBool>,
+    reply: oneshot::Sender<Result<String, VerificationError>>,
+}
+
+/// A few dedicated threads that hash finished downloads. Reading a multi-GB
+/// file back happens here, so download slots move on to the next file while
+/// the previous one is still being checked. The threads exit once every
+/// handle is dropped.
+#[derive(Clone)]
+pub struct HashPool {
+    jobs: Sender<HashJob>,
+}
+
+impl HashPool {
+    /// A thread that cannot be spawned is skipped; if none start, every
+    /// [`HashPool::hash`] call fails with an I/O error instead.
+    pub fn new(threads: usize) -> Self {
+        Self::spawn(threads, hash_file_blocking)
+    }
+
+    /// A pool that hashes memory-mapped files in large slices, for
+    /// `--hash-threads` scans of a whole install.
+    pub fn mapped(threads: usize) -> Self {
+        Self::spawn(threads, hash_file_mapped)
+    }
+
+    fn spawn(threads: usize, hash: HashFn) -> Self {
+        let (jobs, queue) = async_channel::unbounded();
+        for index in 0..threads.max(1) {
+            let queue: Receiver<HashJob> = queue.clone();
+            let _ = thread::Builder::new()
+                .name(format!("hasher-{}", index + 1))
+                .spawn(move || {
+                    while let Ok(job) = queue.recv_blocking() {
+                        let digest = hash(&job.path, job.algorithm, job.should_stop);
+                        let _ = job.reply.send(digest);
+                    }
+                });
+        }
+        Self { jobs }
+    }
+
+    /// Queues `path` for hashing and waits for its digest.
+    pub async fn hash(
+        &self,
+        path: &Path,
+        algorithm: HashAlgorithm,
+        should_stop: Arc<AtomicBool>,
+    ) -> Result<String, VerificationError> {
+        let (reply, digest) = oneshot::channel();
+        let job = HashJob {
+            path: path.to_path_buf(),
+            algorithm,
+            should_stop,
+            reply,
+        };
+        if self.jobs.send(job).await.is_err() {
+            return Err(VerificationError::Io(io::Error::other(
+                "Hasher threads have stopped",
+            )));
+        }
+        digest.await.unwrap_or_else(|_| {
+            Err(VerificationError::Io(io::Error::other(
+                "Hasher thread dropped the job",
+            )))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashPool;
+    use crate::io::file::HashAlgorithm;
+    use crate::io::file::unique_path;
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    #[tokio::test]
+    async fn hashes_queued_files_on_pool_threads() {
+        let path = unique_path("hash-pool");
+        std::fs::write(&path, b"abc").unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        for pool in [HashPool::new(2), HashPool::mapped(2)] {
+            let digests = tokio::join!(
+                pool.hash(&path, HashAlgorithm::Md5, stop.clone()),
+                pool.hash(&path, HashAlgorithm::Md5, stop.clone()),
+                pool.hash(&path, HashAlgorithm::Md5, stop.clone()),
+            );
+
+            for digest in [digests.0, digests.1, digests.2] {
+                assert_eq!(digest.unwrap(), "900150983cd24fb0d6963f7d28e17f72");
+            }
+        }
+    }
+}
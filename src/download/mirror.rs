@@ -0,0 +1,219 @@
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::config::cfg::ResourceItem;
+use crate::download::pipeline::PipelineResult;
+use crate::io::file::{dest_path, resolve_dest};
+use crate::io::reuse::place_file;
+
+pub const MIRROR_INDEX: &str = "index.json";
+pub const OBJECTS_DIR: &str = "objects";
+pub const FILES_DIR: &str = "files";
+/// Where the pipeline downloads into before files move to `objects/`, so
+/// interrupted downloads resume like in any other folder.
+pub const STAGING_DIR: &str = ".incoming";
+
+/// A `--mirror` folder: every file of the index stored once under
+/// `objects/<first two digits>/<digest>`, a copy of the index as `index.json`
+/// and a `files/` tree of hardlinks to the objects that any static HTTP
+/// server can offer to `--base-url`. Objects stay when the game updates, so
+/// the next version only adds what changed.
+pub struct Mirror {
+    root: PathBuf,
+}
+
+impl Mirror {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+        }
+    }
+
+    pub fn staging(&self) -> PathBuf {
+        self.root.join(STAGING_DIR)
+    }
+
+    /// Only resources with a checksum can be addressed by content.
+    fn object_path(&self, item: &ResourceItem) -> Option<PathBuf> {
+        let digest = item.checksum.as_ref()?.digest.to_ascii_lowercase();
+        let prefix = digest.get(..2).unwrap_or(&digest);
+        Some(self.root.join(OBJECTS_DIR).join(prefix).join(&digest))
+    }
+
+    /// Objects are only stored after their checksum passed, so one of the
+    /// right size is trusted.
+    fn has_object(&self, item: &ResourceItem) -> bool {
+        self.object_path(item)
+            .and_then(|path| fs::metadata(path).ok())
+            .is_some_and(|metadata| item.size.is_none_or(|size| size == metadata.len()))
+    }
+
+    /// One resource per missing object; resources without a checksum are
+    /// left out.
+    pub fn pending(&self, resources: &[ResourceItem]) -> Vec<ResourceItem> {
+        let mut seen = HashSet::new();
+        resources
+            .iter()
+            .filter(|item| {
+                self.object_path(item)
+                    .is_some_and(|path| seen.insert(path) && !self.has_object(item))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Moves every file the session finished from the staging folder into
+    /// `objects/`, returning an error per file that could not be moved.
+    pub fn store(&self, pending: &[ResourceItem], result: &PipelineResult) -> Vec<String> {
+        let staging = self.staging();
+        let by_dest: HashMap<&str, &ResourceItem> = pending
+            .iter()
+            .map(|item| (item.dest.as_str(), item))
+            .collect();
+        let mut errors = Vec::new();
+        for file in &result.completed {
+            let Some(item) = by_dest.get(file.dest.as_str()) else {
+                continue;
+            };
+            let Some(object) = self.object_path(item) else {
+                continue;
+            };
+            let moved = resolve_dest(&staging, &item.dest).and_then(|from| {
+                if let Some(parent) = object.parent() {
+                    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                fs::rename(&from, &object).map_err(|e| e.to_string())
+            });
+            if let Err(e) = moved {
+                errors.push(format!(
+                    "Failed to store {} in the mirror: {}",
+                    item.dest, e
+                ));
+            }
+        }
+        remove_empty_dirs(&staging);
+        errors
+    }
+
+    /// Once every resource has its object, writes `index` as `index.json` and
+    /// rebuilds `files/` to match it. A mirror missing files keeps its previous
+    /// index, so LAN installs never start on a version it cannot serve.
+    pub fn publish(&self, index: &Value, resources: &[ResourceItem]) -> Result<(), String> {
+        let missing = resources
+            .iter()
+            .filter(|item| item.checksum.is_some() && !self.has_object(item))
+            .count();
+        if missing > 0 {
+            return Err(format!(
+                "{} files are not in the mirror yet, {} was left unchanged",
+                missing, MIRROR_INDEX
+            ));
+        }
+
+        let building = self.root.join(format!("{}.new", FILES_DIR));
+        remove_dir_if_exists(&building).map_err(|e| e.to_string())?;
+        for item in resources {
+            let Some(object) = self.object_path(item) else {
+                continue;
+            };
+            let target = dest_path(&building, &item.dest);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            place_file(&object, &target, true)
+                .map_err(|e| format!("Failed to link {}: {}", item.dest, e))?;
+        }
+        let files = self.root.join(FILES_DIR);
+        remove_dir_if_exists(&files).map_err(|e| e.to_string())?;
+        fs::rename(&building, &files).map_err(|e| e.to_string())?;
+
+        let contents = serde_json::to_string_pretty(index).map_err(|e| e.to_string())?;
+        let path = self.root.join(MIRROR_INDEX);
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, contents + "\n").map_err(|e| e.to_string())?;
+        fs::rename(&temp, &path).map_err(|e| e.to_string())
+    }
+}
+
+fn remove_dir_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Removes the directories that moving files out of `dir` left empty.
+fn remove_empty_dirs(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            remove_empty_dirs(&entry.path());
+            let _ = fs::remove_dir(entry.path());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mirror;
+    use crate::config::cfg::ResourceItem;
+    use crate::download::pipeline::{CompletedResource, PipelineResult};
+    use crate::io::file::{Checksum, HashAlgorithm, unique_path};
+    use serde_json::json;
+
+    fn item(dest: &str, digest: Option<&str>) -> ResourceItem {
+        ResourceItem {
+            dest: dest.to_string(),
+            checksum: digest.map(|digest| Checksum::new(HashAlgorithm::Md5, digest)),
+            size: Some(3),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }
+    }
+
+    #[test]
+    fn stores_each_digest_once_and_publishes_a_tree() {
+        let root = unique_path("mirror");
+        let mirror = Mirror::new(&root);
+        let resources = [
+            item("Client/a.pak", Some("AB01")),
+            item("Client/en/b.wem", Some("ab01")),
+            item("Client/c.txt", None),
+        ];
+        let index = json!({ "resource": [] });
+
+        let pending = mirror.pending(&resources);
+        assert_eq!(pending.len(), 1);
+        assert!(mirror.publish(&index, &resources).is_err());
+
+        std::fs::create_dir_all(mirror.staging().join("Client")).unwrap();
+        std::fs::write(mirror.staging().join("Client/a.pak"), b"abc").unwrap();
+        let result = PipelineResult {
+            completed: vec![CompletedResource {
+                dest: "Client/a.pak".to_string(),
+                downloaded: true,
+                retries: 0,
+                stats: None,
+            }],
+            ..PipelineResult::default()
+        };
+        assert!(mirror.store(&pending, &result).is_empty());
+        assert!(!mirror.staging().join("Client").exists());
+        assert!(root.join("objects/ab/ab01").is_file());
+        assert!(mirror.pending(&resources).is_empty());
+
+        mirror.publish(&index, &resources).unwrap();
+        assert_eq!(
+            std::fs::read(root.join("files/Client/en/b.wem")).unwrap(),
+            b"abc"
+        );
+        assert!(root.join("index.json").is_file());
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
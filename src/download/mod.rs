@@ -1,2 +1,15 @@
+pub mod backend;
+pub mod control;
+pub mod dedupe;
+pub mod events;
+pub mod filter;
+pub mod groups;
+pub mod hash_pool;
+pub mod mirror;
 pub mod pipeline;
 pub mod progress;
+pub mod queue;
+pub mod report;
+pub mod selection;
+pub mod session;
+pub mod stats;
@@ -1,23 +1,43 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::time::Instant;
+use std::time::{Duration, Instant, SystemTime};
 
 use async_channel::{Receiver, Sender};
-use indicatif::ProgressBar;
+use indicatif::{HumanBytes, ProgressBar};
 use reqwest::Client;
+use tokio::sync::Semaphore;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinSet;
 
-use crate::config::cfg::{Config, DownloadOptions, ResourceItem};
-use crate::download::progress::{DownloadProgress, ProgressDisplay};
+use crate::config::cfg::{Config, DownloadOptions, ResourceItem, SkipExisting};
+use crate::download::backend::DownloadJob;
+use crate::download::control::{DownloadControl, WakeDetector};
+use crate::download::events::EventSink;
+use crate::download::groups::{DirGroup, GroupProgress};
+use crate::download::hash_pool::HashPool;
+use crate::download::progress::{DownloadProgress, FileProgress, ProgressDisplay, TransferStats};
+use crate::download::queue::DownloadQueue;
+use crate::download::stats::SpeedStats;
 use crate::io::file::{
-    VerificationError, calculate_md5_interruptible, check_existing_file_interruptible, file_size,
+    VerificationError, check_chunked_file, check_existing_file_interruptible, dest_path, part_path,
+    resolve_dest,
 };
-use crate::io::logging::{SharedLogFile, log_error};
-use crate::network::client::download_file;
+use crate::io::hash_cache::HASH_CACHE;
+use crate::io::hooks::Hook;
+use crate::io::logging::{SharedLogFile, log_error, log_info, log_warn};
+use crate::io::quarantine::Quarantine;
+use crate::io::reuse::ReuseSource;
+use crate::network::cdn_tracker::CdnStats;
+use crate::network::client::DownloadedFile;
+use crate::network::webhook::progress_message;
 
 const MAX_PIPELINE_RETRIES: usize = 2;
 const DISPLAY_FILENAME_LIMIT: usize = 11;
+const BYTES_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+/// Directories named on the live `[DIRS]` line.
+const GROUPS_SHOWN: usize = 4;
 
 pub struct DownloadTask {
     pub item: ResourceItem,
@@ -29,27 +49,91 @@ pub struct PostVerifyTask {
     pub item: ResourceItem,
     pub expected_size: Option<u64>,
     pub attempt: usize,
+    pub streamed_digest: Option<String>,
+    pub counted_bytes: u64,
+    pub stats: TransferStats,
 }
 
+/// A resource that is intact on disk at the end of the session.
+pub struct CompletedResource {
+    pub dest: String,
+    /// `false` when the file on disk was already valid.
+    pub downloaded: bool,
+    /// Extra downloads after the first one failed its checksum.
+    pub retries: usize,
+    /// How the last download of the file went; `None` when nothing was fetched.
+    pub stats: Option<TransferStats>,
+}
+
+pub struct FailedResource {
+    pub item: ResourceItem,
+    pub reason: String,
+}
+
+#[derive(Default)]
 pub struct PipelineResult {
     pub verified_ok: usize,
     pub downloaded_ok: usize,
     pub failed: usize,
     pub total: usize,
+    pub failures: Vec<FailedResource>,
+    /// Resources never finished because the session was interrupted.
+    pub unfinished: Vec<ResourceItem>,
+    /// Every verified or downloaded resource, in the order it finished.
+    pub completed: Vec<CompletedResource>,
+    /// Bytes fetched from the CDNs; files that were already valid do not count.
+    pub bytes_downloaded: u64,
+    /// Progress per directory, largest first.
+    pub groups: Vec<DirGroup>,
+    /// How each CDN answered, in the order they are tried.
+    pub cdns: Vec<CdnStats>,
+    /// Downloads whose checksum was read back on the hasher threads because
+    /// no digest could be streamed, e.g. after resuming a large partial.
+    pub hashed_after_download: usize,
+}
+
+impl PipelineResult {
+    fn record_failure(&mut self, item: ResourceItem, reason: impl Into<String>) {
+        self.failed += 1;
+        self.failures.push(FailedResource {
+            item,
+            reason: reason.into(),
+        });
+    }
 }
 
 enum PipelineEvent {
-    VerifiedValid { completed_bytes: Option<u64> },
+    VerifiedValid {
+        dest: String,
+        completed_bytes: Option<u64>,
+    },
     NeedDownload(DownloadTask),
-    VerificationFailed { dest: String },
+    VerificationFailed {
+        item: ResourceItem,
+        reason: String,
+    },
     VerificationAborted,
     DownloadSuccess(PostVerifyTask),
-    DownloadFailed { dest: String },
+    DownloadFailed {
+        item: ResourceItem,
+        reason: String,
+    },
     DownloadAborted,
-    PostVerifySuccess,
+    PostVerifySuccess {
+        dest: String,
+        attempt: usize,
+        hashed_after_download: bool,
+        stats: TransferStats,
+    },
     NeedRetry(DownloadTask),
-    PostVerifyFailed { dest: String },
-    PostVerifyIoFailed { dest: String },
+    PostVerifyFailed {
+        item: ResourceItem,
+        reason: String,
+    },
+    PostVerifyIoFailed {
+        item: ResourceItem,
+        reason: String,
+    },
     PostVerifyAborted,
 }
 
@@ -76,25 +160,47 @@ async fn verification_worker(
     log_file: SharedLogFile,
     should_stop: Arc<AtomicBool>,
     verify_bar: ProgressBar,
+    options: Arc<DownloadOptions>,
 ) {
+    let quarantine = Quarantine::new(&folder, options.delete_corrupt);
     while let Ok(item) = rx.recv().await {
         if should_stop.load(Ordering::SeqCst) {
             break;
         }
 
         let expected_size = item.size;
-        let local_path = folder.join(item.dest.replace('\\', "/"));
-        let event = match check_existing_file_interruptible(
-            &local_path,
-            item.md5.as_deref(),
-            expected_size,
-            should_stop.clone(),
-        )
-        .await
-        {
+        let local_path = dest_path(&folder, &item.dest);
+        // Files only reach this worker once their size matched the index.
+        let size_only = options.skip_existing == SkipExisting::Size && expected_size.is_some();
+        if size_only || options.hash_cache.is_cached(&local_path, &item).await {
+            verify_bar.inc(1);
+            let _ = event_tx.send(PipelineEvent::VerifiedValid {
+                dest: item.dest,
+                completed_bytes: expected_size,
+            });
+            continue;
+        }
+
+        let checked = if item.chunks.is_empty() {
+            check_existing_file_interruptible(
+                &local_path,
+                item.checksum.as_ref(),
+                expected_size,
+                should_stop.clone(),
+                &quarantine,
+            )
+            .await
+        } else {
+            check_chunked_file(&local_path, &item, should_stop.clone()).await
+        };
+        let event = match checked {
             Ok(false) => {
                 verify_bar.inc(1);
+                if let Some(checksum) = &item.checksum {
+                    options.hash_cache.record(&local_path, checksum);
+                }
                 PipelineEvent::VerifiedValid {
+                    dest: item.dest,
                     completed_bytes: expected_size,
                 }
             }
@@ -109,11 +215,9 @@ async fn verification_worker(
             Err(VerificationError::Interrupted) => PipelineEvent::VerificationAborted,
             Err(VerificationError::Io(err)) => {
                 verify_bar.inc(1);
-                log_error(
-                    &log_file,
-                    &format!("Verification failed for {}: {}", item.dest, err),
-                );
-                PipelineEvent::VerificationFailed { dest: item.dest }
+                let reason = format!("Verification failed: {}", err);
+                log_error(&log_file, &format!("{} ({})", reason, item.dest));
+                PipelineEvent::VerificationFailed { item, reason }
             }
         };
 
@@ -137,8 +241,12 @@ async fn download_worker(
     should_stop: Arc<AtomicBool>,
     progress: DownloadProgress,
     display: Arc<ProgressDisplay>,
+    options: Arc<DownloadOptions>,
+    events: EventSink,
+    control: DownloadControl,
 ) {
     while let Ok(task) = rx.recv().await {
+        control.wait_while_paused(&should_stop).await;
         if should_stop.load(Ordering::SeqCst) {
             break;
         }
@@ -148,6 +256,8 @@ async fn download_worker(
         task_bar.set_prefix(format!("DL {:02}", slot_index + 1));
 
         let filename = display_filename(&task.item.dest);
+        display.set_active_file(&filename);
+        events.file_started(&task.item.dest, task.expected_size, task.attempt);
 
         if task.attempt > 0 {
             task_bar.set_message(format!(
@@ -161,33 +271,59 @@ async fn download_worker(
         task_bar.set_length(task.expected_size.unwrap_or(0));
         task_bar.set_position(0);
 
-        let ok = download_file(
-            &client,
-            &config,
-            &task.item.dest,
-            &folder,
-            task.expected_size,
-            &log_file,
-            &should_stop,
-            &progress,
-            &display.total_bar,
-            &task_bar,
-        )
-        .await;
+        let job = DownloadJob {
+            client: &client,
+            config: &config,
+            item: &task.item,
+            expected_size: task.expected_size,
+            folder: &folder,
+            log_file: &log_file,
+            should_stop: &should_stop,
+            control: &control,
+            progress: &progress,
+            total_pb: &display.total_bar,
+            task_pb: &task_bar,
+            options: &options,
+        };
+        let reused = match &options.reuse_from {
+            Some(reuse) if task.attempt == 0 => {
+                task_bar.set_message(format!("checking {} in --reuse-from", filename));
+                let reused = reuse_existing(reuse, &job, &should_stop).await;
+                task_bar.set_message(format!("downloading {}", filename));
+                reused
+            }
+            _ => None,
+        };
+        let downloaded = match reused {
+            Some(reused) => {
+                log_info(
+                    &log_file,
+                    &format!("Reused {} from the reference install", task.item.dest),
+                );
+                Ok(reused)
+            }
+            None => options.backend.fetch(&job).await,
+        };
 
         task_bar.set_position(0);
         task_bar.set_length(0);
 
-        if ok {
-            task_bar.set_message("idle");
-            display.slot_pool.release_slot(slot_index).await;
-            let _ = event_tx.send(PipelineEvent::DownloadSuccess(PostVerifyTask {
-                item: task.item,
-                expected_size: task.expected_size,
-                attempt: task.attempt,
-            }));
-            continue;
-        }
+        let reason = match downloaded {
+            Ok(downloaded) => {
+                task_bar.set_message("idle");
+                display.slot_pool.release_slot(slot_index).await;
+                let _ = event_tx.send(PipelineEvent::DownloadSuccess(PostVerifyTask {
+                    item: task.item,
+                    expected_size: task.expected_size,
+                    attempt: task.attempt,
+                    streamed_digest: downloaded.digest,
+                    counted_bytes: downloaded.counted_bytes,
+                    stats: downloaded.stats,
+                }));
+                continue;
+            }
+            Err(reason) => reason,
+        };
 
         display.slot_pool.release_slot(slot_index).await;
         task_bar.set_message(if should_stop.load(Ordering::SeqCst) {
@@ -208,13 +344,36 @@ async fn download_worker(
                 ),
             );
             PipelineEvent::DownloadFailed {
-                dest: task.item.dest,
+                item: task.item,
+                reason,
             }
         };
         let _ = event_tx.send(event);
     }
 }
 
+/// `--reuse-from`: takes the file over from the reference install into its
+/// `.part` file, counted like a download so the totals still add up.
+async fn reuse_existing(
+    reuse: &ReuseSource,
+    job: &DownloadJob<'_>,
+    should_stop: &Arc<AtomicBool>,
+) -> Option<DownloadedFile> {
+    let path = part_path(&resolve_dest(job.folder, &job.item.dest).ok()?);
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.ok()?;
+    }
+    let digest = reuse.place(job.item, &path, should_stop).await?;
+    let mut file_progress =
+        FileProgress::new(job.progress, job.total_pb, job.expected_size.is_some());
+    file_progress.add(job.expected_size.unwrap_or(0)).await;
+    Some(DownloadedFile {
+        digest: Some(digest),
+        counted_bytes: file_progress.counted(),
+        stats: TransferStats::default(),
+    })
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn post_verify_worker(
     worker_id: usize,
@@ -225,21 +384,39 @@ async fn post_verify_worker(
     should_stop: Arc<AtomicBool>,
     progress: DownloadProgress,
     display: Arc<ProgressDisplay>,
+    options: Arc<DownloadOptions>,
+    hash_pool: HashPool,
 ) {
+    let quarantine = Quarantine::new(&folder, options.delete_corrupt);
     while let Ok(task) = rx.recv().await {
         let filename = display_filename(&task.item.dest);
-        let path = folder.join(task.item.dest.replace('\\', "/"));
+        let final_path = dest_path(&folder, &task.item.dest);
+        let path = part_path(&final_path);
 
         if should_stop.load(Ordering::SeqCst) {
             let _ = event_tx.send(PipelineEvent::PostVerifyAborted);
             break;
         }
 
-        let verification = if let Some(expected_md5) = task.item.md5.as_deref() {
-            match calculate_md5_interruptible(&path, should_stop.clone()).await {
-                Ok(actual_md5) => Ok(actual_md5 == expected_md5),
-                Err(err) => Err(err),
-            }
+        // Digest of a download that failed its checksum, for the quarantine report.
+        let mut mismatch = None;
+        let hashed_after_download = task.item.checksum.is_some() && task.streamed_digest.is_none();
+        let verification = if let Some(checksum) = task.item.checksum.as_ref() {
+            let actual = match task.streamed_digest.clone() {
+                Some(digest) => Ok(digest),
+                None => {
+                    hash_pool
+                        .hash(&path, checksum.algorithm, should_stop.clone())
+                        .await
+                }
+            };
+            actual.map(|actual| {
+                let matches = checksum.matches(&actual);
+                if !matches {
+                    mismatch = Some(actual);
+                }
+                matches
+            })
         } else if let Some(expected_size) = task.expected_size {
             match tokio::fs::metadata(&path).await {
                 Ok(metadata) => Ok(metadata.len() == expected_size),
@@ -249,9 +426,25 @@ async fn post_verify_worker(
             Ok(true)
         };
 
+        let verification = match verification {
+            Ok(true) => tokio::fs::rename(&path, &final_path)
+                .await
+                .map(|()| true)
+                .map_err(VerificationError::Io),
+            other => other,
+        };
+
         match verification {
             Ok(true) => {
-                let _ = event_tx.send(PipelineEvent::PostVerifySuccess);
+                if let Some(checksum) = &task.item.checksum {
+                    options.hash_cache.record(&final_path, checksum);
+                }
+                let _ = event_tx.send(PipelineEvent::PostVerifySuccess {
+                    dest: task.item.dest,
+                    attempt: task.attempt,
+                    hashed_after_download,
+                    stats: task.stats,
+                });
                 continue;
             }
             Err(VerificationError::Interrupted) => {
@@ -268,7 +461,10 @@ async fn post_verify_worker(
                         err
                     ),
                 );
-                let _ = event_tx.send(PipelineEvent::PostVerifyIoFailed { dest: filename });
+                let _ = event_tx.send(PipelineEvent::PostVerifyIoFailed {
+                    item: task.item,
+                    reason: format!("Post-download verification failed: {}", err),
+                });
                 continue;
             }
             Ok(false) => {}
@@ -279,13 +475,32 @@ async fn post_verify_worker(
             continue;
         }
 
-        if task.item.size.is_some() {
-            let bytes_to_rollback = file_size(&path).await;
-            progress
-                .rollback_downloaded_bytes(&display.total_bar, bytes_to_rollback)
-                .await;
+        progress
+            .rollback_downloaded_bytes(&display.total_bar, task.counted_bytes)
+            .await;
+        match (&task.item.checksum, mismatch) {
+            (Some(checksum), Some(actual)) => {
+                match quarantine.isolate(&path, checksum, &actual).await {
+                    Ok(Some(kept)) => log_warn(
+                        &log_file,
+                        &format!(
+                            "Checksum mismatch for {}, moved to {}",
+                            task.item.dest,
+                            kept.display()
+                        ),
+                    ),
+                    Ok(None) => {}
+                    Err(err) => {
+                        log_warn(
+                            &log_file,
+                            &format!("Could not quarantine {}: {}", task.item.dest, err),
+                        );
+                        remove_file_if_exists(&path).await;
+                    }
+                }
+            }
+            _ => remove_file_if_exists(&path).await,
         }
-        remove_file_if_exists(&path).await;
 
         if task.attempt < MAX_PIPELINE_RETRIES {
             let _ = event_tx.send(PipelineEvent::NeedRetry(DownloadTask {
@@ -302,7 +517,13 @@ async fn post_verify_worker(
                     filename
                 ),
             );
-            let _ = event_tx.send(PipelineEvent::PostVerifyFailed { dest: filename });
+            let _ = event_tx.send(PipelineEvent::PostVerifyFailed {
+                item: task.item,
+                reason: format!(
+                    "Checksum mismatch after {} attempts",
+                    MAX_PIPELINE_RETRIES + 1
+                ),
+            });
         }
     }
 }
@@ -314,6 +535,33 @@ async fn enqueue_task<T>(tx: &Sender<T>, task: T) -> Result<(), T> {
     }
 }
 
+/// Runs the per-file hook in the background, one at a time, so a slow script
+/// does not hold up the pipeline's event loop.
+fn spawn_file_hook(
+    hooks: &mut JoinSet<()>,
+    slot: &Arc<Semaphore>,
+    hook: &Hook,
+    folder: &Path,
+    dest: &str,
+    log_file: &SharedLogFile,
+) {
+    let hook = hook.clone();
+    let slot = slot.clone();
+    let folder_arg = folder.display().to_string();
+    let file = dest_path(folder, dest).display().to_string();
+    let dest = dest.to_string();
+    let log_file = log_file.clone();
+    hooks.spawn(async move {
+        let _permit = slot.acquire_owned().await.ok();
+        hook.run(
+            &[("folder", &folder_arg), ("file", &file), ("dest", &dest)],
+            &log_file,
+        )
+        .await;
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn run_pipeline(
     client: Arc<Client>,
     config: Arc<Config>,
@@ -322,12 +570,22 @@ pub async fn run_pipeline(
     log_file: SharedLogFile,
     should_stop: Arc<AtomicBool>,
     options: DownloadOptions,
+    events: EventSink,
+    control: DownloadControl,
 ) -> PipelineResult {
     let total = resources.len();
+    if let Some(web_ui) = &options.web_ui {
+        web_ui.attach(&control, &should_stop);
+    }
+    let resources = DownloadQueue::new(resources, options.order);
+    // Kept in queue order, so an interrupted session resumes in the same order.
+    let all_items: Vec<ResourceItem> = resources.iter().cloned().collect();
+    let mut settled: HashSet<String> = HashSet::with_capacity(total);
     let total_download_size: u64 = resources.iter().filter_map(|item| item.size).sum();
     let verify_concurrency = options.verify_concurrency.max(1);
     let download_concurrency = options.download_concurrency.max(1);
     let post_verify_concurrency = verify_concurrency;
+    let options = Arc::new(options);
 
     let mut items_to_verify = Vec::new();
     let mut items_to_download = Vec::new();
@@ -337,7 +595,7 @@ pub async fn run_pipeline(
             break;
         }
 
-        let local_path = folder.join(item.dest.replace('\\', "/"));
+        let local_path = dest_path(&folder, &item.dest);
         let needs_verify = match tokio::fs::metadata(&local_path).await {
             Ok(meta) => {
                 if let Some(expected_size) = item.size {
@@ -361,12 +619,15 @@ pub async fn run_pipeline(
         download_concurrency,
         total_download_size,
         num_to_verify,
+        options.show_progress,
     ));
     let progress = DownloadProgress {
         total_bytes: Arc::new(AtomicU64::new(total_download_size)),
         downloaded_bytes: Arc::new(AtomicU64::new(0)),
         total_bar_lock: Arc::new(tokio::sync::Mutex::new(())),
         start_time: Instant::now(),
+        metrics: options.metrics.clone(),
+        cdns: options.cdns.clone(),
     };
 
     let (event_tx, mut event_rx): (
@@ -386,6 +647,7 @@ pub async fn run_pipeline(
             log_file.clone(),
             should_stop.clone(),
             display.verify_bar.clone(),
+            options.clone(),
         )));
     }
     drop(verify_rx);
@@ -403,10 +665,14 @@ pub async fn run_pipeline(
             should_stop.clone(),
             progress.clone(),
             display.clone(),
+            options.clone(),
+            events.clone(),
+            control.clone(),
         )));
     }
     drop(download_rx);
 
+    let hash_pool = HashPool::new(post_verify_concurrency);
     let mut post_verify_handles = Vec::with_capacity(post_verify_concurrency);
     for worker_id in 0..post_verify_concurrency {
         post_verify_handles.push(tokio::spawn(post_verify_worker(
@@ -418,9 +684,12 @@ pub async fn run_pipeline(
             should_stop.clone(),
             progress.clone(),
             display.clone(),
+            options.clone(),
+            hash_pool.clone(),
         )));
     }
     drop(post_verify_rx);
+    drop(hash_pool);
 
     for item in items_to_verify {
         if should_stop.load(Ordering::SeqCst) {
@@ -448,13 +717,23 @@ pub async fn run_pipeline(
     drop(event_tx);
 
     let mut result = PipelineResult {
-        verified_ok: 0,
-        downloaded_ok: 0,
-        failed: 0,
         total,
+        ..PipelineResult::default()
     };
+    let mut verified_bytes = 0;
     let mut active_tasks = total;
-    let mut shutting_down = should_stop.load(Ordering::SeqCst);
+    // A stop requested before this point is picked up by the first iteration,
+    // which closes the worker queues.
+    let mut shutting_down = false;
+    let mut last_bytes_event = Instant::now();
+    let mut last_webhook_post = Instant::now();
+    let mut last_progress_line = Instant::now();
+    let mut speed = SpeedStats::new(progress.downloaded(), Instant::now());
+    let mut groups = GroupProgress::new(&all_items);
+    display.groups_bar.set_message(groups.render(GROUPS_SHOWN));
+    let mut file_hooks = JoinSet::new();
+    let hook_slot = Arc::new(Semaphore::new(1));
+    let mut wake = WakeDetector::new();
 
     loop {
         if !shutting_down && active_tasks == 0 {
@@ -470,10 +749,105 @@ pub async fn run_pipeline(
             post_verify_tx.close();
         }
 
+        if let Some(webhook) = &options.webhook
+            && last_webhook_post.elapsed() >= webhook.interval
+        {
+            let message = progress_message(
+                result.verified_ok + result.downloaded_ok + result.failed,
+                total,
+                result.failed,
+                progress.downloaded(),
+                total_download_size,
+                speed.smoothed(),
+            );
+            let (webhook, client, log_file) = (webhook.clone(), client.clone(), log_file.clone());
+            tokio::spawn(async move { webhook.post(&client, &message, &log_file).await });
+            last_webhook_post = Instant::now();
+        }
+
+        if let Some(metrics) = &options.metrics {
+            metrics.set_progress(
+                result.verified_ok + result.downloaded_ok + result.failed,
+                total,
+                result.failed,
+                progress.downloaded(),
+                total_download_size,
+                speed.smoothed(),
+            );
+        }
+
+        if let Some(web_ui) = &options.web_ui {
+            web_ui.set_progress(
+                result.verified_ok + result.downloaded_ok + result.failed,
+                total,
+                progress.downloaded(),
+                total_download_size,
+                speed.smoothed(),
+                display.active_file(),
+                result
+                    .failures
+                    .iter()
+                    .map(|failure| (failure.item.dest.as_str(), failure.reason.as_str())),
+            );
+        }
+
+        if let Some(progress_log) = &options.progress_log
+            && last_progress_line.elapsed() >= progress_log.interval
+        {
+            let message = progress_message(
+                result.verified_ok + result.downloaded_ok + result.failed,
+                total,
+                result.failed,
+                progress.downloaded(),
+                total_download_size,
+                speed.smoothed(),
+            );
+            let active = display.active_file();
+            if active.is_empty() {
+                progress_log.append(&message);
+            } else {
+                progress_log.append(&format!("{}, current {}", message, active));
+            }
+            last_progress_line = Instant::now();
+        }
+
+        if events.is_enabled() && last_bytes_event.elapsed() >= BYTES_EVENT_INTERVAL {
+            events.bytes(progress.downloaded(), total_download_size, speed.smoothed());
+            last_bytes_event = Instant::now();
+        }
+
+        let downloaded = progress.downloaded();
+        if speed.record(downloaded, Instant::now()) {
+            let remaining = progress
+                .total_bytes
+                .load(Ordering::SeqCst)
+                .saturating_sub(downloaded);
+            display
+                .stats_bar
+                .set_message(speed.render(remaining, &display.active_file()));
+            display
+                .speed
+                .publish(speed.smoothed(), display.slot_pool.in_use());
+            display.groups_bar.set_message(groups.render(GROUPS_SHOWN));
+        }
+
+        if wake.woke(SystemTime::now()) && !shutting_down {
+            log_warn(
+                &log_file,
+                "The system woke from sleep, reconnecting open downloads",
+            );
+            control.reconnect();
+        }
+
         if shutting_down {
             display
                 .status_bar
                 .set_message(format!("shutdown: left={}", active_tasks));
+        } else if control.is_paused() {
+            display.status_bar.set_message(format!(
+                "paused: {} files left, press r to resume",
+                active_tasks
+            ));
         } else {
             display
                 .status_bar
@@ -487,8 +861,18 @@ pub async fn run_pipeline(
                 };
 
                 match event {
-                    PipelineEvent::VerifiedValid { completed_bytes } => {
+                    PipelineEvent::VerifiedValid { dest, completed_bytes } => {
+                        events.file_done(&dest, "verified");
+                        settled.insert(dest.clone());
+                        groups.complete(&dest);
+                        result.completed.push(CompletedResource {
+                            dest,
+                            downloaded: false,
+                            retries: 0,
+                            stats: None,
+                        });
                         if let Some(bytes) = completed_bytes {
+                            verified_bytes += bytes;
                             progress
                                 .add_downloaded_bytes(&display.total_bar, bytes)
                                 .await;
@@ -501,14 +885,14 @@ pub async fn run_pipeline(
                             continue;
                         }
 
-                        if enqueue_task(&download_tx, task).await.is_err() {
-                            result.failed += 1;
+                        if let Err(task) = enqueue_task(&download_tx, task).await {
+                            result.record_failure(task.item, "Download queue closed");
                             active_tasks = active_tasks.saturating_sub(1);
                         }
                     }
-                    PipelineEvent::VerificationFailed { dest } => {
-                        let _ = dest;
-                        result.failed += 1;
+                    PipelineEvent::VerificationFailed { item, reason } => {
+                        events.error(&item.dest, &reason);
+                        result.record_failure(item, reason);
                         active_tasks = active_tasks.saturating_sub(1);
                     }
                     PipelineEvent::VerificationAborted => {
@@ -518,20 +902,34 @@ pub async fn run_pipeline(
                             continue;
                         }
 
-                        if enqueue_task(&post_verify_tx, task).await.is_err() {
-                            result.failed += 1;
+                        if let Err(task) = enqueue_task(&post_verify_tx, task).await {
+                            result.record_failure(task.item, "Verification queue closed");
                             active_tasks = active_tasks.saturating_sub(1);
                         }
                     }
-                    PipelineEvent::DownloadFailed { dest } => {
-                        let _ = dest;
-                        result.failed += 1;
+                    PipelineEvent::DownloadFailed { item, reason } => {
+                        events.error(&item.dest, &reason);
+                        result.record_failure(item, reason);
                         active_tasks = active_tasks.saturating_sub(1);
                     }
                     PipelineEvent::DownloadAborted => {
                     }
-                    PipelineEvent::PostVerifySuccess => {
+                    PipelineEvent::PostVerifySuccess { dest, attempt, hashed_after_download, stats } => {
+                        events.file_done(&dest, "downloaded");
+                        log_info(&log_file, &format!("Downloaded {}", dest));
+                        if let Some(hook) = &options.on_file_complete {
+                            spawn_file_hook(&mut file_hooks, &hook_slot, hook, &folder, &dest, &log_file);
+                        }
+                        settled.insert(dest.clone());
+                        groups.complete(&dest);
+                        result.completed.push(CompletedResource {
+                            dest,
+                            downloaded: true,
+                            retries: attempt,
+                            stats: Some(stats),
+                        });
                         result.downloaded_ok += 1;
+                        result.hashed_after_download += usize::from(hashed_after_download);
                         active_tasks = active_tasks.saturating_sub(1);
                     }
                     PipelineEvent::NeedRetry(task) => {
@@ -539,19 +937,19 @@ pub async fn run_pipeline(
                             continue;
                         }
 
-                        if enqueue_task(&download_tx, task).await.is_err() {
-                            result.failed += 1;
+                        if let Err(task) = enqueue_task(&download_tx, task).await {
+                            result.record_failure(task.item, "Download queue closed");
                             active_tasks = active_tasks.saturating_sub(1);
                         }
                     }
-                    PipelineEvent::PostVerifyFailed { dest } => {
-                        let _ = dest;
-                        result.failed += 1;
+                    PipelineEvent::PostVerifyFailed { item, reason } => {
+                        events.error(&item.dest, &reason);
+                        result.record_failure(item, reason);
                         active_tasks = active_tasks.saturating_sub(1);
                     }
-                    PipelineEvent::PostVerifyIoFailed { dest } => {
-                        let _ = dest;
-                        result.failed += 1;
+                    PipelineEvent::PostVerifyIoFailed { item, reason } => {
+                        events.error(&item.dest, &reason);
+                        result.record_failure(item, reason);
                         active_tasks = active_tasks.saturating_sub(1);
                     }
                     PipelineEvent::PostVerifyAborted => {
@@ -560,6 +958,7 @@ pub async fn run_pipeline(
             }
             _ = tokio::time::sleep(std::time::Duration::from_millis(250)) => {
                 display.status_bar.tick();
+                display.stats_bar.tick();
             }
         }
     }
@@ -573,11 +972,29 @@ pub async fn run_pipeline(
     for handle in download_handles {
         let _ = handle.await;
     }
+    while file_hooks.join_next().await.is_some() {}
     for handle in post_verify_handles {
         let _ = handle.await;
     }
 
+    if let Err(e) = options.hash_cache.save() {
+        log_error(&log_file, &format!("Failed to save {}: {}", HASH_CACHE, e));
+    }
+
+    result.bytes_downloaded = progress.downloaded().saturating_sub(verified_bytes);
     let stopped = should_stop.load(Ordering::SeqCst);
+    if stopped {
+        settled.extend(
+            result
+                .failures
+                .iter()
+                .map(|failure| failure.item.dest.clone()),
+        );
+        result.unfinished = all_items
+            .into_iter()
+            .filter(|item| !settled.contains(&item.dest))
+            .collect();
+    }
     for slot in 0..display.slot_pool.len() {
         let slot_bar = display.slot_pool.bar(slot);
         if stopped {
@@ -587,6 +1004,53 @@ pub async fn run_pipeline(
         }
     }
 
+    if let Some(metrics) = &options.metrics {
+        metrics.set_progress(
+            result.verified_ok + result.downloaded_ok + result.failed,
+            total,
+            result.failed,
+            progress.downloaded(),
+            total_download_size,
+            0,
+        );
+    }
+    if let Some(web_ui) = &options.web_ui {
+        web_ui.set_progress(
+            result.verified_ok + result.downloaded_ok + result.failed,
+            total,
+            progress.downloaded(),
+            total_download_size,
+            0,
+            String::new(),
+            result
+                .failures
+                .iter()
+                .map(|failure| (failure.item.dest.as_str(), failure.reason.as_str())),
+        );
+        web_ui.finish(stopped);
+    }
+    if let Some(progress_log) = &options.progress_log {
+        progress_log.append(&format!(
+            "{}: {} verified, {} downloaded, {} failed of {} files, {} fetched, avg {}/s",
+            if stopped { "Stopped" } else { "Finished" },
+            result.verified_ok,
+            result.downloaded_ok,
+            result.failed,
+            total,
+            HumanBytes(result.bytes_downloaded),
+            HumanBytes(speed.average())
+        ));
+    }
+    display
+        .groups_bar
+        .finish_with_message(groups.render(GROUPS_SHOWN));
+    result.groups = groups.into_groups();
+    result.cdns = options.cdns.stats(&config.zip_bases);
+    display.stats_bar.finish_with_message(format!(
+        "avg {}/s | peak {}/s",
+        HumanBytes(speed.average()),
+        HumanBytes(speed.peak())
+    ));
     if stopped {
         display.status_bar.finish_with_message("stopped");
         display
@@ -603,3 +1067,112 @@ pub async fn run_pipeline(
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_PIPELINE_RETRIES, run_pipeline};
+    use crate::config::cfg::{Config, DownloadOptions, ResourceItem};
+    use crate::download::backend::{DownloadBackend, DownloadJob, FetchFuture};
+    use crate::download::{
+        control::DownloadControl,
+        events::EventSink,
+        progress::{FileProgress, TransferStats},
+    };
+    use crate::io::file::{Checksum, HashAlgorithm, part_path, resolve_dest, unique_path};
+    use crate::io::logging::setup_logging;
+    use crate::io::quarantine::QUARANTINE_DIR;
+    use crate::network::client::DownloadedFile;
+    use reqwest::Client;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    /// Writes the same body for every file instead of going to the network.
+    struct MockBackend {
+        body: &'static [u8],
+        calls: AtomicUsize,
+    }
+
+    impl DownloadBackend for MockBackend {
+        fn fetch<'a>(&'a self, job: &'a DownloadJob<'a>) -> FetchFuture<'a> {
+            Box::pin(async move {
+                self.calls.fetch_add(1, Ordering::SeqCst);
+                let path = part_path(&resolve_dest(job.folder, &job.item.dest)?);
+                fs::create_dir_all(path.parent().unwrap()).map_err(|e| e.to_string())?;
+                fs::write(&path, self.body).map_err(|e| e.to_string())?;
+                let mut file_progress = FileProgress::new(job.progress, job.total_pb, true);
+                file_progress.add(self.body.len() as u64).await;
+                Ok(DownloadedFile {
+                    digest: None,
+                    counted_bytes: file_progress.counted(),
+                    stats: TransferStats::default(),
+                })
+            })
+        }
+    }
+
+    fn scratch_dir() -> PathBuf {
+        unique_path("pipeline")
+    }
+
+    async fn run_with(body: &'static [u8], folder: &Path) -> (super::PipelineResult, usize) {
+        let backend = Arc::new(MockBackend {
+            body,
+            calls: AtomicUsize::new(0),
+        });
+        let options = DownloadOptions {
+            show_progress: false,
+            backend: backend.clone(),
+            ..DownloadOptions::default()
+        };
+        let item = ResourceItem {
+            dest: "Client/abc.pak".to_string(),
+            checksum: Some(Checksum::new(
+                HashAlgorithm::Md5,
+                "900150983cd24fb0d6963f7d28e17f72",
+            )),
+            size: Some(3),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        };
+        let result = run_pipeline(
+            Arc::new(Client::new()),
+            Arc::new(Config {
+                index_url: String::new(),
+                zip_bases: Vec::new(),
+            }),
+            vec![item],
+            folder.to_path_buf(),
+            setup_logging(&folder.join("logs")).unwrap(),
+            Arc::new(AtomicBool::new(false)),
+            options,
+            EventSink::disabled(),
+            DownloadControl::default(),
+        )
+        .await;
+        (result, backend.calls.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn backend_download_is_verified_and_renamed() {
+        let folder = scratch_dir();
+        let (result, calls) = run_with(b"abc", &folder).await;
+
+        assert_eq!((result.downloaded_ok, result.failed, calls), (1, 0, 1));
+        assert_eq!(fs::read(folder.join("Client/abc.pak")).unwrap(), b"abc");
+        assert_eq!(result.bytes_downloaded, 3);
+        assert_eq!(result.hashed_after_download, 1);
+    }
+
+    #[tokio::test]
+    async fn corrupt_backend_download_is_retried_then_quarantined() {
+        let folder = scratch_dir();
+        let (result, calls) = run_with(b"abd", &folder).await;
+
+        assert_eq!((result.downloaded_ok, result.failed), (0, 1));
+        assert_eq!(calls, MAX_PIPELINE_RETRIES + 1);
+        assert!(!folder.join("Client/abc.pak").exists());
+        assert!(folder.join(QUARANTINE_DIR).join("Client/abc.pak").exists());
+    }
+}
@@ -1,20 +1,33 @@
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{
+    HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState,
+    ProgressStyle,
+};
 use std::{
     collections::VecDeque,
+    fmt::Write as FmtWrite,
     sync::{
         Arc,
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 use tokio::sync::{Mutex, Notify};
 
+use crate::config::{cfg::ResourceItem, style};
+use crate::io::{file::HashAlgorithm, url_log::UrlLog};
+use crate::network::{
+    cdn_tracker::CdnTracker,
+    metrics::{Metrics, cdn_host},
+};
+
 #[derive(Clone)]
 pub struct DownloadProgress {
     pub total_bytes: Arc<AtomicU64>,
     pub downloaded_bytes: Arc<AtomicU64>,
     pub(crate) total_bar_lock: Arc<Mutex<()>>,
     pub start_time: Instant,
+    pub metrics: Option<Metrics>,
+    pub cdns: CdnTracker,
 }
 
 impl DownloadProgress {
@@ -58,11 +71,129 @@ impl DownloadProgress {
     }
 }
 
+/// How one file's download went, for `--stats-csv`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransferStats {
+    /// HTTP requests sent for the file, ranges and retries included.
+    pub requests: usize,
+    /// Bytes received, including any a restart threw away.
+    pub bytes: u64,
+    /// Host of the CDN the last bytes came from.
+    pub cdn: Option<String>,
+    /// Time from the first request to the last byte.
+    pub duration: Duration,
+}
+
+/// One file's contribution to the session total. Bytes go in as deltas and are
+/// taken back exactly when the file restarts or ultimately fails, so the total
+/// never drifts from what verified and in-flight files account for.
+pub struct FileProgress {
+    session: DownloadProgress,
+    total_bar: ProgressBar,
+    counted: u64,
+    tracked: bool,
+    stats: TransferStats,
+    /// `--log-urls` and the dest and MD5 its lines name.
+    url_log: Option<(UrlLog, String, String)>,
+}
+
+impl FileProgress {
+    /// Files without a known size are left out of the session total, which
+    /// only sums known sizes.
+    pub fn new(session: &DownloadProgress, total_bar: &ProgressBar, tracked: bool) -> Self {
+        Self {
+            session: session.clone(),
+            total_bar: total_bar.clone(),
+            counted: 0,
+            tracked,
+            stats: TransferStats::default(),
+            url_log: None,
+        }
+    }
+
+    /// Records each URL requested for `item` in `url_log`.
+    pub fn log_urls(&mut self, url_log: &UrlLog, item: &ResourceItem) {
+        let md5 = item
+            .checksum
+            .as_ref()
+            .filter(|checksum| checksum.algorithm == HashAlgorithm::Md5)
+            .map(|checksum| checksum.digest.clone())
+            .unwrap_or_default();
+        self.url_log = Some((url_log.clone(), item.dest.replace('\\', "/"), md5));
+    }
+
+    pub async fn add(&mut self, amount: u64) {
+        if amount == 0 || !self.tracked {
+            return;
+        }
+        self.session
+            .add_downloaded_bytes(&self.total_bar, amount)
+            .await;
+        self.counted += amount;
+    }
+
+    /// Counts bytes already on disk from an earlier attempt, unless this file
+    /// has contributed before.
+    pub async fn add_existing(&mut self, amount: u64) {
+        if self.counted == 0 {
+            self.add(amount).await;
+        }
+    }
+
+    /// Takes back part of what this file counted, e.g. one failed chunk.
+    pub async fn rollback_bytes(&mut self, amount: u64) {
+        let amount = amount.min(self.counted);
+        self.session
+            .rollback_downloaded_bytes(&self.total_bar, amount)
+            .await;
+        self.counted -= amount;
+    }
+
+    pub async fn rollback(&mut self) {
+        self.rollback_bytes(self.counted).await;
+    }
+
+    /// Counts a request about to be sent for this file to `url`.
+    pub fn record_request(&mut self, url: &str) {
+        self.stats.requests += 1;
+        if let Some((url_log, dest, md5)) = &self.url_log {
+            url_log.record(url, dest, md5);
+        }
+    }
+
+    /// Credits `amount` bytes fetched from `url` to its CDN in the metrics.
+    pub fn record_source(&mut self, url: &str, amount: u64) {
+        if let Some(metrics) = &self.session.metrics {
+            metrics.add_cdn_bytes(url, amount);
+        }
+        self.stats.bytes += amount;
+        let host = cdn_host(url);
+        if self.stats.cdn.as_deref() != Some(host) {
+            self.stats.cdn = Some(host.to_string());
+        }
+    }
+
+    /// What this file's requests did so far; the caller times them.
+    pub fn stats(&self) -> TransferStats {
+        self.stats.clone()
+    }
+
+    /// The session's per-CDN response counts.
+    pub fn cdns(&self) -> &CdnTracker {
+        &self.session.cdns
+    }
+
+    pub fn counted(&self) -> u64 {
+        self.counted
+    }
+}
+
 #[derive(Clone)]
 pub struct ProgressSlotPool {
     bars: Arc<Vec<ProgressBar>>,
     available: Arc<Mutex<VecDeque<usize>>>,
     notify: Arc<Notify>,
+    in_use: Arc<AtomicUsize>,
 }
 
 impl ProgressSlotPool {
@@ -76,6 +207,7 @@ impl ProgressSlotPool {
             bars: Arc::new(bars),
             available: Arc::new(Mutex::new(queue)),
             notify: Arc::new(Notify::new()),
+            in_use: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -85,6 +217,7 @@ impl ProgressSlotPool {
                 let mut guard = self.available.lock().await;
                 guard.pop_front()
             } {
+                self.in_use.fetch_add(1, Ordering::SeqCst);
                 return idx;
             }
 
@@ -97,6 +230,7 @@ impl ProgressSlotPool {
             let mut guard = self.available.lock().await;
             guard.push_back(idx);
         }
+        self.in_use.fetch_sub(1, Ordering::SeqCst);
         self.notify.notify_one();
     }
 
@@ -108,60 +242,131 @@ impl ProgressSlotPool {
         self.bars.len()
     }
 
+    pub fn in_use(&self) -> usize {
+        self.in_use.load(Ordering::SeqCst)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.bars.is_empty()
     }
 }
 
+/// Smoothed speeds published by the pipeline and rendered through the
+/// `{ema_eta}` / `{ema_speed}` template keys, replacing indicatif's own
+/// estimate which resume jumps and pauses throw off.
+#[derive(Clone, Default)]
+pub struct SmoothedSpeed {
+    total: Arc<AtomicU64>,
+    per_file: Arc<AtomicU64>,
+}
+
+impl SmoothedSpeed {
+    /// Active downloads are assumed to share the bandwidth evenly.
+    pub fn publish(&self, total: u64, active_downloads: usize) {
+        self.total.store(total, Ordering::SeqCst);
+        self.per_file
+            .store(total / active_downloads.max(1) as u64, Ordering::SeqCst);
+    }
+}
+
+fn ema_style(template: &str, speed: Arc<AtomicU64>) -> ProgressStyle {
+    let eta_speed = speed.clone();
+    let bar = ProgressStyle::default_bar().template(template).unwrap();
+    style::ticks(bar)
+        .with_key(
+            "ema_eta",
+            move |state: &ProgressState, w: &mut dyn FmtWrite| {
+                let speed = eta_speed.load(Ordering::SeqCst);
+                let remaining = state.len().unwrap_or(0).saturating_sub(state.pos());
+                let _ = match remaining.checked_div(speed) {
+                    Some(secs) => write!(w, "{:#}", HumanDuration(Duration::from_secs(secs))),
+                    None => write!(w, "--"),
+                };
+            },
+        )
+        .with_key(
+            "ema_speed",
+            move |_: &ProgressState, w: &mut dyn FmtWrite| {
+                let _ = write!(w, "{}/s", HumanBytes(speed.load(Ordering::SeqCst)));
+            },
+        )
+        .progress_chars("#>-")
+}
+
 #[derive(Clone)]
 pub struct ProgressDisplay {
     pub status_bar: ProgressBar,
+    pub stats_bar: ProgressBar,
+    pub groups_bar: ProgressBar,
     pub verify_bar: ProgressBar,
     pub total_bar: ProgressBar,
     pub slot_pool: ProgressSlotPool,
+    pub speed: SmoothedSpeed,
+    active_file: Arc<std::sync::Mutex<String>>,
     _multi: Arc<MultiProgress>,
 }
 
 impl ProgressDisplay {
-    pub fn new(download_concurrency: usize, total_download_size: u64, total_files: usize) -> Self {
-        let multi = Arc::new(MultiProgress::new());
+    pub fn new(
+        download_concurrency: usize,
+        total_download_size: u64,
+        total_files: usize,
+        visible: bool,
+    ) -> Self {
+        let multi = Arc::new(if visible {
+            MultiProgress::new()
+        } else {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        });
 
         let status_bar = multi.add(ProgressBar::new_spinner());
-        status_bar.set_style(
+        status_bar.set_style(style::ticks(
             ProgressStyle::default_spinner()
                 .template("{spinner:.yellow} [STATUS] {msg}")
                 .unwrap(),
-        );
+        ));
         status_bar.set_message("running");
 
+        let stats_bar = multi.add(ProgressBar::new_spinner());
+        stats_bar.set_style(style::ticks(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.cyan} [SPEED] {msg}")
+                .unwrap(),
+        ));
+        stats_bar.set_message("waiting for data");
+
+        let groups_bar = multi.add(ProgressBar::new_spinner());
+        groups_bar.set_style(style::ticks(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.blue} [DIRS] {msg}")
+                .unwrap(),
+        ));
+
         // Verification progress bar (top)
         let verify_bar = multi.add(ProgressBar::new(total_files as u64));
-        verify_bar.set_style(
+        verify_bar.set_style(style::ticks(
             ProgressStyle::default_bar()
                 .template("{spinner:.green} [VERIFY] [{wide_bar:.magenta/blue}] {pos}/{len} files ({eta})")
                 .unwrap()
                 .progress_chars("#>-"),
-        );
+        ));
 
         // Total download progress bar
+        let speed = SmoothedSpeed::default();
         let total_bar = multi.add(ProgressBar::new(total_download_size));
-        total_bar.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} [TOTAL] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta}, {binary_bytes_per_sec})")
-                .unwrap()
-                .progress_chars("#>-"),
-        );
+        total_bar.set_style(ema_style(
+            "{spinner:.green} [TOTAL] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({ema_eta}, {ema_speed})",
+            speed.total.clone(),
+        ));
 
         // Per-worker download slot bars (bottom)
         let mut bars = Vec::with_capacity(download_concurrency);
         for idx in 0..download_concurrency {
             let bar = multi.add(ProgressBar::new(0));
-            bar.set_style(
-                ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{prefix}] [{wide_bar:.yellow/blue}] {bytes}/{total_bytes} ({eta}, {binary_bytes_per_sec}) {msg}")
-                    .unwrap()
-                    .progress_chars("#>-"),
-            );
+            bar.set_style(ema_style(
+                "{spinner:.green} [{prefix}] [{wide_bar:.yellow/blue}] {bytes}/{total_bytes} ({ema_eta}, {binary_bytes_per_sec}) {msg}",
+                speed.per_file.clone(),
+            ));
             bar.set_prefix(format!("DL {:02}", idx + 1));
             bar.set_message("idle");
             bars.push(bar);
@@ -169,10 +374,60 @@ impl ProgressDisplay {
 
         Self {
             status_bar,
+            stats_bar,
+            groups_bar,
             verify_bar,
             total_bar,
             slot_pool: ProgressSlotPool::new(bars),
+            speed,
+            active_file: Arc::new(std::sync::Mutex::new(String::new())),
             _multi: multi,
         }
     }
+
+    /// Most recently started download, shown in the stats pane.
+    pub fn set_active_file(&self, name: &str) {
+        if let Ok(mut active) = self.active_file.lock() {
+            name.clone_into(&mut active);
+        }
+    }
+
+    pub fn active_file(&self) -> String {
+        self.active_file
+            .lock()
+            .map(|active| active.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadProgress, FileProgress};
+    use indicatif::ProgressBar;
+    use std::sync::{Arc, atomic::AtomicU64};
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn file_progress_rolls_back_only_its_own_bytes() {
+        let session = DownloadProgress {
+            total_bytes: Arc::new(AtomicU64::new(300)),
+            downloaded_bytes: Arc::new(AtomicU64::new(0)),
+            total_bar_lock: Arc::new(tokio::sync::Mutex::new(())),
+            start_time: Instant::now(),
+            metrics: None,
+            cdns: Default::default(),
+        };
+        let bar = ProgressBar::hidden();
+        let mut first = FileProgress::new(&session, &bar, true);
+        let mut second = FileProgress::new(&session, &bar, true);
+
+        first.add(100).await;
+        second.add_existing(50).await;
+        second.add(25).await;
+        second.add_existing(50).await;
+        first.rollback().await;
+
+        assert_eq!(session.downloaded(), 75);
+        assert_eq!(second.counted(), 75);
+    }
 }
@@ -0,0 +1,111 @@
+use std::{cmp::Reverse, collections::VecDeque};
+
+use crate::config::cfg::ResourceItem;
+
+/// Order in which resources are verified and downloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DownloadOrder {
+    /// As listed by the index.
+    #[default]
+    Manifest,
+    LargestFirst,
+    /// Many quick completions early on, which shows the setup works before
+    /// the multi-gigabyte paks start.
+    SmallestFirst,
+    /// Folder by folder, so each directory is finished before the next starts.
+    Directory,
+}
+
+/// Resources waiting to be processed, arranged by a [`DownloadOrder`].
+/// Sorting is stable, so ties keep the index order; files of unknown size go last.
+pub struct DownloadQueue {
+    items: VecDeque<ResourceItem>,
+}
+
+impl DownloadQueue {
+    pub fn new(mut items: Vec<ResourceItem>, order: DownloadOrder) -> Self {
+        match order {
+            DownloadOrder::Manifest => {}
+            DownloadOrder::LargestFirst => {
+                items.sort_by_key(|item| (item.size.is_none(), Reverse(item.size)));
+            }
+            DownloadOrder::SmallestFirst => {
+                items.sort_by_key(|item| (item.size.is_none(), item.size));
+            }
+            DownloadOrder::Directory => {
+                items.sort_by_cached_key(|item| {
+                    let dest = item.dest.replace('\\', "/");
+                    match dest.rsplit_once('/') {
+                        Some((dir, name)) => (dir.to_string(), name.to_string()),
+                        None => (String::new(), dest),
+                    }
+                });
+            }
+        }
+        Self {
+            items: items.into(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ResourceItem> {
+        self.items.iter()
+    }
+}
+
+impl IntoIterator for DownloadQueue {
+    type Item = ResourceItem;
+    type IntoIter = std::collections::vec_deque::IntoIter<ResourceItem>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadOrder, DownloadQueue};
+    use crate::config::cfg::ResourceItem;
+
+    fn item(dest: &str, size: Option<u64>) -> ResourceItem {
+        ResourceItem {
+            dest: dest.to_string(),
+            checksum: None,
+            size,
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }
+    }
+
+    fn order(order: DownloadOrder) -> Vec<String> {
+        let items = vec![
+            item("Paks/b.pak", Some(50)),
+            item("a.exe", None),
+            item("Paks/a.pak", Some(500)),
+            item("Binaries/c.dll", Some(5)),
+        ];
+        DownloadQueue::new(items, order)
+            .into_iter()
+            .map(|item| item.dest)
+            .collect()
+    }
+
+    #[test]
+    fn queue_orders_by_size_or_folder() {
+        assert_eq!(
+            order(DownloadOrder::Manifest),
+            ["Paks/b.pak", "a.exe", "Paks/a.pak", "Binaries/c.dll"]
+        );
+        assert_eq!(
+            order(DownloadOrder::LargestFirst),
+            ["Paks/a.pak", "Paks/b.pak", "Binaries/c.dll", "a.exe"]
+        );
+        assert_eq!(
+            order(DownloadOrder::SmallestFirst),
+            ["Binaries/c.dll", "Paks/b.pak", "Paks/a.pak", "a.exe"]
+        );
+        assert_eq!(
+            order(DownloadOrder::Directory),
+            ["a.exe", "Binaries/c.dll", "Paks/a.pak", "Paks/b.pak"]
+        );
+    }
+}
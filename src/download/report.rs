@@ -0,0 +1,386 @@
+use chrono::{DateTime, Local};
+use serde_json::{Value, json};
+use std::{fs, io, path::Path};
+
+use crate::config::cfg::ResourceItem;
+use crate::download::pipeline::{FailedResource, PipelineResult};
+use crate::io::util::{parse_resources, resource_to_json};
+
+pub const FAILED_REPORT: &str = "failed.json";
+pub const SESSION_REPORT: &str = "session-report.json";
+const INTERRUPTED_REASON: &str = "Interrupted before completion";
+
+/// One session's record for `session-report.json`.
+pub struct SessionReport<'a> {
+    pub index_url: &'a str,
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    pub interrupted: bool,
+    pub result: &'a PipelineResult,
+}
+
+pub struct FailedReport {
+    pub index_url: Option<String>,
+    pub resources: Vec<ResourceItem>,
+}
+
+fn resource_entry(item: &ResourceItem, reason: &str) -> Value {
+    let mut entry = resource_to_json(item);
+    entry.insert("reason".to_string(), json!(reason));
+    Value::Object(entry)
+}
+
+/// Writes `failed.json` into the download folder, using the index's own
+/// `resource` layout so it can be loaded back like a manifest. Resources left
+/// unfinished by an interrupt are listed too, so `--retry-failed` resumes them.
+/// A session with nothing left to do removes a stale report instead.
+pub fn write_failed_report(
+    folder: &Path,
+    index_url: &str,
+    failures: &[FailedResource],
+    unfinished: &[ResourceItem],
+) -> io::Result<()> {
+    let path = folder.join(FAILED_REPORT);
+    if failures.is_empty() && unfinished.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+
+    let report = json!({
+        "index_url": index_url,
+        "resource": failures
+            .iter()
+            .map(|failure| resource_entry(&failure.item, &failure.reason))
+            .chain(unfinished.iter().map(|item| resource_entry(item, INTERRUPTED_REASON)))
+            .collect::<Vec<_>>(),
+    });
+    let contents = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+    fs::write(path, contents)
+}
+
+/// Writes `session-report.json` into the download folder, replacing the one
+/// from the previous session.
+pub fn write_session_report(folder: &Path, report: &SessionReport) -> io::Result<()> {
+    let contents =
+        serde_json::to_string_pretty(&session_report_json(report)).map_err(io::Error::other)?;
+    fs::write(folder.join(SESSION_REPORT), contents)
+}
+
+fn session_report_json(report: &SessionReport) -> Value {
+    let result = report.result;
+    let elapsed = (report.finished - report.started)
+        .to_std()
+        .unwrap_or_default()
+        .as_secs_f64();
+    let average_speed = if elapsed > 0.0 {
+        (result.bytes_downloaded as f64 / elapsed) as u64
+    } else {
+        0
+    };
+
+    let completed = result.completed.iter().map(|file| {
+        json!({
+            "dest": file.dest,
+            "outcome": if file.downloaded { "downloaded" } else { "skipped_valid" },
+            "retries": file.retries,
+        })
+    });
+    let failed = result.failures.iter().map(|failure| {
+        json!({
+            "dest": failure.item.dest,
+            "outcome": "failed",
+            "reason": failure.reason,
+        })
+    });
+    let unfinished = result.unfinished.iter().map(|item| {
+        json!({
+            "dest": item.dest,
+            "outcome": "unfinished",
+        })
+    });
+
+    json!({
+        "tool_version": env!("CARGO_PKG_VERSION"),
+        "index_url": report.index_url,
+        "started_at": report.started.to_rfc3339(),
+        "finished_at": report.finished.to_rfc3339(),
+        "duration_secs": elapsed,
+        "interrupted": report.interrupted,
+        "total": result.total,
+        "downloaded": result.downloaded_ok,
+        "skipped_valid": result.verified_ok,
+        "failed": result.failed,
+        "unfinished": result.unfinished.len(),
+        "bytes_downloaded": result.bytes_downloaded,
+        "average_bytes_per_sec": average_speed,
+        "retries": result.completed.iter().map(|file| file.retries).sum::<usize>(),
+        "hashed_after_download": result.hashed_after_download,
+        "directories": result.groups.iter().map(|group| json!({
+            "name": group.name,
+            "files": group.files,
+            "files_done": group.files_done,
+            "bytes": group.bytes,
+            "bytes_done": group.bytes_done,
+        })).collect::<Vec<_>>(),
+        "cdns": result.cdns.iter().map(|cdn| json!({
+            "host": cdn.host,
+            "address": cdn.address.map(|ip| ip.to_string()),
+            "ok": cdn.ok,
+            "errors": cdn.errors,
+            "rate_limited": cdn.rate_limited,
+            "benched": cdn.benched,
+        })).collect::<Vec<_>>(),
+        "files": completed.chain(failed).chain(unfinished).collect::<Vec<_>>(),
+    })
+}
+
+/// `--stats-csv`: one row per file with its outcome and, for downloads, the
+/// bytes received, duration, average speed, requests sent and last CDN.
+pub fn write_stats_csv(path: &Path, result: &PipelineResult) -> io::Result<()> {
+    fs::write(path, stats_csv(result))
+}
+
+fn stats_csv(result: &PipelineResult) -> String {
+    let mut csv = String::from(
+        "dest,outcome,bytes,duration_secs,bytes_per_sec,requests,retries,cdn,reason\n",
+    );
+    let mut row = |fields: [&str; 9]| {
+        let fields: Vec<_> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    };
+
+    for file in &result.completed {
+        let outcome = if file.downloaded {
+            "downloaded"
+        } else {
+            "skipped_valid"
+        };
+        let retries = file.retries.to_string();
+        match &file.stats {
+            Some(stats) => {
+                let secs = stats.duration.as_secs_f64();
+                let speed = if secs > 0.0 {
+                    ((stats.bytes as f64 / secs) as u64).to_string()
+                } else {
+                    String::new()
+                };
+                row([
+                    &file.dest,
+                    outcome,
+                    &stats.bytes.to_string(),
+                    &format!("{:.3}", secs),
+                    &speed,
+                    &stats.requests.to_string(),
+                    &retries,
+                    stats.cdn.as_deref().unwrap_or_default(),
+                    "",
+                ]);
+            }
+            None => row([&file.dest, outcome, "", "", "", "", &retries, "", ""]),
+        }
+    }
+    for failure in &result.failures {
+        row([
+            &failure.item.dest,
+            "failed",
+            "",
+            "",
+            "",
+            "",
+            "",
+            "",
+            &failure.reason,
+        ]);
+    }
+    for item in &result.unfinished {
+        row([&item.dest, "unfinished", "", "", "", "", "", "", ""]);
+    }
+    csv
+}
+
+/// Quotes `field` when it holds a comma, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn load_failed_report(folder: &Path) -> Result<FailedReport, String> {
+    let path = folder.join(FAILED_REPORT);
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let data: Value = serde_json::from_str(&contents)
+        .map_err(|e| format!("Invalid {}: {}", path.display(), e))?;
+
+    Ok(FailedReport {
+        index_url: data
+            .get("index_url")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        resources: parse_resources(&data)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        SessionReport, load_failed_report, session_report_json, stats_csv, write_failed_report,
+    };
+    use crate::config::cfg::{FileChunk, ResourceItem};
+    use crate::download::pipeline::{CompletedResource, FailedResource, PipelineResult};
+    use crate::download::progress::TransferStats;
+    use crate::io::file::{Checksum, HashAlgorithm, unique_path};
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn failed_report_round_trips_resources() {
+        let dir = unique_path("report");
+        fs::create_dir_all(&dir).unwrap();
+
+        let failures = vec![FailedResource {
+            item: ResourceItem {
+                dest: "Paks/main.pak".to_string(),
+                checksum: Some(Checksum::new(HashAlgorithm::Sha1, "ABC")),
+                size: Some(42),
+                chunks: vec![FileChunk {
+                    start: 0,
+                    end: 41,
+                    checksum: Checksum::new(HashAlgorithm::Md5, "DEF"),
+                }],
+                unpacked_size: None,
+            },
+            reason: "All CDNs failed".to_string(),
+        }];
+        let unfinished = vec![ResourceItem {
+            dest: "Paks/optional.pak".to_string(),
+            checksum: None,
+            size: None,
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }];
+        write_failed_report(&dir, "https://cdn/index.json", &failures, &unfinished).unwrap();
+
+        let report = load_failed_report(&dir).unwrap();
+        assert_eq!(report.index_url.as_deref(), Some("https://cdn/index.json"));
+        assert_eq!(report.resources.len(), 2);
+        assert_eq!(report.resources[0].dest, "Paks/main.pak");
+        assert_eq!(report.resources[0].size, Some(42));
+        assert_eq!(report.resources[0].checksum, failures[0].item.checksum);
+        assert_eq!(report.resources[0].chunks, failures[0].item.chunks);
+
+        assert_eq!(report.resources[1].dest, "Paks/optional.pak");
+
+        write_failed_report(&dir, "https://cdn/index.json", &[], &[]).unwrap();
+        assert!(load_failed_report(&dir).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn session_report_lists_every_outcome_and_speed() {
+        let result = PipelineResult {
+            verified_ok: 1,
+            downloaded_ok: 1,
+            failed: 1,
+            total: 3,
+            failures: vec![FailedResource {
+                item: ResourceItem {
+                    dest: "Paks/c.pak".to_string(),
+                    checksum: None,
+                    size: None,
+                    chunks: Vec::new(),
+                    unpacked_size: None,
+                },
+                reason: "All CDNs failed".to_string(),
+            }],
+            completed: vec![
+                CompletedResource {
+                    dest: "Paks/a.pak".to_string(),
+                    downloaded: false,
+                    retries: 0,
+                    stats: None,
+                },
+                CompletedResource {
+                    dest: "Paks/b.pak".to_string(),
+                    downloaded: true,
+                    retries: 1,
+                    stats: None,
+                },
+            ],
+            bytes_downloaded: 1000,
+            ..PipelineResult::default()
+        };
+        let started = chrono::Local::now();
+        let report = session_report_json(&SessionReport {
+            index_url: "https://cdn/index.json",
+            started,
+            finished: started + chrono::Duration::seconds(10),
+            interrupted: false,
+            result: &result,
+        });
+
+        assert_eq!(report["average_bytes_per_sec"], 100);
+        assert_eq!(report["retries"], 1);
+        let outcomes: Vec<&str> = report["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| file["outcome"].as_str().unwrap())
+            .collect();
+        assert_eq!(outcomes, ["skipped_valid", "downloaded", "failed"]);
+        assert_eq!(report["files"][2]["reason"], "All CDNs failed");
+    }
+
+    #[test]
+    fn stats_csv_has_a_row_per_file() {
+        let result = PipelineResult {
+            failures: vec![FailedResource {
+                item: ResourceItem {
+                    dest: "Paks/c.pak".to_string(),
+                    checksum: None,
+                    size: None,
+                    chunks: Vec::new(),
+                    unpacked_size: None,
+                },
+                reason: "HTTP error: 404, then \"gone\"".to_string(),
+            }],
+            completed: vec![
+                CompletedResource {
+                    dest: "Paks/a.pak".to_string(),
+                    downloaded: false,
+                    retries: 0,
+                    stats: None,
+                },
+                CompletedResource {
+                    dest: "Paks/b.pak".to_string(),
+                    downloaded: true,
+                    retries: 1,
+                    stats: Some(TransferStats {
+                        requests: 3,
+                        bytes: 4000,
+                        cdn: Some("cdn-b.example.com".to_string()),
+                        duration: Duration::from_secs(2),
+                    }),
+                },
+            ],
+            ..PipelineResult::default()
+        };
+
+        let csv = stats_csv(&result);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "dest,outcome,bytes,duration_secs,bytes_per_sec,requests,retries,cdn,reason",
+                "Paks/a.pak,skipped_valid,,,,,0,,",
+                "Paks/b.pak,downloaded,4000,2.000,2000,3,1,cdn-b.example.com,",
+                "Paks/c.pak,failed,,,,,,,\"HTTP error: 404, then \"\"gone\"\"\"",
+            ]
+        );
+    }
+}
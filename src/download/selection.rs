@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use crate::config::cfg::ResourceItem;
+use crate::download::filter::FilterStats;
+
+const AUDIO_MARKERS: [&str; 3] = ["audio", "wwise", "voice"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioGroup {
+    pub language: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+fn canonical_language(segment: &str) -> Option<&'static str> {
+    match segment.to_ascii_lowercase().as_str() {
+        "zh" | "cn" | "chinese" => Some("zh"),
+        "en" | "english" => Some("en"),
+        "ja" | "jp" | "japanese" => Some("ja"),
+        "ko" | "kr" | "korean" => Some("ko"),
+        _ => None,
+    }
+}
+
+pub fn language_label(code: &str) -> &str {
+    match code {
+        "zh" => "Chinese",
+        "en" => "English",
+        "ja" => "Japanese",
+        "ko" => "Korean",
+        other => other,
+    }
+}
+
+/// Voice-over language of a resource: a language directory nested somewhere
+/// below an audio directory (e.g. `.../WwiseAudio/Media/en/...`).
+pub fn audio_language(dest: &str) -> Option<&'static str> {
+    let mut in_audio = false;
+    for segment in dest.split(['/', '\\']) {
+        if in_audio && let Some(language) = canonical_language(segment) {
+            return Some(language);
+        }
+        let lower = segment.to_ascii_lowercase();
+        if AUDIO_MARKERS.iter().any(|marker| lower.contains(marker)) {
+            in_audio = true;
+        }
+    }
+    None
+}
+
+pub fn audio_groups(resources: &[ResourceItem]) -> Vec<AudioGroup> {
+    let mut groups: BTreeMap<&str, AudioGroup> = BTreeMap::new();
+    for item in resources {
+        if let Some(language) = audio_language(&item.dest) {
+            let group = groups.entry(language).or_insert_with(|| AudioGroup {
+                language: language.to_string(),
+                files: 0,
+                bytes: 0,
+            });
+            group.files += 1;
+            group.bytes += item.size.unwrap_or(0);
+        }
+    }
+    groups.into_values().collect()
+}
+
+/// The requested languages that name no voice-over pack in `groups`.
+pub fn unmatched_languages<'a>(languages: &'a [String], groups: &[AudioGroup]) -> Vec<&'a str> {
+    languages
+        .iter()
+        .map(|language| language.trim())
+        .filter(|language| !language.is_empty())
+        .filter(|language| {
+            canonical_language(language)
+                .is_none_or(|code| !groups.iter().any(|group| group.language == code))
+        })
+        .collect()
+}
+
+/// Keeps non-audio resources and the audio packs of the given languages.
+pub fn retain_audio_languages(
+    resources: &mut Vec<ResourceItem>,
+    languages: &[String],
+) -> FilterStats {
+    let wanted: Vec<&str> = languages
+        .iter()
+        .filter_map(|language| canonical_language(language.trim()))
+        .collect();
+    let mut stats = FilterStats { files: 0, bytes: 0 };
+
+    resources.retain(|item| {
+        let keep = audio_language(&item.dest).is_none_or(|language| wanted.contains(&language));
+        if !keep {
+            stats.files += 1;
+            stats.bytes += item.size.unwrap_or(0);
+        }
+        keep
+    });
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{audio_groups, audio_language, retain_audio_languages, unmatched_languages};
+    use crate::config::cfg::ResourceItem;
+
+    fn item(dest: &str) -> ResourceItem {
+        ResourceItem {
+            dest: dest.to_string(),
+            checksum: None,
+            size: Some(10),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }
+    }
+
+    #[test]
+    fn audio_language_requires_audio_ancestor() {
+        assert_eq!(
+            audio_language("Client/Content/WwiseAudio/Media/en/vo_001.wem"),
+            Some("en")
+        );
+        assert_eq!(audio_language("Client\\Audio\\Japanese\\a.pck"), Some("ja"));
+        assert_eq!(audio_language("Client/Localization/en/text.locres"), None);
+    }
+
+    #[test]
+    fn retain_audio_languages_keeps_shared_resources() {
+        let mut resources = vec![
+            item("Paks/main.pak"),
+            item("WwiseAudio/en/a.pck"),
+            item("WwiseAudio/zh/a.pck"),
+        ];
+
+        let stats = retain_audio_languages(&mut resources, &["en".to_string()]);
+
+        assert_eq!(stats.files, 1);
+        assert_eq!(stats.bytes, 10);
+        assert_eq!(resources.len(), 2);
+    }
+
+    #[test]
+    fn unmatched_languages_names_codes_without_a_pack() {
+        let groups = audio_groups(&[item("WwiseAudio/en/a.pck"), item("WwiseAudio/zh/a.pck")]);
+        let requested = ["English".to_string(), "ja".to_string(), "fr".to_string()];
+
+        assert_eq!(unmatched_languages(&requested, &groups), ["ja", "fr"]);
+    }
+}
@@ -0,0 +1,271 @@
+use async_channel::{Receiver, Sender};
+use reqwest::Client;
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+use tokio::task::JoinHandle;
+
+use crate::config::cfg::{Config, DownloadOptions, ResourceItem};
+use crate::download::{
+    control::DownloadControl,
+    events::{Event, EventSink},
+    pipeline::{PipelineResult, run_pipeline},
+};
+use crate::io::{
+    file::ensure_writable_dir,
+    logging::{SharedLogFile, log_warn},
+    session_lock::SessionLock,
+    util::parse_resources,
+};
+use crate::network::client::{channel_config, config_version, fetch_index, fetch_versions};
+
+/// What a front-end can ask of a running [`DownloadSession`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionCommand {
+    Pause,
+    Resume,
+    /// Skip every file that is downloading right now.
+    SkipCurrent,
+    /// Stop after the files in flight; unfinished ones stay resumable.
+    Cancel,
+}
+
+/// A download running on its own task, driven by [`SessionCommand`]s and
+/// reporting through [`Event`]s instead of the terminal. It never prompts or
+/// draws progress bars, so a GUI can own the screen; resolve the [`Config`]
+/// and resources first with the non-interactive calls in
+/// `network::client` and `io::util`.
+pub struct DownloadSession {
+    commands: Sender<SessionCommand>,
+    events: Receiver<Event>,
+    task: JoinHandle<PipelineResult>,
+}
+
+impl DownloadSession {
+    /// Starts downloading `resources` into `folder`. Progress bars are turned
+    /// off whatever `options` says.
+    pub fn start(
+        client: Client,
+        config: Config,
+        resources: Vec<ResourceItem>,
+        folder: PathBuf,
+        log_file: SharedLogFile,
+        options: DownloadOptions,
+    ) -> Self {
+        Self::spawn(client, config, resources, folder, log_file, options, None)
+    }
+
+    /// [`DownloadSession::start`] holding `lock` until the pipeline ends.
+    fn spawn(
+        client: Client,
+        config: Config,
+        resources: Vec<ResourceItem>,
+        folder: PathBuf,
+        log_file: SharedLogFile,
+        mut options: DownloadOptions,
+        lock: Option<SessionLock>,
+    ) -> Self {
+        options.show_progress = false;
+        let (sink, events) = EventSink::channel();
+        let (commands, received) = async_channel::unbounded();
+        let should_stop = Arc::new(AtomicBool::new(false));
+        let control = DownloadControl::default();
+
+        tokio::spawn(apply_commands(
+            received,
+            control.clone(),
+            should_stop.clone(),
+        ));
+        let task = tokio::spawn(async move {
+            let _lock = lock;
+            let result = run_pipeline(
+                Arc::new(client),
+                Arc::new(config),
+                resources,
+                folder,
+                log_file,
+                should_stop.clone(),
+                options,
+                sink.clone(),
+                control,
+            )
+            .await;
+            sink.summary(&result, should_stop.load(Ordering::SeqCst));
+            result
+        });
+
+        Self {
+            commands,
+            events,
+            task,
+        }
+    }
+
+    /// A handle for sending commands from another thread or task.
+    pub fn commands(&self) -> Sender<SessionCommand> {
+        self.commands.clone()
+    }
+
+    pub fn send(&self, command: SessionCommand) {
+        let _ = self.commands.try_send(command);
+    }
+
+    /// The event stream. It ends with [`Event::Summary`] and closes once the
+    /// session is done.
+    pub fn events(&self) -> Receiver<Event> {
+        self.events.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Waits for the session to end; events not yet received stay queued.
+    pub async fn finish(self) -> PipelineResult {
+        let result = self.task.await;
+        self.commands.close();
+        result.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+}
+
+/// A version a front-end can offer: one channel's current build or its
+/// predownload.
+#[derive(Clone)]
+pub struct Release {
+    /// e.g. `Live - OS` or `Beta - CN (predownload)`.
+    pub label: String,
+    pub version: String,
+    pub config: Config,
+}
+
+/// What a front-end asks before a [`DownloadSession`] exists: the region
+/// picker lists the releases, the folder picker's choice starts one.
+pub enum SetupCommand {
+    /// Answered with [`SetupEvent::Releases`].
+    ListReleases,
+    /// Loads the release's index and starts downloading it into `folder`;
+    /// answered with [`SetupEvent::Started`].
+    Start { release: Release, folder: PathBuf },
+}
+
+pub enum SetupEvent {
+    Releases(Result<Vec<Release>, String>),
+    Started(Result<DownloadSession, String>),
+}
+
+/// Answers [`SetupCommand`]s on its own task, so a front-end never goes
+/// through the terminal prompts to pick a version and folder.
+pub struct Setup {
+    commands: Sender<SetupCommand>,
+    events: Receiver<SetupEvent>,
+}
+
+impl Setup {
+    /// `source_url` replaces the built-in version list, as `--source-url` does.
+    pub fn start(
+        client: Client,
+        source_url: Option<String>,
+        log_file: SharedLogFile,
+        options: DownloadOptions,
+    ) -> Self {
+        let (commands, received) = async_channel::unbounded();
+        let (sender, events) = async_channel::unbounded();
+        tokio::spawn(async move {
+            while let Ok(command) = received.recv().await {
+                let event = match command {
+                    SetupCommand::ListReleases => {
+                        SetupEvent::Releases(releases(&client, source_url.as_deref()).await)
+                    }
+                    SetupCommand::Start { release, folder } => SetupEvent::Started(
+                        start_release(&client, release, folder, &log_file, &options).await,
+                    ),
+                };
+                if sender.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Self { commands, events }
+    }
+
+    pub fn send(&self, command: SetupCommand) {
+        let _ = self.commands.try_send(command);
+    }
+
+    pub fn events(&self) -> Receiver<SetupEvent> {
+        self.events.clone()
+    }
+}
+
+/// Every channel's current build and predownload that has a usable config.
+async fn releases(client: &Client, source_url: Option<&str>) -> Result<Vec<Release>, String> {
+    let mut releases = Vec::new();
+    for entry in fetch_versions(client, source_url).await? {
+        let Some(json) = &entry.json else { continue };
+        for (selected, suffix) in [("default", ""), ("predownload", " (predownload)")] {
+            if json.get(selected).is_none() {
+                continue;
+            }
+            if let Ok(config) = channel_config(json, selected) {
+                releases.push(Release {
+                    label: format!("{}{}", entry.label(), suffix),
+                    version: config_version(json, selected).to_string(),
+                    config,
+                });
+            }
+        }
+    }
+    if releases.is_empty() {
+        return Err("The version list offers no release to download".to_string());
+    }
+    Ok(releases)
+}
+
+async fn start_release(
+    client: &Client,
+    release: Release,
+    folder: PathBuf,
+    log_file: &SharedLogFile,
+    options: &DownloadOptions,
+) -> Result<DownloadSession, String> {
+    ensure_writable_dir(&folder).map_err(|e| e.to_string())?;
+    // Nobody is there to ask about a stale lock, so it is taken over like
+    // with --headless.
+    let lock = SessionLock::acquire(&folder, |owner| {
+        log_warn(
+            log_file,
+            &format!("Taking over the stale lock of {}", owner.describe()),
+        );
+        true
+    })?;
+    let index = fetch_index(client, &release.config, log_file).await?;
+    let resources = parse_resources(&index)?;
+    Ok(DownloadSession::spawn(
+        client.clone(),
+        release.config,
+        resources,
+        folder,
+        log_file.clone(),
+        options.clone(),
+        Some(lock),
+    ))
+}
+
+async fn apply_commands(
+    commands: Receiver<SessionCommand>,
+    control: DownloadControl,
+    should_stop: Arc<AtomicBool>,
+) {
+    while let Ok(command) = commands.recv().await {
+        match command {
+            SessionCommand::Pause => control.pause(),
+            SessionCommand::Resume => control.resume(),
+            SessionCommand::SkipCurrent => control.skip_current(),
+            SessionCommand::Cancel => should_stop.store(true, Ordering::SeqCst),
+        }
+    }
+}
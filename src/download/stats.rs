@@ -0,0 +1,165 @@
+use indicatif::{HumanBytes, HumanDuration};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::config::style;
+
+const WINDOW_SECS: usize = 60;
+/// Span of the exponential moving average behind ETAs; recent seconds dominate,
+/// so pauses and slow starts wash out within about this long.
+const EMA_SECS: f64 = 10.0;
+const SPARK_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// [`SPARK_CHARS`] under `--ascii`.
+const SPARK_ASCII: [char; 8] = ['_', '.', ':', '-', '=', '+', '*', '#'];
+
+/// Per-second download speed samples over a rolling one-minute window.
+pub struct SpeedStats {
+    samples: VecDeque<u64>,
+    ema: Option<f64>,
+    last_bytes: u64,
+    last_sample: Instant,
+    started: Instant,
+    started_bytes: u64,
+    peak: u64,
+}
+
+impl SpeedStats {
+    pub fn new(downloaded: u64, now: Instant) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(WINDOW_SECS),
+            ema: None,
+            last_bytes: downloaded,
+            last_sample: now,
+            started: now,
+            started_bytes: downloaded,
+            peak: 0,
+        }
+    }
+
+    /// Feeds the session's downloaded-bytes counter; returns `true` once a
+    /// new one-second sample has been taken.
+    pub fn record(&mut self, downloaded: u64, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last_sample);
+        if elapsed < Duration::from_secs(1) {
+            return false;
+        }
+
+        let delta = downloaded.saturating_sub(self.last_bytes);
+        let rate = (delta as f64 / elapsed.as_secs_f64()) as u64;
+        if self.samples.len() == WINDOW_SECS {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(rate);
+        let alpha = 2.0 / (EMA_SECS + 1.0);
+        self.ema = Some(match self.ema {
+            Some(ema) => ema + alpha * (rate as f64 - ema),
+            None => rate as f64,
+        });
+        self.peak = self.peak.max(rate);
+        self.last_bytes = downloaded;
+        self.last_sample = now;
+        true
+    }
+
+    pub fn current(&self) -> u64 {
+        self.samples.back().copied().unwrap_or(0)
+    }
+
+    pub fn average(&self) -> u64 {
+        let elapsed = self.last_sample.duration_since(self.started).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0;
+        }
+        (self.last_bytes.saturating_sub(self.started_bytes) as f64 / elapsed) as u64
+    }
+
+    pub fn peak(&self) -> u64 {
+        self.peak
+    }
+
+    /// Exponential moving average of the per-second samples.
+    pub fn smoothed(&self) -> u64 {
+        self.ema.unwrap_or(0.0) as u64
+    }
+
+    pub fn eta(&self, remaining: u64) -> Option<Duration> {
+        remaining
+            .checked_div(self.smoothed())
+            .map(Duration::from_secs)
+    }
+
+    pub fn sparkline(&self) -> String {
+        let max = self.samples.iter().copied().max().unwrap_or(0).max(1);
+        let chars = if style::ascii() {
+            SPARK_ASCII
+        } else {
+            SPARK_CHARS
+        };
+        self.samples
+            .iter()
+            .map(|&rate| {
+                let level = (rate * (SPARK_CHARS.len() as u64 - 1) + max / 2) / max;
+                chars[level as usize]
+            })
+            .collect()
+    }
+
+    pub fn render(&self, remaining: u64, active_file: &str) -> String {
+        let eta = match self.eta(remaining) {
+            Some(eta) => HumanDuration(eta).to_string(),
+            None => "--".to_string(),
+        };
+        format!(
+            "{} now {}/s | avg {}/s | peak {}/s | ETA {} | {}",
+            self.sparkline(),
+            HumanBytes(self.current()),
+            HumanBytes(self.average()),
+            HumanBytes(self.peak()),
+            eta,
+            active_file
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpeedStats;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn record_samples_once_per_second() {
+        let start = Instant::now();
+        let mut stats = SpeedStats::new(0, start);
+
+        assert!(!stats.record(500, start + Duration::from_millis(500)));
+        assert!(stats.record(1_000, start + Duration::from_secs(1)));
+        assert!(stats.record(4_000, start + Duration::from_secs(2)));
+
+        assert_eq!(stats.current(), 3_000);
+        assert_eq!(stats.peak(), 3_000);
+        assert_eq!(stats.average(), 2_000);
+        assert_eq!(stats.smoothed(), 1_363);
+        assert_eq!(stats.sparkline(), "▃█");
+    }
+
+    #[test]
+    fn smoothed_speed_recovers_after_a_pause() {
+        let start = Instant::now();
+        let mut stats = SpeedStats::new(0, start);
+        let mut downloaded = 0;
+
+        for second in 1..=5 {
+            stats.record(downloaded, start + Duration::from_secs(second));
+        }
+        for second in 6..=30 {
+            downloaded += 1_000;
+            stats.record(downloaded, start + Duration::from_secs(second));
+        }
+
+        assert!(stats.smoothed() > 950);
+        assert!(stats.average() < 850);
+        assert_eq!(stats.eta(10_000), Some(Duration::from_secs(10)));
+    }
+}
@@ -0,0 +1,355 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use indicatif::HumanBytes;
+use ratatui::{
+    DefaultTerminal, Frame,
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+};
+use std::{collections::BTreeMap, io};
+
+use crate::config::{
+    cfg::ResourceItem,
+    style::{color_enabled, glyph},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    All,
+    Some,
+    None,
+}
+
+struct Node {
+    name: String,
+    depth: usize,
+    children: Vec<usize>,
+    /// Indices into the resource list of every file at or below this node.
+    files: Vec<usize>,
+    is_dir: bool,
+    expanded: bool,
+}
+
+/// The index as a folder tree with a checkbox per file. Node 0 is the
+/// invisible root; top-level folders start expanded.
+pub struct SelectionTree {
+    nodes: Vec<Node>,
+    sizes: Vec<u64>,
+    selected: Vec<bool>,
+}
+
+impl SelectionTree {
+    pub fn new(resources: &[ResourceItem]) -> Self {
+        let mut nodes = vec![Node {
+            name: String::new(),
+            depth: 0,
+            children: Vec::new(),
+            files: Vec::new(),
+            is_dir: true,
+            expanded: true,
+        }];
+        // Per directory node: child name -> node, so siblings stay sorted.
+        let mut lookup: Vec<BTreeMap<(bool, String), usize>> = vec![BTreeMap::new()];
+
+        for (index, item) in resources.iter().enumerate() {
+            let parts: Vec<&str> = item
+                .dest
+                .split(['/', '\\'])
+                .filter(|part| !part.is_empty())
+                .collect();
+            let mut current = 0;
+            nodes[0].files.push(index);
+            for (depth, part) in parts.iter().enumerate() {
+                let is_dir = depth + 1 < parts.len();
+                // Folders sort before files.
+                let key = (!is_dir, part.to_string());
+                let next = match lookup[current].get(&key) {
+                    Some(&next) => next,
+                    None => {
+                        nodes.push(Node {
+                            name: part.to_string(),
+                            depth,
+                            children: Vec::new(),
+                            files: Vec::new(),
+                            is_dir,
+                            expanded: depth == 0,
+                        });
+                        lookup.push(BTreeMap::new());
+                        let next = nodes.len() - 1;
+                        lookup[current].insert(key, next);
+                        next
+                    }
+                };
+                nodes[next].files.push(index);
+                current = next;
+            }
+        }
+
+        for (node, children) in nodes.iter_mut().zip(lookup) {
+            node.children = children.into_values().collect();
+        }
+
+        Self {
+            nodes,
+            sizes: resources
+                .iter()
+                .map(|item| item.size.unwrap_or(0))
+                .collect(),
+            selected: vec![true; resources.len()],
+        }
+    }
+
+    /// Nodes shown on screen, in order, following expanded folders only.
+    pub fn visible(&self) -> Vec<usize> {
+        let mut rows = Vec::new();
+        let mut stack: Vec<usize> = self.nodes[0].children.iter().rev().copied().collect();
+        while let Some(node) = stack.pop() {
+            rows.push(node);
+            if self.nodes[node].expanded {
+                stack.extend(self.nodes[node].children.iter().rev());
+            }
+        }
+        rows
+    }
+
+    pub fn check(&self, node: usize) -> Check {
+        let files = &self.nodes[node].files;
+        let selected = files.iter().filter(|&&file| self.selected[file]).count();
+        match selected {
+            0 => Check::None,
+            n if n == files.len() => Check::All,
+            _ => Check::Some,
+        }
+    }
+
+    /// Selects everything below `node`, or clears it when it is fully selected.
+    pub fn toggle(&mut self, node: usize) {
+        let select = self.check(node) != Check::All;
+        for &file in &self.nodes[node].files {
+            self.selected[file] = select;
+        }
+    }
+
+    pub fn toggle_all(&mut self) {
+        self.toggle(0);
+    }
+
+    pub fn set_expanded(&mut self, node: usize, expanded: bool) {
+        if self.nodes[node].is_dir {
+            self.nodes[node].expanded = expanded;
+        }
+    }
+
+    /// `(selected, total)` bytes below `node`.
+    pub fn sizes(&self, node: usize) -> (u64, u64) {
+        self.nodes[node]
+            .files
+            .iter()
+            .fold((0, 0), |(selected, total), &file| {
+                let size = self.sizes[file];
+                let chosen = if self.selected[file] { size } else { 0 };
+                (selected + chosen, total + size)
+            })
+    }
+
+    pub fn selection(&self) -> &[bool] {
+        &self.selected
+    }
+}
+
+/// Lets the user pick files from `resources` in a full-screen tree. Returns
+/// `None` when the screen is left without confirming.
+pub fn browse(resources: &[ResourceItem]) -> io::Result<Option<Vec<bool>>> {
+    let mut tree = SelectionTree::new(resources);
+    let mut terminal = ratatui::try_init()?;
+    let result = run(&mut terminal, &mut tree);
+    ratatui::restore();
+    Ok(result?.then(|| tree.selection().to_vec()))
+}
+
+fn run(terminal: &mut DefaultTerminal, tree: &mut SelectionTree) -> io::Result<bool> {
+    let mut state = ListState::default().with_selected(Some(0));
+    loop {
+        let rows = tree.visible();
+        let cursor = state
+            .selected()
+            .unwrap_or(0)
+            .min(rows.len().saturating_sub(1));
+        state.select(Some(cursor));
+        terminal.draw(|frame| draw(frame, tree, &rows, &mut state))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let node = rows.get(cursor).copied();
+        match key.code {
+            KeyCode::Enter => return Ok(true),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(false),
+            KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+            KeyCode::PageUp => state.scroll_up_by(10),
+            KeyCode::PageDown => state.scroll_down_by(10),
+            KeyCode::Home => state.select_first(),
+            KeyCode::End => state.select(Some(rows.len().saturating_sub(1))),
+            KeyCode::Char(' ') => {
+                if let Some(node) = node {
+                    tree.toggle(node);
+                }
+            }
+            KeyCode::Char('a') => tree.toggle_all(),
+            KeyCode::Right | KeyCode::Char('l') => {
+                if let Some(node) = node {
+                    tree.set_expanded(node, true);
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                if let Some(node) = node {
+                    tree.set_expanded(node, false);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, tree: &SelectionTree, rows: &[usize], state: &mut ListState) {
+    let [header, body, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let (selected, total) = tree.sizes(0);
+    let files = tree.selection().iter().filter(|&&chosen| chosen).count();
+    frame.render_widget(
+        Paragraph::new(format!(
+            "Select files to download: {} files, {} of {}",
+            files,
+            HumanBytes(selected),
+            HumanBytes(total)
+        ))
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+        header,
+    );
+
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|&node| {
+            let entry = &tree.nodes[node];
+            let arrow = match (entry.is_dir, entry.expanded) {
+                (false, _) => "  ",
+                (true, true) => glyph("▾ ", "v "),
+                (true, false) => glyph("▸ ", "> "),
+            };
+            let (mark, color) = match tree.check(node) {
+                Check::All => ("[x]", Color::Green),
+                Check::Some => ("[-]", Color::Yellow),
+                Check::None => ("[ ]", Color::DarkGray),
+            };
+            let (selected, total) = tree.sizes(node);
+            let size = if entry.is_dir {
+                format!("  {} / {}", HumanBytes(selected), HumanBytes(total))
+            } else {
+                format!("  {}", HumanBytes(total))
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw("  ".repeat(entry.depth)),
+                Span::raw(arrow),
+                Span::styled(mark, fg(color)),
+                Span::raw(" "),
+                Span::raw(entry.name.as_str()),
+                Span::styled(size, fg(Color::DarkGray)),
+            ]))
+        })
+        .collect();
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(Block::bordered())
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+        body,
+        state,
+    );
+
+    frame.render_widget(
+        Paragraph::new(glyph(
+            "↑↓ move  ←→ collapse/expand  space toggle  a toggle all  enter confirm  q cancel",
+            "up/down move  left/right collapse/expand  space toggle  a toggle all  enter confirm  q cancel",
+        ))
+        .style(fg(Color::DarkGray)),
+        footer,
+    );
+}
+
+/// A foreground color, or no styling under `--no-color`.
+fn fg(color: Color) -> Style {
+    if color_enabled() {
+        Style::default().fg(color)
+    } else {
+        Style::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Check, SelectionTree};
+    use crate::config::cfg::ResourceItem;
+
+    fn item(dest: &str, size: u64) -> ResourceItem {
+        ResourceItem {
+            dest: dest.to_string(),
+            checksum: None,
+            size: Some(size),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }
+    }
+
+    #[test]
+    fn tree_groups_folders_and_toggles_whole_subtrees() {
+        let mut tree = SelectionTree::new(&[
+            item("Client/Paks/main.pak", 100),
+            item("Client/Paks/audio/en.pak", 20),
+            item("Client/Binaries/game.exe", 5),
+            item("launcher.exe", 1),
+        ]);
+
+        let names = |tree: &SelectionTree| -> Vec<String> {
+            tree.visible()
+                .into_iter()
+                .map(|node| tree.nodes[node].name.clone())
+                .collect()
+        };
+        assert_eq!(names(&tree), ["Client", "Binaries", "Paks", "launcher.exe"]);
+
+        let paks = tree.visible()[2];
+        tree.set_expanded(paks, true);
+        assert_eq!(
+            names(&tree),
+            [
+                "Client",
+                "Binaries",
+                "Paks",
+                "audio",
+                "main.pak",
+                "launcher.exe"
+            ]
+        );
+
+        let audio = tree.visible()[3];
+        tree.toggle(audio);
+        assert_eq!(tree.check(audio), Check::None);
+        assert_eq!(tree.check(paks), Check::Some);
+        assert_eq!(tree.sizes(paks), (100, 120));
+        assert_eq!(tree.selection(), [true, false, true, true]);
+
+        tree.toggle(paks);
+        assert_eq!(tree.check(paks), Check::All);
+        tree.toggle_all();
+        assert_eq!(tree.selection(), [false; 4]);
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::AtomicBool},
+};
+
+use crate::config::cfg::ResourceItem;
+use crate::download::pipeline::CompletedResource;
+use crate::io::file::{
+    Checksum, HashAlgorithm, VerificationError, calculate_hash_interruptible, dest_path,
+};
+use crate::io::sanitize::{sanitize_dest, unsanitize_component};
+
+/// Name of the list for `algorithm`, e.g. `checksums.md5`.
+pub fn checksum_list_name(algorithm: HashAlgorithm) -> String {
+    format!("checksums.{}", algorithm.manifest_key())
+}
+
+/// Whether `relative` (below the download folder) is a list `--write-checksums` wrote.
+pub fn is_checksum_list(relative: &str) -> bool {
+    HashAlgorithm::PREFERENCE
+        .iter()
+        .any(|algorithm| relative == checksum_list_name(*algorithm))
+}
+
+/// `--write-checksums`: writes a `checksums.<algorithm>` list in the
+/// `digest  path` format of `md5sum`/`sha256sum` for every file intact at the
+/// end of the session, so `md5sum -c checksums.md5` checks the folder on any
+/// machine. Digests the index has are taken as is, since the files were
+/// verified against them; any other algorithm hashes the files again.
+pub async fn write_checksum_lists(
+    folder: &Path,
+    resources: &[ResourceItem],
+    completed: &[CompletedResource],
+    algorithms: &[HashAlgorithm],
+    should_stop: &Arc<AtomicBool>,
+) -> Result<Vec<PathBuf>, String> {
+    let by_dest: HashMap<&str, &ResourceItem> = resources
+        .iter()
+        .map(|item| (item.dest.as_str(), item))
+        .collect();
+    let mut files: Vec<(String, &CompletedResource)> = completed
+        .iter()
+        .map(|file| (sanitize_dest(&file.dest), file))
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut written = Vec::new();
+    for algorithm in algorithms {
+        let mut list = String::new();
+        for (relative, file) in &files {
+            let known = by_dest
+                .get(file.dest.as_str())
+                .and_then(|item| item.checksum.as_ref())
+                .filter(|checksum| checksum.algorithm == *algorithm);
+            let digest = match known {
+                Some(checksum) => checksum.digest.to_ascii_lowercase(),
+                None => calculate_hash_interruptible(
+                    &dest_path(folder, &file.dest),
+                    *algorithm,
+                    should_stop.clone(),
+                )
+                .await
+                .map_err(|e| match e {
+                    VerificationError::Interrupted => "Interrupted".to_string(),
+                    VerificationError::Io(e) => format!("{}: {}", relative, e),
+                })?,
+            };
+            list.push_str(&format!("{}  {}\n", digest, relative));
+        }
+
+        let path = folder.join(checksum_list_name(*algorithm));
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, list).map_err(|e| e.to_string())?;
+        fs::rename(&temp, &path).map_err(|e| e.to_string())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// `verify --checksums`: the files a `md5sum`, `sha1sum` or `sha256sum` list
+/// names, such as one `--write-checksums` wrote, as resources to verify. The
+/// algorithm follows from the length of each digest.
+pub fn read_checksum_list(path: &Path) -> Result<Vec<ResourceItem>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    parse_checksum_list(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse_checksum_list(contents: &str) -> Result<Vec<ResourceItem>, String> {
+    let mut resources = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let malformed = || format!("line {} is not \"<digest>  <path>\"", number + 1);
+        // coreutils prefixes lines whose path needed escaping with a backslash.
+        let (escaped, line) = match line.strip_prefix('\\') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (digest, rest) = line.split_once(' ').ok_or_else(malformed)?;
+        let path = rest
+            .strip_prefix(' ')
+            .or_else(|| rest.strip_prefix('*'))
+            .filter(|path| !path.is_empty())
+            .ok_or_else(malformed)?;
+        let algorithm = match digest.len() {
+            32 => HashAlgorithm::Md5,
+            40 => HashAlgorithm::Sha1,
+            64 => HashAlgorithm::Sha256,
+            _ => return Err(malformed()),
+        };
+        if !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(malformed());
+        }
+        let path = if escaped {
+            unescape(path)
+        } else {
+            path.to_string()
+        };
+        let dest = path
+            .split('/')
+            .map(unsanitize_component)
+            .collect::<Vec<_>>()
+            .join("/");
+        resources.push(ResourceItem {
+            dest,
+            checksum: Some(Checksum::new(algorithm, digest)),
+            size: None,
+            chunks: Vec::new(),
+            unpacked_size: None,
+        });
+    }
+    Ok(resources)
+}
+
+/// Undoes the `\\` and `\n` escapes of coreutils.
+fn unescape(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(next) => out.push(next),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_checksum_list, parse_checksum_list, write_checksum_lists};
+    use crate::config::cfg::ResourceItem;
+    use crate::download::pipeline::CompletedResource;
+    use crate::io::file::{Checksum, HashAlgorithm, unique_path};
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    #[tokio::test]
+    async fn lists_index_digests_and_hashes_the_rest() {
+        let root = unique_path("checksums");
+        std::fs::create_dir_all(root.join("Client")).unwrap();
+        std::fs::write(root.join("Client/b.pak"), b"abc").unwrap();
+        std::fs::write(root.join("a.txt"), b"abc").unwrap();
+        let item = |dest: &str| ResourceItem {
+            dest: dest.to_string(),
+            checksum: Some(Checksum::new(
+                HashAlgorithm::Md5,
+                "900150983CD24FB0D6963F7D28E17F72",
+            )),
+            size: Some(3),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        };
+        let completed = |dest: &str| CompletedResource {
+            dest: dest.to_string(),
+            downloaded: true,
+            retries: 0,
+            stats: None,
+        };
+
+        let written = write_checksum_lists(
+            &root,
+            &[item("Client/b.pak"), item("a.txt")],
+            &[completed("Client/b.pak"), completed("a.txt")],
+            &[HashAlgorithm::Md5, HashAlgorithm::Sha256],
+            &Arc::new(AtomicBool::new(false)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(written.len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(root.join("checksums.md5")).unwrap(),
+            "900150983cd24fb0d6963f7d28e17f72  Client/b.pak\n\
+             900150983cd24fb0d6963f7d28e17f72  a.txt\n"
+        );
+        let sha256 = std::fs::read_to_string(root.join("checksums.sha256")).unwrap();
+        assert!(sha256.starts_with(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  Client/b.pak\n"
+        ));
+        let listed = parse_checksum_list(&sha256).unwrap();
+        assert_eq!(listed[1].dest, "a.txt");
+        assert!(is_checksum_list("checksums.sha256"));
+        assert!(!is_checksum_list("Client/checksums.md5"));
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn parses_coreutils_lines() {
+        let list = "# made by hand\r\n\
+                    900150983cd24fb0d6963f7d28e17f72 *Client/My Paks/a%3A.pak\r\n\
+                    \\a9993e364706816aba3e25717850c26c9cd0d89d  odd\\\\name\n\n";
+        let resources = parse_checksum_list(list).unwrap();
+        assert_eq!(resources[0].dest, "Client/My Paks/a:.pak");
+        assert_eq!(
+            resources[0].checksum.as_ref().unwrap().algorithm,
+            HashAlgorithm::Md5
+        );
+        assert_eq!(resources[1].dest, "odd\\name");
+        assert_eq!(
+            resources[1].checksum.as_ref().unwrap().algorithm,
+            HashAlgorithm::Sha1
+        );
+        assert!(parse_checksum_list("abc  a.pak").is_err());
+        assert!(parse_checksum_list("900150983cd24fb0d6963f7d28e17f72 a.pak").is_err());
+    }
+}
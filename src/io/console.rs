@@ -1,53 +1,298 @@
-use crate::{config::status::Status, download::pipeline::PipelineResult};
+use crate::{
+    archive::extract::{ExtractResult, print_extract_results},
+    commands::verify::VerifyReport,
+    config::status::Status,
+    download::{
+        pipeline::PipelineResult,
+        report::{FAILED_REPORT, SESSION_REPORT},
+    },
+    io::{file::FileState, i18n::Msg},
+    network::health::CdnReport,
+};
 use colored::Colorize;
-use std::{io, path::Path};
+use indicatif::{HumanBytes, HumanDuration};
+use std::{io, path::Path, time::Duration};
 
-pub fn print_results(result: &PipelineResult, folder: &Path) {
+/// Clears the terminal. Best effort: without a console there is nothing to clear.
+pub fn clear_screen() {
+    #[cfg(windows)]
+    let _ = winconsole::console::clear();
+    #[cfg(not(target_os = "windows"))]
+    let _ = std::process::Command::new("clear").status();
+}
+
+pub fn print_cdn_reports(reports: &[CdnReport]) {
+    println!("\n{}\n", Msg::CdnHealth.text().on_blue().white().bold());
+
+    for report in reports {
+        let (glyph, status) = match &report.status {
+            Ok(status) if report.is_healthy() => (Status::success(), status.to_string().green()),
+            Ok(status) => (Status::error(), status.to_string().red()),
+            Err(e) => (Status::error(), Msg::CdnUnreachable.fill(&[e]).red()),
+        };
+        let latency = report
+            .latency
+            .map(|latency| format!("{} ms", latency.as_millis()))
+            .unwrap_or_else(|| "--".to_string());
+        let throughput = report
+            .throughput
+            .map(|speed| format!("{}/s", HumanBytes(speed)))
+            .unwrap_or_else(|| "--".to_string());
+
+        println!("{} {}", glyph, report.base_url.cyan());
+        println!(
+            "    {}",
+            Msg::CdnStats.fill(&[&status, &latency.cyan(), &throughput.cyan()])
+        );
+    }
+
+    let healthy = reports.iter().filter(|report| report.is_healthy()).count();
+    println!(
+        "\n{} {}",
+        Status::info(),
+        Msg::CdnsServing.fill(&[
+            &healthy.to_string().cyan(),
+            &reports.len().to_string().cyan()
+        ])
+    );
+}
+
+/// `speedtest`: the download time of the selected files at the speed the
+/// sample came in from `estimate`'s CDN.
+pub fn print_download_estimate(
+    total_bytes: u64,
+    files: usize,
+    without_size: usize,
+    estimate: Option<(&CdnReport, Duration)>,
+) {
+    let Some((report, time)) = estimate else {
+        println!(
+            "{} {}",
+            Status::error(),
+            Msg::NoDownloadEstimate.text().red()
+        );
+        return;
+    };
+    println!(
+        "{} {}",
+        Status::info(),
+        Msg::DownloadEstimate.fill(&[
+            &HumanBytes(total_bytes).to_string().cyan(),
+            &files.to_string().cyan(),
+            &HumanDuration(time).to_string().cyan(),
+            &report.base_url.cyan()
+        ])
+    );
+    if without_size > 0 {
+        println!(
+            "{} {}",
+            Status::warning(),
+            Msg::UnsizedLeftOut.fill(&[&without_size.to_string().yellow()])
+        );
+    }
+}
+
+/// `remaining_bytes` is set when the session was interrupted and switches the
+/// screen to a resume hint instead of the final summary.
+pub fn print_results(
+    result: &PipelineResult,
+    folder: &Path,
+    extract: Option<&ExtractResult>,
+    remaining_bytes: Option<u64>,
+    wait_for_enter: bool,
+) {
     let success = result.verified_ok + result.downloaded_ok;
     let unprocessed = result
         .total
         .saturating_sub(success.saturating_add(result.failed));
 
-    let title = if success == result.total && result.failed == 0 && unprocessed == 0 {
-        " DOWNLOAD COMPLETE ".on_blue().white().bold()
+    let title = if remaining_bytes.is_some() {
+        Msg::DownloadInterrupted.text().on_yellow().black().bold()
+    } else if success == result.total && result.failed == 0 && unprocessed == 0 {
+        Msg::DownloadComplete.text().on_blue().white().bold()
     } else {
-        " PARTIAL DOWNLOAD ".on_blue().white().bold()
+        Msg::PartialDownload.text().on_blue().white().bold()
     };
 
     println!("\n{}\n", title);
     println!(
-        "{} Successfully verified: {}",
+        "{} {}",
         Status::success(),
-        result.verified_ok.to_string().green()
+        Msg::VerifiedCount.fill(&[&result.verified_ok.to_string().green()])
     );
     println!(
-        "{} Successfully downloaded: {}",
+        "{} {}",
         Status::success(),
-        result.downloaded_ok.to_string().green()
+        Msg::DownloadedCount.fill(&[&result.downloaded_ok.to_string().green()])
     );
     println!(
-        "{} Failed: {}",
+        "{} {}",
         Status::error(),
-        result.failed.to_string().red()
+        Msg::FailedCount.fill(&[&result.failed.to_string().red()])
     );
     println!(
-        "{} Unprocessed: {}",
+        "{} {}",
         Status::warning(),
-        unprocessed.to_string().yellow()
+        Msg::UnprocessedCount.fill(&[&unprocessed.to_string().yellow()])
     );
     println!(
-        "{} Total files: {}",
+        "{} {}",
         Status::info(),
-        result.total.to_string().cyan()
+        Msg::TotalFiles.fill(&[&result.total.to_string().cyan()])
     );
     println!(
-        "{} Files saved to: {}",
+        "{} {}",
         Status::info(),
-        folder.display().to_string().cyan()
+        Msg::FilesSavedTo.fill(&[&folder.display().to_string().cyan()])
     );
+    println!(
+        "{} {}",
+        Status::info(),
+        Msg::DownloadedBytes.fill(&[
+            &HumanBytes(result.bytes_downloaded).to_string().cyan(),
+            &SESSION_REPORT.cyan()
+        ])
+    );
+    if result.groups.len() > 1 {
+        println!("{} {}", Status::info(), Msg::ByDirectory.text());
+        let width = result
+            .groups
+            .iter()
+            .map(|group| group.name.chars().count())
+            .max()
+            .unwrap_or(0);
+        for group in &result.groups {
+            let percent = format!("{:>3}%", group.percent());
+            println!(
+                "    {:<width$}  {}  {}",
+                group.name,
+                if group.files_done == group.files {
+                    percent.green()
+                } else {
+                    percent.yellow()
+                },
+                Msg::GroupDetail.fill(&[
+                    &group.files_done,
+                    &group.files,
+                    &HumanBytes(group.bytes_done),
+                    &HumanBytes(group.bytes)
+                ]),
+                width = width
+            );
+        }
+    }
+    if result.cdns.iter().any(|cdn| cdn.errors > 0) {
+        println!("{} {}", Status::info(), Msg::ByCdn.text());
+        for cdn in &result.cdns {
+            let detail = Msg::CdnDetail.fill(&[&cdn.ok, &cdn.errors, &cdn.rate_limited]);
+            if cdn.benched > 0 {
+                let benched = Msg::CdnBenched.fill(&[&cdn.benched]);
+                println!("    {}  {}, {}", cdn.host, detail, benched.yellow());
+            } else {
+                println!("    {}  {}", cdn.host, detail);
+            }
+        }
+    }
+    if let Some(remaining_bytes) = remaining_bytes {
+        println!(
+            "{} {}",
+            Status::warning(),
+            Msg::Remaining.fill(&[
+                &(result.failed + unprocessed).to_string().yellow(),
+                &HumanBytes(remaining_bytes).to_string().yellow()
+            ])
+        );
+        println!(
+            "{} {}",
+            Status::info(),
+            Msg::ResumeHint.fill(&[&"--retry-failed".cyan(), &FAILED_REPORT.cyan()])
+        );
+    } else if result.failed > 0 {
+        println!(
+            "{} {}",
+            Status::warning(),
+            Msg::RetryHint.fill(&[&FAILED_REPORT.cyan(), &"--retry-failed".cyan()])
+        );
+    }
+    if let Some(extract) = extract {
+        print_extract_results(extract);
+    }
 
-    if unprocessed == 0 {
-        println!("\n{} Press Enter to exit...", Status::warning());
+    if unprocessed == 0 && wait_for_enter {
+        println!("\n{} {}", Status::warning(), Msg::PressEnterToExit.text());
         let _ = io::stdin().read_line(&mut String::new());
     }
 }
+
+/// `repair_hint` is off for checksum lists, which `repair` cannot download from.
+pub fn print_verify_results(report: &VerifyReport, folder: &Path, repair_hint: bool) {
+    let title = if report.problems.is_empty() && report.unchecked == 0 && report.extra.is_empty() {
+        Msg::VerifyPassed.text().on_blue().white().bold()
+    } else {
+        Msg::VerifyFailed.text().on_red().white().bold()
+    };
+    println!("\n{}\n", title);
+
+    for (item, state) in &report.problems {
+        let detail = match state {
+            FileState::Missing => Msg::FileMissing.text().to_string(),
+            FileState::WrongSize(actual) => match item.size {
+                Some(expected) => {
+                    Msg::WrongSize.fill(&[&HumanBytes(*actual), &HumanBytes(expected)])
+                }
+                None => Msg::UnexpectedSize.fill(&[&HumanBytes(*actual)]),
+            },
+            FileState::Corrupt => Msg::ChecksumMismatch.text().to_string(),
+            FileState::Intact => continue,
+        };
+        println!("{} {} ({})", Status::error(), item.dest, detail.red());
+    }
+    for relative in &report.extra {
+        println!(
+            "{} {} ({})",
+            Status::warning(),
+            relative,
+            Msg::NotInChecksumList.text().yellow()
+        );
+    }
+    if !report.problems.is_empty() || !report.extra.is_empty() {
+        println!();
+    }
+
+    println!(
+        "{} {}",
+        Status::success(),
+        Msg::IntactCount.fill(&[&report.intact.to_string().green()])
+    );
+    println!(
+        "{} {}",
+        Status::error(),
+        Msg::DamagedCount.fill(&[&report.problems.len().to_string().red()])
+    );
+    if report.unchecked > 0 {
+        println!(
+            "{} {}",
+            Status::warning(),
+            Msg::UncheckedCount.fill(&[&report.unchecked.to_string().yellow()])
+        );
+    }
+    if !report.extra.is_empty() {
+        println!(
+            "{} {}",
+            Status::warning(),
+            Msg::ExtraCount.fill(&[&report.extra.len().to_string().yellow()])
+        );
+    }
+    println!(
+        "{} {}",
+        Status::info(),
+        Msg::Folder.fill(&[&folder.display().to_string().cyan()])
+    );
+    if !report.problems.is_empty() && repair_hint {
+        println!(
+            "{} {}",
+            Status::info(),
+            Msg::RepairHint.fill(&[&"wuwa-downloader repair".cyan()])
+        );
+    }
+}
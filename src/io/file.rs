@@ -1,14 +1,28 @@
 use md5::{Digest, Md5};
+use sha1::Sha1;
+use sha2::Sha256;
 use std::{
     fs,
-    io::{self, BufReader, Read, Write},
-    path::{Path, PathBuf},
+    io::{self, BufReader, Read, SeekFrom},
+    path::{Component, Path, PathBuf},
     sync::Arc,
     sync::atomic::{AtomicBool, Ordering},
 };
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use xxhash_rust::xxh3::Xxh3;
 
-use crate::config::status::Status;
-use crate::io::util::read_line;
+use crate::config::{
+    cfg::{FileChunk, ResourceItem},
+    status::Status,
+};
+use crate::io::{
+    i18n::Msg,
+    prompt::Prompter,
+    quarantine::Quarantine,
+    sanitize::{dest_parts, sanitize_component},
+    util::CONFIRM_ENV,
+};
+use crate::sys;
 
 #[derive(Debug)]
 pub enum VerificationError {
@@ -16,20 +30,126 @@ pub enum VerificationError {
     Io(io::Error),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// Strongest first; the manifest's best available digest is the one we check.
+    pub const PREFERENCE: [HashAlgorithm; 4] = [
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha1,
+        HashAlgorithm::Md5,
+        HashAlgorithm::Xxh3,
+    ];
+
+    pub fn manifest_key(self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// Name in the IANA hash registry, as Metalink and aria2 spell it.
+    /// XXH3 has none, so external tools cannot check it.
+    pub fn iana_name(self) -> Option<&'static str> {
+        match self {
+            HashAlgorithm::Md5 => Some("md5"),
+            HashAlgorithm::Sha1 => Some("sha-1"),
+            HashAlgorithm::Sha256 => Some("sha-256"),
+            HashAlgorithm::Xxh3 => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: HashAlgorithm,
+    pub digest: String,
+}
+
+impl Checksum {
+    pub fn new(algorithm: HashAlgorithm, digest: &str) -> Self {
+        Self {
+            algorithm,
+            digest: digest.trim().to_ascii_lowercase(),
+        }
+    }
+
+    pub fn matches(&self, actual: &str) -> bool {
+        self.digest.eq_ignore_ascii_case(actual)
+    }
+}
+
+pub enum Hasher {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Xxh3(Box<Xxh3>),
+}
+
+impl Hasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::new(Xxh3::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Xxh3(hasher) => hasher.update(data),
+        }
+    }
+
+    pub fn finalize_hex(self) -> String {
+        match self {
+            Hasher::Md5(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Xxh3(hasher) => format!("{:016x}", hasher.digest()),
+        }
+    }
+}
+
 const CHECKSUM_CANCELLATION_ERROR: &str = "Checksum calculation cancelled";
+const READ_SIZE: usize = 262_144;
+/// Slice size of the `--hash-threads` path, large enough to keep NVMe drives busy.
+const MAPPED_READ_SIZE: usize = 8 * 1024 * 1024;
 
-fn calculate_md5_sync(path: &Path) -> io::Result<String> {
-    calculate_md5_sync_interruptible(path, None)
+fn calculate_hash_sync(path: &Path, algorithm: HashAlgorithm) -> io::Result<String> {
+    calculate_hash_sync_interruptible(path, algorithm, None)
 }
 
-fn calculate_md5_sync_interruptible(
+fn calculate_hash_sync_interruptible(
     path: &Path,
+    algorithm: HashAlgorithm,
     should_stop: Option<Arc<AtomicBool>>,
 ) -> io::Result<String> {
+    hash_file_sync(path, algorithm, should_stop, READ_SIZE).map(Hasher::finalize_hex)
+}
+
+fn hash_file_sync(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    should_stop: Option<Arc<AtomicBool>>,
+    read_size: usize,
+) -> io::Result<Hasher> {
     let file = fs::File::open(path)?;
-    let mut reader = BufReader::with_capacity(262_144, file);
-    let mut hasher = Md5::new();
-    let mut buffer = [0_u8; 262_144];
+    let mut reader = BufReader::with_capacity(read_size, file);
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0_u8; read_size];
 
     loop {
         if let Some(should_stop) = &should_stop
@@ -46,46 +166,141 @@ fn calculate_md5_sync_interruptible(
         if read == 0 {
             break;
         }
+        sys::throttle_blocking(read);
         hasher.update(&buffer[..read]);
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
+    Ok(hasher)
 }
 
-pub async fn calculate_md5(path: &Path) -> Result<String, String> {
+pub async fn calculate_hash(path: &Path, algorithm: HashAlgorithm) -> Result<String, String> {
     let path_buf = path.to_path_buf();
-    tokio::task::spawn_blocking(move || calculate_md5_sync(&path_buf))
+    tokio::task::spawn_blocking(move || calculate_hash_sync(&path_buf, algorithm))
         .await
-        .map_err(|e| format!("Failed to join MD5 task: {}", e))?
-        .map_err(|e| format!("Failed to calculate MD5: {}", e))
+        .map_err(|e| format!("Failed to join hash task: {}", e))?
+        .map_err(|e| format!("Failed to calculate {}: {}", algorithm.manifest_key(), e))
 }
 
-pub async fn calculate_md5_interruptible(
+pub async fn calculate_hash_interruptible(
     path: &Path,
+    algorithm: HashAlgorithm,
     should_stop: Arc<AtomicBool>,
 ) -> Result<String, VerificationError> {
     let path_buf = path.to_path_buf();
-    tokio::task::spawn_blocking(move || {
-        calculate_md5_sync_interruptible(&path_buf, Some(should_stop))
-    })
-    .await
-    .map_err(|e| {
-        VerificationError::Io(io::Error::other(format!("Failed to join MD5 task: {}", e)))
-    })?
-    .map_err(|e| match e.kind() {
+    tokio::task::spawn_blocking(move || hash_file_blocking(&path_buf, algorithm, should_stop))
+        .await
+        .map_err(|e| {
+            VerificationError::Io(io::Error::other(format!("Failed to join hash task: {}", e)))
+        })?
+}
+
+/// Hashes `path` on the calling thread, giving up once `should_stop` is set.
+pub fn hash_file_blocking(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    should_stop: Arc<AtomicBool>,
+) -> Result<String, VerificationError> {
+    calculate_hash_sync_interruptible(path, algorithm, Some(should_stop))
+        .map_err(|e| hash_error(e, algorithm))
+}
+
+/// Like [`hash_file_blocking`], but hashes a read-only memory map of the file
+/// in large slices (`--hash-threads`). Where the file cannot be mapped it
+/// falls back to large sequential reads. A file truncated while it is mapped
+/// crashes the process, so callers hold the folder's session lock.
+pub fn hash_file_mapped(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    should_stop: Arc<AtomicBool>,
+) -> Result<String, VerificationError> {
+    let hash = || -> io::Result<String> {
+        let file = fs::File::open(path)?;
+        let len = file.metadata()?.len();
+        let mapped = (len > 0)
+            .then(|| {
+                with_mapping(&file, |data| {
+                    let mut hasher = Hasher::new(algorithm);
+                    for slice in data.chunks(MAPPED_READ_SIZE) {
+                        if should_stop.load(Ordering::SeqCst) {
+                            return Err(io::Error::other(CHECKSUM_CANCELLATION_ERROR));
+                        }
+                        sys::throttle_blocking(slice.len());
+                        hasher.update(slice);
+                    }
+                    Ok(hasher)
+                })
+            })
+            .flatten();
+        match mapped {
+            Some(hasher) => hasher,
+            None => hash_file_sync(path, algorithm, Some(should_stop.clone()), MAPPED_READ_SIZE),
+        }
+        .map(Hasher::finalize_hex)
+    };
+    hash().map_err(|e| hash_error(e, algorithm))
+}
+
+fn hash_error(e: io::Error, algorithm: HashAlgorithm) -> VerificationError {
+    match e.kind() {
         io::ErrorKind::Other if e.to_string() == CHECKSUM_CANCELLATION_ERROR => {
             VerificationError::Interrupted
         }
         _ => VerificationError::Io(io::Error::new(
             e.kind(),
-            format!("Failed to calculate MD5: {}", e),
+            format!("Failed to calculate {}: {}", algorithm.manifest_key(), e),
         )),
-    })
+    }
+}
+
+/// Runs `f` over a read-only map of `file`, or returns `None` when the file
+/// cannot be mapped.
+fn with_mapping<T>(file: &fs::File, f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+    // SAFETY: the map is only read, and every caller holds the folder's
+    // session lock, so no download, repair or clean resizes the file while
+    // it is mapped. A file changed by another program can still end the
+    // process, which is why `--hash-threads` is opt-in.
+    let map = unsafe { memmap2::Mmap::map(file) }.ok()?;
+    // Only a hint for readahead; mapping works the same without it.
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    Some(f(&map))
+}
+
+/// Seeds a hasher with the bytes already on disk so a resumed download can keep
+/// hashing the appended tail instead of re-reading the whole file afterwards.
+pub async fn hash_existing_prefix(
+    path: &Path,
+    algorithm: HashAlgorithm,
+    should_stop: &AtomicBool,
+) -> Result<Hasher, VerificationError> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(VerificationError::Io)?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0_u8; 262_144];
+
+    loop {
+        if should_stop.load(Ordering::SeqCst) {
+            return Err(VerificationError::Interrupted);
+        }
+
+        let read = file
+            .read(&mut buffer)
+            .await
+            .map_err(VerificationError::Io)?;
+        if read == 0 {
+            break;
+        }
+        sys::throttle_blocking(read);
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher)
 }
 
 pub async fn check_existing_file(
     path: &Path,
-    expected_md5: Option<&str>,
+    expected: Option<&Checksum>,
     expected_size: Option<u64>,
 ) -> bool {
     let metadata = match tokio::fs::metadata(path).await {
@@ -102,9 +317,9 @@ pub async fn check_existing_file(
         return true;
     }
 
-    if let Some(md5) = expected_md5 {
-        match calculate_md5(path).await {
-            Ok(actual_md5) if actual_md5 == md5 => {}
+    if let Some(checksum) = expected {
+        match calculate_hash(path, checksum.algorithm).await {
+            Ok(actual) if checksum.matches(&actual) => {}
             _ => {
                 let _ = tokio::fs::remove_file(path).await;
                 return true;
@@ -117,9 +332,10 @@ pub async fn check_existing_file(
 
 pub async fn check_existing_file_interruptible(
     path: &Path,
-    expected_md5: Option<&str>,
+    expected: Option<&Checksum>,
     expected_size: Option<u64>,
     should_stop: Arc<AtomicBool>,
+    quarantine: &Quarantine,
 ) -> Result<bool, VerificationError> {
     let metadata = match tokio::fs::metadata(path).await {
         Ok(metadata) => metadata,
@@ -138,11 +354,12 @@ pub async fn check_existing_file_interruptible(
         return Ok(true);
     }
 
-    if let Some(md5) = expected_md5 {
-        match calculate_md5_interruptible(path, should_stop).await {
-            Ok(actual_md5) if actual_md5 == md5 => {}
-            Ok(_) => {
-                tokio::fs::remove_file(path)
+    if let Some(checksum) = expected {
+        match calculate_hash_interruptible(path, checksum.algorithm, should_stop).await {
+            Ok(actual) if checksum.matches(&actual) => {}
+            Ok(actual) => {
+                quarantine
+                    .isolate(path, checksum, &actual)
                     .await
                     .map_err(VerificationError::Io)?;
                 return Ok(true);
@@ -154,6 +371,101 @@ pub async fn check_existing_file_interruptible(
     Ok(false)
 }
 
+/// Hashes each chunk's byte range of `path`; `true` where the chunk matches.
+/// Ranges past the end of the file count as damaged.
+pub async fn verify_chunks(
+    path: &Path,
+    chunks: &[FileChunk],
+    should_stop: &AtomicBool,
+) -> Result<Vec<bool>, VerificationError> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(VerificationError::Io)?;
+    let mut buffer = vec![0_u8; 262_144];
+    let mut intact = Vec::with_capacity(chunks.len());
+
+    for chunk in chunks {
+        file.seek(SeekFrom::Start(chunk.start))
+            .await
+            .map_err(VerificationError::Io)?;
+        let mut hasher = Hasher::new(chunk.checksum.algorithm);
+        let mut remaining = chunk.size();
+        while remaining > 0 {
+            if should_stop.load(Ordering::SeqCst) {
+                return Err(VerificationError::Interrupted);
+            }
+            let want = remaining.min(buffer.len() as u64) as usize;
+            let read = file
+                .read(&mut buffer[..want])
+                .await
+                .map_err(VerificationError::Io)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            remaining -= read as u64;
+        }
+        intact.push(remaining == 0 && chunk.checksum.matches(&hasher.finalize_hex()));
+    }
+
+    Ok(intact)
+}
+
+/// Counterpart of [`check_existing_file_interruptible`] for chunked resources:
+/// a damaged file is kept so that only its bad chunks are downloaded again.
+pub async fn check_chunked_file(
+    path: &Path,
+    item: &ResourceItem,
+    should_stop: Arc<AtomicBool>,
+) -> Result<bool, VerificationError> {
+    match inspect_file(path, item.checksum.as_ref(), item.size, should_stop.clone()).await? {
+        FileState::Intact if item.checksum.is_some() => Ok(false),
+        FileState::Intact => Ok(verify_chunks(path, &item.chunks, &should_stop)
+            .await?
+            .contains(&false)),
+        _ => Ok(true),
+    }
+}
+
+/// What a file on disk looks like compared to its index entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileState {
+    Intact,
+    Missing,
+    WrongSize(u64),
+    Corrupt,
+}
+
+/// Read-only counterpart of [`check_existing_file_interruptible`]: reports the
+/// state of `path` without deleting anything.
+pub async fn inspect_file(
+    path: &Path,
+    expected: Option<&Checksum>,
+    expected_size: Option<u64>,
+    should_stop: Arc<AtomicBool>,
+) -> Result<FileState, VerificationError> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(FileState::Missing),
+        Err(err) => return Err(VerificationError::Io(err)),
+    };
+
+    if let Some(size) = expected_size
+        && metadata.len() != size
+    {
+        return Ok(FileState::WrongSize(metadata.len()));
+    }
+
+    if let Some(checksum) = expected {
+        let actual = calculate_hash_interruptible(path, checksum.algorithm, should_stop).await?;
+        if !checksum.matches(&actual) {
+            return Ok(FileState::Corrupt);
+        }
+    }
+
+    Ok(FileState::Intact)
+}
+
 pub async fn file_size(path: &Path) -> u64 {
     tokio::fs::metadata(path)
         .await
@@ -161,6 +473,187 @@ pub async fn file_size(path: &Path) -> u64 {
         .unwrap_or(0)
 }
 
+/// In-progress downloads live next to their destination as `<name>.part` and
+/// only take the final name once verified.
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+/// Reserves `len` bytes of disk for `path` without changing its length, so
+/// resume offsets stay valid. Filesystems that cannot preallocate are skipped;
+/// running out of space is reported as `StorageFull`.
+pub fn reserve_space(path: &Path, len: u64) -> io::Result<()> {
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    match reserve_file_space(&file, len) {
+        Err(e) if e.kind() == io::ErrorKind::StorageFull => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn reserve_file_space(file: &fs::File, len: u64) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let ret = unsafe {
+        libc::fallocate(
+            file.as_raw_fd(),
+            libc::FALLOC_FL_KEEP_SIZE,
+            0,
+            len as libc::off_t,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(windows)]
+fn reserve_file_space(file: &fs::File, len: u64) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        FILE_ALLOCATION_INFO, FileAllocationInfo, SetFileInformationByHandle,
+    };
+
+    let info = FILE_ALLOCATION_INFO {
+        AllocationSize: len as i64,
+    };
+    let ret = unsafe {
+        SetFileInformationByHandle(
+            file.as_raw_handle(),
+            FileAllocationInfo,
+            &info as *const _ as *const _,
+            std::mem::size_of::<FILE_ALLOCATION_INFO>() as u32,
+        )
+    };
+    if ret != 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn reserve_file_space(_file: &fs::File, _len: u64) -> io::Result<()> {
+    Ok(())
+}
+
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    fs4::available_space(path)
+}
+
+/// Bytes still to be written for `resources` under `folder`, crediting whatever
+/// is already on disk (complete files and resumable partials).
+pub async fn bytes_still_needed(folder: &Path, resources: &[ResourceItem]) -> u64 {
+    let mut needed = 0_u64;
+    for item in resources {
+        let Some(size) = item.size else {
+            continue;
+        };
+        let path = dest_path(folder, &item.dest);
+        let local = match file_size(&path).await {
+            0 => file_size(&part_path(&path)).await,
+            local => local,
+        };
+        needed = needed.saturating_add(size.saturating_sub(local.min(size)));
+    }
+    needed
+}
+
+/// Joins a manifest `dest` onto `folder`, refusing anything that could land
+/// outside it: absolute paths, drive prefixes and `..` components.
+pub fn resolve_dest(folder: &Path, dest: &str) -> Result<PathBuf, String> {
+    let normalized = dest.replace('\\', "/");
+    let relative = Path::new(&normalized);
+    let safe = !normalized.is_empty()
+        && relative.components().all(|component| match component {
+            Component::Normal(part) => !part.to_string_lossy().contains(':'),
+            Component::CurDir => true,
+            _ => false,
+        });
+
+    if safe {
+        Ok(dest_path(folder, dest))
+    } else {
+        Err(format!("Unsafe destination path in index: {}", dest))
+    }
+}
+
+/// Where `dest` lives under `folder`. Components are sanitized and pushed one
+/// at a time so Windows gets native separators, which `\\?\` paths require.
+pub fn dest_path(folder: &Path, dest: &str) -> PathBuf {
+    let mut path = folder.to_path_buf();
+    for part in dest_parts(dest) {
+        path.push(sanitize_component(part).as_ref());
+    }
+    long_path(path)
+}
+
+/// Windows refuses paths of `MAX_PATH` (260) characters or more unless they
+/// carry the `\\?\` prefix, which deep manifest trees under a deep download
+/// folder easily reach. Other platforms get `path` back unchanged.
+#[cfg(windows)]
+pub fn long_path(path: PathBuf) -> PathBuf {
+    const MAX_PATH: usize = 260;
+    if path.as_os_str().len() < MAX_PATH {
+        return path;
+    }
+    let Ok(absolute) = std::path::absolute(&path) else {
+        return path;
+    };
+    let Some(text) = absolute.to_str() else {
+        return path;
+    };
+    if text.starts_with(r"\\?\") {
+        absolute
+    } else if let Some(share) = text.strip_prefix(r"\\") {
+        PathBuf::from(format!(r"\\?\UNC\{}", share))
+    } else {
+        PathBuf::from(format!(r"\\?\{}", text))
+    }
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// Undoes [`long_path`], e.g. to compare a file with the download folder.
+pub fn short_path(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(text) if cfg!(windows) && text.starts_with(r"\\?\UNC\") => {
+            PathBuf::from(format!(r"\\{}", &text[8..]))
+        }
+        Some(text) if cfg!(windows) && text.starts_with(r"\\?\") => PathBuf::from(&text[4..]),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Guards against symlinked directories inside the download folder that point
+/// elsewhere; `path`'s parent must already exist.
+pub fn ensure_within(folder: &Path, path: &Path) -> Result<(), String> {
+    let root = fs::canonicalize(folder)
+        .map_err(|e| format!("Cannot resolve {}: {}", folder.display(), e))?;
+    let parent = path.parent().unwrap_or(folder);
+    let resolved = fs::canonicalize(parent)
+        .map_err(|e| format!("Cannot resolve {}: {}", parent.display(), e))?;
+
+    if resolved.starts_with(&root) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} resolves outside the download folder",
+            path.display()
+        ))
+    }
+}
+
 pub fn get_filename(path: &str) -> String {
     Path::new(path)
         .file_name()
@@ -169,15 +662,31 @@ pub fn get_filename(path: &str) -> String {
         .to_string()
 }
 
-pub fn get_dir() -> Result<PathBuf, io::Error> {
-    loop {
-        print!(
-            "{} Please specify the directory where the game should be downloaded (press Enter to use the current directory): ",
-            Status::question()
-        );
-        io::stdout().flush()?;
+/// Resolves the download folder: `supplied` (from `--dir`) wins, then the
+/// native folder picker when `picker` is set, then the prompt.
+pub fn get_dir(
+    supplied: Option<&Path>,
+    picker: bool,
+    prompter: &dyn Prompter,
+) -> Result<PathBuf, io::Error> {
+    if let Some(path) = supplied {
+        let path = PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).into_owned());
+        ensure_writable_dir(&path)?;
+        return Ok(path);
+    }
 
-        let input = read_line()?;
+    if picker && let Some(path) = pick_dir() {
+        match ensure_writable_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) => prompter.say(&format!("{} {}", Status::error(), e)),
+        }
+    }
+
+    loop {
+        let (input, _) = prompter.ask(
+            &format!("{} {}", Status::question(), Msg::AskDir.text()),
+            None,
+        )?;
         let path = input.trim();
 
         let path = if path.is_empty() {
@@ -186,40 +695,180 @@ pub fn get_dir() -> Result<PathBuf, io::Error> {
             PathBuf::from(shellexpand::tilde(path).into_owned())
         };
 
-        if path.is_dir() {
-            return Ok(path);
+        if !path.exists() {
+            let (input, _) = prompter.ask(
+                &format!("{} {}", Status::warning(), Msg::CreateDir.text()),
+                Some(CONFIRM_ENV),
+            )?;
+            if !matches!(input.trim().to_ascii_lowercase().as_str(), "y" | "yes") {
+                continue;
+            }
         }
 
-        print!(
-            "{} Directory does not exist. Create? (y/n): ",
-            Status::warning()
-        );
-        io::stdout().flush()?;
+        match ensure_writable_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(e) => prompter.say(&format!("{} {}", Status::error(), e)),
+        }
+    }
+}
+
+/// Creates `path` if needed and proves it can hold downloads by writing a
+/// probe file, so permission problems surface before any transfer starts.
+pub fn ensure_writable_dir(path: &Path) -> io::Result<()> {
+    if path.exists() && !path.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is a file, not a directory", path.display()),
+        ));
+    }
+
+    fs::create_dir_all(path).map_err(|e| describe_dir_error(path, e))?;
 
-        let input = read_line()?;
+    let probe = path.join(".wuwa-downloader-write-test");
+    fs::File::create(&probe).map_err(|e| describe_dir_error(path, e))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
 
-        if input.trim().eq_ignore_ascii_case("y") {
-            fs::create_dir_all(&path)?;
-            return Ok(path);
+fn describe_dir_error(path: &Path, err: io::Error) -> io::Error {
+    let message = match err.kind() {
+        io::ErrorKind::PermissionDenied => format!(
+            "No write permission for {} (choose another folder or run as administrator)",
+            path.display()
+        ),
+        io::ErrorKind::ReadOnlyFilesystem => {
+            format!("{} is on a read-only drive", path.display())
         }
-    }
+        _ => format!("Cannot use {} as download folder: {}", path.display(), err),
+    };
+    io::Error::new(err.kind(), message)
+}
+
+#[cfg(windows)]
+fn pick_dir() -> Option<PathBuf> {
+    println!(
+        "{} Select the download folder in the dialog (cancel to type a path instead)",
+        Status::question()
+    );
+    rfd::FileDialog::new()
+        .set_title("Select the Wuthering Waves download folder")
+        .pick_folder()
+}
+
+#[cfg(not(windows))]
+fn pick_dir() -> Option<PathBuf> {
+    None
+}
+
+/// A fresh path under the temp dir for a unit test to write into.
+#[cfg(test)]
+pub(crate) fn unique_path(name: &str) -> PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    std::env::temp_dir().join(format!("wuwa-downloader-{name}-{nanos}"))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{VerificationError, check_existing_file_interruptible};
+    use super::{
+        Checksum, FileState, HashAlgorithm, Hasher, VerificationError,
+        check_existing_file_interruptible, dest_path, ensure_writable_dir, get_dir,
+        hash_existing_prefix, inspect_file, resolve_dest, unique_path, verify_chunks,
+    };
+    use crate::config::cfg::FileChunk;
+    use crate::io::prompt::ScriptedPrompter;
+    use crate::io::quarantine::Quarantine;
     use std::fs;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
     use std::sync::Arc;
     use std::sync::atomic::AtomicBool;
-    use std::time::{SystemTime, UNIX_EPOCH};
 
-    fn unique_path(name: &str) -> PathBuf {
-        let nanos = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        std::env::temp_dir().join(format!("wuwa-downloader-{name}-{nanos}"))
+    /// Deletes mismatches, so the checks leave nothing behind but the report.
+    fn scratch_quarantine() -> Quarantine {
+        Quarantine::new(&unique_path("quarantine"), true)
+    }
+
+    fn abc_md5() -> Checksum {
+        Checksum::new(HashAlgorithm::Md5, "900150983cd24fb0d6963f7d28e17f72")
+    }
+
+    #[test]
+    fn hasher_produces_known_digests() {
+        let digest = |algorithm| {
+            let mut hasher = Hasher::new(algorithm);
+            hasher.update(b"abc");
+            hasher.finalize_hex()
+        };
+
+        assert_eq!(digest(HashAlgorithm::Md5), abc_md5().digest);
+        assert_eq!(
+            digest(HashAlgorithm::Sha1),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            digest(HashAlgorithm::Sha256),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(digest(HashAlgorithm::Xxh3).len(), 16);
+    }
+
+    #[tokio::test]
+    async fn hash_existing_prefix_continues_into_appended_tail() {
+        let path = unique_path("prefix");
+        fs::write(&path, b"ab").unwrap();
+
+        let mut hasher = hash_existing_prefix(&path, HashAlgorithm::Md5, &AtomicBool::new(false))
+            .await
+            .unwrap();
+        hasher.update(b"c");
+
+        assert!(abc_md5().matches(&hasher.finalize_hex()));
+        let _ = fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn inspect_file_reports_state_without_deleting() {
+        let path = unique_path("inspect");
+        let stop = || Arc::new(AtomicBool::new(false));
+        let md5 = abc_md5();
+
+        let state = inspect_file(&path, Some(&md5), Some(3), stop()).await;
+        assert_eq!(state.unwrap(), FileState::Missing);
+
+        fs::write(&path, b"abd").unwrap();
+        let state = inspect_file(&path, Some(&md5), Some(3), stop()).await;
+        assert_eq!(state.unwrap(), FileState::Corrupt);
+        let state = inspect_file(&path, Some(&md5), Some(4), stop()).await;
+        assert_eq!(state.unwrap(), FileState::WrongSize(3));
+        assert!(path.exists());
+
+        fs::write(&path, b"abc").unwrap();
+        let state = inspect_file(&path, Some(&md5), Some(3), stop()).await;
+        assert_eq!(state.unwrap(), FileState::Intact);
+        let _ = fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn verify_chunks_flags_damaged_and_missing_ranges() {
+        let path = unique_path("chunks");
+        let chunk = |start, end| FileChunk {
+            start,
+            end,
+            checksum: abc_md5(),
+        };
+        fs::write(&path, b"abcabxab").unwrap();
+
+        let intact = verify_chunks(
+            &path,
+            &[chunk(0, 2), chunk(3, 5), chunk(6, 8)],
+            &AtomicBool::new(false),
+        )
+        .await
+        .unwrap();
+        assert_eq!(intact, [true, false, false]);
+        let _ = fs::remove_file(path);
     }
 
     #[tokio::test]
@@ -231,6 +880,7 @@ mod tests {
             None,
             Some(4),
             Arc::new(AtomicBool::new(false)),
+            &scratch_quarantine(),
         )
         .await
         .unwrap();
@@ -249,6 +899,7 @@ mod tests {
             None,
             Some(4),
             Arc::new(AtomicBool::new(false)),
+            &scratch_quarantine(),
         )
         .await
         .unwrap();
@@ -268,6 +919,7 @@ mod tests {
             None,
             Some(3),
             Arc::new(AtomicBool::new(false)),
+            &scratch_quarantine(),
         )
         .await
         .unwrap();
@@ -279,19 +931,22 @@ mod tests {
     #[tokio::test]
     async fn check_existing_file_interruptible_returns_true_and_deletes_for_checksum_mismatch() {
         let path = unique_path("checksum-mismatch");
+        let report_dir = unique_path("checksum-mismatch-report");
         fs::write(&path, b"abc").unwrap();
 
         let result = check_existing_file_interruptible(
             &path,
-            Some("deadbeef"),
+            Some(&Checksum::new(HashAlgorithm::Md5, "deadbeef")),
             Some(3),
             Arc::new(AtomicBool::new(false)),
+            &Quarantine::new(&report_dir, true),
         )
         .await
         .unwrap();
 
         assert!(result);
         assert!(!path.exists());
+        let _ = fs::remove_dir_all(report_dir);
     }
 
     #[tokio::test]
@@ -301,9 +956,10 @@ mod tests {
 
         let result = check_existing_file_interruptible(
             &path,
-            Some("900150983cd24fb0d6963f7d28e17f72"),
+            Some(&abc_md5()),
             Some(3),
             Arc::new(AtomicBool::new(false)),
+            &scratch_quarantine(),
         )
         .await
         .unwrap();
@@ -320,9 +976,10 @@ mod tests {
 
         let result = check_existing_file_interruptible(
             &path,
-            Some("900150983cd24fb0d6963f7d28e17f72"),
+            Some(&abc_md5()),
             Some(3),
             Arc::new(AtomicBool::new(true)),
+            &scratch_quarantine(),
         )
         .await;
 
@@ -337,13 +994,94 @@ mod tests {
 
         let result = check_existing_file_interruptible(
             &path,
-            Some("900150983cd24fb0d6963f7d28e17f72"),
+            Some(&abc_md5()),
             None,
             Arc::new(AtomicBool::new(false)),
+            &scratch_quarantine(),
         )
         .await;
 
         assert!(matches!(result, Err(VerificationError::Io(_))));
         let _ = fs::remove_dir(path);
     }
+
+    #[test]
+    fn ensure_writable_dir_creates_folders_and_rejects_files() {
+        let dir = unique_path("writable").join("nested");
+        ensure_writable_dir(&dir).unwrap();
+        assert!(dir.is_dir());
+
+        let file = dir.join("file.bin");
+        fs::write(&file, b"abc").unwrap();
+        let err = ensure_writable_dir(&file).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn get_dir_creates_the_typed_folder_once_confirmed() {
+        let dir = unique_path("get-dir").join("game");
+        let typed = dir.to_string_lossy().into_owned();
+        let prompter = ScriptedPrompter::new([typed.as_str(), "n", typed.as_str(), "y"]);
+
+        assert_eq!(get_dir(None, false, &prompter).unwrap(), dir);
+        assert!(dir.is_dir());
+        assert_eq!(prompter.transcript().len(), 4);
+
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn resolve_dest_rejects_escaping_paths() {
+        let folder = PathBuf::from("/games/wuwa");
+
+        assert_eq!(
+            resolve_dest(&folder, "Client\\Paks\\main.pak").unwrap(),
+            folder.join("Client/Paks/main.pak")
+        );
+        assert!(resolve_dest(&folder, "..\\..\\Windows\\evil.dll").is_err());
+        assert!(resolve_dest(&folder, "Client/../../evil.dll").is_err());
+        assert!(resolve_dest(&folder, "/etc/passwd").is_err());
+        assert!(resolve_dest(&folder, "C:\\evil.dll").is_err());
+        assert!(resolve_dest(&folder, "").is_err());
+    }
+
+    #[test]
+    fn dest_path_pushes_each_component() {
+        let path = dest_path(Path::new("wuwa"), "Client\\Paks/./main.pak");
+        let parts: Vec<_> = path.iter().map(|part| part.to_string_lossy()).collect();
+
+        assert_eq!(parts, ["wuwa", "Client", "Paks", "main.pak"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_paths_get_the_verbatim_prefix() {
+        let deep = format!(r"C:\games\{}main.pak", "nested\\".repeat(40));
+        let path = dest_path(Path::new(r"C:\games"), &deep[9..]);
+
+        assert!(path.to_str().unwrap().starts_with(r"\\?\C:\games\nested\"));
+        assert_eq!(super::short_path(&path), PathBuf::from(&deep));
+        assert_eq!(
+            dest_path(Path::new(r"C:\games"), "main.pak"),
+            PathBuf::from(r"C:\games\main.pak")
+        );
+    }
+
+    #[test]
+    fn part_path_appends_suffix() {
+        assert_eq!(
+            super::part_path(&PathBuf::from("/games/Paks/main.pak")),
+            PathBuf::from("/games/Paks/main.pak.part")
+        );
+    }
+
+    #[test]
+    fn reserve_space_keeps_file_length() {
+        let path = unique_path("reserve");
+        super::reserve_space(&path, 1 << 20).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        let _ = fs::remove_file(path);
+    }
 }
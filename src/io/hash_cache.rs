@@ -0,0 +1,224 @@
+use serde_json::{Map, Value, json};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::UNIX_EPOCH,
+};
+
+use crate::config::cfg::ResourceItem;
+use crate::io::file::{Checksum, HashAlgorithm, short_path};
+
+pub const HASH_CACHE: &str = ".hash-cache.json";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CacheEntry {
+    size: u64,
+    mtime_ns: u64,
+    checksum: Checksum,
+}
+
+/// Digests of files verified in earlier sessions, keyed by their path below
+/// the download folder. An entry only counts while the file keeps the size and
+/// modification time it had when it was hashed.
+#[derive(Clone, Default)]
+pub struct HashCache {
+    inner: Option<Arc<Shared>>,
+}
+
+struct Shared {
+    folder: PathBuf,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    dirty: AtomicBool,
+}
+
+impl HashCache {
+    /// A cache that never matches and never saves.
+    pub fn disabled() -> Self {
+        Self { inner: None }
+    }
+
+    /// Loads the folder's cache; a missing or unreadable cache file starts empty.
+    pub fn load(folder: &Path) -> Self {
+        let entries = fs::read_to_string(folder.join(HASH_CACHE))
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+            .map(|data| parse_entries(&data))
+            .unwrap_or_default();
+        Self {
+            inner: Some(Arc::new(Shared {
+                folder: folder.to_path_buf(),
+                entries: Mutex::new(entries),
+                dirty: AtomicBool::new(false),
+            })),
+        }
+    }
+
+    /// Whether `path` was already verified against `expected` and is unchanged since.
+    pub fn is_verified(&self, path: &Path, metadata: &fs::Metadata, expected: &Checksum) -> bool {
+        let Some(shared) = &self.inner else {
+            return false;
+        };
+        let Some(mtime_ns) = mtime_ns(metadata) else {
+            return false;
+        };
+        let key = shared.key(path);
+        let entries = shared.entries();
+        entries.get(&key).is_some_and(|entry| {
+            entry.size == metadata.len()
+                && entry.mtime_ns == mtime_ns
+                && entry.checksum == *expected
+        })
+    }
+
+    /// Whether `path` is unchanged since it last matched `item`'s checksum and
+    /// size, so hashing it again can be skipped.
+    pub async fn is_cached(&self, path: &Path, item: &ResourceItem) -> bool {
+        let Some(checksum) = &item.checksum else {
+            return false;
+        };
+        if self.inner.is_none() {
+            return false;
+        }
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) => {
+                item.size.is_none_or(|size| size == metadata.len())
+                    && self.is_verified(path, &metadata, checksum)
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Remembers that `path`, as it is on disk now, hashes to `checksum`.
+    pub fn record(&self, path: &Path, checksum: &Checksum) {
+        let Some(shared) = &self.inner else {
+            return;
+        };
+        let Some((size, mtime_ns)) = fs::metadata(path)
+            .ok()
+            .and_then(|metadata| Some((metadata.len(), mtime_ns(&metadata)?)))
+        else {
+            return;
+        };
+        let entry = CacheEntry {
+            size,
+            mtime_ns,
+            checksum: checksum.clone(),
+        };
+        let key = shared.key(path);
+        let mut entries = shared.entries();
+        if entries.get(&key) != Some(&entry) {
+            entries.insert(key, entry);
+            shared.dirty.store(true, Ordering::SeqCst);
+        }
+    }
+
+    /// Writes the cache back if anything was recorded this session.
+    pub fn save(&self) -> io::Result<()> {
+        let Some(shared) = &self.inner else {
+            return Ok(());
+        };
+        if !shared.dirty.swap(false, Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let files: Map<String, Value> = shared
+            .entries()
+            .iter()
+            .map(|(key, entry)| {
+                (
+                    key.clone(),
+                    json!({
+                        "size": entry.size,
+                        "mtime_ns": entry.mtime_ns,
+                        "algorithm": entry.checksum.algorithm.manifest_key(),
+                        "digest": entry.checksum.digest,
+                    }),
+                )
+            })
+            .collect();
+        let contents =
+            serde_json::to_string(&json!({ "files": files })).map_err(io::Error::other)?;
+        fs::write(shared.folder.join(HASH_CACHE), contents).inspect_err(|_| {
+            shared.dirty.store(true, Ordering::SeqCst);
+        })
+    }
+}
+
+impl Shared {
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn key(&self, path: &Path) -> String {
+        let path = short_path(path);
+        path.strip_prefix(&self.folder)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+}
+
+fn mtime_ns(metadata: &fs::Metadata) -> Option<u64> {
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    u64::try_from(modified.as_nanos()).ok()
+}
+
+fn parse_entries(data: &Value) -> HashMap<String, CacheEntry> {
+    let Some(files) = data.get("files").and_then(Value::as_object) else {
+        return HashMap::new();
+    };
+    files
+        .iter()
+        .filter_map(|(key, entry)| {
+            let algorithm = HashAlgorithm::PREFERENCE.into_iter().find(|algorithm| {
+                entry.get("algorithm").and_then(Value::as_str) == Some(algorithm.manifest_key())
+            })?;
+            Some((
+                key.clone(),
+                CacheEntry {
+                    size: entry.get("size")?.as_u64()?,
+                    mtime_ns: entry.get("mtime_ns")?.as_u64()?,
+                    checksum: Checksum::new(algorithm, entry.get("digest")?.as_str()?),
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HASH_CACHE, HashCache};
+    use crate::io::file::{Checksum, HashAlgorithm, unique_path};
+    use std::fs;
+
+    #[test]
+    fn cache_survives_reload_and_misses_after_changes() {
+        let dir = unique_path("hash-cache");
+        fs::create_dir_all(dir.join("Paks")).unwrap();
+        let path = dir.join("Paks").join("main.pak");
+        fs::write(&path, b"abc").unwrap();
+        let checksum = Checksum::new(HashAlgorithm::Md5, "900150983cd24fb0d6963f7d28e17f72");
+
+        let cache = HashCache::load(&dir);
+        assert!(!cache.is_verified(&path, &fs::metadata(&path).unwrap(), &checksum));
+        cache.record(&path, &checksum);
+        cache.save().unwrap();
+        assert!(dir.join(HASH_CACHE).exists());
+
+        let cache = HashCache::load(&dir);
+        let metadata = fs::metadata(&path).unwrap();
+        assert!(cache.is_verified(&path, &metadata, &checksum));
+        let other = Checksum::new(HashAlgorithm::Md5, "00000000000000000000000000000000");
+        assert!(!cache.is_verified(&path, &metadata, &other));
+
+        fs::write(&path, b"abcd").unwrap();
+        assert!(!cache.is_verified(&path, &fs::metadata(&path).unwrap(), &checksum));
+        assert!(!HashCache::disabled().is_verified(&path, &metadata, &checksum));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,149 @@
+use std::process::Stdio;
+use tokio::process::Command;
+
+use crate::io::logging::{SharedLogFile, log_info, log_warn};
+
+/// A user command run after downloads. The template is split into arguments
+/// once and placeholders are substituted per argument, so values containing
+/// spaces or quotes reach the program intact without going through a shell.
+#[derive(Clone, Debug)]
+pub struct Hook {
+    template: String,
+    args: Vec<String>,
+}
+
+/// Parses a hook template such as `notify.sh "{folder}" {failed}`.
+pub fn parse_hook(value: &str) -> Result<Hook, String> {
+    let args = split_command(value)?;
+    if args.is_empty() {
+        return Err("hook command cannot be empty".to_string());
+    }
+    Ok(Hook {
+        template: value.to_string(),
+        args,
+    })
+}
+
+impl Hook {
+    /// Replaces every `{name}` with its value; unknown placeholders are left as-is.
+    pub fn render(&self, vars: &[(&str, &str)]) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|arg| {
+                vars.iter().fold(arg.clone(), |arg, (name, value)| {
+                    arg.replace(&format!("{{{}}}", name), value)
+                })
+            })
+            .collect()
+    }
+
+    /// Runs the hook to completion. Its output goes to the log file rather
+    /// than the console so progress bars stay intact.
+    pub async fn run(&self, vars: &[(&str, &str)], log_file: &SharedLogFile) {
+        let args = self.render(vars);
+        let output = Command::new(&args[0])
+            .args(&args[1..])
+            .stdin(Stdio::null())
+            .output()
+            .await;
+
+        let output = match output {
+            Ok(output) => output,
+            Err(e) => {
+                log_warn(
+                    log_file,
+                    &format!("Hook `{}` failed to start: {}", self.template, e),
+                );
+                return;
+            }
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut message = format!("Hook `{}` exited with {}", args.join(" "), output.status);
+        for stream in [stdout.trim(), stderr.trim()] {
+            if !stream.is_empty() {
+                message.push('\n');
+                message.push_str(stream);
+            }
+        }
+
+        if output.status.success() {
+            log_info(log_file, &message);
+        } else {
+            log_warn(log_file, &message);
+        }
+    }
+}
+
+/// Splits on whitespace, honouring single and double quotes. Backslashes are
+/// kept literally so Windows paths survive.
+fn split_command(value: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+
+    for c in value.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            None if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(format!("unterminated quote in hook command: {}", value));
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_hook, split_command};
+
+    #[test]
+    fn split_command_honours_quotes_and_keeps_backslashes() {
+        assert_eq!(
+            split_command(r#"C:\tools\move.bat "{folder}" 'a b' """#).unwrap(),
+            [r"C:\tools\move.bat", "{folder}", "a b", ""]
+        );
+        assert!(split_command("echo 'open").is_err());
+        assert!(parse_hook("   ").is_err());
+    }
+
+    #[test]
+    fn render_substitutes_placeholders_per_argument() {
+        let hook = parse_hook("notify.sh {folder} done={success}/{total} {unknown}").unwrap();
+        let args = hook.render(&[
+            ("folder", "/games/Wuthering Waves"),
+            ("success", "10"),
+            ("total", "12"),
+        ]);
+        assert_eq!(
+            args,
+            [
+                "notify.sh",
+                "/games/Wuthering Waves",
+                "done=10/12",
+                "{unknown}"
+            ]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! Message tables for prompts, result screens and hints. Lines written only
+//! to the log file stay in English so they can be searched and quoted in bug
+//! reports.
+
+use std::{fmt::Display, sync::OnceLock};
+
+/// Language of prompts and result screens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Lang {
+    /// English
+    En,
+    /// Simplified Chinese
+    #[value(name = "zh-cn", alias = "zh")]
+    ZhCn,
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// Fixes the language for the rest of the process: `lang` when given,
+/// otherwise the system locale.
+pub fn set_lang(lang: Option<Lang>) {
+    let _ = LANG.set(lang.unwrap_or_else(system_lang));
+}
+
+pub fn lang() -> Lang {
+    *LANG.get_or_init(system_lang)
+}
+
+fn system_lang() -> Lang {
+    system_locale().map_or(Lang::En, |locale| locale_lang(&locale))
+}
+
+/// `zh_CN.UTF-8`, `zh-Hans` and `zh-SG` read as Simplified Chinese; every
+/// other locale, Traditional Chinese included, as English.
+fn locale_lang(locale: &str) -> Lang {
+    let locale = locale.to_ascii_lowercase().replace('_', "-");
+    let traditional = ["-tw", "-hk", "-mo", "-hant"]
+        .iter()
+        .any(|tag| locale.contains(tag));
+    if locale.starts_with("zh") && !traditional {
+        Lang::ZhCn
+    } else {
+        Lang::En
+    }
+}
+
+#[cfg(windows)]
+fn system_locale() -> Option<String> {
+    use windows_sys::Win32::Globalization::GetUserDefaultLocaleName;
+
+    let mut name = [0_u16; 85];
+    let len = unsafe { GetUserDefaultLocaleName(name.as_mut_ptr(), name.len() as i32) };
+    let len = usize::try_from(len).ok()?.checked_sub(1)?;
+    Some(String::from_utf16_lossy(&name[..len]))
+}
+
+#[cfg(not(windows))]
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+}
+
+macro_rules! messages {
+    ($($name:ident => $en:literal, $zh:literal;)*) => {
+        /// A translatable message; `{}` marks where [`Msg::fill`] puts arguments.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Msg {
+            $($name,)*
+        }
+
+        impl Msg {
+            #[cfg(test)]
+            const ALL: &[Msg] = &[$(Msg::$name,)*];
+
+            pub fn in_lang(self, lang: Lang) -> &'static str {
+                match lang {
+                    Lang::En => match self {
+                        $(Msg::$name => $en,)*
+                    },
+                    Lang::ZhCn => match self {
+                        $(Msg::$name => $zh,)*
+                    },
+                }
+            }
+        }
+    };
+}
+
+messages! {
+    // Version and source prompts
+    DownloadModeSelection => "Download Mode Selection", "下载模式选择";
+    ModeLatest => "1. Latest game versions (from official sources)", "1. 最新游戏版本（来自官方源）";
+    ModeCustom => "2. Custom version (provide resource URLs)", "2. 自定义版本（提供资源 URL）";
+    ChooseMode => "Choose download mode (1 or 2): ", "请选择下载模式（1 或 2）：";
+    InvalidChoiceOneOrTwo => "Invalid choice, please enter 1 or 2", "无效选择，请输入 1 或 2";
+    CustomConfig => "Custom Version Configuration", "自定义版本配置";
+    EnterIndexUrl => "Enter resource.json URL: ", "请输入 resource.json 的 URL：";
+    EmptyIndexUrl => "Resource JSON URL cannot be empty", "resource.json 的 URL 不能为空";
+    EnterBaseUrl => "Enter resource base path URL (ending with /zip): ", "请输入资源基础路径 URL（以 /zip 结尾）：";
+    EmptyBaseUrl => "Resource base path URL cannot be empty", "资源基础路径 URL 不能为空";
+    ConfigLoaded => "Configuration loaded successfully", "配置加载成功";
+    FetchingConfig => "Fetching download configuration...", "正在获取下载配置...";
+    UsingConfig => "Using {}.config", "使用 {}.config";
+    ChooseConfig => "Choose config to use (1=default, 2=predownload): ", "请选择要使用的配置（1=default，2=predownload）：";
+    CdnListElsewhere => "CDN list missing in '{}', but found in '{}'.", "'{}' 中缺少 CDN 列表，但在 '{}' 中找到。";
+    UseOtherCdnList => "Do you want to use the CDN list from '{}'? [Y/n]: ", "是否使用 '{}' 中的 CDN 列表？[Y/n]：";
+    InvalidYesNo => "Invalid choice, please press Enter for Yes, or 'n' for No", "无效选择，按回车表示“是”，输入 n 表示“否”";
+    EnterCdnsManually => "Please enter CDN URLs manually.", "请手动输入 CDN URL。";
+    EnterCdns => "Enter CDN URLs (comma-separated): ", "请输入 CDN URL（用逗号分隔）：";
+    NoCdnUrls => "No valid CDN URLs found", "未找到有效的 CDN URL";
+    AvailableVersions => "Available versions:", "可用版本：";
+    SelectVersion => "Select version: ", "请选择版本：";
+    InvalidSelection => "Invalid selection", "无效选择";
+
+    // Download options prompts
+    VoiceLanguagesFound => "Voice-over languages found:", "找到以下语音语言：";
+    VoiceLanguageEntry => "{}. {} ({} files, {})", "{}. {}（{} 个文件，{}）";
+    SelectLanguages => "Select languages to download (e.g. 1,3) [default all]: ", "请选择要下载的语言（例如 1,3）[默认全部]：";
+    InvalidLanguage => "Invalid selection '{}', downloading all languages", "无效选择 '{}'，将下载全部语言";
+    EnterDownloadConcurrency => "Enter concurrent downloads [default {}]: ", "请输入并发下载数 [默认 {}]：";
+    EnterVerifyConcurrency => "Enter concurrent verifications [default {}]: ", "请输入并发校验数 [默认 {}]：";
+    ConcurrencyClamped => "Value too large, using {}", "数值过大，改用 {}";
+    ConcurrencyInvalid => "Invalid value, using the default {}", "无效数值，使用默认值 {}";
+    AskDir => "Please specify the directory where the game should be downloaded (press Enter to use the current directory): ", "请指定游戏的下载目录（直接按回车使用当前目录）：";
+    CreateDir => "Directory does not exist. Create? (y/n): ", "目录不存在，是否创建？(y/n)：";
+    SpaceUnknown => "Could not determine free space ({}), skipping disk space check", "无法确定可用空间（{}），跳过磁盘空间检查";
+    NotEnoughSpace => "Not enough free space: {} required (+{} margin), {} available", "可用空间不足：需要 {}（另加 {} 余量），可用 {}";
+    ContinueAnyway => "Continue anyway? (y/n): ", "仍要继续吗？(y/n)：";
+    SpaceCancelled => "Download cancelled: not enough disk space", "下载已取消：磁盘空间不足";
+
+    // Session
+    DownloadFolder => "Download folder: {}", "下载目录：{}";
+    DownloadConcurrency => "Download concurrency: {}", "并发下载数：{}";
+    VerifyConcurrency => "Verify concurrency: {}", "并发校验数：{}";
+    FilesToDownload => "Found {} files to download", "共找到 {} 个待下载文件";
+    DownloadSize => "Download size: {} ({} still to fetch)", "下载大小：{}（仍需获取 {}）";
+    SizeAfterExtraction => "Size on disk after extraction: {}", "解压后占用空间：{}";
+    FreeSpace => "Free space: {}", "可用空间：{}";
+    KeysHint => "Keys: p pause, r resume, s skip current files, q quit", "按键：p 暂停，r 继续，s 跳过当前文件，q 退出";
+    Paused => "Paused, press r to resume", "已暂停，按 r 继续";
+    Resumed => "Resumed", "已继续";
+    SkippingCurrent => "Skipping the files currently downloading", "正在跳过当前下载中的文件";
+    Stopping => "Stopping after in-flight chunks are saved", "保存正在传输的数据块后停止";
+    StoppingForceQuit => "Stopping after in-flight chunks are saved, press Ctrl-C again to force quit", "保存正在传输的数据块后停止，再次按 Ctrl-C 强制退出";
+    ExtractingArchives => "Extracting archives...", "正在解压压缩包...";
+    PressEnterToExit => "Press Enter to exit...", "按回车键退出...";
+
+    // Result screens
+    DownloadComplete => " DOWNLOAD COMPLETE ", " 下载完成 ";
+    DownloadInterrupted => " DOWNLOAD INTERRUPTED ", " 下载已中断 ";
+    PartialDownload => " PARTIAL DOWNLOAD ", " 部分下载 ";
+    VerifiedCount => "Successfully verified: {}", "校验通过：{}";
+    DownloadedCount => "Successfully downloaded: {}", "下载成功：{}";
+    FailedCount => "Failed: {}", "失败：{}";
+    UnprocessedCount => "Unprocessed: {}", "未处理：{}";
+    TotalFiles => "Total files: {}", "文件总数：{}";
+    FilesSavedTo => "Files saved to: {}", "文件保存至：{}";
+    DownloadedBytes => "Downloaded {}; per-file outcomes in {}", "已下载 {}；每个文件的结果见 {}";
+    ByDirectory => "By directory:", "按目录：";
+    GroupDetail => "{}/{} files, {} of {}", "{}/{} 个文件，{} / {}";
+    MirrorUpToDate => "{} files are already in the mirror", "{} 个文件已在镜像中";
+    MirrorReady => "Mirror ready: share {} with the serve command and install from it on other PCs", "镜像已就绪：使用 serve 命令共享 {}，即可在其他电脑上从它安装";
+    ServingFolder => "Serving {} on port {}, press Ctrl-C to stop", "正在提供 {}（端口 {}），按 Ctrl-C 停止";
+    InstallFromMirror => "Install on other PCs with --index-file http://<this-pc>:{}/index.json --base-url http://<this-pc>:{}/files", "在其他电脑上使用 --index-file http://<本机>:{}/index.json --base-url http://<本机>:{}/files 安装";
+    InstallFromFolder => "Download on other PCs with --base-url http://<this-pc>:{}", "在其他电脑上使用 --base-url http://<本机>:{} 下载";
+    ByCdn => "By CDN:", "按 CDN：";
+    CdnDetail => "{} ok, {} errors ({} rate limited)", "{} 次成功，{} 次错误（{} 次限流）";
+    CdnBenched => "paused {} times", "暂停使用 {} 次";
+    DuplicatesCopied => "{} files share content with another file and will be copied instead of downloaded", "{} 个文件与其他文件内容相同，将复制而不是下载";
+    Remaining => "Remaining: {} files, {} still to download", "剩余：{} 个文件，还需下载 {}";
+    ResumeHint => "Run again with the same folder to resume (partial files continue where they stopped), or with {} to resume only the files listed in {}", "使用相同目录再次运行即可继续（未完成的文件会从中断处继续），或加上 {} 仅继续 {} 中列出的文件";
+    RetryHint => "Failed files listed in {}; run again with {} to retry only those", "失败的文件已列在 {} 中；加上 {} 再次运行可仅重试这些文件";
+    ArchivesExtracted => "Archives extracted: {}", "已解压的压缩包：{}";
+    ArchivesFailed => "Archives failed to extract: {}", "解压失败的压缩包：{}";
+    ArchivesRemoved => "Archives removed: {}", "已删除的压缩包：{}";
+    VerifyPassed => " VERIFY PASSED ", " 校验通过 ";
+    VerifyFailed => " VERIFY FAILED ", " 校验失败 ";
+    FileMissing => "missing", "缺失";
+    WrongSize => "size {} instead of {}", "大小为 {}，应为 {}";
+    UnexpectedSize => "unexpected size {}", "大小异常：{}";
+    ChecksumMismatch => "checksum mismatch", "校验和不匹配";
+    IntactCount => "Intact: {}", "完好：{}";
+    DamagedCount => "Missing or damaged: {}", "缺失或损坏：{}";
+    UncheckedCount => "Unchecked: {}", "未检查：{}";
+    NotInChecksumList => "not in the checksum list", "不在校验和列表中";
+    ExtraCount => "Not in the checksum list: {}", "不在校验和列表中：{}";
+    Folder => "Folder: {}", "目录：{}";
+    RepairHint => "Run {} with the same folder to re-download them", "对同一目录运行 {} 以重新下载这些文件";
+    CdnHealth => " CDN HEALTH ", " CDN 状态 ";
+    CdnUnreachable => "unreachable: {}", "无法访问：{}";
+    CdnStats => "status {} | latency {} | throughput {}", "状态 {} | 延迟 {} | 吞吐量 {}";
+    CdnsServing => "{} of {} CDNs are serving files", "{} / {} 个 CDN 正常提供文件";
+    DownloadEstimate => "{} ({} files) would take about {} from {}", "{}（{} 个文件）预计需要约 {}，来自 {}";
+    NoDownloadEstimate => "No CDN delivered data, so the download time cannot be estimated", "没有 CDN 返回数据，无法估算下载时间";
+    UnsizedLeftOut => "{} files without a size in the index are not counted", "索引中 {} 个没有大小的文件未计入";
+
+    // Other commands
+    ConfirmOrphans => "Delete {} files ({}) that are not part of the game index? (y/n): ", "删除 {} 个不在游戏索引中的文件（{}）？(y/n)：";
+    ConfirmUpdate => "Install {} now? (y/n): ", "现在安装 {} 吗？(y/n)：";
+    UpdateAvailable => "wuwa-downloader {} is available (this is v{}); run `wuwa-downloader self-update` to install it", "wuwa-downloader {} 已发布（当前为 v{}）；运行 `wuwa-downloader self-update` 进行安装";
+}
+
+impl Msg {
+    /// The message in the current language.
+    pub fn text(self) -> &'static str {
+        self.in_lang(lang())
+    }
+
+    /// The message with each `{}` replaced by the next of `args`.
+    pub fn fill(self, args: &[&dyn Display]) -> String {
+        let mut parts = self.text().split("{}");
+        let mut out = parts.next().unwrap_or_default().to_string();
+        let mut args = args.iter();
+        for part in parts {
+            if let Some(arg) = args.next() {
+                out.push_str(&arg.to_string());
+            }
+            out.push_str(part);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lang, Msg, locale_lang};
+
+    #[test]
+    fn every_translation_takes_the_same_arguments() {
+        for msg in Msg::ALL {
+            assert_eq!(
+                msg.in_lang(Lang::En).matches("{}").count(),
+                msg.in_lang(Lang::ZhCn).matches("{}").count(),
+                "{:?}",
+                msg
+            );
+        }
+        assert_eq!(
+            Msg::NotEnoughSpace.fill(&[&"1 GiB", &"2 GiB", &3]),
+            match super::lang() {
+                Lang::En => "Not enough free space: 1 GiB required (+2 GiB margin), 3 available",
+                Lang::ZhCn => "可用空间不足：需要 1 GiB（另加 2 GiB 余量），可用 3",
+            }
+        );
+    }
+
+    #[test]
+    fn reads_simplified_chinese_locales() {
+        assert_eq!(locale_lang("zh_CN.UTF-8"), Lang::ZhCn);
+        assert_eq!(locale_lang("zh-Hans-CN"), Lang::ZhCn);
+        assert_eq!(locale_lang("zh_TW.UTF-8"), Lang::En);
+        assert_eq!(locale_lang("zh-Hant-HK"), Lang::En);
+        assert_eq!(locale_lang("en_US.UTF-8"), Lang::En);
+        assert_eq!(locale_lang("de-DE"), Lang::En);
+    }
+}
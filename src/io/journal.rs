@@ -0,0 +1,203 @@
+use serde_json::{Map, Value, json};
+use std::{
+    collections::HashMap,
+    fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::io::file::short_path;
+
+pub const WRITE_JOURNAL: &str = ".write-journal.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct JournalEntry {
+    expected_size: Option<u64>,
+    /// Bytes at the start of the `.part` file known to be on disk.
+    synced: u64,
+}
+
+/// How much of each in-flight `.part` file has been fsynced, kept in the
+/// download folder so that after a power loss a resume starts from bytes that
+/// are known to be intact instead of whatever tail the filesystem kept.
+#[derive(Clone, Default)]
+pub struct WriteJournal {
+    inner: Option<Arc<Shared>>,
+}
+
+struct Shared {
+    folder: PathBuf,
+    interval: Duration,
+    entries: Mutex<HashMap<String, JournalEntry>>,
+}
+
+impl WriteJournal {
+    /// A journal that trusts every partial file and never syncs.
+    pub fn disabled() -> Self {
+        Self { inner: None }
+    }
+
+    /// Loads the folder's journal; a zero `interval` disables journaling.
+    pub fn open(folder: &Path, interval: Duration) -> Self {
+        if interval.is_zero() {
+            return Self::disabled();
+        }
+        let entries = fs::read_to_string(folder.join(WRITE_JOURNAL))
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+            .map(|data| parse_entries(&data))
+            .unwrap_or_default();
+        Self {
+            inner: Some(Arc::new(Shared {
+                folder: folder.to_path_buf(),
+                interval,
+                entries: Mutex::new(entries),
+            })),
+        }
+    }
+
+    /// How often an in-flight file is synced and recorded.
+    pub fn interval(&self) -> Option<Duration> {
+        self.inner.as_ref().map(|shared| shared.interval)
+    }
+
+    /// How many leading bytes of the partial at `path` can be trusted, or
+    /// `None` when the journal knows nothing about it. A partial recorded for
+    /// a different size belongs to another version of the file.
+    pub fn trusted_len(&self, path: &Path, expected_size: Option<u64>) -> Option<u64> {
+        let shared = self.inner.as_ref()?;
+        let entry = *shared.entries().get(&shared.key(path))?;
+        Some(if entry.expected_size == expected_size {
+            entry.synced
+        } else {
+            0
+        })
+    }
+
+    /// Records that the first `synced` bytes of `path` are on disk. The caller
+    /// must have synced them first.
+    pub fn record(&self, path: &Path, expected_size: Option<u64>, synced: u64) -> io::Result<()> {
+        let Some(shared) = &self.inner else {
+            return Ok(());
+        };
+        let mut entries = shared.entries();
+        entries.insert(
+            shared.key(path),
+            JournalEntry {
+                expected_size,
+                synced,
+            },
+        );
+        shared.save(&entries)
+    }
+
+    /// Drops `path` once it no longer needs recovering.
+    pub fn forget(&self, path: &Path) -> io::Result<()> {
+        let Some(shared) = &self.inner else {
+            return Ok(());
+        };
+        let mut entries = shared.entries();
+        if entries.remove(&shared.key(path)).is_none() {
+            return Ok(());
+        }
+        shared.save(&entries)
+    }
+}
+
+impl Shared {
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, JournalEntry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn key(&self, path: &Path) -> String {
+        let path = short_path(path);
+        path.strip_prefix(&self.folder)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    /// Replaces the journal file through a synced temporary, so a crash
+    /// leaves either the old journal or the new one.
+    fn save(&self, entries: &HashMap<String, JournalEntry>) -> io::Result<()> {
+        let path = self.folder.join(WRITE_JOURNAL);
+        if entries.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        let files: Map<String, Value> = entries
+            .iter()
+            .map(|(key, entry)| {
+                (
+                    key.clone(),
+                    json!({ "expected_size": entry.expected_size, "synced": entry.synced }),
+                )
+            })
+            .collect();
+        let contents =
+            serde_json::to_string(&json!({ "files": files })).map_err(io::Error::other)?;
+        let temp = path.with_extension("json.tmp");
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, &path)
+    }
+}
+
+fn parse_entries(data: &Value) -> HashMap<String, JournalEntry> {
+    let Some(files) = data.get("files").and_then(Value::as_object) else {
+        return HashMap::new();
+    };
+    files
+        .iter()
+        .filter_map(|(key, entry)| {
+            Some((
+                key.clone(),
+                JournalEntry {
+                    expected_size: entry.get("expected_size").and_then(Value::as_u64),
+                    synced: entry.get("synced")?.as_u64()?,
+                },
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WRITE_JOURNAL, WriteJournal};
+    use crate::io::file::unique_path;
+    use std::time::Duration;
+
+    #[test]
+    fn survives_a_reload_and_rejects_other_versions() {
+        let dir = unique_path("journal");
+        std::fs::create_dir_all(&dir).unwrap();
+        let part = dir.join("Client/a.pak.part");
+        let interval = Duration::from_secs(5);
+
+        let journal = WriteJournal::open(&dir, interval);
+        assert_eq!(journal.trusted_len(&part, Some(100)), None);
+        journal.record(&part, Some(100), 64).unwrap();
+
+        let reloaded = WriteJournal::open(&dir, interval);
+        assert_eq!(reloaded.trusted_len(&part, Some(100)), Some(64));
+        assert_eq!(reloaded.trusted_len(&part, Some(200)), Some(0));
+        assert_eq!(
+            WriteJournal::open(&dir, Duration::ZERO).trusted_len(&part, Some(100)),
+            None
+        );
+
+        reloaded.forget(&part).unwrap();
+        assert!(!dir.join(WRITE_JOURNAL).exists());
+        assert_eq!(
+            WriteJournal::open(&dir, interval).trusted_len(&part, Some(100)),
+            None
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
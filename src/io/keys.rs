@@ -0,0 +1,112 @@
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal,
+};
+use log::{info, warn};
+use std::{
+    io::{self, IsTerminal},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::config::{outcome::Outcome, status::Status};
+use crate::download::control::DownloadControl;
+use crate::io::i18n::Msg;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Background thread that turns single key presses into download controls:
+/// `p` pause, `r` resume, `s` skip the files in flight, `q` quit gracefully.
+pub struct KeyListener {
+    done: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl KeyListener {
+    /// Returns `None` when stdin is not an interactive terminal.
+    pub fn spawn(control: DownloadControl, should_stop: Arc<AtomicBool>) -> Option<Self> {
+        if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+            return None;
+        }
+        enable_key_mode().ok()?;
+
+        let done = Arc::new(AtomicBool::new(false));
+        let thread_done = done.clone();
+        let handle = thread::spawn(move || {
+            while !thread_done.load(Ordering::SeqCst) {
+                if !event::poll(POLL_INTERVAL).unwrap_or(false) {
+                    continue;
+                }
+                let Ok(Event::Key(key)) = event::read() else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Char('p') if !control.is_paused() => {
+                        control.pause();
+                        info!("{}", Msg::Paused.text());
+                    }
+                    KeyCode::Char('r') if control.is_paused() => {
+                        control.resume();
+                        info!("{}", Msg::Resumed.text());
+                    }
+                    KeyCode::Char('s') => {
+                        control.skip_current();
+                        info!("{}", Msg::SkippingCurrent.text());
+                    }
+                    KeyCode::Char('q') => {
+                        should_stop.store(true, Ordering::SeqCst);
+                        control.resume();
+                        info!("{}", Msg::Stopping.text());
+                    }
+                    // Windows raw mode delivers Ctrl-C as a key instead of a
+                    // signal, so it is handled here like the Ctrl-C handler does.
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        if should_stop.swap(true, Ordering::SeqCst) {
+                            let _ = terminal::disable_raw_mode();
+                            eprintln!("\n{} Force exiting after second Ctrl-C", Status::warning());
+                            Outcome::Interrupted.exit();
+                        }
+                        control.resume();
+                        warn!("{}", Msg::StoppingForceQuit.text());
+                    }
+                    _ => {}
+                }
+            }
+        });
+
+        Some(Self { done, handle })
+    }
+
+    /// Stops listening and hands the terminal back for line-based prompts.
+    pub fn stop(self) {
+        self.done.store(true, Ordering::SeqCst);
+        let _ = self.handle.join();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Raw mode delivers keys without Enter. On Unix, output processing and signals
+/// are switched back on so progress bars render normally and Ctrl-C still works.
+fn enable_key_mode() -> io::Result<()> {
+    terminal::enable_raw_mode()?;
+
+    #[cfg(unix)]
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+            termios.c_oflag |= libc::OPOST;
+            termios.c_lflag |= libc::ISIG;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+        }
+    }
+
+    Ok(())
+}
@@ -1,29 +1,179 @@
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
 use std::{
     fs::{self, OpenOptions},
-    io::Write,
+    io::{self, Write},
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
-    time::SystemTime,
 };
 
-pub type SharedLogFile = Arc<Mutex<fs::File>>;
+use crate::config::status::Status;
 
-pub fn setup_logging() -> SharedLogFile {
-    Arc::new(Mutex::new(
-        OpenOptions::new()
+pub type SharedLogFile = Arc<Mutex<LogFile>>;
+
+/// Log target for positive outcomes, rendered with the success glyph.
+pub const SUCCESS: &str = "wuwa_downloader::success";
+
+struct ConsoleLogger {
+    to_stderr: bool,
+}
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with("wuwa_downloader") && metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let glyph = match record.level() {
+            _ if record.target() == SUCCESS => Status::success(),
+            Level::Error => Status::error(),
+            Level::Warn => Status::warning(),
+            Level::Info => Status::info(),
+            Level::Debug | Level::Trace => Status::progress(),
+        };
+
+        if self.to_stderr {
+            eprintln!("{} {}", glyph, record.args());
+        } else {
+            println!("{} {}", glyph, record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Routes `log` macros to the console. `to_stderr` keeps stdout free for
+/// machine-readable output.
+pub fn setup_console_logging(level: LevelFilter, to_stderr: bool) {
+    if log::set_boxed_logger(Box::new(ConsoleLogger { to_stderr })).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Rotate once the active log grows past this size.
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// Number of rotated logs (`logs.1.log` ... `logs.N.log`) kept around.
+const KEPT_LOGS: usize = 3;
+const LOG_NAME: &str = "logs";
+
+pub struct LogFile {
+    dir: PathBuf,
+    file: fs::File,
+    written: u64,
+    max_bytes: u64,
+}
+
+impl LogFile {
+    fn open(dir: &Path, max_bytes: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = log_path(dir, 0);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            written,
+            max_bytes,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let _ = fs::remove_file(log_path(&self.dir, KEPT_LOGS));
+        for index in (0..KEPT_LOGS).rev() {
+            let from = log_path(&self.dir, index);
+            if from.exists() {
+                fs::rename(&from, log_path(&self.dir, index + 1))?;
+            }
+        }
+        self.file = OpenOptions::new()
             .create(true)
             .append(true)
-            .open("logs.log")
-            .expect("Failed to create/open log file"),
-    ))
+            .open(log_path(&self.dir, 0))?;
+        self.written = 0;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if self.written > 0 && self.written + line.len() as u64 + 1 > self.max_bytes {
+            let _ = self.rotate();
+        }
+        if writeln!(self.file, "{}", line).is_ok() {
+            self.written += line.len() as u64 + 1;
+        }
+    }
+}
+
+fn log_path(dir: &Path, index: usize) -> PathBuf {
+    match index {
+        0 => dir.join(format!("{}.log", LOG_NAME)),
+        n => dir.join(format!("{}.{}.log", LOG_NAME, n)),
+    }
+}
+
+fn timestamp() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+}
+
+/// Opens (or creates) `logs.log` in `dir` and marks the start of a new session.
+pub fn setup_logging(dir: &Path) -> io::Result<SharedLogFile> {
+    let mut log = LogFile::open(dir, MAX_LOG_BYTES)?;
+    log.write_line(&format!(
+        "===== {} session started (wuwa-downloader v{}) =====",
+        timestamp(),
+        env!("CARGO_PKG_VERSION")
+    ));
+    Ok(Arc::new(Mutex::new(log)))
+}
+
+fn write_entry(log_file: &SharedLogFile, level: Level, message: &str) {
+    if let Ok(mut log) = log_file.lock() {
+        log.write_line(&format!("{} [{:<5}] {}", timestamp(), level, message));
+    }
+}
+
+pub fn log_info(log_file: &SharedLogFile, message: &str) {
+    write_entry(log_file, Level::Info, message);
+}
+
+pub fn log_warn(log_file: &SharedLogFile, message: &str) {
+    write_entry(log_file, Level::Warn, message);
 }
 
 pub fn log_error(log_file: &SharedLogFile, message: &str) {
-    let timestamp = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
+    write_entry(log_file, Level::Error, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogFile, log_path};
+    use crate::io::file::unique_path;
+    use std::fs;
+
+    #[test]
+    fn write_line_rotates_past_size_limit() {
+        let dir = unique_path("logs");
+        let mut log = LogFile::open(&dir, 16).unwrap();
+
+        log.write_line("first entry");
+        log.write_line("second entry");
+        log.write_line("third entry");
 
-    if let Ok(mut file) = log_file.lock() {
-        let _ = writeln!(file, "[{}] ERROR: {}", timestamp, message);
+        assert_eq!(
+            fs::read_to_string(log_path(&dir, 0)).unwrap(),
+            "third entry\n"
+        );
+        assert_eq!(
+            fs::read_to_string(log_path(&dir, 1)).unwrap(),
+            "second entry\n"
+        );
+        assert_eq!(
+            fs::read_to_string(log_path(&dir, 2)).unwrap(),
+            "first entry\n"
+        );
+        let _ = fs::remove_dir_all(dir);
     }
 }
@@ -0,0 +1,92 @@
+use chrono::Local;
+use serde_json::{Value, json};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::config::cfg::Config;
+
+/// Folder below the download folder that keeps the manifests of past sessions.
+pub const MANIFEST_DIR: &str = ".wuwa/manifests";
+
+/// Saves the index a session downloads from as `<time>-index.json`, which
+/// `--index-file` and `diff` can load, and the CDN config next to it as
+/// `<time>-config.json`. Nothing is written when the index is the same as
+/// the newest one saved, so repeated sessions of one version keep one copy.
+pub fn save_manifest(
+    folder: &Path,
+    index: &Value,
+    config: &Config,
+) -> Result<Option<PathBuf>, String> {
+    let dir = folder.join(MANIFEST_DIR);
+    let index_text = serde_json::to_string_pretty(index).map_err(|e| e.to_string())? + "\n";
+    if latest_index(&dir)
+        .and_then(|path| fs::read_to_string(path).ok())
+        .is_some_and(|latest| latest == index_text)
+    {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let now = Local::now();
+    let stamp = now.format("%Y%m%d-%H%M%S");
+    let config_text = serde_json::to_string_pretty(&json!({
+        "time": now.to_rfc3339(),
+        "index_url": config.index_url,
+        "zip_bases": config.zip_bases,
+    }))
+    .map_err(|e| e.to_string())?;
+    let index_path = dir.join(format!("{}-index.json", stamp));
+    for (path, text) in [
+        (index_path.clone(), index_text),
+        (
+            dir.join(format!("{}-config.json", stamp)),
+            config_text + "\n",
+        ),
+    ] {
+        fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    }
+    Ok(Some(index_path))
+}
+
+/// Newest `*-index.json` in `dir`; the timestamped names sort by time.
+fn latest_index(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.ends_with("-index.json"))
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MANIFEST_DIR, save_manifest};
+    use crate::config::cfg::Config;
+    use crate::io::file::unique_path;
+    use serde_json::json;
+
+    #[test]
+    fn saves_each_index_once() {
+        let root = unique_path("manifests");
+        let config = Config {
+            index_url: "https://cdn/index.json".to_string(),
+            zip_bases: vec!["https://cdn/zip/".to_string()],
+        };
+        let index = json!({ "resource": [{ "dest": "a.pak", "md5": "aa", "size": 1 }] });
+
+        let saved = save_manifest(&root, &index, &config).unwrap().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&saved).unwrap(),
+            serde_json::to_string_pretty(&index).unwrap() + "\n"
+        );
+        assert!(save_manifest(&root, &index, &config).unwrap().is_none());
+        let files = std::fs::read_dir(root.join(MANIFEST_DIR)).unwrap().count();
+        assert_eq!(files, 2);
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
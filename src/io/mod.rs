@@ -1,4 +1,21 @@
+pub mod browser;
+pub mod checksum_list;
 pub mod console;
 pub mod file;
+pub mod hash_cache;
+pub mod hooks;
+pub mod i18n;
+pub mod journal;
+pub mod keys;
 pub mod logging;
+pub mod manifest_archive;
+pub mod notify;
+pub mod progress_log;
+pub mod prompt;
+pub mod quarantine;
+pub mod reuse;
+pub mod sanitize;
+pub mod session_lock;
+pub mod size_cache;
+pub mod url_log;
 pub mod util;
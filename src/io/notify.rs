@@ -0,0 +1,66 @@
+use log::debug;
+use notify_rust::Notification;
+
+use crate::download::pipeline::PipelineResult;
+
+const APP_NAME: &str = "Wuthering Waves Downloader";
+
+/// Shows a desktop notification. Failures (no notification daemon, headless
+/// sessions) are only logged at debug level since the console still has the result.
+pub fn notify(summary: &str, body: &str) {
+    let shown = Notification::new()
+        .appname(APP_NAME)
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(e) = shown {
+        debug!("Desktop notification failed: {}", e);
+    }
+}
+
+/// Title and body for the end of a download session.
+pub fn session_message(result: &PipelineResult, interrupted: bool) -> (&'static str, String) {
+    let succeeded = result.verified_ok + result.downloaded_ok;
+    let title = if interrupted {
+        "Download interrupted"
+    } else if result.failed > 0 {
+        "Download finished with errors"
+    } else {
+        "Download complete"
+    };
+    let body = format!(
+        "{} of {} files ready, {} failed",
+        succeeded, result.total, result.failed
+    );
+    (title, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::session_message;
+    use crate::download::pipeline::PipelineResult;
+
+    #[test]
+    fn session_message_reflects_outcome() {
+        let mut result = PipelineResult {
+            verified_ok: 3,
+            downloaded_ok: 5,
+            total: 8,
+            ..PipelineResult::default()
+        };
+        assert_eq!(
+            session_message(&result, false),
+            (
+                "Download complete",
+                "8 of 8 files ready, 0 failed".to_string()
+            )
+        );
+
+        result.failed = 1;
+        assert_eq!(
+            session_message(&result, false).0,
+            "Download finished with errors"
+        );
+        assert_eq!(session_message(&result, true).0, "Download interrupted");
+    }
+}
@@ -0,0 +1,67 @@
+use chrono::Local;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// `--progress-log`: a file that gets one progress line every `interval`, for
+/// unattended runs followed with `tail -f` instead of the progress bars.
+#[derive(Clone)]
+pub struct ProgressLog {
+    file: Arc<Mutex<File>>,
+    pub interval: Duration,
+}
+
+impl ProgressLog {
+    /// Opens `path` for appending, so earlier sessions stay above the new one.
+    pub fn open(path: &Path, interval: Duration) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+            interval,
+        })
+    }
+
+    /// Appends `message` with a timestamp. Write errors are dropped: a full
+    /// disk shows up in the download itself.
+    pub fn append(&self, message: &str) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{} {}", timestamp(), message);
+        }
+    }
+}
+
+fn timestamp() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProgressLog;
+    use crate::io::file::unique_path;
+    use std::time::Duration;
+
+    #[test]
+    fn appends_timestamped_lines() {
+        let path = unique_path("progress");
+        std::fs::write(&path, "earlier session\n").unwrap();
+
+        let log = ProgressLog::open(&path, Duration::from_secs(10)).unwrap();
+        log.append("Downloading: 1/2 files");
+        log.append("Finished: 2/2 files");
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], "earlier session");
+        assert!(
+            lines[1].ends_with(" Downloading: 1/2 files"),
+            "{}",
+            lines[1]
+        );
+        assert!(lines[2].ends_with(" Finished: 2/2 files"));
+        assert_eq!(lines.len(), 3);
+    }
+}
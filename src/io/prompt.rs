@@ -0,0 +1,103 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::io::{
+    console::clear_screen,
+    util::{read_answer, read_line},
+};
+
+/// Every question the setup asks (download mode, version, config, folder,
+/// languages, concurrency, disk space) goes through a `Prompter`, so the selection logic runs the same against
+/// the terminal, scripted answers in tests, or a GUI.
+pub trait Prompter: Send + Sync {
+    /// Shows a line that needs no answer: a menu entry, hint or error.
+    fn say(&self, line: &str);
+
+    /// Shows `question` and returns the answer. With `preset`, the
+    /// environment variable of that name may answer instead; the flag is set
+    /// for such answers, which must not be asked again when invalid.
+    fn ask(&self, question: &str, preset: Option<&str>) -> io::Result<(String, bool)>;
+
+    /// Clears the screen before a new menu.
+    fn clear(&self) {}
+}
+
+/// Reads answers from stdin, or from `WUWA_*` variables where a prompt has one.
+pub struct TerminalPrompter;
+
+impl Prompter for TerminalPrompter {
+    fn say(&self, line: &str) {
+        println!("{}", line);
+    }
+
+    fn ask(&self, question: &str, preset: Option<&str>) -> io::Result<(String, bool)> {
+        print!("{}", question);
+        io::stdout().flush()?;
+        match preset {
+            Some(var) => read_answer(var),
+            None => Ok((read_line()?, false)),
+        }
+    }
+
+    fn clear(&self) {
+        clear_screen();
+    }
+}
+
+/// Answers prompts from a fixed list and records everything shown, for
+/// tests and front-ends that collect the answers up front. Running out of
+/// answers is an `UnexpectedEof` error, like a closed stdin.
+#[derive(Default)]
+pub struct ScriptedPrompter {
+    answers: Mutex<VecDeque<String>>,
+    transcript: Mutex<Vec<String>>,
+}
+
+impl ScriptedPrompter {
+    pub fn new<I, S>(answers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            answers: Mutex::new(answers.into_iter().map(Into::into).collect()),
+            transcript: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Lines shown and questions asked so far, in order.
+    pub fn transcript(&self) -> Vec<String> {
+        self.transcript
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn record(&self, line: &str) {
+        self.transcript
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(line.to_string());
+    }
+}
+
+impl Prompter for ScriptedPrompter {
+    fn say(&self, line: &str) {
+        self.record(line);
+    }
+
+    fn ask(&self, question: &str, _preset: Option<&str>) -> io::Result<(String, bool)> {
+        self.record(question);
+        let answer = self
+            .answers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop_front();
+        answer
+            .map(|answer| (answer, false))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "No scripted answer left"))
+    }
+}
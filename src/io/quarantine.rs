@@ -0,0 +1,127 @@
+use chrono::Local;
+use serde_json::json;
+use std::{
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::io::file::{Checksum, dest_path, short_path};
+use crate::io::sanitize::unsanitize_component;
+
+pub const QUARANTINE_DIR: &str = "quarantine";
+const QUARANTINE_REPORT: &str = "report.jsonl";
+
+/// Where files that fail their checksum go. By default they are moved below
+/// `<folder>/quarantine/`, replacing an older copy of the same file, and every
+/// mismatch is appended to `quarantine/report.jsonl` with both digests.
+#[derive(Clone, Debug)]
+pub struct Quarantine {
+    folder: PathBuf,
+    delete: bool,
+}
+
+impl Quarantine {
+    /// `delete` removes corrupt files instead of keeping them; the report is
+    /// written either way.
+    pub fn new(folder: &Path, delete: bool) -> Self {
+        Self {
+            folder: folder.to_path_buf(),
+            delete,
+        }
+    }
+
+    /// Takes `path` out of the download folder after it hashed to `actual`
+    /// instead of `expected`. Returns where the file was kept, if anywhere.
+    pub async fn isolate(
+        &self,
+        path: &Path,
+        expected: &Checksum,
+        actual: &str,
+    ) -> io::Result<Option<PathBuf>> {
+        let dest = self.dest_of(path);
+        let kept = if self.delete {
+            tokio::fs::remove_file(path).await?;
+            None
+        } else {
+            let target = dest_path(&self.folder.join(QUARANTINE_DIR), &dest);
+            if let Some(parent) = target.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::rename(path, &target).await?;
+            Some(target)
+        };
+
+        let entry = json!({
+            "time": Local::now().to_rfc3339(),
+            "dest": dest,
+            "algorithm": expected.algorithm.manifest_key(),
+            "expected": expected.digest,
+            "actual": actual.to_ascii_lowercase(),
+            "quarantined": kept.as_ref().map(|path| path.display().to_string()),
+        });
+        self.append_report(&entry.to_string())?;
+        Ok(kept)
+    }
+
+    /// Index-style path of `path`, with the `.part` suffix of downloads dropped
+    /// and sanitized names turned back into the index's.
+    fn dest_of(&self, path: &Path) -> String {
+        let path = short_path(path);
+        let relative = path
+            .strip_prefix(&self.folder)
+            .unwrap_or(&path)
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => Some(unsanitize_component(&part.to_string_lossy())),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        relative
+            .strip_suffix(".part")
+            .unwrap_or(&relative)
+            .to_string()
+    }
+
+    fn append_report(&self, line: &str) -> io::Result<()> {
+        let dir = self.folder.join(QUARANTINE_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let mut report = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(QUARANTINE_REPORT))?;
+        writeln!(report, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{QUARANTINE_DIR, QUARANTINE_REPORT, Quarantine};
+    use crate::io::file::{Checksum, HashAlgorithm, unique_path};
+    use std::fs;
+
+    #[tokio::test]
+    async fn isolate_moves_file_and_records_both_digests() {
+        let dir = unique_path("quarantine");
+        fs::create_dir_all(dir.join("Paks")).unwrap();
+        let path = dir.join("Paks").join("main.pak.part");
+        fs::write(&path, b"abd").unwrap();
+        let expected = Checksum::new(HashAlgorithm::Md5, "900150983cd24fb0d6963f7d28e17f72");
+
+        let kept = Quarantine::new(&dir, false)
+            .isolate(&path, &expected, "ABCDEF")
+            .await
+            .unwrap();
+        let target = dir.join(QUARANTINE_DIR).join("Paks").join("main.pak");
+        assert_eq!(kept.as_deref(), Some(target.as_path()));
+        assert!(!path.exists());
+        assert_eq!(fs::read(&target).unwrap(), b"abd");
+
+        let report = fs::read_to_string(dir.join(QUARANTINE_DIR).join(QUARANTINE_REPORT)).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(report.trim()).unwrap();
+        assert_eq!(entry["dest"], "Paks/main.pak");
+        assert_eq!(entry["expected"], expected.digest);
+        assert_eq!(entry["actual"], "abcdef");
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::AtomicBool},
+};
+
+use crate::config::cfg::ResourceItem;
+use crate::io::file::{dest_path, hash_file_blocking};
+
+/// `--reuse-from`: an existing installation whose files are taken over
+/// instead of downloaded when they match the index.
+#[derive(Clone, Debug)]
+pub struct ReuseSource {
+    pub dir: PathBuf,
+    /// Hardlink instead of cloning or copying. Both installs then share one
+    /// file, so patching either changes the other.
+    pub hardlink: bool,
+}
+
+impl ReuseSource {
+    /// Puts the reference copy of `item` at `target` if it has the expected
+    /// size and checksum, and returns its digest. `None` means download it.
+    pub async fn place(
+        &self,
+        item: &ResourceItem,
+        target: &Path,
+        should_stop: &Arc<AtomicBool>,
+    ) -> Option<String> {
+        let checksum = item.checksum.clone()?;
+        let source = dest_path(&self.dir, &item.dest);
+        let metadata = tokio::fs::metadata(&source).await.ok()?;
+        if !metadata.is_file() || item.size.is_some_and(|size| size != metadata.len()) {
+            return None;
+        }
+
+        let should_stop = should_stop.clone();
+        let target = target.to_path_buf();
+        let hardlink = self.hardlink;
+        tokio::task::spawn_blocking(move || {
+            let digest = hash_file_blocking(&source, checksum.algorithm, should_stop).ok()?;
+            if !checksum.matches(&digest) {
+                return None;
+            }
+            place_file(&source, &target, hardlink).ok()?;
+            Some(digest)
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+}
+
+/// Hardlinks or clones `source` to `target`, copying when the filesystem can
+/// do neither, e.g. across drives.
+pub(crate) fn place_file(source: &Path, target: &Path, hardlink: bool) -> io::Result<()> {
+    match fs::remove_file(target) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let linked = if hardlink {
+        fs::hard_link(source, target)
+    } else {
+        reflink(source, target)
+    };
+    if linked.is_err() {
+        let _ = fs::remove_file(target);
+        fs::copy(source, target)?;
+    }
+    Ok(())
+}
+
+/// A copy-on-write clone, which costs no space on Btrfs, XFS and similar.
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let from = fs::File::open(source)?;
+    let to = fs::File::create(target)?;
+    let ret = unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_source: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReuseSource;
+    use crate::config::cfg::ResourceItem;
+    use crate::io::file::{Checksum, HashAlgorithm, unique_path};
+    use std::sync::{Arc, atomic::AtomicBool};
+
+    #[tokio::test]
+    async fn takes_over_only_matching_files() {
+        let root = unique_path("reuse");
+        std::fs::create_dir_all(root.join("old/Client")).unwrap();
+        std::fs::create_dir_all(root.join("new")).unwrap();
+        std::fs::write(root.join("old/Client/a.pak"), b"abc").unwrap();
+        let item = |digest: &str| ResourceItem {
+            dest: "Client/a.pak".to_string(),
+            checksum: Some(Checksum::new(HashAlgorithm::Md5, digest)),
+            size: Some(3),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        };
+        let stop = Arc::new(AtomicBool::new(false));
+        let target = root.join("new/a.pak.part");
+
+        for hardlink in [false, true] {
+            let reuse = ReuseSource {
+                dir: root.join("old"),
+                hardlink,
+            };
+            let digest = reuse
+                .place(&item("900150983cd24fb0d6963f7d28e17f72"), &target, &stop)
+                .await;
+            assert_eq!(digest.as_deref(), Some("900150983cd24fb0d6963f7d28e17f72"));
+            assert_eq!(std::fs::read(&target).unwrap(), b"abc");
+            assert!(reuse.place(&item("00"), &target, &stop).await.is_none());
+        }
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+
+/// Device names Windows reserves in every folder, with or without an extension.
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters no Windows file name may contain, plus `%`, which escapes them.
+const ESCAPED_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*', '%'];
+
+/// The non-empty components of a manifest `dest`, whichever separator it uses.
+pub fn dest_parts(dest: &str) -> impl Iterator<Item = &str> {
+    dest.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+}
+
+/// Makes one `dest` component creatable on every platform. Characters Windows
+/// rejects, the first letter of a reserved device name and trailing dots or
+/// spaces are percent-encoded; `%` is encoded too, so [`unsanitize_component`]
+/// restores the original exactly. Names that need none of this are borrowed.
+pub fn sanitize_component(part: &str) -> Cow<'_, str> {
+    let stem = part.split('.').next().unwrap_or(part);
+    let reserved = RESERVED_NAMES
+        .iter()
+        .any(|name| stem.trim_end().eq_ignore_ascii_case(name));
+    let kept_len = part.trim_end_matches(['.', ' ']).len();
+    let needs_escape = |index: usize, c: char| {
+        ESCAPED_CHARS.contains(&c)
+            || c.is_control()
+            || index >= kept_len
+            || (reserved && index == 0)
+    };
+    if !part.char_indices().any(|(index, c)| needs_escape(index, c)) {
+        return Cow::Borrowed(part);
+    }
+
+    let mut out = String::with_capacity(part.len() + 8);
+    for (index, c) in part.char_indices() {
+        if needs_escape(index, c) {
+            let mut bytes = [0_u8; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                out.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Reverses [`sanitize_component`].
+pub fn unsanitize_component(part: &str) -> String {
+    if !part.contains('%') {
+        return part.to_string();
+    }
+    let bytes = part.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| part.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                index += 3;
+            }
+            None => {
+                out.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// `dest` as stored below the download folder: `/`-separated, with every
+/// component sanitized.
+pub fn sanitize_dest(dest: &str) -> String {
+    dest_parts(dest)
+        .map(sanitize_component)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether [`sanitize_dest`] stores `dest` under a different name.
+pub fn is_renamed(dest: &str) -> bool {
+    dest_parts(dest).any(|part| matches!(sanitize_component(part), Cow::Owned(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_renamed, sanitize_component, sanitize_dest, unsanitize_component};
+
+    #[test]
+    fn escapes_names_windows_cannot_create() {
+        assert_eq!(sanitize_component("main.pak"), "main.pak");
+        assert_eq!(sanitize_component("CON"), "%43ON");
+        assert_eq!(sanitize_component("nul.txt"), "%6Eul.txt");
+        assert_eq!(sanitize_component("console.txt"), "console.txt");
+        assert_eq!(sanitize_component("notes. "), "notes%2E%20");
+        assert_eq!(sanitize_component("a<b>?.pak"), "a%3Cb%3E%3F.pak");
+        assert_eq!(sanitize_component("100%"), "100%25");
+        assert_eq!(sanitize_component("語音\u{7}.pak"), "語音%07.pak");
+        assert_eq!(
+            sanitize_dest("\\Client\\AUX/./voice?.wem"),
+            "Client/%41UX/voice%3F.wem"
+        );
+        assert!(is_renamed("Client/lpt1"));
+        assert!(!is_renamed("Client\\Paks\\main.pak"));
+    }
+
+    #[test]
+    fn unsanitize_restores_the_original() {
+        for name in [
+            "CON",
+            "nul.txt",
+            "notes. ",
+            "a<b>?.pak",
+            "100%",
+            "語音\u{7}.pak",
+            "plain",
+        ] {
+            assert_eq!(unsanitize_component(&sanitize_component(name)), name);
+        }
+    }
+}
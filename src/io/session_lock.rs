@@ -0,0 +1,251 @@
+use chrono::Local;
+use serde_json::{Value, json};
+use std::{
+    fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use crate::sys;
+
+pub const SESSION_LOCK: &str = ".session.lock";
+
+/// How often a running session touches its lock.
+const HEARTBEAT: Duration = Duration::from_secs(30);
+/// A lock untouched for this long belongs to a session that is gone, even
+/// when its PID has been reused or lives on another machine.
+const STALE_AFTER: Duration = Duration::from_secs(120);
+
+/// The session a lock file names.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub host: String,
+    pub started: String,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        Self {
+            pid: std::process::id(),
+            host: sys::hostname(),
+            started: Local::now().to_rfc3339(),
+        }
+    }
+
+    fn parse(text: &str) -> Self {
+        let data: Value = serde_json::from_str(text).unwrap_or_default();
+        Self {
+            pid: data
+                .get("pid")
+                .and_then(Value::as_u64)
+                .and_then(|pid| u32::try_from(pid).ok())
+                .unwrap_or(0),
+            host: data
+                .get("host")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            started: data
+                .get("started")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        json!({"pid": self.pid, "host": self.host, "started": self.started}).to_string() + "\n"
+    }
+
+    pub fn describe(&self) -> String {
+        if self.host.is_empty() {
+            format!("PID {}, started {}", self.pid, self.started)
+        } else {
+            format!(
+                "PID {} on {}, started {}",
+                self.pid, self.host, self.started
+            )
+        }
+    }
+}
+
+/// Whether the session that last touched a lock `age` ago has ended: its
+/// heartbeat stopped, or its process is gone from this machine. A lock still
+/// being written names no PID yet.
+fn is_stale(owner: &LockOwner, age: Duration, host: &str) -> bool {
+    age > STALE_AFTER || (owner.pid != 0 && owner.host == host && !sys::process_alive(owner.pid))
+}
+
+/// Keeps a second session out of a download folder, where two of them would
+/// append to the same `.part` files. The lock file is removed on drop; one
+/// left behind by a crash goes stale and can be taken over.
+pub struct SessionLock {
+    path: PathBuf,
+    owner: LockOwner,
+    stop: Option<Sender<()>>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+impl SessionLock {
+    /// Locks `folder` for this process. A stale lock is replaced when
+    /// `take_over` agrees; a live one is an error naming its session.
+    pub fn acquire(
+        folder: &Path,
+        take_over: impl FnOnce(&LockOwner) -> bool,
+    ) -> Result<Self, String> {
+        let path = folder.join(SESSION_LOCK);
+        let owner = LockOwner::current();
+        let mut take_over = Some(take_over);
+
+        loop {
+            match create(&path, &owner) {
+                Ok(()) => return Ok(Self::start(path, owner)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(format!("Failed to create {}: {}", path.display(), e)),
+            }
+
+            let Some((other, age)) = read(&path) else {
+                // Removed while we looked; try again.
+                continue;
+            };
+            if !is_stale(&other, age, &owner.host) {
+                return Err(format!(
+                    "Another session ({}) is downloading into {}; wait for it to finish or choose another folder",
+                    other.describe(),
+                    folder.display()
+                ));
+            }
+            // A second stale lock means another session took over between
+            // our removal and create.
+            let Some(take_over) = take_over.take() else {
+                return Err(format!(
+                    "Another session took over {} first",
+                    folder.display()
+                ));
+            };
+            if !take_over(&other) {
+                return Err(format!(
+                    "Left the stale lock of {} in place",
+                    other.describe()
+                ));
+            }
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to remove {}: {}", path.display(), e)),
+            }
+        }
+    }
+
+    fn start(path: PathBuf, owner: LockOwner) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let touched = path.clone();
+        let heartbeat = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT) {
+                let _ = fs::File::options()
+                    .write(true)
+                    .open(&touched)
+                    .and_then(|file| file.set_modified(SystemTime::now()));
+            }
+        });
+        Self {
+            path,
+            owner,
+            stop: Some(stop),
+            heartbeat: Some(heartbeat),
+        }
+    }
+}
+
+impl Drop for SessionLock {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(heartbeat) = self.heartbeat.take() {
+            let _ = heartbeat.join();
+        }
+        // Leave the lock alone if another session took it over.
+        if read(&self.path).is_some_and(|(owner, _)| owner == self.owner) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn create(path: &Path, owner: &LockOwner) -> io::Result<()> {
+    let mut file = fs::File::options()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    file.write_all(owner.to_json().as_bytes())
+}
+
+/// The owner of the lock at `path` and how long ago it was last touched.
+fn read(path: &Path) -> Option<(LockOwner, Duration)> {
+    let text = fs::read_to_string(path).ok()?;
+    let age = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .unwrap_or_default();
+    Some((LockOwner::parse(&text), age))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LockOwner, SESSION_LOCK, SessionLock, is_stale};
+    use crate::io::file::unique_path;
+    use crate::sys;
+    use std::fs;
+    use std::time::Duration;
+
+    #[test]
+    fn keeps_a_second_session_out_until_the_lock_goes_stale() {
+        let dir = unique_path("lock");
+        fs::create_dir_all(&dir).unwrap();
+
+        let lock = SessionLock::acquire(&dir, |_| true).unwrap();
+        let error = SessionLock::acquire(&dir, |_| true).err().unwrap();
+        assert!(error.contains(&format!("PID {}", std::process::id())));
+        drop(lock);
+        assert!(!dir.join(SESSION_LOCK).exists());
+
+        // A crashed session on this machine: its PID is gone.
+        let mut child = std::process::Command::new("cargo")
+            .arg("--version")
+            .stdout(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let dead = child.id();
+        child.wait().unwrap();
+        let crashed = LockOwner {
+            pid: dead,
+            host: sys::hostname(),
+            started: "2026-01-01T00:00:00+00:00".to_string(),
+        };
+        fs::write(dir.join(SESSION_LOCK), crashed.to_json()).unwrap();
+        assert!(SessionLock::acquire(&dir, |_| false).is_err());
+        let mut offered = None;
+        let lock = SessionLock::acquire(&dir, |owner| {
+            offered = Some(owner.clone());
+            true
+        })
+        .unwrap();
+        assert_eq!(offered, Some(crashed));
+        drop(lock);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn a_silent_lock_is_stale_wherever_it_was_taken() {
+        let owner = LockOwner {
+            pid: std::process::id(),
+            host: "elsewhere".to_string(),
+            started: String::new(),
+        };
+        assert!(!is_stale(&owner, Duration::from_secs(5), "here"));
+        assert!(is_stale(&owner, Duration::from_secs(600), "here"));
+    }
+}
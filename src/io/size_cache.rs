@@ -0,0 +1,100 @@
+use serde_json::{Map, Value, json};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const SIZE_CACHE: &str = ".size-cache.json";
+
+/// Sizes the CDN reported for files the index left unsized, so a resumed or
+/// retried session does not HEAD them again. Entries only count for the
+/// index they were probed under.
+pub struct SizeCache {
+    path: PathBuf,
+    index_url: String,
+    sizes: HashMap<String, u64>,
+    dirty: bool,
+}
+
+impl SizeCache {
+    /// Loads the folder's cache; one from another index, or none, starts empty.
+    pub fn load(folder: &Path, index_url: &str) -> Self {
+        let data = fs::read_to_string(folder.join(SIZE_CACHE))
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+            .unwrap_or_default();
+        let sizes = if data.get("index_url").and_then(Value::as_str) == Some(index_url) {
+            data.get("files")
+                .and_then(Value::as_object)
+                .map(|files| {
+                    files
+                        .iter()
+                        .filter_map(|(dest, size)| Some((dest.clone(), size.as_u64()?)))
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+        Self {
+            path: folder.join(SIZE_CACHE),
+            index_url: index_url.to_string(),
+            sizes,
+            dirty: false,
+        }
+    }
+
+    pub fn get(&self, dest: &str) -> Option<u64> {
+        self.sizes.get(dest).copied()
+    }
+
+    pub fn insert(&mut self, dest: &str, size: u64) {
+        if self.sizes.insert(dest.to_string(), size) != Some(size) {
+            self.dirty = true;
+        }
+    }
+
+    /// Writes the cache back if a size was probed this session.
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let files: Map<String, Value> = self
+            .sizes
+            .iter()
+            .map(|(dest, size)| (dest.clone(), json!(size)))
+            .collect();
+        let contents =
+            serde_json::to_string(&json!({ "index_url": self.index_url, "files": files }))
+                .map_err(io::Error::other)?;
+        fs::write(&self.path, contents)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SIZE_CACHE, SizeCache};
+    use crate::io::file::unique_path;
+    use std::fs;
+
+    #[test]
+    fn keeps_sizes_for_the_index_they_were_probed_under() {
+        let dir = unique_path("sizes");
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut cache = SizeCache::load(&dir, "https://example.com/1.0/index.json");
+        assert_eq!(cache.get("Client/a.pak"), None);
+        cache.insert("Client/a.pak", 1234);
+        cache.save().unwrap();
+        assert!(dir.join(SIZE_CACHE).exists());
+
+        let cache = SizeCache::load(&dir, "https://example.com/1.0/index.json");
+        assert_eq!(cache.get("Client/a.pak"), Some(1234));
+        let cache = SizeCache::load(&dir, "https://example.com/1.1/index.json");
+        assert_eq!(cache.get("Client/a.pak"), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
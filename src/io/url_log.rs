@@ -0,0 +1,107 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+pub const URL_LOG: &str = "urls.txt";
+const HEADER: &str = "url\tdest\tmd5";
+
+/// `--log-urls`: every URL a file was requested from, once, as
+/// `url<TAB>dest<TAB>md5` lines in `urls.txt` next to the log.
+#[derive(Clone, Default)]
+pub struct UrlLog {
+    inner: Option<Arc<Mutex<Shared>>>,
+}
+
+struct Shared {
+    file: File,
+    seen: HashSet<String>,
+}
+
+impl UrlLog {
+    /// A log that records nothing.
+    pub fn disabled() -> Self {
+        Self { inner: None }
+    }
+
+    /// Opens `urls.txt` in `dir` for appending; URLs it already lists are
+    /// not written again.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(URL_LOG);
+        let existing = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let seen: HashSet<String> = existing
+            .lines()
+            .filter(|line| *line != HEADER)
+            .filter_map(|line| line.split('\t').next())
+            .map(str::to_string)
+            .collect();
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if existing.is_empty() {
+            writeln!(file, "{}", HEADER)?;
+        }
+        Ok(Self {
+            inner: Some(Arc::new(Mutex::new(Shared { file, seen }))),
+        })
+    }
+
+    /// Adds `url` unless it is listed already. Write errors are dropped, as
+    /// the log is only a record.
+    pub fn record(&self, url: &str, dest: &str, md5: &str) {
+        let Some(shared) = &self.inner else {
+            return;
+        };
+        let mut shared = shared.lock().unwrap_or_else(PoisonError::into_inner);
+        if shared.seen.insert(url.to_string()) {
+            let _ = writeln!(shared.file, "{}\t{}\t{}", url, dest, md5);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{URL_LOG, UrlLog};
+    use crate::io::file::unique_path;
+
+    #[test]
+    fn lists_each_url_once_across_sessions() {
+        let dir = unique_path("urls");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let log = UrlLog::open(&dir).unwrap();
+        log.record(
+            "https://cdn-a/zip/a.pak",
+            "a.pak",
+            "900150983cd24fb0d6963f7d28e17f72",
+        );
+        log.record(
+            "https://cdn-a/zip/a.pak",
+            "a.pak",
+            "900150983cd24fb0d6963f7d28e17f72",
+        );
+        drop(log);
+        let log = UrlLog::open(&dir).unwrap();
+        log.record(
+            "https://cdn-a/zip/a.pak",
+            "a.pak",
+            "900150983cd24fb0d6963f7d28e17f72",
+        );
+        log.record("https://cdn-b/zip/b.pak", "b.pak", "");
+
+        let contents = std::fs::read_to_string(dir.join(URL_LOG)).unwrap();
+        assert_eq!(
+            contents,
+            "url\tdest\tmd5\n\
+             https://cdn-a/zip/a.pak\ta.pak\t900150983cd24fb0d6963f7d28e17f72\n\
+             https://cdn-b/zip/b.pak\tb.pak\t\n"
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
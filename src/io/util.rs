@@ -1,7 +1,10 @@
-use serde_json::Value;
+use indicatif::HumanBytes;
+use log::warn;
+use serde_json::{Map, Value, json};
 use std::{
+    collections::HashSet,
     io,
-    io::Write,
+    path::Path,
     sync::Arc,
     sync::atomic::AtomicBool,
     sync::atomic::{AtomicUsize, Ordering},
@@ -9,45 +12,262 @@ use std::{
 
 use crate::{
     config::{
-        cfg::{DownloadOptions, ResourceItem},
+        cfg::{DownloadOptions, FileChunk, ResourceItem},
+        outcome::Outcome,
         status::Status,
     },
-    io::logging::{SharedLogFile, log_error},
+    download::selection::{AudioGroup, language_label},
+    io::{
+        file::{Checksum, HashAlgorithm, available_space},
+        i18n::Msg,
+        logging::{SharedLogFile, log_error, log_warn},
+        prompt::Prompter,
+    },
 };
 
-pub fn parse_resources(data: &Value) -> Result<Vec<ResourceItem>, String> {
-    let resources = data
-        .get("resource")
-        .and_then(Value::as_array)
+/// `(category, region)` pairs a version list must provide.
+pub const VERSION_CHANNELS: [(&str, &str); 4] = [
+    ("live", "os"),
+    ("live", "cn"),
+    ("beta", "os"),
+    ("beta", "cn"),
+];
+
+/// Manifest key of the main resource list; other lists are extra groups.
+pub const MAIN_GROUP: &str = "resource";
+
+/// One resource list of an index, named after its manifest key.
+pub struct ResourceGroup {
+    pub name: String,
+    pub resources: Vec<ResourceItem>,
+}
+
+/// Every top-level array of `dest` entries in the index, `resource` first.
+/// Launcher indices keep language packs and patch files in lists of their own.
+pub fn parse_resource_groups(data: &Value) -> Result<Vec<ResourceGroup>, String> {
+    let object = data
+        .as_object()
         .ok_or_else(|| "No resources found in index file".to_string())?;
 
-    let mut parsed = Vec::with_capacity(resources.len());
-    for item in resources {
-        if let Some(dest) = item.get("dest").and_then(Value::as_str) {
-            parsed.push(ResourceItem {
-                dest: dest.to_string(),
-                md5: item
-                    .get("md5")
-                    .and_then(Value::as_str)
-                    .map(|md5| md5.to_string()),
-                size: item.get("size").and_then(Value::as_u64),
-            });
+    let mut groups: Vec<ResourceGroup> = object
+        .iter()
+        .filter_map(|(name, value)| {
+            let entries = value.as_array()?;
+            let resources: Vec<ResourceItem> = entries.iter().filter_map(parse_resource).collect();
+            (!resources.is_empty()).then(|| ResourceGroup {
+                name: name.clone(),
+                resources,
+            })
+        })
+        .collect();
+    if groups.is_empty() {
+        return Err("No resources found in index file".to_string());
+    }
+    groups.sort_by_key(|group| group.name != MAIN_GROUP);
+    Ok(groups)
+}
+
+/// One top-level key of an index, as `--list-groups` reports it.
+pub struct IndexKey {
+    pub name: String,
+    /// JSON type of the value: array, object, string, number, bool or null.
+    pub kind: &'static str,
+    /// Array entries or object keys; 0 for other values.
+    pub entries: usize,
+    /// Entries [`parse_resource_groups`] reads as files (those with a `dest`).
+    pub files: usize,
+}
+
+/// Every top-level key of the index by name, with what the parser
+/// makes of it, to explain file counts that differ from other tools.
+pub fn index_keys(data: &Value) -> Vec<IndexKey> {
+    let Some(object) = data.as_object() else {
+        return Vec::new();
+    };
+    object
+        .iter()
+        .map(|(name, value)| {
+            let (kind, entries, files) = match value {
+                Value::Array(entries) => (
+                    "array",
+                    entries.len(),
+                    entries.iter().filter_map(parse_resource).count(),
+                ),
+                Value::Object(keys) => ("object", keys.len(), 0),
+                Value::String(_) => ("string", 0, 0),
+                Value::Number(_) => ("number", 0, 0),
+                Value::Bool(_) => ("bool", 0, 0),
+                Value::Null => ("null", 0, 0),
+            };
+            IndexKey {
+                name: name.clone(),
+                kind,
+                entries,
+                files,
+            }
+        })
+        .collect()
+}
+
+/// Resources of all groups; a file listed by several groups is kept once.
+pub fn merge_groups(groups: Vec<ResourceGroup>) -> Vec<ResourceItem> {
+    let mut seen = HashSet::new();
+    groups
+        .into_iter()
+        .flat_map(|group| group.resources)
+        .filter(|item| seen.insert(item.dest.clone()))
+        .collect()
+}
+
+pub fn parse_resources(data: &Value) -> Result<Vec<ResourceItem>, String> {
+    parse_resource_groups(data).map(merge_groups)
+}
+
+fn parse_resource(item: &Value) -> Option<ResourceItem> {
+    let dest = item.get("dest").and_then(Value::as_str)?;
+    Some(ResourceItem {
+        dest: dest.to_string(),
+        checksum: parse_checksum(item),
+        size: item.get("size").and_then(Value::as_u64),
+        chunks: parse_chunks(item),
+        unpacked_size: item.get("uncompressedSize").and_then(Value::as_u64),
+    })
+}
+
+fn parse_checksum(item: &Value) -> Option<Checksum> {
+    HashAlgorithm::PREFERENCE.iter().find_map(|algorithm| {
+        item.get(algorithm.manifest_key())
+            .and_then(Value::as_str)
+            .filter(|digest| !digest.trim().is_empty())
+            .map(|digest| Checksum::new(*algorithm, digest))
+    })
+}
+
+/// Reads `chunkInfos`. A list with any unusable entry is dropped as a whole,
+/// so the file falls back to being downloaded in one piece.
+fn parse_chunks(item: &Value) -> Vec<FileChunk> {
+    let Some(infos) = item.get("chunkInfos").and_then(Value::as_array) else {
+        return Vec::new();
+    };
+    infos
+        .iter()
+        .map(|info| {
+            let start = info.get("start")?.as_u64()?;
+            let end = info.get("end")?.as_u64()?;
+            (end >= start).then_some(())?;
+            Some(FileChunk {
+                start,
+                end,
+                checksum: parse_checksum(info)?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+/// Inverse of [`parse_resources`] for a single entry.
+pub fn resource_to_json(item: &ResourceItem) -> Map<String, Value> {
+    let mut entry = Map::new();
+    entry.insert("dest".to_string(), json!(item.dest));
+    if let Some(size) = item.size {
+        entry.insert("size".to_string(), json!(size));
+    }
+    if let Some(checksum) = &item.checksum {
+        entry.insert(
+            checksum.algorithm.manifest_key().to_string(),
+            json!(checksum.digest),
+        );
+    }
+    if let Some(unpacked_size) = item.unpacked_size {
+        entry.insert("uncompressedSize".to_string(), json!(unpacked_size));
+    }
+    if !item.chunks.is_empty() {
+        let chunks: Vec<Value> = item
+            .chunks
+            .iter()
+            .map(|chunk| {
+                json!({
+                    "start": chunk.start,
+                    "end": chunk.end,
+                    chunk.checksum.algorithm.manifest_key(): chunk.checksum.digest,
+                })
+            })
+            .collect();
+        entry.insert("chunkInfos".to_string(), json!(chunks));
+    }
+    entry
+}
+
+pub fn ask_audio_languages(
+    prompter: &dyn Prompter,
+    groups: &[AudioGroup],
+) -> Result<Vec<String>, String> {
+    let all: Vec<String> = groups.iter().map(|group| group.language.clone()).collect();
+
+    prompter.say(&format!(
+        "\n{} {}",
+        Status::info(),
+        Msg::VoiceLanguagesFound.text()
+    ));
+    for (i, group) in groups.iter().enumerate() {
+        prompter.say(&Msg::VoiceLanguageEntry.fill(&[
+            &(i + 1),
+            &language_label(&group.language),
+            &group.files,
+            &HumanBytes(group.bytes),
+        ]));
+    }
+    let (input, preset) = prompter
+        .ask(
+            &format!("{} {}", Status::question(), Msg::SelectLanguages.text()),
+            Some(AUDIO_LANG_ENV),
+        )
+        .map_err(read_error)?;
+    let trimmed = input.trim();
+    if trimmed.is_empty() || trimmed.eq_ignore_ascii_case("all") {
+        return Ok(all);
+    }
+
+    let mut selected = Vec::new();
+    for choice in trimmed.split(',') {
+        match choice.trim().parse::<usize>() {
+            Ok(index) if (1..=groups.len()).contains(&index) => {
+                selected.push(groups[index - 1].language.clone());
+            }
+            _ if preset => return Err(invalid_preset(AUDIO_LANG_ENV, &input)),
+            _ => {
+                prompter.say(&format!(
+                    "{} {}",
+                    Status::warning(),
+                    Msg::InvalidLanguage.fill(&[&choice.trim()])
+                ));
+                return Ok(all);
+            }
         }
     }
 
-    Ok(parsed)
+    Ok(selected)
 }
 
-pub fn ask_concurrency() -> Result<DownloadOptions, io::Error> {
+pub fn ask_concurrency(prompter: &dyn Prompter) -> Result<DownloadOptions, String> {
     let defaults = DownloadOptions::default();
-    let download_concurrency =
-        prompt_concurrency("concurrent downloads", defaults.download_concurrency)?;
-    let verify_concurrency =
-        prompt_concurrency("concurrent verifications", defaults.verify_concurrency)?;
+    let download_concurrency = prompt_concurrency(
+        prompter,
+        Msg::EnterDownloadConcurrency,
+        defaults.download_concurrency,
+        CONCURRENCY_ENV,
+    )?;
+    let verify_concurrency = prompt_concurrency(
+        prompter,
+        Msg::EnterVerifyConcurrency,
+        defaults.verify_concurrency,
+        VERIFY_JOBS_ENV,
+    )?;
 
     Ok(DownloadOptions {
         download_concurrency,
         verify_concurrency,
+        ..defaults
     })
 }
 
@@ -63,16 +283,18 @@ fn clamp_worker_count(value: usize, default_value: usize) -> usize {
     value.min(worker_count_limit(default_value))
 }
 
-fn prompt_concurrency(label: &str, default_value: usize) -> Result<usize, io::Error> {
-    print!(
-        "{} Enter {} [default {}]: ",
-        Status::question(),
-        label,
-        default_value
-    );
-    io::stdout().flush().unwrap();
-
-    let input = read_line()?;
+fn prompt_concurrency(
+    prompter: &dyn Prompter,
+    prompt: Msg,
+    default_value: usize,
+    var: &str,
+) -> Result<usize, String> {
+    let (input, preset) = prompter
+        .ask(
+            &format!("{} {}", Status::question(), prompt.fill(&[&default_value])),
+            Some(var),
+        )
+        .map_err(read_error)?;
     let trimmed = input.trim();
     if trimmed.is_empty() {
         return Ok(default_value);
@@ -83,33 +305,120 @@ fn prompt_concurrency(label: &str, default_value: usize) -> Result<usize, io::Er
     {
         let limit = worker_count_limit(default_value);
         if parsed > limit {
-            println!(
-                "{} Value too large, clamping {} to {}",
+            prompter.say(&format!(
+                "{} {}",
                 Status::warning(),
-                label,
-                limit
-            );
+                Msg::ConcurrencyClamped.fill(&[&limit])
+            ));
             return Ok(clamp_worker_count(parsed, default_value));
         }
 
         return Ok(parsed);
     }
 
-    println!(
-        "{} Invalid value, using default {} for {}",
+    if preset {
+        return Err(invalid_preset(var, &input));
+    }
+    prompter.say(&format!(
+        "{} {}",
         Status::warning(),
-        default_value,
-        label
-    );
+        Msg::ConcurrencyInvalid.fill(&[&default_value])
+    ));
     Ok(default_value)
 }
 
+/// Warns and asks for confirmation when `required` plus `margin` bytes would not
+/// fit on the drive holding `folder`. Returns whether the download should start;
+/// without a `prompter` a shortfall is never confirmed.
+pub fn check_disk_space(
+    prompter: Option<&dyn Prompter>,
+    folder: &Path,
+    required: u64,
+    margin: u64,
+) -> Result<bool, String> {
+    let available = match available_space(folder) {
+        Ok(available) => available,
+        Err(err) => {
+            warn!("{}", Msg::SpaceUnknown.fill(&[&err]));
+            return Ok(true);
+        }
+    };
+
+    if available >= required.saturating_add(margin) {
+        return Ok(true);
+    }
+
+    warn!(
+        "{}",
+        Msg::NotEnoughSpace.fill(&[
+            &HumanBytes(required),
+            &HumanBytes(margin),
+            &HumanBytes(available),
+        ])
+    );
+    let Some(prompter) = prompter else {
+        return Ok(false);
+    };
+    let (input, preset) = prompter
+        .ask(
+            &format!("{} {}", Status::question(), Msg::ContinueAnyway.text()),
+            Some(CONFIRM_ENV),
+        )
+        .map_err(read_error)?;
+    match input.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" => Ok(true),
+        "" | "n" | "no" => Ok(false),
+        _ if preset => Err(invalid_preset(CONFIRM_ENV, &input)),
+        _ => Ok(false),
+    }
+}
+
 pub fn read_line() -> Result<String, io::Error> {
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
     Ok(input)
 }
 
+/// Answers the download mode prompt (`latest`/`custom` or `1`/`2`).
+pub const MODE_ENV: &str = "WUWA_MODE";
+/// Answers the version prompt (`live-os`, `live-cn`, `beta-os`, `beta-cn` or `1`-`4`).
+pub const VERSION_ENV: &str = "WUWA_VERSION";
+/// Answers the config prompt (`default`/`predownload` or `1`/`2`).
+pub const CONFIG_ENV: &str = "WUWA_CONFIG";
+/// Answers yes/no questions such as creating a missing folder.
+pub const CONFIRM_ENV: &str = "WUWA_CONFIRM";
+/// Answers the voice-over language prompt (`all` or numbers such as `1,3`).
+pub const AUDIO_LANG_ENV: &str = "WUWA_AUDIO_LANG";
+/// Answers the concurrent downloads prompt.
+pub const CONCURRENCY_ENV: &str = "WUWA_CONCURRENCY";
+/// Answers the concurrent verifications prompt.
+pub const VERIFY_JOBS_ENV: &str = "WUWA_VERIFY_JOBS";
+
+/// Reads the answer to the prompt just printed: the `var` environment
+/// variable when it is set, echoed so the transcript reads the same, or
+/// else a line from stdin. The flag is set for preset answers, which the
+/// caller must not ask again for when they are invalid.
+pub fn read_answer(var: &str) -> Result<(String, bool), io::Error> {
+    if let Some(answer) = std::env::var(var)
+        .ok()
+        .map(|answer| answer.trim().to_string())
+        .filter(|answer| !answer.is_empty())
+    {
+        println!("{}", answer);
+        return Ok((answer, true));
+    }
+    Ok((read_line()?, false))
+}
+
+pub fn read_error(e: io::Error) -> String {
+    format!("Failed to read input: {}", e)
+}
+
+/// The error for a preset answer a prompt does not accept.
+pub fn invalid_preset(var: &str, answer: &str) -> String {
+    format!("{}=\"{}\" is not a valid answer", var, answer.trim())
+}
+
 pub fn read_line_interruptible(should_stop: &AtomicBool) -> Result<String, io::Error> {
     if should_stop.load(Ordering::SeqCst) {
         return Err(io::Error::new(
@@ -142,6 +451,23 @@ pub fn read_line_interruptible(should_stop: &AtomicBool) -> Result<String, io::E
     }
 }
 
+/// Checks that a version list has a URL for every channel the menu offers.
+pub fn validate_version_list(data: &Value) -> Result<(), String> {
+    let missing: Vec<String> = VERSION_CHANNELS
+        .iter()
+        .filter(|(category, version)| data[category][version].as_str().is_none())
+        .map(|(category, version)| format!("{}.{}", category, version))
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "missing URLs for {} (expected {{\"live\": {{\"os\": \"<url>\", \"cn\": \"<url>\"}}, \"beta\": {{...}}}})",
+        missing.join(", ")
+    ))
+}
+
 pub fn get_version(data: &Value, category: &str, version: &str) -> Result<String, String> {
     data[category][version]
         .as_str()
@@ -149,36 +475,93 @@ pub fn get_version(data: &Value, category: &str, version: &str) -> Result<String
         .ok_or_else(|| format!("Missing {} URL", version))
 }
 
-pub fn exit_with_error(log_file: &SharedLogFile, error: &str) -> ! {
+/// Logs `error` and exits; `interactive` keeps the console open until Enter.
+pub fn exit_with_error(log_file: &SharedLogFile, error: &str, interactive: bool) -> ! {
     log_error(log_file, error);
+    if !interactive {
+        Outcome::Error.exit();
+    }
 
     #[cfg(windows)]
-    clear().unwrap();
+    let _ = clear();
 
     println!("{} {}", Status::error(), error);
-    println!("\n{} Press Enter to exit...", Status::warning());
+    println!("\n{} {}", Status::warning(), Msg::PressEnterToExit.text());
     let _ = io::stdin().read_line(&mut String::new());
-    std::process::exit(1);
+    Outcome::Error.exit();
 }
 
-pub fn setup_ctrlc(should_stop: Arc<std::sync::atomic::AtomicBool>) {
+/// Installs the Ctrl-C handler that sets `should_stop`. Without one (no
+/// console, handler already taken) Ctrl-C kills the process as usual.
+pub fn setup_ctrlc(should_stop: Arc<std::sync::atomic::AtomicBool>, log_file: &SharedLogFile) {
     let interrupt_count = Arc::new(AtomicUsize::new(0));
 
-    ctrlc::set_handler(move || {
+    let installed = ctrlc::set_handler(move || {
         let count = interrupt_count.fetch_add(1, Ordering::SeqCst) + 1;
         should_stop.store(true, Ordering::SeqCst);
 
-        if count >= 2 {
+        if count == 1 {
+            warn!("{}", Msg::StoppingForceQuit.text());
+        } else {
+            let _ = crossterm::terminal::disable_raw_mode();
             eprintln!("\n{} Force exiting after second Ctrl-C", Status::warning());
-            std::process::exit(130);
+            Outcome::Interrupted.exit();
         }
-    })
-    .unwrap();
+    });
+    if let Err(e) = installed {
+        log_warn(
+            log_file,
+            &format!(
+                "Could not install the Ctrl-C handler, interrupting will not stop cleanly: {}",
+                e
+            ),
+        );
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{clamp_worker_count, worker_count_limit};
+    use super::{
+        ask_audio_languages, ask_concurrency, clamp_worker_count, index_keys,
+        parse_resource_groups, parse_resources, resource_to_json, validate_version_list,
+        worker_count_limit,
+    };
+    use crate::config::cfg::DownloadOptions;
+    use crate::download::selection::AudioGroup;
+    use crate::io::{i18n::Msg, prompt::ScriptedPrompter};
+    use serde_json::json;
+
+    #[test]
+    fn setup_prompts_take_scripted_answers() {
+        let groups: Vec<AudioGroup> = ["en", "ja", "zh"]
+            .into_iter()
+            .map(|language| AudioGroup {
+                language: language.to_string(),
+                files: 1,
+                bytes: 1,
+            })
+            .collect();
+        let prompter = ScriptedPrompter::new(["3,1", "x"]);
+        assert_eq!(
+            ask_audio_languages(&prompter, &groups).unwrap(),
+            ["zh", "en"]
+        );
+        assert_eq!(ask_audio_languages(&prompter, &groups).unwrap().len(), 3);
+        let invalid = Msg::InvalidLanguage.fill(&[&"x"]);
+        assert!(
+            prompter
+                .transcript()
+                .iter()
+                .any(|line| line.ends_with(&invalid))
+        );
+
+        let defaults = DownloadOptions::default();
+        let prompter = ScriptedPrompter::new(["2", "0"]);
+        let options = ask_concurrency(&prompter).unwrap();
+        assert_eq!(options.download_concurrency, 2);
+        assert_eq!(options.verify_concurrency, defaults.verify_concurrency);
+        assert!(ask_concurrency(&prompter).is_err(), "no answers left");
+    }
 
     #[test]
     fn clamp_worker_count_limits_large_values() {
@@ -192,4 +575,65 @@ mod tests {
     fn worker_count_limit_never_drops_below_default() {
         assert!(worker_count_limit(8) >= 8);
     }
+
+    #[test]
+    fn validate_version_list_names_missing_channels() {
+        let mut data = json!({
+            "live": {"os": "https://a", "cn": "https://b"},
+            "beta": {"os": "https://c", "cn": "https://d"},
+        });
+        assert!(validate_version_list(&data).is_ok());
+
+        data["beta"]["cn"] = json!(null);
+        data["live"] = json!("https://a");
+        let err = validate_version_list(&data).unwrap_err();
+        assert!(err.starts_with("missing URLs for live.os, live.cn, beta.cn"));
+    }
+
+    #[test]
+    fn parse_resource_groups_finds_every_list_main_first() {
+        let index = json!({
+            "patchResource": [{"dest": "Paks/patch.pak"}, {"dest": "Paks/main.pak"}],
+            "resource": [{"dest": "Paks/main.pak", "size": 10}],
+            "zlanguage": [{"dest": "Paks/en.zip", "size": 4, "uncompressedSize": 9}],
+            "cdnList": [{"url": "https://cdn"}],
+            "version": "2.0.0",
+        });
+        let groups = parse_resource_groups(&index).unwrap();
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(names, ["resource", "patchResource", "zlanguage"]);
+        assert_eq!(groups[2].resources[0].unpacked_size, Some(9));
+        assert_eq!(
+            resource_to_json(&groups[2].resources[0])["uncompressedSize"],
+            json!(9)
+        );
+
+        let merged = parse_resources(&index).unwrap();
+        let dests: Vec<&str> = merged.iter().map(|item| item.dest.as_str()).collect();
+        assert_eq!(dests, ["Paks/main.pak", "Paks/patch.pak", "Paks/en.zip"]);
+        assert_eq!(merged[0].size, Some(10));
+
+        assert!(parse_resources(&json!({ "resource": [] })).is_err());
+    }
+
+    #[test]
+    fn index_keys_count_entries_the_parser_skips() {
+        let index = json!({
+            "resource": [{"dest": "a.pak"}, {"path": "b.pak"}],
+            "sampleHashInfo": {"a": 1, "b": 2},
+            "version": "2.0.0",
+        });
+        let keys: Vec<_> = index_keys(&index)
+            .into_iter()
+            .map(|key| (key.name, key.kind, key.entries, key.files))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("resource".to_string(), "array", 2, 1),
+                ("sampleHashInfo".to_string(), "object", 2, 0),
+                ("version".to_string(), "string", 0, 0),
+            ]
+        );
+    }
 }
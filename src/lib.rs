@@ -1,4 +1,7 @@
+pub mod archive;
+pub mod commands;
 pub mod config;
 pub mod download;
 pub mod io;
 pub mod network;
+pub mod sys;
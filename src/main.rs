@@ -1,12 +1,9 @@
-use colored::*;
-use reqwest::Client;
-
-#[cfg(not(target_os = "windows"))]
-use std::process::Command;
-use std::sync::atomic::Ordering;
+use clap::Parser;
+use log::{error, warn};
+use std::process::ExitCode;
 
 #[cfg(windows)]
-use winconsole::console::{clear, set_title};
+use winconsole::console::set_title;
 
 #[cfg(windows)]
 fn enable_ansi_support() {
@@ -34,105 +31,132 @@ fn enable_ansi_support() {
 }
 
 use wuwa_downloader::{
-    config::status::Status,
-    download::pipeline::run_pipeline,
+    commands::{
+        Context, check_cdn, clean, diff, download, export, info, repair, self_update, serve,
+        speedtest, verify,
+    },
+    config::{
+        cli::{Cli, Commands},
+        outcome::Outcome,
+        style,
+    },
     io::{
-        console::print_results,
-        file::get_dir,
-        logging::setup_logging,
-        util::{ask_concurrency, exit_with_error, parse_resources, setup_ctrlc},
+        console::clear_screen,
+        i18n::set_lang,
+        logging::{setup_console_logging, setup_logging},
     },
-    network::client::{fetch_index, get_config},
+    network::{http::build_client, pacing},
+    sys,
 };
 
-#[tokio::main]
-async fn main() {
-    #[cfg(windows)]
-    clear().unwrap();
-    #[cfg(not(target_os = "windows"))]
-    Command::new("clear").status().unwrap();
+fn main() -> ExitCode {
+    // Usage errors are configuration errors; clap's own code 2 means partial failure here.
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        if e.use_stderr() {
+            let _ = e.print();
+            Outcome::Error.exit();
+        }
+        e.exit()
+    });
+    // Before the runtime starts, so its threads inherit the lower priority.
+    let mut warnings = Vec::new();
+    if cli.background
+        && let Err(e) = sys::enter_background()
+    {
+        warnings.push(e);
+    }
+    // Held on this thread until the run ends.
+    let keep_awake = cli
+        .keep_awake
+        .then(sys::keep_awake)
+        .and_then(|keep_awake| keep_awake.map_err(|e| warnings.push(e)).ok());
+    let runtime = match tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start the async runtime: {}", e);
+            return Outcome::Error.into();
+        }
+    };
+    let code = runtime.block_on(run(cli, warnings));
+    drop(keep_awake);
+    code
+}
+
+async fn run(mut cli: Cli, warnings: Vec<String>) -> ExitCode {
+    style::init(cli.no_color, cli.ascii);
+    setup_console_logging(cli.log_level(), cli.json_to_stdout());
+    for warning in warnings {
+        warn!("{}", warning);
+    }
+    set_lang(cli.lang);
+    pacing::init(cli.http.request_delay, cli.http.max_head_rps);
+
+    if !cli.json_to_stdout() && !cli.headless() {
+        clear_screen();
+    }
 
     #[cfg(windows)]
     {
-        set_title("Wuthering Waves Downloader").unwrap();
+        let _ = set_title("Wuthering Waves Downloader");
         enable_ansi_support();
     }
 
-    let log_file = setup_logging();
-    let client = Client::new();
-
-    let config = match get_config(&client).await {
-        Ok(c) => c,
-        Err(e) => exit_with_error(&log_file, &e),
-    };
-
-    let folder = match get_dir() {
-        Ok(folder) => folder,
-        Err(e) => exit_with_error(
-            &log_file,
-            &format!("Failed to read download directory: {}", e),
-        ),
+    self_update::remove_replaced_executable();
+
+    let log_file = match setup_logging(&cli.log_dir) {
+        Ok(log_file) => log_file,
+        Err(e) => {
+            error!(
+                "Failed to open log file in {}: {}",
+                cli.log_dir.display(),
+                e
+            );
+            return Outcome::Error.into();
+        }
     };
-    let options = match ask_concurrency() {
-        Ok(options) => options,
-        Err(e) => exit_with_error(&log_file, &format!("Failed to read concurrency: {}", e)),
+    if let Err(e) = cli.source.prepare_base_urls() {
+        error!("{}", e);
+        return Outcome::Error.into();
+    }
+    let client = match build_client(&cli.http_options()) {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to create HTTP client: {}", e);
+            return Outcome::Error.into();
+        }
     };
 
-    #[cfg(windows)]
-    clear().unwrap();
-    #[cfg(not(target_os = "windows"))]
-    Command::new("clear").status().unwrap();
-
-    println!(
-        "\n{} Download folder: {}",
-        Status::info(),
-        folder.display().to_string().cyan()
-    );
-    println!(
-        "{} Download concurrency: {}",
-        Status::info(),
-        options.download_concurrency.to_string().cyan()
-    );
-    println!(
-        "{} Verify concurrency: {}\n",
-        Status::info(),
-        options.verify_concurrency.to_string().cyan()
-    );
-
-    let data = match fetch_index(&client, &config, &log_file).await {
-        Ok(data) => data,
-        Err(e) => exit_with_error(&log_file, &e),
+    let command = cli.command();
+    let update_check = if cli.no_update_check || matches!(command, Commands::SelfUpdate(_)) {
+        None
+    } else {
+        self_update::start_update_check(&client, &cli.log_dir)
     };
-    let resources = match parse_resources(&data) {
-        Ok(resources) => resources,
-        Err(err) => exit_with_error(&log_file, &err),
+    let ctx = Context {
+        cli,
+        client,
+        log_file,
     };
-
-    println!(
-        "{} Found {} files to download\n",
-        Status::info(),
-        resources.len().to_string().cyan()
-    );
-    let should_stop = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-    setup_ctrlc(should_stop.clone());
-
-    let result = run_pipeline(
-        std::sync::Arc::new(client),
-        std::sync::Arc::new(config),
-        resources,
-        folder.clone(),
-        log_file.clone(),
-        should_stop.clone(),
-        options,
-    )
-    .await;
-
-    #[cfg(windows)]
-    clear().unwrap();
-
-    print_results(&result, &folder);
-
-    if should_stop.load(Ordering::SeqCst) {
-        std::process::exit(130);
+    let outcome = match &command {
+        Commands::Download(args) => download::run(&ctx, args).await,
+        Commands::Verify(args) => verify::run(&ctx, args).await,
+        Commands::Repair(args) => repair::run(&ctx, args).await,
+        Commands::Export(args) => export::run(&ctx, args).await,
+        Commands::Diff(args) => diff::run(&ctx, args).await,
+        Commands::Info(args) => info::run(&ctx, args).await,
+        Commands::Clean(args) => clean::run(&ctx, args).await,
+        Commands::CheckCdn => check_cdn::run(&ctx).await,
+        Commands::Speedtest(args) => speedtest::run(&ctx, args).await,
+        Commands::SelfUpdate(args) => self_update::run(&ctx, args).await,
+        Commands::Serve(args) => serve::run(&ctx, args).await,
+        #[cfg(feature = "gui")]
+        Commands::Gui => wuwa_downloader::commands::gui::run(&ctx).await,
+    };
+    if let Some(check) = update_check {
+        self_update::finish_update_check(check).await;
     }
+    outcome.into()
 }
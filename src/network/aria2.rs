@@ -0,0 +1,303 @@
+use reqwest::Client;
+use serde_json::{Value, json};
+use std::{
+    path::Path,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+use crate::config::cfg::ResourceItem;
+use crate::download::{
+    backend::{DownloadBackend, DownloadJob, FetchFuture},
+    progress::{FileProgress, TransferStats},
+};
+use crate::io::file::{part_path, resolve_dest};
+use crate::io::logging::log_error;
+use crate::network::client::{Cancellation, DownloadedFile, build_download_url};
+
+pub const DEFAULT_RPC_URL: &str = "http://localhost:6800/jsonrpc";
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Status polls in a row that may fail before the download is given up.
+const POLL_ATTEMPTS: u32 = 3;
+const RPC_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Pushes every file to a running aria2c daemon and polls it until the file
+/// completes. aria2 writes the `.part` file itself, so it has to see the
+/// download folder under the same path as this process.
+pub struct Aria2Backend {
+    pub rpc_url: String,
+    pub secret: Option<String>,
+}
+
+impl DownloadBackend for Aria2Backend {
+    fn fetch<'a>(&'a self, job: &'a DownloadJob<'a>) -> FetchFuture<'a> {
+        Box::pin(self.download(job))
+    }
+}
+
+impl Aria2Backend {
+    async fn download(&self, job: &DownloadJob<'_>) -> Result<DownloadedFile, String> {
+        let cancel = Cancellation::new(job.should_stop, job.control);
+        if cancel.is_cancelled() {
+            return Err(cancel.reason());
+        }
+        let started = Instant::now();
+
+        let dest = job.item.dest.replace('\\', "/");
+        let path = match resolve_dest(job.folder, &job.item.dest) {
+            Ok(path) => part_path(&path),
+            Err(msg) => {
+                log_error(job.log_file, &msg);
+                return Err(msg);
+            }
+        };
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("Error creating directory for {}: {}", dest, e))?;
+        }
+
+        let gid = self
+            .call(
+                job.client,
+                "aria2.addUri",
+                add_uri_params(
+                    job.item,
+                    &job.config.zip_bases,
+                    job.options.url_map.get(&job.item.dest),
+                    &path,
+                ),
+            )
+            .await
+            .and_then(|result| {
+                result
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| "aria2 returned no download id".to_string())
+            })
+            .map_err(|e| format!("aria2 rejected {}: {}", dest, e))?;
+
+        job.task_pb.set_length(job.expected_size.unwrap_or(0));
+        let mut file_progress =
+            FileProgress::new(job.progress, job.total_pb, job.expected_size.is_some());
+        let outcome = self.watch(job, &gid, &cancel, &mut file_progress).await;
+        if outcome.is_err() {
+            // Otherwise aria2 keeps writing the `.part` while the pipeline
+            // retries it with a new download.
+            let _ = self
+                .call(job.client, "aria2.forceRemove", json!([gid]))
+                .await;
+        }
+        let outcome = outcome.map_err(|e| format!("aria2 failed {}: {}", dest, e));
+
+        match outcome {
+            Ok(()) => Ok(DownloadedFile {
+                // aria2 checks the index checksum itself when it knows the
+                // algorithm; the pipeline re-hashes the file either way.
+                digest: None,
+                counted_bytes: file_progress.counted(),
+                // aria2 picks the mirror and retries on its own.
+                stats: TransferStats {
+                    bytes: file_progress.counted(),
+                    duration: started.elapsed(),
+                    ..TransferStats::default()
+                },
+            }),
+            Err(msg) => {
+                if !job.should_stop.load(Ordering::SeqCst) {
+                    file_progress.rollback().await;
+                }
+                if !cancel.is_cancelled() {
+                    log_error(job.log_file, &msg);
+                }
+                Err(msg)
+            }
+        }
+    }
+
+    /// Polls `gid` until aria2 finishes it, mirroring its progress and pausing
+    /// it with the session. Returns an error once it failed, was removed, the
+    /// session skipped or stopped it, or aria2 stopped answering.
+    async fn watch(
+        &self,
+        job: &DownloadJob<'_>,
+        gid: &str,
+        cancel: &Cancellation<'_>,
+        file_progress: &mut FileProgress,
+    ) -> Result<(), String> {
+        let mut paused = false;
+        let mut failed_polls = 0;
+        loop {
+            if cancel.is_cancelled() {
+                return Err(cancel.reason());
+            }
+            if job.control.is_paused() != paused {
+                paused = !paused;
+                let method = if paused {
+                    "aria2.pause"
+                } else {
+                    "aria2.unpause"
+                };
+                let _ = self.call(job.client, method, json!([gid])).await;
+            }
+
+            let status = match self
+                .call(
+                    job.client,
+                    "aria2.tellStatus",
+                    json!([
+                        gid,
+                        ["status", "totalLength", "completedLength", "errorMessage"]
+                    ]),
+                )
+                .await
+            {
+                Ok(status) => status,
+                Err(e) => {
+                    failed_polls += 1;
+                    if failed_polls >= POLL_ATTEMPTS {
+                        return Err(e);
+                    }
+                    sleep(POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+            failed_polls = 0;
+            let completed = number_field(&status, "completedLength");
+            let total = number_field(&status, "totalLength");
+            if total > 0 {
+                job.task_pb.set_length(total);
+            }
+            job.task_pb.set_position(completed);
+            file_progress
+                .add(completed.saturating_sub(file_progress.counted()))
+                .await;
+
+            match status.get("status").and_then(Value::as_str) {
+                Some("complete") => return Ok(()),
+                Some("error") => {
+                    return Err(status
+                        .get("errorMessage")
+                        .and_then(Value::as_str)
+                        .unwrap_or("unknown error")
+                        .to_string());
+                }
+                Some("removed") => return Err("removed from aria2".to_string()),
+                _ => sleep(POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    /// Sends one JSON-RPC call, prepending the secret token when configured.
+    async fn call(&self, client: &Client, method: &str, params: Value) -> Result<Value, String> {
+        let body = rpc_request(method, self.secret.as_deref(), params);
+        let response: Value = client
+            .post(&self.rpc_url)
+            .timeout(RPC_TIMEOUT)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("aria2 RPC unreachable at {}: {}", self.rpc_url, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid aria2 RPC response: {}", e))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(error
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("unknown RPC error")
+                .to_string());
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+}
+
+fn rpc_request(method: &str, secret: Option<&str>, params: Value) -> Value {
+    let mut params = match params {
+        Value::Array(params) => params,
+        other => vec![other],
+    };
+    if let Some(secret) = secret {
+        params.insert(0, json!(format!("token:{}", secret)));
+    }
+    json!({ "jsonrpc": "2.0", "id": "wuwa-downloader", "method": method, "params": params })
+}
+
+/// `aria2.addUri` parameters: the `--url-map` entry and every CDN as mirrors
+/// of the same file, written to `path` and resumed if it already exists.
+fn add_uri_params(
+    item: &ResourceItem,
+    bases: &[String],
+    mapped: Option<&str>,
+    path: &Path,
+) -> Value {
+    let uris: Vec<String> = mapped
+        .map(str::to_string)
+        .into_iter()
+        .chain(
+            bases
+                .iter()
+                .map(|base_url| build_download_url(base_url, &item.dest)),
+        )
+        .collect();
+    let mut options = json!({
+        "dir": path.parent().map(|dir| dir.display().to_string()).unwrap_or_default(),
+        "out": path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+        "continue": "true",
+        "allow-overwrite": "true",
+        "auto-file-renaming": "false",
+    });
+    if let Some(checksum) = &item.checksum
+        && let Some(name) = checksum.algorithm.iana_name()
+    {
+        options["checksum"] = json!(format!("{}={}", name, checksum.digest));
+    }
+    json!([uris, options])
+}
+
+/// aria2 reports every number as a decimal string.
+fn number_field(status: &Value, key: &str) -> u64 {
+    status
+        .get(key)
+        .and_then(Value::as_str)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_uri_params, rpc_request};
+    use crate::config::cfg::ResourceItem;
+    use crate::io::file::{Checksum, HashAlgorithm};
+    use serde_json::json;
+    use std::path::Path;
+
+    #[test]
+    fn add_uri_lists_every_cdn_and_the_token_first() {
+        let item = ResourceItem {
+            dest: "Client\\pak.pak".to_string(),
+            checksum: Some(Checksum {
+                algorithm: HashAlgorithm::Md5,
+                digest: "abc".to_string(),
+            }),
+            size: Some(3),
+            chunks: Vec::new(),
+            unpacked_size: None,
+        };
+        let bases = vec!["https://a/".to_string(), "https://b".to_string()];
+        let params = add_uri_params(&item, &bases, None, Path::new("/games/Client/pak.pak.part"));
+        let request = rpc_request("aria2.addUri", Some("s3cret"), params);
+
+        assert_eq!(request["method"], "aria2.addUri");
+        assert_eq!(request["params"][0], "token:s3cret");
+        assert_eq!(
+            request["params"][1],
+            json!(["https://a/Client/pak.pak", "https://b/Client/pak.pak"])
+        );
+        assert_eq!(request["params"][2]["dir"], "/games/Client");
+        assert_eq!(request["params"][2]["out"], "pak.pak.part");
+        assert_eq!(request["params"][2]["checksum"], "md5=abc");
+    }
+}
@@ -0,0 +1,134 @@
+use serde_json::{Map, Value, json};
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::network::{cdn_tracker::CdnStats, metrics::cdn_host};
+
+/// Per-CDN outcomes summed over past sessions, kept in `--log-dir`.
+pub const CDN_HISTORY: &str = ".cdn-history.json";
+/// Responses a CDN needs before its history counts towards the order.
+const MIN_SAMPLES: u64 = 20;
+
+/// Successful and failed responses of every CDN host over past sessions.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CdnHistory {
+    hosts: HashMap<String, (u64, u64)>,
+}
+
+impl CdnHistory {
+    /// Loads the history in `log_dir`; a missing or unreadable file starts empty.
+    pub fn load(log_dir: &Path) -> Self {
+        let data: Option<Value> = fs::read_to_string(log_dir.join(CDN_HISTORY))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok());
+        let hosts = data
+            .as_ref()
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(host, entry)| {
+                let count = |key: &str| entry.get(key).and_then(Value::as_u64);
+                Some((host.clone(), (count("ok")?, count("errors")?)))
+            })
+            .collect();
+        Self { hosts }
+    }
+
+    /// Adds the outcomes of a session.
+    pub fn record(&mut self, stats: &[CdnStats]) {
+        for cdn in stats {
+            let (ok, errors) = self.hosts.entry(cdn.host.clone()).or_default();
+            *ok += cdn.ok;
+            *errors += cdn.errors;
+        }
+    }
+
+    pub fn save(&self, log_dir: &Path) -> Result<(), String> {
+        let data: Map<String, Value> = self
+            .hosts
+            .iter()
+            .map(|(host, (ok, errors))| (host.clone(), json!({ "ok": ok, "errors": errors })))
+            .collect();
+        fs::write(log_dir.join(CDN_HISTORY), Value::Object(data).to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Share of failed responses in whole percent, once there are enough of them.
+    fn error_percent(&self, host: &str) -> Option<u64> {
+        let (ok, errors) = *self.hosts.get(host)?;
+        let total = ok + errors;
+        (total >= MIN_SAMPLES).then(|| errors * 100 / total)
+    }
+}
+
+/// Sorts `bases` by `--cdn-priority` (highest first, 0 when not given), then
+/// by how often each CDN failed in past sessions when `history` is given.
+/// The sort is stable, so CDNs that tie keep the order they came in.
+pub fn order_cdns(
+    bases: &mut [String],
+    priorities: &[(String, i32)],
+    history: Option<&CdnHistory>,
+) {
+    bases.sort_by_cached_key(|base| {
+        let host = cdn_host(base);
+        let priority = priorities
+            .iter()
+            .find(|(wanted, _)| wanted.eq_ignore_ascii_case(host))
+            .map_or(0, |(_, priority)| *priority);
+        let errors = history.and_then(|history| history.error_percent(host));
+        (-i64::from(priority), errors.unwrap_or(0))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CdnHistory, order_cdns};
+    use crate::network::cdn_tracker::CdnStats;
+
+    #[test]
+    fn orders_by_priority_then_past_failures() {
+        let mut history = CdnHistory::default();
+        history.record(&[
+            CdnStats {
+                host: "flaky".to_string(),
+                ok: 50,
+                errors: 50,
+                ..CdnStats::default()
+            },
+            CdnStats {
+                host: "new".to_string(),
+                errors: 5,
+                ..CdnStats::default()
+            },
+        ]);
+        let bases = [
+            "https://flaky/zip/",
+            "https://new/zip/",
+            "https://slow/zip/",
+            "https://fast/zip/",
+        ];
+        let mut ordered = bases.map(str::to_string);
+        let priorities = [("FAST".to_string(), 10), ("slow".to_string(), -1)];
+
+        order_cdns(&mut ordered, &priorities, Some(&history));
+        assert_eq!(
+            ordered,
+            [
+                "https://fast/zip/",
+                "https://new/zip/",
+                "https://flaky/zip/",
+                "https://slow/zip/"
+            ]
+        );
+
+        let mut ordered = bases.map(str::to_string);
+        order_cdns(&mut ordered, &priorities, None);
+        assert_eq!(
+            ordered[..3],
+            [
+                "https://fast/zip/",
+                "https://flaky/zip/",
+                "https://new/zip/"
+            ]
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use reqwest::StatusCode;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::io::logging::{SharedLogFile, log_info, log_warn};
+use crate::network::metrics::cdn_host;
+
+/// Rejections in a row after which a CDN is benched.
+const BENCH_AFTER: u32 = 3;
+/// Health of a CDN nothing has been asked from yet.
+const UNTRIED_HEALTH: f64 = 0.5;
+/// How much the latest outcome moves a CDN's health.
+const HEALTH_WEIGHT: f64 = 0.25;
+
+/// How one CDN host fared over the session.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CdnStats {
+    pub host: String,
+    /// Address the last connection went to, which shows the IP family used.
+    pub address: Option<IpAddr>,
+    /// Successful responses to file and chunk requests.
+    pub ok: u64,
+    /// Responses with an error status, rate limits included.
+    pub errors: u64,
+    /// `429 Too Many Requests` responses.
+    pub rate_limited: u64,
+    /// Times it was benched.
+    pub benched: u64,
+}
+
+#[derive(Default)]
+struct CdnState {
+    stats: CdnStats,
+    rejections_in_row: u32,
+    benched_until: Option<Instant>,
+    /// Recent outcomes, from 0 when every request failed to 1 when all worked.
+    health: Option<f64>,
+}
+
+impl CdnState {
+    fn health(&self) -> f64 {
+        self.health.unwrap_or(UNTRIED_HEALTH)
+    }
+
+    fn record_health(&mut self, healthy: bool) {
+        let outcome = if healthy { 1.0 } else { 0.0 };
+        self.health = Some(self.health() * (1.0 - HEALTH_WEIGHT) + outcome * HEALTH_WEIGHT);
+    }
+}
+
+/// Per-CDN outcomes shared by every download worker, keyed by host. A CDN
+/// that answers 429, 403 or 5xx several times in a row is skipped for
+/// `cooldown`, so the remaining files go straight to the others instead of
+/// hammering it. Each file tries the healthiest CDNs first, so once one
+/// works the rest of the session starts with it.
+#[derive(Clone, Default)]
+pub struct CdnTracker {
+    cooldown: Duration,
+    log_file: Option<SharedLogFile>,
+    states: Arc<Mutex<HashMap<String, CdnState>>>,
+}
+
+impl CdnTracker {
+    /// A zero `cooldown` only counts and never benches.
+    pub fn new(cooldown: Duration, log_file: &SharedLogFile) -> Self {
+        Self {
+            cooldown,
+            log_file: Some(log_file.clone()),
+            states: Arc::default(),
+        }
+    }
+
+    fn states(&self) -> MutexGuard<'_, HashMap<String, CdnState>> {
+        self.states.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Counts the status `url` was answered with, benching its CDN after
+    /// too many rejections in a row. The address a CDN is reached at is
+    /// logged the first time and whenever it switches IP family.
+    pub fn record(&self, url: &str, status: StatusCode, remote: Option<SocketAddr>) {
+        let host = cdn_host(url);
+        let mut states = self.states();
+        let state = states.entry(host.to_string()).or_default();
+        if let Some(ip) = remote.map(|remote| remote.ip()) {
+            let switched = state
+                .stats
+                .address
+                .is_none_or(|known| known.is_ipv4() != ip.is_ipv4());
+            if switched && let Some(log_file) = &self.log_file {
+                let family = if ip.is_ipv4() { "IPv4" } else { "IPv6" };
+                log_info(
+                    log_file,
+                    &format!("CDN {} connected over {} ({})", host, family, ip),
+                );
+            }
+            state.stats.address = Some(ip);
+        }
+        // A range past the end says nothing about the CDN.
+        if status != StatusCode::RANGE_NOT_SATISFIABLE {
+            state.record_health(status.is_success());
+        }
+        if status.is_success() {
+            state.stats.ok += 1;
+            state.rejections_in_row = 0;
+            return;
+        }
+        state.stats.errors += 1;
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            state.stats.rate_limited += 1;
+        }
+        let throttled = status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::FORBIDDEN
+            || status.is_server_error();
+        if !throttled {
+            return;
+        }
+        state.rejections_in_row += 1;
+        if self.cooldown.is_zero() || state.rejections_in_row < BENCH_AFTER {
+            return;
+        }
+        state.rejections_in_row = 0;
+        state.benched_until = Some(Instant::now() + self.cooldown);
+        state.stats.benched += 1;
+        if let Some(log_file) = &self.log_file {
+            log_warn(
+                log_file,
+                &format!(
+                    "CDN {} answered HTTP {} {} times in a row, using the others for {}s",
+                    host,
+                    status,
+                    BENCH_AFTER,
+                    self.cooldown.as_secs()
+                ),
+            );
+        }
+    }
+
+    /// Counts a request to `url` that got no usable answer, or a size probe
+    /// that did, towards its CDN's health only.
+    pub fn record_health(&self, url: &str, healthy: bool) {
+        self.states()
+            .entry(cdn_host(url).to_string())
+            .or_default()
+            .record_health(healthy);
+    }
+
+    /// Indices of `bases`, healthiest first; CDNs with equal health keep
+    /// their order.
+    pub fn preferred(&self, bases: &[String]) -> Vec<usize> {
+        let states = self.states();
+        let health = |base: &str| {
+            states
+                .get(cdn_host(base))
+                .map_or(UNTRIED_HEALTH, CdnState::health)
+        };
+        let mut order: Vec<usize> = (0..bases.len()).collect();
+        order.sort_by(|&a, &b| health(&bases[b]).total_cmp(&health(&bases[a])));
+        order
+    }
+
+    /// Whether to pass over the CDN at `base_url` for now. A CDN is never
+    /// skipped when every one of `all` is benched, so downloads do not stall.
+    pub fn skip(&self, base_url: &str, all: &[String]) -> bool {
+        let states = self.states();
+        let now = Instant::now();
+        let benched = |base: &str| {
+            states
+                .get(cdn_host(base))
+                .and_then(|state| state.benched_until)
+                .is_some_and(|until| until > now)
+        };
+        benched(base_url) && all.iter().any(|base| !benched(base))
+    }
+
+    /// Every CDN host that answered, in the order of `bases`.
+    pub fn stats(&self, bases: &[String]) -> Vec<CdnStats> {
+        let states = self.states();
+        let mut stats: Vec<CdnStats> = Vec::new();
+        for base in bases {
+            let host = cdn_host(base);
+            if let Some(state) = states.get(host)
+                && !stats.iter().any(|known| known.host == host)
+            {
+                stats.push(CdnStats {
+                    host: host.to_string(),
+                    ..state.stats.clone()
+                });
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CdnTracker;
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    #[test]
+    fn benches_a_cdn_that_keeps_rejecting() {
+        let bases = ["https://a/pre".to_string(), "https://b/pre".to_string()];
+        let tracker = CdnTracker {
+            cooldown: Duration::from_secs(60),
+            ..CdnTracker::default()
+        };
+        let a = "https://a/pre/x.pak";
+
+        tracker.record(a, StatusCode::TOO_MANY_REQUESTS, None);
+        tracker.record(a, StatusCode::NOT_FOUND, None);
+        tracker.record(a, StatusCode::FORBIDDEN, None);
+        assert!(!tracker.skip(&bases[0], &bases));
+        tracker.record(a, StatusCode::BAD_GATEWAY, None);
+        assert!(tracker.skip(&bases[0], &bases));
+        assert!(!tracker.skip(&bases[1], &bases));
+
+        for _ in 0..3 {
+            tracker.record("https://b/pre/x.pak", StatusCode::SERVICE_UNAVAILABLE, None);
+        }
+        assert!(!tracker.skip(&bases[0], &bases), "all benched");
+
+        let remote = "[2001:db8::1]:443".parse().ok();
+        tracker.record("https://b/pre/y.pak", StatusCode::PARTIAL_CONTENT, remote);
+        let stats = tracker.stats(&bases);
+        assert_eq!(stats[0].host, "a");
+        assert_eq!(
+            (stats[0].errors, stats[0].rate_limited, stats[0].benched),
+            (4, 1, 1)
+        );
+        assert_eq!((stats[1].ok, stats[1].benched), (1, 1));
+        assert!(stats[1].address.is_some_and(|ip| ip.is_ipv6()));
+    }
+
+    #[test]
+    fn prefers_the_cdn_that_last_worked() {
+        let bases = [
+            "https://a/pre".to_string(),
+            "https://b/pre".to_string(),
+            "https://c/pre".to_string(),
+        ];
+        let tracker = CdnTracker::default();
+        assert_eq!(tracker.preferred(&bases), [0, 1, 2]);
+
+        tracker.record_health("https://a/pre/x.pak", false);
+        tracker.record("https://b/pre/x.pak", StatusCode::OK, None);
+        assert_eq!(tracker.preferred(&bases), [1, 2, 0]);
+
+        tracker.record(
+            "https://b/pre/y.pak",
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            None,
+        );
+        assert_eq!(tracker.preferred(&bases), [1, 2, 0]);
+        for _ in 0..3 {
+            tracker.record("https://b/pre/z.pak", StatusCode::BAD_GATEWAY, None);
+        }
+        assert_eq!(tracker.preferred(&bases), [2, 0, 1]);
+    }
+}
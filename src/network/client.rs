@@ -1,34 +1,67 @@
 use colored::Colorize;
 use indicatif::ProgressBar;
-use reqwest::{Client, StatusCode};
+use log::{debug, info, trace, warn};
+use reqwest::{Client, StatusCode, header::CONTENT_LENGTH};
 use serde_json::{Value, from_str};
-#[cfg(not(target_os = "windows"))]
-use std::process::Command;
 use std::{
-    io::{self, Write},
+    io::SeekFrom,
     path::Path,
-    sync::atomic::{AtomicBool, Ordering},
-    time::Duration,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
 };
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use tokio::time::sleep;
 
-#[cfg(windows)]
-use winconsole::console::clear;
-
-use crate::config::cfg::Config;
+use crate::config::cfg::{Config, FileChunk, ResourceItem};
 use crate::config::status::Status;
-use crate::download::progress::DownloadProgress;
-use crate::io::file::{file_size, get_filename};
-use crate::io::logging::{SharedLogFile, log_error};
-use crate::io::util::{get_version, read_line};
-
-const INDEX_URL: &str = "https://gist.githubusercontent.com/yuhkix/b8796681ac2cd3bab11b7e8cdc022254/raw/4435fd290c07f7f766a6d2ab09ed3096d83b02e3/wuwa.json";
-const MAX_RETRIES: usize = 3;
-const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10_000);
+use crate::download::{
+    backend::DownloadJob,
+    control::DownloadControl,
+    progress::{FileProgress, TransferStats},
+};
+use crate::io::file::{
+    Checksum, Hasher, VerificationError, ensure_within, file_size, get_filename,
+    hash_existing_prefix, part_path, reserve_space, resolve_dest, short_path, verify_chunks,
+};
+use crate::io::i18n::Msg;
+use crate::io::journal::WriteJournal;
+use crate::io::logging::{SUCCESS, SharedLogFile, log_error, log_warn};
+use crate::io::prompt::Prompter;
+use crate::io::size_cache::SizeCache;
+use crate::io::util::{
+    CONFIG_ENV, CONFIRM_ENV, MODE_ENV, VERSION_CHANNELS, VERSION_ENV, get_version, invalid_preset,
+    read_error, validate_version_list,
+};
+use crate::network::cdn_tracker::CdnTracker;
+use crate::network::pacing::{self, RequestKind};
+use crate::network::retry::{RetryPolicy, is_retryable_status};
+use crate::sys;
+
+const SOURCE_URL: &str = "https://gist.githubusercontent.com/yuhkix/b8796681ac2cd3bab11b7e8cdc022254/raw/4435fd290c07f7f766a6d2ab09ed3096d83b02e3/wuwa.json";
+const SIZE_PROBE_CONCURRENCY: usize = 16;
+const DOWNLOAD_INTERRUPTED: &str = "Download interrupted";
+const DOWNLOAD_SKIPPED: &str = "Skipped by user";
+const RECONNECT_AFTER_WAKE: &str = "Reconnecting after the system woke from sleep";
+/// Resumed partials up to this size are hashed before the download continues.
+const INLINE_PREFIX_HASH_LIMIT: u64 = 64 * 1024 * 1024;
+/// Most of a partial's end `--verify-partials` reads back into memory.
+const MAX_PARTIAL_TAIL: u64 = 1024 * 1024;
+
+pub struct DownloadedFile {
+    /// Digest computed while streaming, absent when it could not be produced.
+    pub digest: Option<String>,
+    /// Bytes this file added to the session total.
+    pub counted_bytes: u64,
+    pub stats: TransferStats,
+}
 
 enum DownloadAttemptResult {
-    Completed,
+    Completed(Option<String>),
     Retryable(String),
     RangeNotSatisfiable,
     RangeUnsupported,
@@ -37,30 +70,51 @@ enum DownloadAttemptResult {
 }
 
 enum CdnDownloadResult {
-    Success,
+    Success(Option<String>),
     RetryWithoutResume,
     Failed(String),
     Interrupted,
 }
 
-fn clear_screen() {
-    #[cfg(windows)]
-    {
-        clear().unwrap();
-    }
-
-    #[cfg(not(target_os = "windows"))]
-    {
-        Command::new("clear").status().unwrap();
+/// Joins a CDN base URL and an index path with exactly one `/` between
+/// segments, whatever slashes either side brings; backslashes in `dest` are
+/// separators too. The `//` after the scheme and a leading `/` of a base
+/// without one are kept.
+pub fn build_download_url(base_url: &str, dest: &str) -> String {
+    let (scheme, rest) = match base_url.split_once("://") {
+        Some((scheme, rest)) => (Some(scheme), rest),
+        None => (None, base_url),
+    };
+    let segments: Vec<&str> = rest
+        .split('/')
+        .chain(dest.split(['/', '\\']))
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let root = if rest.starts_with('/') { "/" } else { "" };
+    match scheme {
+        Some(scheme) => format!("{}://{}{}", scheme, root, segments.join("/")),
+        None => format!("{}{}", root, segments.join("/")),
     }
 }
 
-pub fn build_download_url(base_url: &str, dest: &str) -> String {
-    format!(
-        "{}/{}",
-        base_url.trim_end_matches('/'),
-        dest.trim_start_matches('/')
-    )
+/// Where to fetch `dest` from, in order: its `--url-map` entry, then every
+/// CDN. Each comes with a name for the log and its CDN base URL, if any.
+fn source_urls<'a>(
+    config: &'a Config,
+    dest: &str,
+    mapped: Option<&str>,
+    tracker: &CdnTracker,
+) -> Vec<(String, Option<&'a str>, String)> {
+    let mapped = mapped.map(|url| ("Mapped URL".to_string(), None, url.to_string()));
+    let cdns = tracker.preferred(&config.zip_bases).into_iter().map(|i| {
+        let base_url = &config.zip_bases[i];
+        (
+            format!("CDN {}", i + 1),
+            Some(base_url.as_str()),
+            build_download_url(base_url, dest),
+        )
+    });
+    mapped.into_iter().chain(cdns).collect()
 }
 
 async fn decompress_if_gzipped(response: reqwest::Response) -> Result<String, String> {
@@ -75,14 +129,12 @@ pub async fn fetch_index(
     config: &Config,
     log_file: &SharedLogFile,
 ) -> Result<Value, String> {
-    println!("{} Fetching index file...", Status::info());
+    info!("Fetching index file...");
 
-    let response = match client
+    let request = client
         .get(&config.index_url)
-        .timeout(Duration::from_secs(30))
-        .send()
-        .await
-    {
+        .timeout(Duration::from_secs(30));
+    let response = match pacing::send(request, RequestKind::Metadata).await {
         Ok(resp) => resp,
         Err(e) => {
             let msg = format!("Error fetching index file: {}", e);
@@ -106,7 +158,7 @@ pub async fn fetch_index(
         }
     };
 
-    println!("{} Index file downloaded successfully", Status::success());
+    info!(target: SUCCESS, "Index file downloaded successfully");
 
     match from_str(&text) {
         Ok(v) => Ok(v),
@@ -118,45 +170,143 @@ pub async fn fetch_index(
     }
 }
 
-async fn remove_partial_file(path: &Path) {
-    if tokio::fs::try_exists(path).await.unwrap_or(false) {
-        let _ = tokio::fs::remove_file(path).await;
+/// Reads a resource index saved from a previous session or exported by hand,
+/// so the download works when the index host is unreachable.
+pub fn load_index_file(path: &Path, log_file: &SharedLogFile) -> Result<Value, String> {
+    info!("Loading index file {}...", path.display());
+
+    let parsed = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading index file {}: {}", path.display(), e))
+        .and_then(|text| {
+            from_str(&text).map_err(|e| format!("Error parsing index file JSON: {}", e))
+        });
+    if let Err(msg) = &parsed {
+        log_error(log_file, msg);
     }
+    parsed
 }
 
-async fn rollback_counted_bytes(
-    progress: &DownloadProgress,
-    total_pb: &ProgressBar,
-    counted_bytes_for_file: &mut u64,
-) {
-    let amount = *counted_bytes_for_file;
-    if amount == 0 {
-        return;
+async fn head_content_length(client: &Client, url: &str, timeout: Duration) -> Option<u64> {
+    let request = client.head(url).timeout(timeout);
+    let response = pacing::send(request, RequestKind::Head).await.ok()?;
+
+    if !response.status().is_success() {
+        return None;
     }
 
-    progress.rollback_downloaded_bytes(total_pb, amount).await;
-    *counted_bytes_for_file = 0;
+    response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
 }
 
-async fn wait_for_stop(should_stop: &AtomicBool) {
-    while !should_stop.load(Ordering::SeqCst) {
-        sleep(Duration::from_millis(100)).await;
+/// Fills in sizes the index left out, from `cache` where an earlier session
+/// probed them and otherwise with one bounded concurrent HEAD per file, which
+/// is cached in turn. Each probe starts at the healthiest CDN in `tracker`.
+/// Returns how many sizes were resolved.
+pub async fn probe_missing_sizes(
+    client: &Client,
+    config: &Config,
+    resources: &mut [ResourceItem],
+    cache: &mut SizeCache,
+    tracker: &CdnTracker,
+    head_timeout: Duration,
+    log_file: &SharedLogFile,
+) -> usize {
+    let semaphore = Arc::new(Semaphore::new(SIZE_PROBE_CONCURRENCY));
+    let zip_bases = Arc::new(config.zip_bases.clone());
+    let mut tasks = JoinSet::new();
+    let mut resolved = 0;
+
+    for (index, item) in resources.iter_mut().enumerate() {
+        if item.size.is_some() {
+            continue;
+        }
+        if let Some(size) = cache.get(&item.dest) {
+            item.size = Some(size);
+            resolved += 1;
+            continue;
+        }
+
+        let client = client.clone();
+        let semaphore = semaphore.clone();
+        let zip_bases = zip_bases.clone();
+        let tracker = tracker.clone();
+        let dest = item.dest.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await.ok();
+            for i in tracker.preferred(&zip_bases) {
+                let url = build_download_url(&zip_bases[i], &dest);
+                let size = head_content_length(&client, &url, head_timeout).await;
+                tracker.record_health(&url, size.is_some());
+                if size.is_some() {
+                    return (index, size);
+                }
+            }
+            (index, None)
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, Some(size))) => {
+                resources[index].size = Some(size);
+                cache.insert(&resources[index].dest, size);
+                resolved += 1;
+            }
+            Ok((index, None)) => log_warn(
+                log_file,
+                &format!("Could not determine size of {}", resources[index].dest),
+            ),
+            Err(e) => log_error(log_file, &format!("Size probe task failed: {}", e)),
+        }
     }
+
+    resolved
 }
 
-async fn count_total_progress(
-    progress: &DownloadProgress,
-    total_pb: &ProgressBar,
-    counted_bytes_for_file: &mut u64,
-    amount: u64,
-    track_total: bool,
-) {
-    if amount == 0 || !track_total {
-        return;
+async fn remove_partial_file(path: &Path) {
+    if tokio::fs::try_exists(path).await.unwrap_or(false) {
+        let _ = tokio::fs::remove_file(path).await;
     }
+}
 
-    progress.add_downloaded_bytes(total_pb, amount).await;
-    *counted_bytes_for_file += amount;
+/// Why an in-flight download should give up: a global stop or a user skip
+/// issued after the file started.
+pub(crate) struct Cancellation<'a> {
+    should_stop: &'a AtomicBool,
+    control: &'a DownloadControl,
+    skip_epoch: u64,
+}
+
+impl<'a> Cancellation<'a> {
+    /// Only skips issued from now on cancel the download.
+    pub(crate) fn new(should_stop: &'a AtomicBool, control: &'a DownloadControl) -> Self {
+        Self {
+            should_stop,
+            control,
+            skip_epoch: control.skip_epoch(),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.should_stop.load(Ordering::SeqCst) || self.control.skipped_since(self.skip_epoch)
+    }
+
+    async fn wait(&self) {
+        while !self.is_cancelled() {
+            sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    pub(crate) fn reason(&self) -> String {
+        if self.should_stop.load(Ordering::SeqCst) {
+            DOWNLOAD_INTERRUPTED.to_string()
+        } else {
+            DOWNLOAD_SKIPPED.to_string()
+        }
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -164,21 +314,18 @@ async fn download_single_file(
     client: &Client,
     url: &str,
     path: &Path,
-    should_stop: &std::sync::atomic::AtomicBool,
-    progress: &DownloadProgress,
-    total_pb: &ProgressBar,
+    checksum: Option<&Checksum>,
+    cancel: &Cancellation<'_>,
+    file_progress: &mut FileProgress,
     task_pb: &ProgressBar,
     allow_resume: bool,
-    counted_bytes_for_file: &mut u64,
-    track_total: bool,
+    expected_size: Option<u64>,
+    journal: &WriteJournal,
 ) -> DownloadAttemptResult {
     let local_size = file_size(path).await;
     let use_range = allow_resume && local_size > 0;
 
-    let request = client
-        .get(url)
-        .timeout(DOWNLOAD_TIMEOUT)
-        .header("Connection", "keep-alive");
+    let request = client.get(url).header("Connection", "keep-alive");
 
     let request = if use_range {
         request.header("Range", format!("bytes={}-", local_size))
@@ -186,13 +333,30 @@ async fn download_single_file(
         request
     };
 
+    file_progress.record_request(url);
     let mut response = match tokio::select! {
-        _ = wait_for_stop(should_stop) => return DownloadAttemptResult::Interrupted,
+        _ = cancel.wait() => return DownloadAttemptResult::Interrupted,
         resp = request.send() => resp,
     } {
         Ok(resp) => resp,
-        Err(e) => return DownloadAttemptResult::Retryable(format!("Network error: {}", e)),
+        Err(e) => {
+            file_progress.cdns().record_health(url, false);
+            return DownloadAttemptResult::Retryable(format!("Network error: {}", e));
+        }
     };
+    file_progress
+        .cdns()
+        .record(url, response.status(), response.remote_addr());
+    debug!(
+        "GET {} -> {}{}",
+        url,
+        response.status(),
+        if use_range {
+            format!(" (resume from {})", local_size)
+        } else {
+            String::new()
+        }
+    );
 
     if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
         return DownloadAttemptResult::RangeNotSatisfiable;
@@ -209,7 +373,11 @@ async fn download_single_file(
     }
 
     if !response.status().is_success() && response.status() != StatusCode::PARTIAL_CONTENT {
-        return DownloadAttemptResult::HttpError(format!("HTTP error: {}", response.status()));
+        let msg = format!("HTTP error: {}", response.status());
+        if is_retryable_status(response.status()) {
+            return DownloadAttemptResult::Retryable(msg);
+        }
+        return DownloadAttemptResult::HttpError(msg);
     }
 
     let append_mode = use_range && response.status() == StatusCode::PARTIAL_CONTENT;
@@ -219,18 +387,10 @@ async fn download_single_file(
     if append_mode {
         options.append(true);
         task_pb.set_position(local_size);
-        if *counted_bytes_for_file == 0 {
-            count_total_progress(
-                progress,
-                total_pb,
-                counted_bytes_for_file,
-                local_size,
-                track_total,
-            )
-            .await;
-        }
+        file_progress.add_existing(local_size).await;
     } else {
-        options.write(true).truncate(true);
+        // Truncating an already empty file would release its preallocated space.
+        options.write(true).truncate(local_size > 0);
         task_pb.set_position(0);
     }
 
@@ -238,14 +398,47 @@ async fn download_single_file(
         Ok(file) => file,
         Err(e) => return DownloadAttemptResult::Retryable(format!("File open error: {}", e)),
     };
+    // A restarted file has nothing trustworthy until its first sync.
+    if !append_mode && let Err(e) = journal.record(path, expected_size, 0) {
+        return DownloadAttemptResult::Retryable(format!("Journal write error: {}", e));
+    }
+    let mut hasher = match checksum {
+        // Re-reading a large partial here would hold the download slot; the
+        // hasher threads check the finished file instead.
+        Some(_) if append_mode && local_size > INLINE_PREFIX_HASH_LIMIT => None,
+        Some(checksum) if append_mode => {
+            match hash_existing_prefix(path, checksum.algorithm, cancel.should_stop).await {
+                Ok(hasher) => Some(hasher),
+                Err(VerificationError::Interrupted) => return DownloadAttemptResult::Interrupted,
+                // Post-download verification falls back to a full re-read.
+                Err(VerificationError::Io(_)) => None,
+            }
+        }
+        Some(checksum) => Some(Hasher::new(checksum.algorithm)),
+        None => None,
+    };
 
+    let mut written = if append_mode { local_size } else { 0 };
+    let mut last_sync = Instant::now();
+    let reconnect_epoch = cancel.control.reconnect_epoch();
     loop {
-        if should_stop.load(std::sync::atomic::Ordering::SeqCst) {
+        // Sync before bailing out so the partial file holds every byte written
+        // so far and the next run can resume from all of it.
+        cancel.control.wait_while_paused(cancel.should_stop).await;
+        if cancel.is_cancelled() {
+            let _ = sync_to_journal(&mut file, journal, path, expected_size, written).await;
             return DownloadAttemptResult::Interrupted;
         }
 
         let chunk = match tokio::select! {
-            _ = wait_for_stop(should_stop) => return DownloadAttemptResult::Interrupted,
+            _ = cancel.wait() => {
+                let _ = sync_to_journal(&mut file, journal, path, expected_size, written).await;
+                return DownloadAttemptResult::Interrupted;
+            }
+            _ = cancel.control.wait_for_reconnect(reconnect_epoch) => {
+                let _ = sync_to_journal(&mut file, journal, path, expected_size, written).await;
+                return DownloadAttemptResult::Retryable(RECONNECT_AFTER_WAKE.to_string());
+            }
             chunk = response.chunk() => chunk,
         } {
             Ok(Some(chunk)) => chunk,
@@ -253,27 +446,61 @@ async fn download_single_file(
             Err(e) => return DownloadAttemptResult::Retryable(format!("Read error: {}", e)),
         };
 
+        sys::throttle(chunk.len()).await;
         if let Err(e) = file.write_all(&chunk).await {
             return DownloadAttemptResult::Retryable(format!("Write error: {}", e));
         }
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
 
         let size = chunk.len() as u64;
+        trace!("{}: +{} bytes", url, size);
         task_pb.inc(size);
-        count_total_progress(
-            progress,
-            total_pb,
-            counted_bytes_for_file,
-            size,
-            track_total,
-        )
-        .await;
+        file_progress.add(size).await;
+        file_progress.record_source(url, size);
+
+        written += size;
+        if journal
+            .interval()
+            .is_some_and(|interval| last_sync.elapsed() >= interval)
+        {
+            if let Err(e) = sync_to_journal(&mut file, journal, path, expected_size, written).await
+            {
+                return DownloadAttemptResult::Retryable(e);
+            }
+            last_sync = Instant::now();
+        }
     }
 
     if let Err(e) = file.flush().await {
         return DownloadAttemptResult::Retryable(format!("File flush error: {}", e));
     }
 
-    DownloadAttemptResult::Completed
+    DownloadAttemptResult::Completed(hasher.map(Hasher::finalize_hex))
+}
+
+/// Flushes `file` and, with journaling on, syncs it and records its first
+/// `written` bytes as safe to resume from.
+async fn sync_to_journal(
+    file: &mut tokio::fs::File,
+    journal: &WriteJournal,
+    path: &Path,
+    expected_size: Option<u64>,
+    written: u64,
+) -> Result<(), String> {
+    file.flush()
+        .await
+        .map_err(|e| format!("File flush error: {}", e))?;
+    if journal.interval().is_none() {
+        return Ok(());
+    }
+    file.sync_data()
+        .await
+        .map_err(|e| format!("File sync error: {}", e))?;
+    journal
+        .record(path, expected_size, written)
+        .map_err(|e| format!("Journal write error: {}", e))
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -281,26 +508,35 @@ async fn try_download_with_cdns(
     client: &Client,
     config: &Config,
     dest: &str,
+    mapped: Option<&str>,
     path: &Path,
+    expected_size: Option<u64>,
+    checksum: Option<&Checksum>,
     log_file: &SharedLogFile,
-    should_stop: &std::sync::atomic::AtomicBool,
-    progress: &DownloadProgress,
-    total_pb: &ProgressBar,
+    cancel: &Cancellation<'_>,
+    file_progress: &mut FileProgress,
     task_pb: &ProgressBar,
     allow_resume: bool,
-    counted_bytes_for_file: &mut u64,
-    track_total: bool,
+    retry: &RetryPolicy,
+    journal: &WriteJournal,
 ) -> CdnDownloadResult {
+    let max_attempts = retry.max_attempts.max(1);
     let mut saw_range_unsupported = false;
     let mut last_error = "Unknown error".to_string();
 
-    for (i, base_url) in config.zip_bases.iter().enumerate() {
-        if should_stop.load(std::sync::atomic::Ordering::SeqCst) {
+    for (source, base_url, url) in source_urls(config, dest, mapped, file_progress.cdns()) {
+        if cancel.is_cancelled() {
             return CdnDownloadResult::Interrupted;
         }
+        if let Some(base_url) = base_url
+            && file_progress.cdns().skip(base_url, &config.zip_bases)
+        {
+            debug!("Skipping benched {} for {}", source, dest);
+            continue;
+        }
 
-        let url = build_download_url(base_url, dest);
-        let mut retries = MAX_RETRIES;
+        let mut retries = max_attempts;
+        debug!("Trying {} for {}", source, dest);
 
         while retries > 0 {
             let local_size = if allow_resume {
@@ -312,42 +548,57 @@ async fn try_download_with_cdns(
                 client,
                 &url,
                 path,
-                should_stop,
-                progress,
-                total_pb,
+                checksum,
+                cancel,
+                file_progress,
                 task_pb,
                 allow_resume,
-                counted_bytes_for_file,
-                track_total,
+                expected_size,
+                journal,
             )
             .await;
 
             match attempt {
-                DownloadAttemptResult::Completed => {
-                    return CdnDownloadResult::Success;
+                DownloadAttemptResult::Completed(digest) => {
+                    return CdnDownloadResult::Success(digest);
                 }
                 DownloadAttemptResult::Interrupted => {
                     return CdnDownloadResult::Interrupted;
                 }
                 DownloadAttemptResult::Retryable(err) => {
+                    debug!("{} transient failure for {}: {}", source, dest, err);
                     last_error = err;
                     retries -= 1;
                     if !allow_resume {
-                        rollback_counted_bytes(progress, total_pb, counted_bytes_for_file).await;
+                        file_progress.rollback().await;
                         task_pb.set_position(0);
                     }
                     if retries > 0 {
+                        let delay = retry.backoff(max_attempts - retries - 1);
                         task_pb.set_message(format!(
-                            "retrying {} ({} left)",
+                            "retrying {} in {:.1}s ({} left)",
                             get_filename(dest).yellow(),
+                            delay.as_secs_f64(),
                             retries
                         ));
+                        tokio::select! {
+                            _ = cancel.wait() => return CdnDownloadResult::Interrupted,
+                            _ = sleep(delay) => {}
+                        }
                     }
                 }
                 DownloadAttemptResult::RangeNotSatisfiable => {
+                    if local_size > 0 && expected_size == Some(local_size) {
+                        // Nothing left past our offset: the partial is already whole,
+                        // so keep it and let post-download verification decide.
+                        file_progress.add_existing(local_size).await;
+                        task_pb.set_position(local_size);
+                        return CdnDownloadResult::Success(None);
+                    }
+
                     last_error = "Range not satisfiable, restarting file".to_string();
                     retries -= 1;
-                    rollback_counted_bytes(progress, total_pb, counted_bytes_for_file).await;
+                    file_progress.rollback().await;
                     remove_partial_file(path).await;
                     task_pb.set_position(0);
                     task_pb.set_message(format!(
@@ -360,21 +611,22 @@ async fn try_download_with_cdns(
                     if local_size > 0 {
                         saw_range_unsupported = true;
                         last_error = format!(
-                            "CDN {} does not support resuming {}",
-                            i + 1,
+                            "{} does not support resuming {}",
+                            source,
                             get_filename(dest)
                         );
-                        log_error(log_file, &last_error);
+                        log_warn(log_file, &last_error);
                     }
                     break;
                 }
                 DownloadAttemptResult::HttpError(err) => {
+                    debug!("{} failed for {}: {}, trying next", source, dest, err);
                     last_error = err;
-                    log_error(
+                    log_warn(
                         log_file,
                         &format!(
-                            "CDN {} failed for {}: {}",
-                            i + 1,
+                            "{} failed for {}: {}",
+                            source,
                             get_filename(dest),
                             last_error
                         ),
@@ -385,11 +637,11 @@ async fn try_download_with_cdns(
         }
 
         if retries == 0 {
-            log_error(
+            log_warn(
                 log_file,
                 &format!(
-                    "CDN {} retries exhausted for {}: {}",
-                    i + 1,
+                    "{} retries exhausted for {}: {}",
+                    source,
                     get_filename(dest),
                     last_error
                 ),
@@ -404,28 +656,406 @@ async fn try_download_with_cdns(
     }
 }
 
+/// Fetches one chunk with a Range request and writes it in place, checking
+/// its digest while streaming. A chunk that does not finish intact takes its
+/// bytes back out of the progress, since it is always fetched again whole.
+async fn download_chunk(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    chunk: &FileChunk,
+    cancel: &Cancellation<'_>,
+    file_progress: &mut FileProgress,
+    task_pb: &ProgressBar,
+) -> DownloadAttemptResult {
+    let request = client
+        .get(url)
+        .header("Range", format!("bytes={}-{}", chunk.start, chunk.end));
+    file_progress.record_request(url);
+    let mut response = match tokio::select! {
+        _ = cancel.wait() => return DownloadAttemptResult::Interrupted,
+        resp = request.send() => resp,
+    } {
+        Ok(resp) => resp,
+        Err(e) => {
+            file_progress.cdns().record_health(url, false);
+            return DownloadAttemptResult::Retryable(format!("Network error: {}", e));
+        }
+    };
+    file_progress
+        .cdns()
+        .record(url, response.status(), response.remote_addr());
+    debug!(
+        "GET {} -> {} (bytes {}-{})",
+        url,
+        response.status(),
+        chunk.start,
+        chunk.end
+    );
+
+    match response.status() {
+        StatusCode::PARTIAL_CONTENT => {}
+        status if status.is_success() => return DownloadAttemptResult::RangeUnsupported,
+        status => {
+            let msg = format!("HTTP error: {}", status);
+            if is_retryable_status(status) {
+                return DownloadAttemptResult::Retryable(msg);
+            }
+            return DownloadAttemptResult::HttpError(msg);
+        }
+    }
+
+    let mut file = match tokio::fs::OpenOptions::new().write(true).open(path).await {
+        Ok(file) => file,
+        Err(e) => return DownloadAttemptResult::Retryable(format!("File open error: {}", e)),
+    };
+    if let Err(e) = file.seek(SeekFrom::Start(chunk.start)).await {
+        return DownloadAttemptResult::Retryable(format!("Seek error: {}", e));
+    }
+
+    let mut hasher = Hasher::new(chunk.checksum.algorithm);
+    let mut written = 0;
+    let reconnect_epoch = cancel.control.reconnect_epoch();
+    let streamed = loop {
+        cancel.control.wait_while_paused(cancel.should_stop).await;
+        if cancel.is_cancelled() {
+            break DownloadAttemptResult::Interrupted;
+        }
+
+        let data = match tokio::select! {
+            _ = cancel.wait() => break DownloadAttemptResult::Interrupted,
+            _ = cancel.control.wait_for_reconnect(reconnect_epoch) => {
+                break DownloadAttemptResult::Retryable(RECONNECT_AFTER_WAKE.to_string());
+            }
+            data = response.chunk() => data,
+        } {
+            Ok(Some(data)) => data,
+            Ok(None) => break DownloadAttemptResult::Completed(None),
+            Err(e) => break DownloadAttemptResult::Retryable(format!("Read error: {}", e)),
+        };
+
+        let size = data.len() as u64;
+        // Never spill into the next chunk, whatever the server sends.
+        if written + size > chunk.size() {
+            break DownloadAttemptResult::Retryable("Response longer than the range".to_string());
+        }
+        sys::throttle(data.len()).await;
+        if let Err(e) = file.write_all(&data).await {
+            break DownloadAttemptResult::Retryable(format!("Write error: {}", e));
+        }
+        hasher.update(&data);
+        written += size;
+        trace!("{}: +{} bytes", url, size);
+        task_pb.inc(size);
+        file_progress.add(size).await;
+        file_progress.record_source(url, size);
+    };
+
+    let flushed = file.flush().await;
+    let result = match (streamed, flushed) {
+        (DownloadAttemptResult::Completed(_), Err(e)) => {
+            DownloadAttemptResult::Retryable(format!("File flush error: {}", e))
+        }
+        (DownloadAttemptResult::Completed(_), Ok(()))
+            if written != chunk.size() || !chunk.checksum.matches(&hasher.finalize_hex()) =>
+        {
+            DownloadAttemptResult::Retryable(format!(
+                "Checksum mismatch for bytes {}-{}",
+                chunk.start, chunk.end
+            ))
+        }
+        (streamed, _) => streamed,
+    };
+
+    if !matches!(result, DownloadAttemptResult::Completed(_)) {
+        file_progress.rollback_bytes(written).await;
+        task_pb.set_position(task_pb.position().saturating_sub(written));
+    }
+    result
+}
+
+/// Tries every CDN for one chunk, with the usual retry policy per CDN.
 #[allow(clippy::too_many_arguments)]
-pub async fn download_file(
+async fn fetch_chunk_with_cdns(
     client: &Client,
     config: &Config,
     dest: &str,
-    folder: &Path,
+    mapped: Option<&str>,
+    path: &Path,
+    chunk: &FileChunk,
+    log_file: &SharedLogFile,
+    cancel: &Cancellation<'_>,
+    file_progress: &mut FileProgress,
+    task_pb: &ProgressBar,
+    retry: &RetryPolicy,
+) -> CdnDownloadResult {
+    let max_attempts = retry.max_attempts.max(1);
+    let mut last_error = "Unknown error".to_string();
+
+    for (source, base_url, url) in source_urls(config, dest, mapped, file_progress.cdns()) {
+        if let Some(base_url) = base_url
+            && file_progress.cdns().skip(base_url, &config.zip_bases)
+        {
+            debug!("Skipping benched {} for {}", source, dest);
+            continue;
+        }
+        for attempt in 0..max_attempts {
+            if cancel.is_cancelled() {
+                return CdnDownloadResult::Interrupted;
+            }
+
+            match download_chunk(client, &url, path, chunk, cancel, file_progress, task_pb).await {
+                DownloadAttemptResult::Completed(_) => return CdnDownloadResult::Success(None),
+                DownloadAttemptResult::Interrupted => return CdnDownloadResult::Interrupted,
+                DownloadAttemptResult::Retryable(err) => {
+                    debug!("{} transient failure for {}: {}", source, dest, err);
+                    last_error = err;
+                    if attempt + 1 < max_attempts {
+                        tokio::select! {
+                            _ = cancel.wait() => return CdnDownloadResult::Interrupted,
+                            _ = sleep(retry.backoff(attempt)) => {}
+                        }
+                    }
+                }
+                DownloadAttemptResult::RangeUnsupported
+                | DownloadAttemptResult::RangeNotSatisfiable => {
+                    last_error = format!("{} does not support byte ranges", source);
+                    log_warn(log_file, &last_error);
+                    break;
+                }
+                DownloadAttemptResult::HttpError(err) => {
+                    last_error = err;
+                    log_warn(
+                        log_file,
+                        &format!(
+                            "{} failed for {}: {}",
+                            source,
+                            get_filename(dest),
+                            last_error
+                        ),
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    CdnDownloadResult::Failed(format!(
+        "bytes {}-{}: {}",
+        chunk.start, chunk.end, last_error
+    ))
+}
+
+/// Brings a chunked resource up to date in place: chunks that already match
+/// are kept and only damaged or missing ones are fetched, so repairing a huge
+/// pak costs a few ranges instead of the whole file.
+#[allow(clippy::too_many_arguments)]
+async fn download_chunks(
+    client: &Client,
+    config: &Config,
+    dest: &str,
+    mapped: Option<&str>,
+    path: &Path,
     expected_size: Option<u64>,
+    chunks: &[FileChunk],
     log_file: &SharedLogFile,
-    should_stop: &std::sync::atomic::AtomicBool,
-    progress: &DownloadProgress,
-    total_pb: &ProgressBar,
+    cancel: &Cancellation<'_>,
+    file_progress: &mut FileProgress,
     task_pb: &ProgressBar,
-) -> bool {
-    if should_stop.load(std::sync::atomic::Ordering::SeqCst) {
-        return false;
+    retry: &RetryPolicy,
+) -> CdnDownloadResult {
+    let opened = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(path)
+        .await;
+    let resized = match (opened, expected_size) {
+        (Ok(file), Some(size)) => file.set_len(size).await,
+        (Ok(_), None) => Ok(()),
+        (Err(e), _) => Err(e),
+    };
+    if let Err(e) = resized {
+        return CdnDownloadResult::Failed(format!("File open error: {}", e));
     }
 
+    let intact = match verify_chunks(path, chunks, cancel.should_stop).await {
+        Ok(intact) => intact,
+        Err(VerificationError::Interrupted) => return CdnDownloadResult::Interrupted,
+        Err(VerificationError::Io(e)) => {
+            return CdnDownloadResult::Failed(format!("Chunk check failed: {}", e));
+        }
+    };
+    let mut pending = Vec::new();
+    for (chunk, ok) in chunks.iter().zip(intact) {
+        if ok {
+            task_pb.inc(chunk.size());
+            file_progress.add(chunk.size()).await;
+        } else {
+            pending.push(chunk);
+        }
+    }
+    debug!(
+        "{}: fetching {} of {} chunks",
+        dest,
+        pending.len(),
+        chunks.len()
+    );
+
+    for chunk in pending {
+        match fetch_chunk_with_cdns(
+            client,
+            config,
+            dest,
+            mapped,
+            path,
+            chunk,
+            log_file,
+            cancel,
+            file_progress,
+            task_pb,
+            retry,
+        )
+        .await
+        {
+            CdnDownloadResult::Success(_) => {}
+            failed => return failed,
+        }
+    }
+
+    CdnDownloadResult::Success(None)
+}
+
+/// Cuts a partial left by an unclean exit back to the bytes the journal saw
+/// synced, so a resume never builds on a tail the filesystem may have lost.
+async fn recover_partial(
+    path: &Path,
+    expected_size: Option<u64>,
+    journal: &WriteJournal,
+    log_file: &SharedLogFile,
+) {
+    let Some(trusted) = journal.trusted_len(path, expected_size) else {
+        return;
+    };
+    let local_size = file_size(path).await;
+    if local_size <= trusted {
+        return;
+    }
+    let truncated = match tokio::fs::OpenOptions::new().write(true).open(path).await {
+        Ok(file) => file.set_len(trusted).await,
+        Err(e) => Err(e),
+    };
+    match truncated {
+        Ok(()) => log_warn(
+            log_file,
+            &format!(
+                "Dropped {} unsynced bytes of {} left by an unclean exit",
+                local_size - trusted,
+                short_path(path).display()
+            ),
+        ),
+        // Resuming from the whole partial is what happened before journaling;
+        // post-download verification still catches a bad tail.
+        Err(e) => log_warn(
+            log_file,
+            &format!("Failed to trim {}: {}", short_path(path).display(), e),
+        ),
+    }
+}
+
+/// `--verify-partials`: fetches the last `tail` bytes (at most 1 MiB) of the
+/// partial at `path` again and compares them with what is on disk, so a
+/// corrupt partial is restarted now instead of failing its checksum after
+/// the rest arrives. `None` when no CDN could serve the range.
+#[allow(clippy::too_many_arguments)]
+async fn partial_tail_matches(
+    client: &Client,
+    config: &Config,
+    dest: &str,
+    mapped: Option<&str>,
+    path: &Path,
+    tail: u64,
+    cancel: &Cancellation<'_>,
+    tracker: &CdnTracker,
+) -> Option<bool> {
+    let local_size = file_size(path).await;
+    if local_size == 0 || tail == 0 {
+        return None;
+    }
+    let start = local_size - tail.min(MAX_PARTIAL_TAIL).min(local_size);
+    let mut local = vec![0; (local_size - start) as usize];
+    let mut file = tokio::fs::File::open(path).await.ok()?;
+    file.seek(SeekFrom::Start(start)).await.ok()?;
+    file.read_exact(&mut local).await.ok()?;
+
+    for (source, base_url, url) in source_urls(config, dest, mapped, tracker) {
+        if let Some(base_url) = base_url
+            && tracker.skip(base_url, &config.zip_bases)
+        {
+            debug!("Skipping benched {} for {}", source, dest);
+            continue;
+        }
+        let request = client
+            .get(url)
+            .header("Range", format!("bytes={}-{}", start, local_size - 1));
+        let response = tokio::select! {
+            _ = cancel.wait() => return None,
+            resp = pacing::send(request, RequestKind::Metadata) => resp,
+        };
+        let Ok(response) = response else { continue };
+        // A full 200 body is not worth fetching just for the check.
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            continue;
+        }
+        let Ok(remote) = response.bytes().await else {
+            continue;
+        };
+        debug!(
+            "{}: checked bytes {}-{} of the partial",
+            dest,
+            start,
+            local_size - 1
+        );
+        return Some(remote[..] == local[..]);
+    }
+    None
+}
+
+pub async fn download_file(job: &DownloadJob<'_>) -> Result<DownloadedFile, String> {
+    let DownloadJob {
+        client,
+        config,
+        item,
+        expected_size,
+        folder,
+        log_file,
+        should_stop,
+        control,
+        progress,
+        total_pb,
+        task_pb,
+        options,
+    } = *job;
+    let dest = &item.dest;
+    let checksum = item.checksum.as_ref();
+    let chunks = &item.chunks;
+    let cancel = Cancellation::new(should_stop, control);
+    if cancel.is_cancelled() {
+        return Err(cancel.reason());
+    }
+    let started = Instant::now();
+
     let normalized_dest = dest.replace('\\', "/");
-    let path = folder.join(&normalized_dest);
+    let mapped = options.url_map.get(dest);
+    let final_path = match resolve_dest(folder, dest) {
+        Ok(path) => path,
+        Err(msg) => {
+            log_error(log_file, &msg);
+            return Err(msg);
+        }
+    };
+    let path = part_path(&final_path);
     let filename = get_filename(&normalized_dest);
-    let mut counted_bytes_for_file = 0_u64;
-    let track_total = expected_size.is_some();
 
     if let Some(total) = expected_size {
         task_pb.set_length(total);
@@ -436,39 +1066,107 @@ pub async fn download_file(
     if let Some(parent) = path.parent()
         && let Err(e) = tokio::fs::create_dir_all(parent).await
     {
-        log_error(
-            log_file,
-            &format!("Directory error for {}: {}", normalized_dest, e),
-        );
+        let msg = format!("Directory error for {}: {}", normalized_dest, e);
+        log_error(log_file, &msg);
         task_pb.set_message(format!("directory error: {}", e));
-        return false;
+        return Err(msg);
     }
 
-    let first_pass = try_download_with_cdns(
-        client,
-        config,
-        &normalized_dest,
-        &path,
-        log_file,
-        should_stop,
-        progress,
-        total_pb,
-        task_pb,
-        true,
-        &mut counted_bytes_for_file,
-        track_total,
-    )
-    .await;
-
-    match first_pass {
-        CdnDownloadResult::Interrupted => return false,
-        CdnDownloadResult::Success => {}
+    if let Err(msg) = ensure_within(folder, &path) {
+        log_error(log_file, &msg);
+        return Err(msg);
+    }
+
+    // A truncated file left under the final name by an older version becomes
+    // the partial, so it is resumed instead of downloaded again.
+    if !path.exists() && final_path.is_file() {
+        let _ = tokio::fs::rename(&final_path, &path).await;
+    }
+
+    if options.preallocate
+        && let Some(size) = expected_size
+        && let Err(e) = reserve_space(&path, size)
+    {
+        let msg = format!("Not enough disk space for {}: {}", normalized_dest, e);
+        log_error(log_file, &msg);
+        task_pb.set_message(format!("disk full: {}", filename));
+        return Err(msg);
+    }
+
+    if chunks.is_empty() {
+        recover_partial(&path, expected_size, &options.journal, log_file).await;
+        if let Some(tail) = options.verify_partials
+            && partial_tail_matches(
+                client,
+                config,
+                &normalized_dest,
+                mapped,
+                &path,
+                tail,
+                &cancel,
+                &options.cdns,
+            )
+            .await
+                == Some(false)
+        {
+            log_warn(
+                log_file,
+                &format!(
+                    "Partial of {} does not match the CDN, restarting it",
+                    normalized_dest
+                ),
+            );
+            remove_partial_file(&path).await;
+        }
+    }
+
+    let mut file_progress = FileProgress::new(progress, total_pb, expected_size.is_some());
+    file_progress.log_urls(&options.url_log, item);
+    let first_pass = if chunks.is_empty() {
+        try_download_with_cdns(
+            client,
+            config,
+            &normalized_dest,
+            mapped,
+            &path,
+            expected_size,
+            checksum,
+            log_file,
+            &cancel,
+            &mut file_progress,
+            task_pb,
+            true,
+            &options.retry,
+            &options.journal,
+        )
+        .await
+    } else {
+        download_chunks(
+            client,
+            config,
+            &normalized_dest,
+            mapped,
+            &path,
+            expected_size,
+            chunks,
+            log_file,
+            &cancel,
+            &mut file_progress,
+            task_pb,
+            &options.retry,
+        )
+        .await
+    };
+
+    let outcome = match first_pass {
+        CdnDownloadResult::Interrupted => Err(cancel.reason()),
+        CdnDownloadResult::Success(digest) => Ok(digest),
         CdnDownloadResult::RetryWithoutResume => {
             task_pb.set_message(format!(
                 "CDN does not support resume, restarting {}",
                 filename.yellow()
             ));
-            rollback_counted_bytes(progress, total_pb, &mut counted_bytes_for_file).await;
+            file_progress.rollback().await;
             remove_partial_file(&path).await;
             task_pb.set_position(0);
 
@@ -476,91 +1174,128 @@ pub async fn download_file(
                 client,
                 config,
                 &normalized_dest,
+                mapped,
                 &path,
+                expected_size,
+                checksum,
                 log_file,
-                should_stop,
-                progress,
-                total_pb,
+                &cancel,
+                &mut file_progress,
                 task_pb,
                 false,
-                &mut counted_bytes_for_file,
-                track_total,
+                &options.retry,
+                &options.journal,
             )
             .await
             {
-                CdnDownloadResult::Success => {}
-                CdnDownloadResult::Interrupted => return false,
+                CdnDownloadResult::Success(digest) => Ok(digest),
+                CdnDownloadResult::Interrupted => Err(cancel.reason()),
                 CdnDownloadResult::RetryWithoutResume => {
-                    log_error(
-                        log_file,
-                        &format!("No CDN supports full redownload for {}", normalized_dest),
-                    );
-                    return false;
+                    let msg = format!("No CDN supports full redownload for {}", normalized_dest);
+                    log_error(log_file, &msg);
+                    Err(msg)
                 }
                 CdnDownloadResult::Failed(err) => {
-                    log_error(
-                        log_file,
-                        &format!(
-                            "Failed downloading {} after fallback: {}",
-                            normalized_dest, err
-                        ),
+                    let msg = format!(
+                        "Failed downloading {} after fallback: {}",
+                        normalized_dest, err
                     );
-                    return false;
+                    log_error(log_file, &msg);
+                    Err(msg)
                 }
             }
         }
         CdnDownloadResult::Failed(err) => {
-            log_error(
-                log_file,
-                &format!("All CDNs failed for {}: {}", normalized_dest, err),
-            );
-            return false;
+            let msg = format!("All CDNs failed for {}: {}", normalized_dest, err);
+            log_error(log_file, &msg);
+            Err(msg)
         }
-    }
+    };
 
-    true
+    match outcome {
+        Ok(digest) => {
+            if let Err(e) = options.journal.forget(&path) {
+                log_warn(
+                    log_file,
+                    &format!("Failed to update the write journal: {}", e),
+                );
+            }
+            Ok(DownloadedFile {
+                digest,
+                counted_bytes: file_progress.counted(),
+                stats: TransferStats {
+                    duration: started.elapsed(),
+                    ..file_progress.stats()
+                },
+            })
+        }
+        Err(msg) => {
+            // A stopped session keeps its partials counted; anything else that
+            // gave up takes its bytes back out of the total.
+            if !should_stop.load(Ordering::SeqCst) {
+                file_progress.rollback().await;
+            }
+            Err(msg)
+        }
+    }
 }
 
-pub fn ask_download_mode(_client: &Client) -> Result<String, String> {
-    println!("\n{} Download Mode Selection", Status::info());
-    println!(
-        "{} 1. Latest game versions (from official sources)",
-        Status::question()
-    );
-    println!(
-        "{} 2. Custom version (provide resource URLs)",
-        Status::question()
-    );
+pub fn ask_download_mode(prompter: &dyn Prompter) -> Result<String, String> {
+    prompter.say(&format!(
+        "\n{} {}",
+        Status::info(),
+        Msg::DownloadModeSelection.text()
+    ));
+    prompter.say(&format!(
+        "{} {}",
+        Status::question(),
+        Msg::ModeLatest.text()
+    ));
+    prompter.say(&format!(
+        "{} {}",
+        Status::question(),
+        Msg::ModeCustom.text()
+    ));
 
     loop {
-        print!("\n{} Choose download mode (1 or 2): ", Status::question());
-        io::stdout()
-            .flush()
-            .map_err(|e| format!("Failed to flush stdout: {}", e))?;
-
-        let input = read_line().map_err(|e| format!("Failed to read input: {}", e))?;
-
-        match input.trim() {
-            "1" => return Ok("latest".to_string()),
-            "2" => return Ok("custom".to_string()),
-            _ => println!("{} Invalid choice, please enter 1 or 2", Status::error()),
+        let (input, preset) = prompter
+            .ask(
+                &format!("\n{} {}", Status::question(), Msg::ChooseMode.text()),
+                Some(MODE_ENV),
+            )
+            .map_err(read_error)?;
+
+        match input.trim().to_ascii_lowercase().as_str() {
+            "1" | "latest" => return Ok("latest".to_string()),
+            "2" | "custom" => return Ok("custom".to_string()),
+            _ if preset => return Err(invalid_preset(MODE_ENV, &input)),
+            _ => prompter.say(&format!(
+                "{} {}",
+                Status::error(),
+                Msg::InvalidChoiceOneOrTwo.text()
+            )),
         }
     }
 }
 
-pub fn get_custom_config(_client: &Client) -> Result<Config, String> {
-    println!("\n{} Custom Version Configuration", Status::info());
-
-    print!("{} Enter resource.json URL: ", Status::question());
-    io::stdout()
-        .flush()
-        .map_err(|e| format!("Failed to flush stdout: {}", e))?;
-
-    let index_url = read_line().map_err(|e| format!("Failed to read input: {}", e))?;
+/// `bases` (from `--base-url` or `--cdn-list`) replace the base URL prompt.
+pub fn get_custom_config(prompter: &dyn Prompter, bases: &[String]) -> Result<Config, String> {
+    prompter.say(&format!(
+        "\n{} {}",
+        Status::info(),
+        Msg::CustomConfig.text()
+    ));
+
+    let (index_url, _) = prompter
+        .ask(
+            &format!("{} {}", Status::question(), Msg::EnterIndexUrl.text()),
+            None,
+        )
+        .map_err(read_error)?;
 
     let index_url = index_url.trim();
     if index_url.is_empty() {
-        return Err("Resource JSON URL cannot be empty".to_string());
+        return Err(Msg::EmptyIndexUrl.text().to_string());
     }
 
     let index_url = if index_url.starts_with("http://") || index_url.starts_with("https://") {
@@ -569,19 +1304,24 @@ pub fn get_custom_config(_client: &Client) -> Result<Config, String> {
         format!("https://{}", index_url)
     };
 
-    print!(
-        "{} Enter resource base path URL (ending with /zip): ",
-        Status::question()
-    );
-    io::stdout()
-        .flush()
-        .map_err(|e| format!("Failed to flush stdout: {}", e))?;
+    if !bases.is_empty() {
+        info!(target: SUCCESS, "{}", Msg::ConfigLoaded.text());
+        return Ok(Config {
+            index_url,
+            zip_bases: bases.to_vec(),
+        });
+    }
 
-    let base_url = read_line().map_err(|e| format!("Failed to read input: {}", e))?;
+    let (base_url, _) = prompter
+        .ask(
+            &format!("{} {}", Status::question(), Msg::EnterBaseUrl.text()),
+            None,
+        )
+        .map_err(read_error)?;
 
     let base_url = base_url.trim().to_string();
     if base_url.is_empty() {
-        return Err("Resource base path URL cannot be empty".to_string());
+        return Err(Msg::EmptyBaseUrl.text().to_string());
     }
 
     let base_url = if base_url.starts_with("http://") || base_url.starts_with("https://") {
@@ -596,29 +1336,35 @@ pub fn get_custom_config(_client: &Client) -> Result<Config, String> {
         format!("{}/", base_url)
     };
 
-    println!("\n{} Configuration loaded successfully", Status::success());
+    info!(target: SUCCESS, "{}", Msg::ConfigLoaded.text());
     Ok(Config {
         index_url,
         zip_bases: vec![base_url],
     })
 }
 
-pub async fn get_config(client: &Client) -> Result<Config, String> {
-    let mode = ask_download_mode(client)?;
+/// `source_url` replaces the built-in version list, e.g. with a self-hosted mirror.
+pub async fn get_config(
+    client: &Client,
+    prompter: &dyn Prompter,
+    source_url: Option<&str>,
+    bases: &[String],
+) -> Result<Config, String> {
+    let mode = ask_download_mode(prompter)?;
 
     if mode == "custom" {
-        return get_custom_config(client);
+        return get_custom_config(prompter, bases);
     }
 
-    let selected_index_url = fetch_gist(client).await?;
+    let selected_index_url = fetch_gist(client, prompter, source_url).await?;
 
-    clear_screen();
-    println!("{} Fetching download configuration...", Status::info());
+    prompter.clear();
+    info!("{}", Msg::FetchingConfig.text());
 
-    let response = client
+    let request = client
         .get(&selected_index_url)
-        .timeout(Duration::from_secs(30))
-        .send()
+        .timeout(Duration::from_secs(30));
+    let response = pacing::send(request, RequestKind::Metadata)
         .await
         .map_err(|e| format!("Network error: {}", e))?;
 
@@ -628,34 +1374,42 @@ pub async fn get_config(client: &Client) -> Result<Config, String> {
 
     let config_text = decompress_if_gzipped(response).await?;
     let config: Value = from_str(&config_text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    select_config(prompter, &config)
+}
 
+/// Picks the default or predownload config of a version file, asking when
+/// both exist and falling back to the other config's CDN list or to CDNs
+/// typed in when the chosen one has none.
+fn select_config(prompter: &dyn Prompter, config: &Value) -> Result<Config, String> {
     let has_default = config.get("default").is_some();
     let has_predownload = config.get("predownload").is_some();
 
     let selected_config = match (has_default, has_predownload) {
         (true, false) => {
-            println!("{} Using default.config", Status::info());
+            info!("{}", Msg::UsingConfig.fill(&[&"default"]));
             "default"
         }
         (false, true) => {
-            println!("{} Using predownload.config", Status::info());
+            info!("{}", Msg::UsingConfig.fill(&[&"predownload"]));
             "predownload"
         }
         (true, true) => loop {
-            print!(
-                "{} Choose config to use (1=default, 2=predownload): ",
-                Status::question()
-            );
-            io::stdout()
-                .flush()
-                .map_err(|e| format!("Failed to flush stdout: {}", e))?;
-
-            let input = read_line().map_err(|e| format!("Failed to read input: {}", e))?;
-
-            match input.trim() {
-                "1" => break "default",
-                "2" => break "predownload",
-                _ => println!("{} Invalid choice, please enter 1 or 2", Status::error()),
+            let (input, preset) = prompter
+                .ask(
+                    &format!("{} {}", Status::question(), Msg::ChooseConfig.text()),
+                    Some(CONFIG_ENV),
+                )
+                .map_err(read_error)?;
+
+            match input.trim().to_ascii_lowercase().as_str() {
+                "1" | "default" => break "default",
+                "2" | "predownload" => break "predownload",
+                _ if preset => return Err(invalid_preset(CONFIG_ENV, &input)),
+                _ => prompter.say(&format!(
+                    "{} {}",
+                    Status::error(),
+                    Msg::InvalidChoiceOneOrTwo.text()
+                )),
             }
         },
         (false, false) => {
@@ -686,49 +1440,44 @@ pub async fn get_config(client: &Client) -> Result<Config, String> {
     let mut cdn_urls = Vec::new();
     let mut cdn_list_opt = config_data.get("cdnList").and_then(Value::as_array);
 
-    if cdn_list_opt.as_ref().map_or(true, |list| list.is_empty()) {
+    if cdn_list_opt.as_ref().is_none_or(|list| list.is_empty()) {
         let other_config = if selected_config == "default" {
             "predownload"
         } else {
             "default"
         };
-        if let Some(other_data) = config.get(other_config) {
-            if let Some(list) = other_data.get("cdnList").and_then(Value::as_array) {
-                if !list.is_empty() {
-                    println!(
-                        "{} CDN list missing in '{}', but found in '{}'.",
-                        Status::warning(),
-                        selected_config,
-                        other_config
-                    );
+        if let Some(other_data) = config.get(other_config)
+            && let Some(list) = other_data.get("cdnList").and_then(Value::as_array)
+            && !list.is_empty()
+        {
+            prompter.say(&format!(
+                "{} {}",
+                Status::warning(),
+                Msg::CdnListElsewhere.fill(&[&selected_config, &other_config])
+            ));
 
-                    loop {
-                        print!(
-                            "{} Do you want to use the CDN list from '{}'? [Y/n]: ",
+            loop {
+                let (input, preset) = prompter
+                    .ask(
+                        &format!(
+                            "{} {}",
                             Status::question(),
-                            other_config
-                        );
-                        io::stdout()
-                            .flush()
-                            .map_err(|e| format!("Failed to flush stdout: {}", e))?;
-
-                        let input =
-                            read_line().map_err(|e| format!("Failed to read input: {}", e))?;
-
-                        match input.trim().to_lowercase().as_str() {
-                            "y" | "yes" | "" => {
-                                cdn_list_opt = Some(list);
-                                break;
-                            }
-                            "n" | "no" => {
-                                break;
-                            }
-                            _ => println!(
-                                "{} Invalid choice, please press Enter for Yes, or 'n' for No",
-                                Status::error()
-                            ),
-                        }
+                            Msg::UseOtherCdnList.fill(&[&other_config])
+                        ),
+                        Some(CONFIRM_ENV),
+                    )
+                    .map_err(read_error)?;
+
+                match input.trim().to_lowercase().as_str() {
+                    "y" | "yes" | "" => {
+                        cdn_list_opt = Some(list);
+                        break;
+                    }
+                    "n" | "no" => {
+                        break;
                     }
+                    _ if preset => return Err(invalid_preset(CONFIRM_ENV, &input)),
+                    _ => prompter.say(&format!("{} {}", Status::error(), Msg::InvalidYesNo.text())),
                 }
             }
         }
@@ -743,13 +1492,17 @@ pub async fn get_config(client: &Client) -> Result<Config, String> {
     }
 
     if cdn_urls.is_empty() {
-        println!("{} Please enter CDN URLs manually.", Status::info());
-        print!("{} Enter CDN URLs (comma-separated): ", Status::question());
-        io::stdout()
-            .flush()
-            .map_err(|e| format!("Failed to flush stdout: {}", e))?;
-
-        let input = read_line().map_err(|e| format!("Failed to read input: {}", e))?;
+        prompter.say(&format!(
+            "{} {}",
+            Status::info(),
+            Msg::EnterCdnsManually.text()
+        ));
+        let (input, _) = prompter
+            .ask(
+                &format!("{} {}", Status::question(), Msg::EnterCdns.text()),
+                None,
+            )
+            .map_err(read_error)?;
 
         cdn_urls = input
             .trim()
@@ -760,93 +1513,379 @@ pub async fn get_config(client: &Client) -> Result<Config, String> {
     }
 
     if cdn_urls.is_empty() {
-        return Err("No valid CDN URLs found".to_string());
+        return Err(Msg::NoCdnUrls.text().to_string());
     }
 
-    let full_index_url = build_download_url(&cdn_urls[0], index_file);
-    let zip_bases = cdn_urls
-        .iter()
-        .map(|cdn| build_download_url(cdn, base_url))
-        .collect();
-
-    Ok(Config {
-        index_url: full_index_url,
-        zip_bases,
-    })
+    Ok(assemble_config(&cdn_urls, base_url, index_file))
 }
 
-pub async fn fetch_gist(client: &Client) -> Result<String, String> {
-    let response = client
-        .get(INDEX_URL)
-        .timeout(Duration::from_secs(30))
-        .send()
+/// Downloads the version list (the built-in gist unless `source_url` is set)
+/// and checks it offers every channel.
+pub async fn fetch_version_list(
+    client: &Client,
+    source_url: Option<&str>,
+) -> Result<Value, String> {
+    let source_url = source_url.unwrap_or(SOURCE_URL);
+    let request = client.get(source_url).timeout(Duration::from_secs(30));
+    let response = pacing::send(request, RequestKind::Metadata)
         .await
-        .map_err(|e| format!("Network error: {}", e))?;
+        .map_err(|e| format!("Network error fetching version list {}: {}", source_url, e))?;
 
     if !response.status().is_success() {
-        return Err(format!("Server error: HTTP {}", response.status()));
+        return Err(format!(
+            "Server error fetching version list {}: HTTP {}",
+            source_url,
+            response.status()
+        ));
     }
 
     let gist_data_text = decompress_if_gzipped(response).await?;
-    let gist_data: Value = from_str(&gist_data_text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let gist_data: Value = from_str(&gist_data_text)
+        .map_err(|e| format!("Version list {} is not valid JSON: {}", source_url, e))?;
+    validate_version_list(&gist_data)
+        .map_err(|e| format!("Unexpected version list format at {}: {}", source_url, e))?;
+    Ok(gist_data)
+}
 
-    clear_screen();
+pub async fn fetch_json(client: &Client, url: &str) -> Result<Value, String> {
+    let request = client.get(url).timeout(Duration::from_secs(30));
+    let response = pacing::send(request, RequestKind::Metadata)
+        .await
+        .map_err(|e| format!("Network error fetching {}: {}", url, e))?;
 
-    let entries = [
-        ("live", "os", "Live - OS"),
-        ("live", "cn", "Live - CN"),
-        ("beta", "os", "Beta - OS"),
-        ("beta", "cn", "Beta - CN"),
-    ];
+    if !response.status().is_success() {
+        return Err(format!(
+            "Server error fetching {}: HTTP {}",
+            url,
+            response.status()
+        ));
+    }
 
-    println!("{} Available versions:", Status::info());
+    let text = decompress_if_gzipped(response).await?;
+    from_str(&text).map_err(|e| format!("Invalid JSON from {}: {}", url, e))
+}
 
-    for (i, (cat, ver, label)) in entries.iter().enumerate() {
-        let index_url = get_version(&gist_data, cat, ver)?;
+/// Game version advertised by a `default` or `predownload` config.
+pub fn config_version<'a>(version_json: &'a Value, selected: &str) -> &'a str {
+    version_json
+        .get(selected)
+        .and_then(|d| d.get("config"))
+        .and_then(|c| c.get("version"))
+        .or_else(|| version_json.get(selected).and_then(|d| d.get("version")))
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown")
+}
 
-        let resp = match client
-            .get(&index_url)
-            .timeout(Duration::from_secs(30))
-            .send()
-            .await
-        {
-            Ok(resp) => resp,
+/// Non-interactive [`get_config`] for one config of a version file. A missing
+/// CDN list is taken from the other config without asking, and is an error
+/// when neither has one.
+pub fn channel_config(version_json: &Value, selected: &str) -> Result<Config, String> {
+    let config_data = version_json
+        .get(selected)
+        .ok_or_else(|| format!("Missing {} config in response", selected))?;
+    let base_config = config_data
+        .get("config")
+        .ok_or_else(|| format!("Missing config in {} response", selected))?;
+    let base_url = base_config
+        .get("baseUrl")
+        .and_then(Value::as_str)
+        .ok_or("Missing or invalid baseUrl")?;
+    let index_file = base_config
+        .get("indexFile")
+        .and_then(Value::as_str)
+        .ok_or("Missing or invalid indexFile")?;
+
+    let cdn_list = |data: &Value| -> Vec<String> {
+        data.get("cdnList")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|cdn| cdn.get("url").and_then(Value::as_str))
+            .map(|url| url.trim_end_matches('/').to_string())
+            .collect()
+    };
+    let other = if selected == "default" {
+        "predownload"
+    } else {
+        "default"
+    };
+    let mut cdn_urls = cdn_list(config_data);
+    if cdn_urls.is_empty()
+        && let Some(other_data) = version_json.get(other)
+    {
+        cdn_urls = cdn_list(other_data);
+    }
+    if cdn_urls.is_empty() {
+        return Err(format!("No CDN list in {} config", selected));
+    }
+
+    Ok(assemble_config(&cdn_urls, base_url, index_file))
+}
+
+/// Non-interactive [`get_config`]: `selected` config of the `channel`/`region`
+/// version file from the version list.
+pub async fn fetch_channel_config(
+    client: &Client,
+    source_url: Option<&str>,
+    channel: &str,
+    region: &str,
+    selected: &str,
+) -> Result<Config, String> {
+    let versions = fetch_version_list(client, source_url).await?;
+    let version_json = fetch_json(client, &get_version(&versions, channel, region)?).await?;
+    if version_json.get(selected).is_none() {
+        return Err(format!(
+            "{} - {} has no {} config",
+            channel,
+            region.to_uppercase(),
+            selected
+        ));
+    }
+    info!(
+        "Using {} {} - {} ({})",
+        selected,
+        channel,
+        region.to_uppercase(),
+        config_version(&version_json, selected).cyan()
+    );
+    channel_config(&version_json, selected)
+}
+
+fn assemble_config(cdn_urls: &[String], base_url: &str, index_file: &str) -> Config {
+    Config {
+        index_url: build_download_url(&cdn_urls[0], index_file),
+        zip_bases: cdn_urls
+            .iter()
+            .map(|cdn| build_download_url(cdn, base_url))
+            .collect(),
+    }
+}
+
+/// One channel of the version list, with its version file if it could be fetched.
+pub struct VersionEntry {
+    pub category: &'static str,
+    pub region: &'static str,
+    pub url: String,
+    pub json: Option<Value>,
+}
+
+impl VersionEntry {
+    pub fn label(&self) -> String {
+        let mut category = self.category.chars();
+        let category: String = category
+            .next()
+            .map(|first| first.to_uppercase().chain(category).collect())
+            .unwrap_or_default();
+        format!("{} - {}", category, self.region.to_uppercase())
+    }
+}
+
+/// Every channel of the version list, live/beta × os/cn, with its version
+/// file; a version file that fails to load is logged and left `None`.
+pub async fn fetch_versions(
+    client: &Client,
+    source_url: Option<&str>,
+) -> Result<Vec<VersionEntry>, String> {
+    let gist_data = fetch_version_list(client, source_url).await?;
+    let mut entries = Vec::new();
+    for (category, region) in VERSION_CHANNELS {
+        let url = get_version(&gist_data, category, region)?;
+        let json = match fetch_json(client, &url).await {
+            Ok(json) => Some(json),
             Err(e) => {
-                println!("{} Failed to fetch {}: {}", Status::warning(), index_url, e);
-                continue;
+                warn!("{}", e);
+                None
             }
         };
+        entries.push(VersionEntry {
+            category,
+            region,
+            url,
+            json,
+        });
+    }
+    Ok(entries)
+}
 
-        let version_json: Value = {
-            let version_text = decompress_if_gzipped(resp)
-                .await
-                .unwrap_or_else(|_| "{}".to_string());
-            from_str(&version_text).unwrap_or(Value::Null)
-        };
-
-        let version = version_json
-            .get("default")
-            .and_then(|d| d.get("config"))
-            .and_then(|c| c.get("version"))
-            .or_else(|| version_json.get("default").and_then(|d| d.get("version")))
-            .and_then(|v| v.as_str())
-            .unwrap_or("unknown");
+pub async fn fetch_gist(
+    client: &Client,
+    prompter: &dyn Prompter,
+    source_url: Option<&str>,
+) -> Result<String, String> {
+    let entries = fetch_versions(client, source_url).await?;
+    prompter.clear();
+    choose_version(prompter, &entries)
+}
 
-        println!("{}. {} ({})", i + 1, label, version);
+/// Lists `entries` and returns the index URL of the one picked.
+fn choose_version(prompter: &dyn Prompter, entries: &[VersionEntry]) -> Result<String, String> {
+    prompter.say(&format!(
+        "{} {}",
+        Status::info(),
+        Msg::AvailableVersions.text()
+    ));
+
+    for (i, entry) in entries.iter().enumerate() {
+        let version = entry
+            .json
+            .as_ref()
+            .map_or("unknown", |json| config_version(json, "default"));
+        prompter.say(&format!("{}. {} ({})", i + 1, entry.label(), version));
     }
 
     loop {
-        print!("{} Select version: ", Status::question());
-        io::stdout().flush().unwrap();
+        let (input, preset) = prompter
+            .ask(
+                &format!("{} {}", Status::question(), Msg::SelectVersion.text()),
+                Some(VERSION_ENV),
+            )
+            .map_err(read_error)?;
+
+        let chosen = version_choice(&input).and_then(|(category, region)| {
+            entries
+                .iter()
+                .find(|entry| entry.category == category && entry.region == region)
+        });
+        match chosen {
+            Some(entry) => return Ok(entry.url.clone()),
+            None if preset => return Err(invalid_preset(VERSION_ENV, &input)),
+            None => prompter.say(&format!(
+                "{} {}",
+                Status::error(),
+                Msg::InvalidSelection.text()
+            )),
+        }
+    }
+}
 
-        let input = read_line().map_err(|e| format!("Failed to read input: {}", e))?;
+/// The `(category, region)` a version prompt answer picks: its number in the
+/// list or a name such as `live-os`.
+fn version_choice(input: &str) -> Option<(&'static str, &'static str)> {
+    let input = input.trim().to_ascii_lowercase();
+    VERSION_CHANNELS
+        .iter()
+        .enumerate()
+        .find(|(i, (category, region))| {
+            input == (i + 1).to_string() || input == format!("{}-{}", category, region)
+        })
+        .map(|(_, channel)| *channel)
+}
 
-        match input.trim() {
-            "1" => return get_version(&gist_data, "live", "os"),
-            "2" => return get_version(&gist_data, "live", "cn"),
-            "3" => return get_version(&gist_data, "beta", "os"),
-            "4" => return get_version(&gist_data, "beta", "cn"),
-            _ => println!("{} Invalid selection", Status::error()),
+#[cfg(test)]
+mod tests {
+    use super::{
+        VersionEntry, build_download_url, channel_config, choose_version, config_version,
+        select_config, version_choice,
+    };
+    use crate::io::prompt::ScriptedPrompter;
+    use serde_json::json;
+
+    #[test]
+    fn build_download_url_joins_with_single_slashes() {
+        let expected = "https://cdn.example.com/zip/Client/Paks/a.pak";
+        for base in [
+            "https://cdn.example.com/zip",
+            "https://cdn.example.com/zip/",
+            "https://cdn.example.com//zip//",
+        ] {
+            for dest in [
+                "Client/Paks/a.pak",
+                "/Client/Paks/a.pak",
+                "Client\\Paks//a.pak",
+            ] {
+                assert_eq!(build_download_url(base, dest), expected, "{base} + {dest}");
+            }
         }
+        assert_eq!(
+            build_download_url("https://cdn.example.com/", "/launcher/zip/"),
+            "https://cdn.example.com/launcher/zip"
+        );
+        assert_eq!(
+            build_download_url("/launcher/zip/", "a.pak"),
+            "/launcher/zip/a.pak"
+        );
+        assert_eq!(
+            build_download_url("file:///srv/mirror/", "a.pak"),
+            "file:///srv/mirror/a.pak"
+        );
+    }
+
+    #[test]
+    fn version_choice_takes_numbers_and_names() {
+        assert_eq!(version_choice(" 2\n"), Some(("live", "cn")));
+        assert_eq!(version_choice("Beta-OS"), Some(("beta", "os")));
+        assert_eq!(version_choice("5"), None);
+        assert_eq!(version_choice("live"), None);
+    }
+
+    #[test]
+    fn channel_config_borrows_the_cdn_list_of_the_other_config() {
+        let version_json = json!({
+            "default": {
+                "config": {"version": "2.4.0", "baseUrl": "/zip", "indexFile": "/index.json"},
+                "cdnList": [{"url": "https://cdn-a/"}, {"url": "https://cdn-b"}],
+            },
+            "predownload": {
+                "config": {"version": "2.5.0", "baseUrl": "/pre", "indexFile": "/pre.json"},
+            },
+        });
+
+        let config = channel_config(&version_json, "predownload").unwrap();
+        assert_eq!(config.index_url, "https://cdn-a/pre.json");
+        assert_eq!(config.zip_bases, ["https://cdn-a/pre", "https://cdn-b/pre"]);
+        assert_eq!(config_version(&version_json, "predownload"), "2.5.0");
+        assert!(channel_config(&json!({"default": {"config": {}}}), "default").is_err());
+    }
+
+    #[test]
+    fn choose_version_asks_again_after_an_invalid_answer() {
+        let entries = vec![
+            VersionEntry {
+                category: "live",
+                region: "os",
+                url: "https://example.com/live-os.json".to_string(),
+                json: Some(json!({"default": {"config": {"version": "2.4.0"}}})),
+            },
+            VersionEntry {
+                category: "beta",
+                region: "cn",
+                url: "https://example.com/beta-cn.json".to_string(),
+                json: None,
+            },
+        ];
+        let prompter = ScriptedPrompter::new(["7", "beta-cn"]);
+
+        let url = choose_version(&prompter, &entries).unwrap();
+        assert_eq!(url, "https://example.com/beta-cn.json");
+        let transcript = prompter.transcript();
+        assert_eq!(transcript[1], "1. Live - OS (2.4.0)");
+        assert_eq!(transcript[2], "2. Beta - CN (unknown)");
+        // Two questions with the error in between.
+        assert_eq!(transcript.len(), 6);
+    }
+
+    #[test]
+    fn select_config_asks_for_the_config_and_a_missing_cdn_list() {
+        let version_json = json!({
+            "default": {
+                "config": {"baseUrl": "/zip", "indexFile": "/index.json"},
+                "cdnList": [{"url": "https://cdn-a/"}],
+            },
+            "predownload": {
+                "config": {"baseUrl": "/pre", "indexFile": "/pre.json"},
+            },
+        });
+
+        let prompter = ScriptedPrompter::new(["predownload", "y"]);
+        let config = select_config(&prompter, &version_json).unwrap();
+        assert_eq!(config.index_url, "https://cdn-a/pre.json");
+
+        // Declining the other list leaves the CDNs to be typed in.
+        let prompter = ScriptedPrompter::new(["2", "n", "https://cdn-b/, https://cdn-c"]);
+        let config = select_config(&prompter, &version_json).unwrap();
+        assert_eq!(config.zip_bases, ["https://cdn-b/pre", "https://cdn-c/pre"]);
+
+        let prompter = ScriptedPrompter::new(["2"]);
+        assert!(select_config(&prompter, &version_json).is_err());
     }
 }
@@ -0,0 +1,111 @@
+use reqwest::{
+    Client, Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::ACCEPT,
+};
+use serde_json::Value;
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+const DOH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `--doh-url`: looks CDN hosts up through a DNS-over-HTTPS server's JSON
+/// API (as offered by Cloudflare and Google) instead of the system resolver,
+/// for networks whose DNS hands out broken or poisoned CDN addresses.
+pub struct DohResolver {
+    /// Has no custom resolver itself, so the DoH server is found through
+    /// the system resolver (or given by IP).
+    client: Client,
+    url: Url,
+}
+
+impl DohResolver {
+    pub fn new(url: &Url) -> reqwest::Result<Self> {
+        Ok(Self {
+            client: Client::builder().timeout(DOH_TIMEOUT).build()?,
+            url: url.clone(),
+        })
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let client = self.client.clone();
+        let url = self.url.clone();
+        Box::pin(async move {
+            let host = name.as_str();
+            let (v4, v6) = tokio::join!(
+                query(&client, &url, host, "A"),
+                query(&client, &url, host, "AAAA")
+            );
+            let mut ips = Vec::new();
+            let mut error = None;
+            for answer in [v4, v6] {
+                match answer {
+                    Ok(found) => ips.extend(found),
+                    Err(e) => error = Some(e),
+                }
+            }
+            if ips.is_empty() {
+                let reason = error.unwrap_or_else(|| "no address".to_string());
+                return Err(format!("DoH lookup of {} failed: {}", host, reason).into());
+            }
+            let addrs: Addrs = Box::new(ips.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+async fn query(client: &Client, url: &Url, host: &str, kind: &str) -> Result<Vec<IpAddr>, String> {
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .append_pair("name", host)
+        .append_pair("type", kind);
+    let response = client
+        .get(url)
+        .header(ACCEPT, "application/dns-json")
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let body = response.json().await.map_err(|e| e.to_string())?;
+    Ok(parse_answers(&body))
+}
+
+/// Addresses from the A and AAAA records of a `application/dns-json`
+/// answer; CNAME records in the chain are skipped.
+fn parse_answers(body: &Value) -> Vec<IpAddr> {
+    body.get("Answer")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|answer| matches!(answer.get("type").and_then(Value::as_u64), Some(1 | 28)))
+        .filter_map(|answer| answer.get("data")?.as_str()?.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_answers;
+    use serde_json::json;
+
+    #[test]
+    fn reads_addresses_and_skips_cnames() {
+        let body = json!({
+            "Status": 0,
+            "Answer": [
+                { "name": "cdn.example.com", "type": 5, "data": "edge.example.net." },
+                { "name": "edge.example.net", "type": 1, "data": "192.0.2.7" },
+                { "name": "edge.example.net", "type": 28, "data": "2001:db8::7" }
+            ]
+        });
+        let ips: Vec<String> = parse_answers(&body)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(ips, ["192.0.2.7", "2001:db8::7"]);
+        assert!(parse_answers(&json!({ "Status": 3 })).is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+use log::debug;
+use percent_encoding::percent_decode_str;
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    thread,
+};
+
+use crate::io::file::resolve_dest;
+use crate::network::metrics::{read_request_with_headers, write_response};
+
+/// `serve`: answers GET and HEAD for every file below `root`, with byte
+/// ranges so other PCs can resume and split downloads like on a CDN. Each
+/// connection gets its own thread; this blocks until the listener fails.
+pub fn serve_folder(listener: TcpListener, root: PathBuf) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let root = root.clone();
+        thread::Builder::new()
+            .name("serve".to_string())
+            .spawn(move || {
+                let _ = respond(stream, &root);
+            })?;
+    }
+    Ok(())
+}
+
+fn respond(stream: TcpStream, root: &Path) -> io::Result<()> {
+    let (method, path, headers, mut stream) = read_request_with_headers(stream)?;
+    if method != "GET" && method != "HEAD" {
+        return write_response(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            "Only GET and HEAD are supported\n",
+        );
+    }
+    let file = local_path(root, &path).and_then(|local| {
+        let file = File::open(local).ok()?;
+        let len = file.metadata().ok().filter(|m| m.is_file())?.len();
+        Some((file, len))
+    });
+    let Some((mut file, len)) = file else {
+        debug!("{} {} -> 404", method, path);
+        return write_response(&mut stream, "404 Not Found", "text/plain", "Not found\n");
+    };
+
+    let range = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("range"))
+        .map(|(_, value)| parse_range(value, len));
+    let (status, start, end) = match range {
+        None => ("200 OK", 0, len),
+        Some(Some((start, end))) => ("206 Partial Content", start, end),
+        Some(None) => {
+            debug!("{} {} -> 416", method, path);
+            write!(
+                stream,
+                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                len
+            )?;
+            return stream.flush();
+        }
+    };
+    debug!("{} {} -> {} ({}-{})", method, path, status, start, end);
+
+    let content_type = if path.ends_with(".json") {
+        "application/json"
+    } else {
+        "application/octet-stream"
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n",
+        status,
+        content_type,
+        end - start
+    )?;
+    if start != 0 || end != len {
+        write!(
+            stream,
+            "Content-Range: bytes {}-{}/{}\r\n",
+            start,
+            end - 1,
+            len
+        )?;
+    }
+    write!(stream, "Connection: close\r\n\r\n")?;
+    if method == "GET" {
+        file.seek(SeekFrom::Start(start))?;
+        io::copy(&mut file.take(end - start), &mut stream)?;
+    }
+    stream.flush()
+}
+
+/// Maps a request path onto `root` the way downloads name files, refusing
+/// anything outside it and hidden entries such as the mirror's staging folder.
+fn local_path(root: &Path, path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(path).decode_utf8().ok()?;
+    let relative = decoded.trim_start_matches('/');
+    if relative.split('/').any(|part| part.starts_with('.')) {
+        return None;
+    }
+    resolve_dest(root, relative).ok()
+}
+
+/// The half-open byte span a `Range: bytes=...` header asks for, or `None`
+/// when it cannot be served.
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    let (first, last) = spec.split_once('-')?;
+    let (start, end) = if first.is_empty() {
+        let suffix: u64 = last.parse().ok()?;
+        (len.saturating_sub(suffix), len)
+    } else {
+        let start: u64 = first.parse().ok()?;
+        let end = if last.is_empty() {
+            len
+        } else {
+            last.parse::<u64>().ok()?.saturating_add(1).min(len)
+        };
+        (start, end)
+    };
+    (start < end).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_range, serve_folder};
+    use crate::io::file::unique_path;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    fn get(addr: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_files_and_ranges_inside_the_folder() {
+        assert_eq!(parse_range("bytes=2-", 10), Some((2, 10)));
+        assert_eq!(parse_range("bytes=-4", 10), Some((6, 10)));
+        assert_eq!(parse_range("bytes=0-99", 10), Some((0, 10)));
+        assert_eq!(parse_range("bytes=10-", 10), None);
+
+        let root = unique_path("serve");
+        std::fs::create_dir_all(root.join("files/Client/My Paks")).unwrap();
+        std::fs::create_dir_all(root.join(".incoming")).unwrap();
+        std::fs::write(root.join("files/Client/My Paks/a.pak"), b"0123456789").unwrap();
+        std::fs::write(root.join(".incoming/b.pak"), b"x").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let served = root.clone();
+        std::thread::spawn(move || serve_folder(listener, served));
+
+        let full = get(addr, "GET /files/Client/My%20Paks/a.pak HTTP/1.1\r\n\r\n");
+        assert!(full.starts_with("HTTP/1.1 200 OK\r\n"), "{}", full);
+        assert!(full.ends_with("\r\n\r\n0123456789"));
+
+        let part = get(
+            addr,
+            "GET /files/Client/My%20Paks/a.pak HTTP/1.1\r\nRange: bytes=4-6\r\n\r\n",
+        );
+        assert!(
+            part.starts_with("HTTP/1.1 206 Partial Content\r\n"),
+            "{}",
+            part
+        );
+        assert!(part.contains("Content-Range: bytes 4-6/10\r\n"));
+        assert!(part.ends_with("\r\n\r\n456"));
+
+        let head = get(addr, "HEAD /files/Client/My%20Paks/a.pak HTTP/1.1\r\n\r\n");
+        assert!(head.contains("Content-Length: 10\r\n"));
+        assert!(head.ends_with("\r\n\r\n"));
+
+        for hidden in [
+            "/.incoming/b.pak",
+            "/files/../files/Client",
+            "/files/Client",
+        ] {
+            let response = get(addr, &format!("GET {} HTTP/1.1\r\n\r\n", hidden));
+            assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+        }
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,154 @@
+use reqwest::{Client, StatusCode, header::RANGE};
+use std::time::{Duration, Instant};
+
+use crate::config::cfg::{Config, ResourceItem};
+use crate::network::client::build_download_url;
+
+/// Bytes `check-cdn` reads from every CDN to estimate its throughput.
+pub const SAMPLE_BYTES: u64 = 4 * 1024 * 1024;
+/// Time allowed per CDN for `SAMPLE_BYTES`; larger samples get proportionally more.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct CdnReport {
+    pub base_url: String,
+    pub status: Result<StatusCode, String>,
+    /// Time until the response headers arrived.
+    pub latency: Option<Duration>,
+    /// Bytes per second while reading the sample, absent when nothing was read.
+    pub throughput: Option<u64>,
+}
+
+impl CdnReport {
+    pub fn is_healthy(&self) -> bool {
+        self.status.as_ref().is_ok_and(|status| status.is_success())
+    }
+}
+
+/// Picks the largest resource so the sample read is not cut short by a tiny file.
+pub fn sample_resource(resources: &[ResourceItem]) -> Option<&ResourceItem> {
+    resources.iter().max_by_key(|item| item.size.unwrap_or(0))
+}
+
+/// Requests the first `sample_bytes` of `sample` from every CDN in turn, so
+/// results are not skewed by the CDNs competing for the same connection.
+pub async fn check_cdns(
+    client: &Client,
+    config: &Config,
+    sample: &ResourceItem,
+    sample_bytes: u64,
+) -> Vec<CdnReport> {
+    let mut reports = Vec::with_capacity(config.zip_bases.len());
+    for base_url in &config.zip_bases {
+        let url = build_download_url(base_url, &sample.dest);
+        reports.push(check_cdn(client, base_url, &url, sample_bytes).await);
+    }
+    reports
+}
+
+/// How long `total_bytes` would take from the CDN downloads start on: the
+/// first healthy one, as every file tries the CDNs in order.
+pub fn estimate_download_time(
+    reports: &[CdnReport],
+    total_bytes: u64,
+) -> Option<(&CdnReport, Duration)> {
+    let report = reports.iter().find(|report| report.is_healthy())?;
+    let speed = report.throughput.filter(|speed| *speed > 0)?;
+    Some((report, Duration::from_secs(total_bytes.div_ceil(speed))))
+}
+
+async fn check_cdn(client: &Client, base_url: &str, url: &str, sample_bytes: u64) -> CdnReport {
+    let started = Instant::now();
+    let timeout = CHECK_TIMEOUT.mul_f64((sample_bytes as f64 / SAMPLE_BYTES as f64).max(1.0));
+    let response = client
+        .get(url)
+        .header(RANGE, format!("bytes=0-{}", sample_bytes - 1))
+        .timeout(timeout)
+        .send()
+        .await;
+
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            return CdnReport {
+                base_url: base_url.to_string(),
+                status: Err(e.to_string()),
+                latency: None,
+                throughput: None,
+            };
+        }
+    };
+
+    let latency = started.elapsed();
+    let status = response.status();
+    let throughput = if status.is_success() {
+        read_sample(response, sample_bytes).await
+    } else {
+        None
+    };
+
+    CdnReport {
+        base_url: base_url.to_string(),
+        status: Ok(status),
+        latency: Some(latency),
+        throughput,
+    }
+}
+
+async fn read_sample(mut response: reqwest::Response, sample_bytes: u64) -> Option<u64> {
+    let started = Instant::now();
+    let mut read = 0u64;
+
+    while read < sample_bytes {
+        match response.chunk().await {
+            Ok(Some(chunk)) => read += chunk.len() as u64,
+            Ok(None) | Err(_) => break,
+        }
+    }
+
+    let elapsed = started.elapsed().as_secs_f64();
+    (read > 0 && elapsed > 0.0).then(|| (read as f64 / elapsed) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CdnReport, estimate_download_time, sample_resource};
+    use crate::config::cfg::ResourceItem;
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    fn item(dest: &str, size: Option<u64>) -> ResourceItem {
+        ResourceItem {
+            dest: dest.to_string(),
+            checksum: None,
+            size,
+            chunks: Vec::new(),
+            unpacked_size: None,
+        }
+    }
+
+    #[test]
+    fn sample_resource_prefers_the_largest_file() {
+        let resources = [item("a", Some(10)), item("b", None), item("c", Some(500))];
+        assert_eq!(sample_resource(&resources).unwrap().dest, "c");
+        assert!(sample_resource(&[]).is_none());
+    }
+
+    #[test]
+    fn estimate_uses_the_first_healthy_cdn() {
+        let report = |base_url: &str, status: u16, throughput: Option<u64>| CdnReport {
+            base_url: base_url.to_string(),
+            status: Ok(StatusCode::from_u16(status).unwrap()),
+            latency: None,
+            throughput,
+        };
+        let reports = [
+            report("a", 503, None),
+            report("b", 206, Some(1000)),
+            report("c", 206, Some(5000)),
+        ];
+        let (cdn, time) = estimate_download_time(&reports, 10_500).unwrap();
+        assert_eq!(cdn.base_url, "b");
+        assert_eq!(time, Duration::from_secs(11));
+        assert!(estimate_download_time(&reports[..1], 10_500).is_none());
+    }
+}
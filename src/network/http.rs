@@ -0,0 +1,367 @@
+use reqwest::{
+    Client, Proxy, Url,
+    header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT},
+};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::network::doh::DohResolver;
+
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+/// Proxy variables reqwest reads on its own, in both spellings.
+const PROXY_VARS: [&str; 8] = [
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+    "NO_PROXY",
+    "no_proxy",
+];
+
+#[derive(Clone, Debug)]
+pub struct HttpOptions {
+    /// Idle keep-alive connections kept open per CDN host.
+    pub pool_size: usize,
+    pub tcp_nodelay: bool,
+    /// Never negotiate HTTP/2, for CDNs or proxies that mishandle it.
+    pub http1_only: bool,
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every index, HEAD and GET request.
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub connect_timeout: Duration,
+    /// Longest wait for the next bytes of a response. Transfers themselves have
+    /// no deadline, so a slow but steady multi-gigabyte download is never cut off.
+    pub read_timeout: Duration,
+    /// Connect only over this address family, for ISPs that route the CDNs
+    /// badly over the other one.
+    pub ip_family: Option<IpFamily>,
+    /// `--resolve` pins: hosts connected to at a fixed address, skipping DNS.
+    pub resolve: Vec<(String, SocketAddr)>,
+    /// DNS-over-HTTPS endpoint used for every other host.
+    pub doh_url: Option<Url>,
+    /// `--proxy-map`: hosts reached through their own HTTP or SOCKS proxy.
+    pub proxy_map: Vec<(String, Url)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    /// Binding to the family's unspecified address makes the connector skip
+    /// every resolved address of the other family.
+    fn local_address(self) -> IpAddr {
+        match self {
+            IpFamily::V4 => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpFamily::V6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        }
+    }
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            pool_size: 16,
+            tcp_nodelay: true,
+            http1_only: false,
+            user_agent: None,
+            headers: Vec::new(),
+            connect_timeout: Duration::from_secs(15),
+            read_timeout: Duration::from_secs(60),
+            ip_family: None,
+            resolve: Vec::new(),
+            doh_url: None,
+            proxy_map: Vec::new(),
+        }
+    }
+}
+
+/// Shared client for the whole session, so the HEAD and GET of a file (and
+/// every later file on the same CDN) reuse one pooled connection. HTTP/2 is
+/// picked through ALPN where the CDN offers it.
+pub fn build_client(options: &HttpOptions) -> reqwest::Result<Client> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &options.headers {
+        headers.append(name.clone(), value.clone());
+    }
+
+    let mut builder = Client::builder()
+        .default_headers(headers)
+        .pool_max_idle_per_host(options.pool_size)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(TCP_KEEPALIVE)
+        .tcp_nodelay(options.tcp_nodelay)
+        .connect_timeout(options.connect_timeout)
+        .read_timeout(options.read_timeout)
+        .local_address(options.ip_family.map(IpFamily::local_address));
+
+    for (host, addr) in &options.resolve {
+        builder = builder.resolve(host, *addr);
+    }
+    if let Some(url) = &options.doh_url {
+        builder = builder.dns_resolver(Arc::new(DohResolver::new(url)?));
+    }
+
+    if !options.proxy_map.is_empty() {
+        // Each proxy keeps its own connections, so every mirror still gets a
+        // pool of its own. Setting a proxy turns off reqwest's own reading of
+        // the proxy variables, so unlisted hosts look them up here instead.
+        let proxy_map = options.proxy_map.clone();
+        let env: Vec<(&str, String)> = PROXY_VARS
+            .iter()
+            .filter_map(|&name| Some((name, std::env::var(name).ok()?)))
+            .collect();
+        builder = builder.proxy(Proxy::custom(move |url| {
+            proxy_for(&proxy_map, url, |name| {
+                env.iter()
+                    .find(|(set, _)| *set == name)
+                    .map(|(_, value)| value.as_str())
+            })
+        }));
+    }
+
+    if let Some(user_agent) = &options.user_agent {
+        builder = builder.user_agent(user_agent);
+    } else if options.headers.iter().all(|(name, _)| name != USER_AGENT) {
+        builder = builder.user_agent(concat!("wuwa-downloader/", env!("CARGO_PKG_VERSION")));
+    }
+
+    let builder = if options.http1_only {
+        builder.http1_only()
+    } else {
+        builder.http2_adaptive_window(true)
+    };
+
+    builder.build()
+}
+
+/// The `--proxy-map` proxy of `url`'s host, or for an unlisted host the one
+/// `HTTPS_PROXY`/`HTTP_PROXY` or `ALL_PROXY` set, unless `NO_PROXY` names it.
+fn proxy_for<'a>(
+    proxy_map: &[(String, Url)],
+    url: &Url,
+    var: impl Fn(&str) -> Option<&'a str>,
+) -> Option<Url> {
+    let host = url.host_str()?;
+    if let Some((_, proxy)) = proxy_map
+        .iter()
+        .find(|(wanted, _)| wanted.eq_ignore_ascii_case(host))
+    {
+        return Some(proxy.clone());
+    }
+
+    let var = |name: &str| {
+        var(name)
+            .or_else(|| var(&name.to_ascii_lowercase()))
+            .filter(|value| !value.trim().is_empty())
+    };
+    let bypassed = var("NO_PROXY").is_some_and(|list| {
+        list.split(',').map(str::trim).any(|entry| {
+            let entry = entry.trim_start_matches('.');
+            entry == "*"
+                || host.eq_ignore_ascii_case(entry)
+                || host
+                    .to_ascii_lowercase()
+                    .ends_with(&format!(".{}", entry.to_ascii_lowercase()))
+        })
+    });
+    if bypassed {
+        return None;
+    }
+    let scheme_var = if url.scheme() == "https" {
+        "HTTPS_PROXY"
+    } else {
+        "HTTP_PROXY"
+    };
+    let proxy = var(scheme_var).or_else(|| var("ALL_PROXY"))?.trim();
+    if proxy.contains("://") {
+        Url::parse(proxy).ok()
+    } else {
+        Url::parse(&format!("http://{}", proxy)).ok()
+    }
+}
+
+/// Parses a curl-style `HOST:PORT:ADDR` pin, with IPv6 addresses optionally
+/// in brackets. The port is only kept for familiarity: the pin applies to
+/// every port of the host.
+pub fn parse_resolve(value: &str) -> Result<(String, SocketAddr), String> {
+    let malformed = || format!("expected \"HOST:PORT:ADDR\", got \"{}\"", value);
+    let (host, rest) = value.split_once(':').ok_or_else(malformed)?;
+    let (port, addr) = rest.split_once(':').ok_or_else(malformed)?;
+    let port: u16 = port
+        .parse()
+        .map_err(|_| format!("invalid port \"{}\"", port))?;
+    let addr = addr.trim_start_matches('[').trim_end_matches(']');
+    let ip: IpAddr = addr
+        .parse()
+        .map_err(|_| format!("invalid IP address \"{}\"", addr))?;
+    if host.is_empty() {
+        return Err(malformed());
+    }
+    Ok((host.to_ascii_lowercase(), SocketAddr::new(ip, port)))
+}
+
+/// Parses a `HOST=PROXY` pair of `--proxy-map`.
+pub fn parse_proxy_map(value: &str) -> Result<(String, Url), String> {
+    let (host, proxy) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected \"HOST=PROXY\", got \"{}\"", value))?;
+    let host = host.trim();
+    if host.is_empty() {
+        return Err(format!("expected \"HOST=PROXY\", got \"{}\"", value));
+    }
+    let proxy =
+        Url::parse(proxy.trim()).map_err(|e| format!("invalid proxy \"{}\": {}", proxy, e))?;
+    if !matches!(
+        proxy.scheme(),
+        "http" | "https" | "socks4" | "socks4a" | "socks5" | "socks5h"
+    ) {
+        return Err(format!(
+            "unsupported proxy scheme \"{}\" (use http, https, socks4, socks4a, socks5 or socks5h)",
+            proxy.scheme()
+        ));
+    }
+    Ok((host.to_ascii_lowercase(), proxy))
+}
+
+/// Parses a `Key: Value` header argument.
+pub fn parse_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = value
+        .split_once(':')
+        .ok_or_else(|| format!("expected \"Key: Value\", got \"{}\"", value))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("invalid header name \"{}\"", name.trim()))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|_| format!("invalid value for header {}", name))?;
+    Ok((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        HttpOptions, IpFamily, build_client, parse_header, parse_proxy_map, parse_resolve,
+        proxy_for,
+    };
+    use reqwest::Url;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn ip_family_limits_which_addresses_are_tried() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buffer = [0; 1024];
+                let _ = stream.read(&mut buffer);
+                let _ = stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+            }
+        });
+        let client = |ip_family| {
+            build_client(&HttpOptions {
+                ip_family,
+                ..HttpOptions::default()
+            })
+            .unwrap()
+        };
+
+        let response = client(Some(IpFamily::V4)).get(&url).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 204);
+        assert!(response.remote_addr().unwrap().is_ipv4());
+        assert!(client(Some(IpFamily::V6)).get(&url).send().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn proxy_map_routes_only_the_listed_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buffer = [0; 1024];
+                let _ = stream.read(&mut buffer);
+                let _ = stream.write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n");
+            }
+        });
+        let client = build_client(&HttpOptions {
+            proxy_map: vec![parse_proxy_map(&format!("mirror.invalid={}", proxy)).unwrap()],
+            ..HttpOptions::default()
+        })
+        .unwrap();
+
+        let response = client
+            .get("http://mirror.invalid/a.pak")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 204);
+        assert!(
+            client
+                .get("http://other.invalid/a.pak")
+                .send()
+                .await
+                .is_err()
+        );
+        assert!(parse_proxy_map("cdn.example.com=ftp://proxy:21").is_err());
+        assert!(parse_proxy_map("socks5://proxy:1080").is_err());
+    }
+
+    #[test]
+    fn unlisted_hosts_keep_the_environment_proxy() {
+        let map = vec![parse_proxy_map("mirror.example.com=socks5://127.0.0.1:1080").unwrap()];
+        let env = |name: &str| match name {
+            "HTTPS_PROXY" => Some("corp-proxy:3128"),
+            "no_proxy" => Some("localhost,.internal.example.com"),
+            _ => None,
+        };
+        let proxy = |url: &str| proxy_for(&map, &Url::parse(url).unwrap(), env);
+
+        assert_eq!(
+            proxy("https://mirror.example.com/a.pak").unwrap().as_str(),
+            "socks5://127.0.0.1:1080"
+        );
+        assert_eq!(
+            proxy("https://other.example.com/a.pak").unwrap().as_str(),
+            "http://corp-proxy:3128/"
+        );
+        assert_eq!(proxy("https://cdn.internal.example.com/a.pak"), None);
+        assert_eq!(proxy("http://other.example.com/a.pak"), None);
+    }
+
+    #[test]
+    fn parse_header_splits_on_first_colon() {
+        let (name, value) = parse_header("Referer: https://example.com:8443/").unwrap();
+        assert_eq!(name.as_str(), "referer");
+        assert_eq!(value.to_str().unwrap(), "https://example.com:8443/");
+    }
+
+    #[test]
+    fn parse_resolve_takes_curl_syntax() {
+        let (host, addr) = parse_resolve("CDN.example.com:443:[2001:db8::1]").unwrap();
+        assert_eq!(host, "cdn.example.com");
+        assert_eq!(addr.to_string(), "[2001:db8::1]:443");
+        assert_eq!(
+            parse_resolve("cdn.example.com:443:1.2.3.4")
+                .unwrap()
+                .1
+                .to_string(),
+            "1.2.3.4:443"
+        );
+        assert!(parse_resolve("cdn.example.com:1.2.3.4").is_err());
+        assert!(parse_resolve("cdn.example.com:443:cdn2").is_err());
+    }
+
+    #[test]
+    fn parse_header_rejects_malformed_input() {
+        assert!(parse_header("NoColon").is_err());
+        assert!(parse_header("Bad Name: value").is_err());
+        assert!(parse_header("X-Test: line\nbreak").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as FmtWrite,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct Counters {
+    downloaded_bytes: AtomicU64,
+    total_bytes: AtomicU64,
+    bytes_per_sec: AtomicU64,
+    files_total: AtomicU64,
+    files_remaining: AtomicU64,
+    files_failed: AtomicU64,
+    cdn_bytes: Mutex<BTreeMap<String, u64>>,
+}
+
+/// `--metrics-port`: live download figures in the Prometheus text format,
+/// served from a background thread for as long as the process runs.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    counters: Arc<Counters>,
+}
+
+impl Metrics {
+    /// Listens on every interface, so a Prometheus on another machine can
+    /// scrape `http://<host>:<port>/metrics`.
+    pub fn serve(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let metrics = Self::default();
+        serve_on(listener, metrics.clone())?;
+        Ok(metrics)
+    }
+
+    pub fn set_progress(
+        &self,
+        files_done: usize,
+        files_total: usize,
+        failed: usize,
+        downloaded: u64,
+        total_bytes: u64,
+        bytes_per_sec: u64,
+    ) {
+        let counters = &self.counters;
+        counters
+            .downloaded_bytes
+            .store(downloaded, Ordering::Relaxed);
+        counters.total_bytes.store(total_bytes, Ordering::Relaxed);
+        counters
+            .bytes_per_sec
+            .store(bytes_per_sec, Ordering::Relaxed);
+        counters
+            .files_total
+            .store(files_total as u64, Ordering::Relaxed);
+        counters.files_remaining.store(
+            files_total.saturating_sub(files_done) as u64,
+            Ordering::Relaxed,
+        );
+        counters
+            .files_failed
+            .store(failed as u64, Ordering::Relaxed);
+    }
+
+    /// Credits `amount` bytes received from `url` to that CDN's host.
+    pub fn add_cdn_bytes(&self, url: &str, amount: u64) {
+        if let Ok(mut cdn_bytes) = self.counters.cdn_bytes.lock() {
+            match cdn_bytes.get_mut(cdn_host(url)) {
+                Some(total) => *total += amount,
+                None => {
+                    cdn_bytes.insert(cdn_host(url).to_string(), amount);
+                }
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let counters = &self.counters;
+        let mut out = String::new();
+        let gauges = [
+            (
+                "wuwa_downloaded_bytes",
+                "Bytes downloaded this session",
+                &counters.downloaded_bytes,
+            ),
+            (
+                "wuwa_download_size_bytes",
+                "Bytes this session has to download",
+                &counters.total_bytes,
+            ),
+            (
+                "wuwa_download_speed_bytes_per_second",
+                "Smoothed download speed",
+                &counters.bytes_per_sec,
+            ),
+            (
+                "wuwa_files_total",
+                "Files in the session",
+                &counters.files_total,
+            ),
+            (
+                "wuwa_files_remaining",
+                "Files not yet verified, downloaded or failed",
+                &counters.files_remaining,
+            ),
+            (
+                "wuwa_files_failed",
+                "Files that failed for good",
+                &counters.files_failed,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP wuwa_cdn_bytes_total Bytes received from each CDN host"
+        );
+        let _ = writeln!(out, "# TYPE wuwa_cdn_bytes_total counter");
+        if let Ok(cdn_bytes) = self.counters.cdn_bytes.lock() {
+            for (host, bytes) in cdn_bytes.iter() {
+                let _ = writeln!(
+                    out,
+                    "wuwa_cdn_bytes_total{{cdn=\"{}\"}} {}",
+                    host.replace('\\', "\\\\").replace('"', "\\\""),
+                    bytes
+                );
+            }
+        }
+        out
+    }
+}
+
+/// The `host[:port]` part of `url`.
+pub(crate) fn cdn_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?', '#']).next().unwrap_or(rest)
+}
+
+fn serve_on(listener: TcpListener, metrics: Metrics) -> io::Result<()> {
+    thread::Builder::new()
+        .name("metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = respond(stream, &metrics);
+            }
+        })?;
+    Ok(())
+}
+
+/// Answers one request and closes the connection; scrapers reconnect anyway.
+fn respond(stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    let (_, path, mut stream) = read_request(stream)?;
+    match path.as_str() {
+        "/metrics" => write_response(
+            &mut stream,
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            &metrics.render(),
+        ),
+        _ => write_response(
+            &mut stream,
+            "404 Not Found",
+            "text/plain",
+            "Not found; try /metrics\n",
+        ),
+    }
+}
+
+/// Reads a request's method and path, without the query string, and skips
+/// its headers. Bodies are never needed by the embedded endpoints.
+pub(crate) fn read_request(stream: TcpStream) -> io::Result<(String, String, TcpStream)> {
+    let (method, path, _, stream) = read_request_with_headers(stream)?;
+    Ok((method, path, stream))
+}
+
+/// A request's method, path and `(name, value)` headers, and the stream to answer on.
+pub(crate) type Request = (String, String, Vec<(String, String)>, TcpStream);
+
+/// Like [`read_request`], but keeps the headers.
+pub(crate) fn read_request_with_headers(stream: TcpStream) -> io::Result<Request> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut headers = Vec::new();
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("");
+    let path = path.split('?').next().unwrap_or(path).to_string();
+    Ok((method, path, headers, reader.into_inner()))
+}
+
+pub(crate) fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Metrics, cdn_host, serve_on};
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    #[test]
+    fn serves_progress_and_per_cdn_bytes() {
+        assert_eq!(
+            cdn_host("https://cdn-a.example:8443/pre/a.pak"),
+            "cdn-a.example:8443"
+        );
+        assert_eq!(cdn_host("cdn-b/pre"), "cdn-b");
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let metrics = Metrics::default();
+        serve_on(listener, metrics.clone()).unwrap();
+        metrics.set_progress(3, 10, 1, 2048, 4096, 512);
+        metrics.add_cdn_bytes("https://cdn-a/pre/a.pak", 1000);
+        metrics.add_cdn_bytes("https://cdn-a/pre/b.pak", 48);
+        metrics.add_cdn_bytes("https://cdn-b/pre/c.pak", 1000);
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        for line in [
+            "wuwa_downloaded_bytes 2048",
+            "wuwa_download_speed_bytes_per_second 512",
+            "wuwa_files_remaining 7",
+            "wuwa_files_failed 1",
+            "wuwa_cdn_bytes_total{cdn=\"cdn-a\"} 1048",
+            "wuwa_cdn_bytes_total{cdn=\"cdn-b\"} 1000",
+        ] {
+            assert!(response.lines().any(|l| l == line), "missing {}", line);
+        }
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 404"));
+    }
+}
@@ -1 +1,14 @@
+pub mod aria2;
+pub mod cdn_history;
+pub mod cdn_tracker;
 pub mod client;
+pub mod doh;
+pub mod file_server;
+pub mod health;
+pub mod http;
+pub mod metrics;
+pub mod pacing;
+pub mod retry;
+pub mod url_map;
+pub mod web_ui;
+pub mod webhook;
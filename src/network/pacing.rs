@@ -0,0 +1,147 @@
+use log::warn;
+use reqwest::{RequestBuilder, Response, StatusCode, header::RETRY_AFTER};
+use std::{
+    sync::{Mutex, OnceLock, PoisonError},
+    time::Duration,
+};
+use tokio::time::{Instant, sleep_until};
+
+/// Attempts a metadata request gets after `429 Too Many Requests`.
+const RATE_LIMIT_RETRIES: u32 = 4;
+/// Longest `Retry-After` honoured; a CDN asking for more is treated as down.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+static PACING: OnceLock<Pacing> = OnceLock::new();
+
+/// Which budget a request is paced against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestKind {
+    /// Index, version list and config downloads, and `--verify-partials`
+    /// range checks.
+    Metadata,
+    /// Size probes, which `--max-head-rps` also limits.
+    Head,
+}
+
+/// Hands out start times at least `gap` apart, in call order.
+#[derive(Debug)]
+pub struct Pacer {
+    gap: Duration,
+    next: Mutex<Option<Instant>>,
+}
+
+impl Pacer {
+    pub fn new(gap: Duration) -> Self {
+        Self {
+            gap,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Waits until this caller's turn.
+    pub async fn wait(&self) {
+        if self.gap.is_zero() {
+            return;
+        }
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+            let slot = next.map_or(now, |next| next.max(now));
+            *next = Some(slot + self.gap);
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
+
+struct Pacing {
+    metadata: Pacer,
+    head: Pacer,
+}
+
+/// `--request-delay` spaces out every metadata request and `--max-head-rps`
+/// additionally caps HEAD probes, so repeated exports or dry runs stay
+/// below a CDN's rate limit. Downloads themselves are never paced.
+pub fn init(request_delay: Duration, max_head_rps: Option<u32>) {
+    let head_gap = max_head_rps
+        .map_or(Duration::ZERO, |rps| Duration::from_secs(1) / rps)
+        .max(request_delay);
+    let _ = PACING.set(Pacing {
+        metadata: Pacer::new(request_delay),
+        head: Pacer::new(head_gap),
+    });
+}
+
+/// Sends a metadata request in its turn, and on `429 Too Many Requests`
+/// waits as long as `Retry-After` asks (or a growing backoff without one)
+/// before trying again.
+pub async fn send(request: RequestBuilder, kind: RequestKind) -> reqwest::Result<Response> {
+    let mut request = request;
+    let mut attempt = 0;
+    loop {
+        if let Some(pacing) = PACING.get() {
+            match kind {
+                RequestKind::Metadata => pacing.metadata.wait().await,
+                RequestKind::Head => pacing.head.wait().await,
+            }
+        }
+        let retry = request.try_clone();
+        let response = request.send().await?;
+        let Some(next) = retry.filter(|_| {
+            response.status() == StatusCode::TOO_MANY_REQUESTS && attempt < RATE_LIMIT_RETRIES
+        }) else {
+            return Ok(response);
+        };
+        let delay = retry_after(&response).unwrap_or(Duration::from_secs(2 << attempt));
+        if delay > MAX_RETRY_AFTER {
+            return Ok(response);
+        }
+        warn!(
+            "Rate limited by {}, retrying in {}s",
+            response.url().host_str().unwrap_or("server"),
+            delay.as_secs()
+        );
+        sleep_until(Instant::now() + delay).await;
+        request = next;
+        attempt += 1;
+    }
+}
+
+/// The wait a `Retry-After` header asks for, given in seconds or as an HTTP date.
+pub fn retry_after(response: &Response) -> Option<Duration> {
+    parse_retry_after(response.headers().get(RETRY_AFTER)?.to_str().ok()?)
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.signed_duration_since(chrono::Utc::now());
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pacer, parse_retry_after};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[tokio::test]
+    async fn spaces_requests_and_reads_retry_after() {
+        let pacer = Pacer::new(Duration::from_millis(50));
+        let started = Instant::now();
+        for _ in 0..3 {
+            pacer.wait().await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        assert_eq!(parse_retry_after(" 7 "), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
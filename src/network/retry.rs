@@ -0,0 +1,78 @@
+use reqwest::StatusCode;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: usize,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential delay for the given zero-based retry, capped at `max_delay`,
+    /// with jitter drawn from the upper half of the window.
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let factor = 1_u32 << retry.min(16);
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        delay.mul_f64(0.5 + jitter() * 0.5)
+    }
+}
+
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn jitter() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RetryPolicy, is_retryable_status};
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_grows_and_respects_cap() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(8),
+        };
+
+        assert!(policy.backoff(0) <= Duration::from_secs(1));
+        assert!(policy.backoff(2) >= Duration::from_secs(2));
+        assert!(policy.backoff(40) <= Duration::from_secs(8));
+    }
+
+    #[test]
+    fn only_transient_statuses_are_retryable() {
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(StatusCode::NOT_FOUND));
+        assert!(!is_retryable_status(StatusCode::FORBIDDEN));
+    }
+}
@@ -0,0 +1,75 @@
+use serde_json::Value;
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+/// `--url-map`: full URLs that replace the CDNs for single files, e.g. ones
+/// a community mirror rehosted because every CDN answers 404 for them.
+#[derive(Clone, Default)]
+pub struct UrlMap {
+    urls: Arc<HashMap<String, String>>,
+}
+
+impl UrlMap {
+    /// Reads a JSON object that maps index `dest` paths to URLs.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let data: Value = serde_json::from_str(&contents)
+            .map_err(|e| format!("Invalid JSON in {}: {}", path.display(), e))?;
+        Self::parse(&data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(data: &Value) -> Result<Self, String> {
+        let object = data
+            .as_object()
+            .ok_or_else(|| "expected an object of \"dest\": \"URL\" pairs".to_string())?;
+        let mut urls = HashMap::new();
+        for (dest, url) in object {
+            let url = url
+                .as_str()
+                .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+                .ok_or_else(|| format!("\"{}\" does not map to an http(s) URL", dest))?;
+            urls.insert(normalize(dest), url.to_string());
+        }
+        Ok(Self {
+            urls: Arc::new(urls),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty()
+    }
+
+    /// The URL `dest` is mapped to, whichever slashes either side uses.
+    pub fn get(&self, dest: &str) -> Option<&str> {
+        self.urls.get(&normalize(dest)).map(String::as_str)
+    }
+}
+
+fn normalize(dest: &str) -> String {
+    dest.replace('\\', "/").trim_start_matches('/').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrlMap;
+    use serde_json::json;
+
+    #[test]
+    fn maps_dests_regardless_of_slashes() {
+        let map = UrlMap::parse(&json!({
+            "/Client/Paks/broken.pak": "https://mirror.example/broken.pak"
+        }))
+        .unwrap();
+        assert_eq!(
+            map.get("Client\\Paks\\broken.pak"),
+            Some("https://mirror.example/broken.pak")
+        );
+        assert_eq!(map.get("Client/Paks/other.pak"), None);
+        assert!(UrlMap::parse(&json!({ "a.pak": "mirror.example/a.pak" })).is_err());
+        assert!(UrlMap::parse(&json!(["a.pak"])).is_err());
+    }
+}
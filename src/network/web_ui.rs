@@ -0,0 +1,272 @@
+use serde_json::{Value, json};
+use std::{
+    io::{self, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::download::control::DownloadControl;
+use crate::network::metrics::{read_request, write_response};
+
+const PUSH_INTERVAL: Duration = Duration::from_secs(1);
+const PAGE: &str = include_str!("web_ui.html");
+
+#[derive(Default)]
+struct Status {
+    files_done: usize,
+    files_total: usize,
+    downloaded: u64,
+    total_bytes: u64,
+    bytes_per_sec: u64,
+    current: String,
+    state: &'static str,
+    failures: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+struct Shared {
+    status: Mutex<Status>,
+    controls: Mutex<Option<(DownloadControl, Arc<AtomicBool>)>>,
+}
+
+/// `--web-ui`: a status page with live progress, the files that failed and
+/// pause/resume/cancel buttons, for checking on a headless run from a phone.
+/// There is no login, so keep it on a trusted network.
+#[derive(Clone, Default)]
+pub struct WebUi {
+    shared: Arc<Shared>,
+}
+
+impl WebUi {
+    pub fn serve(addr: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let web_ui = Self::default();
+        serve_on(listener, web_ui.clone())?;
+        Ok(web_ui)
+    }
+
+    /// Hands the buttons the running session's switches.
+    pub fn attach(&self, control: &DownloadControl, should_stop: &Arc<AtomicBool>) {
+        if let Ok(mut controls) = self.shared.controls.lock() {
+            *controls = Some((control.clone(), should_stop.clone()));
+        }
+        if let Ok(mut status) = self.shared.status.lock() {
+            status.state = "running";
+        }
+    }
+
+    /// Records the latest figures; `failures` is the session's list so far,
+    /// of which only the new entries are copied.
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_progress<'a>(
+        &self,
+        files_done: usize,
+        files_total: usize,
+        downloaded: u64,
+        total_bytes: u64,
+        bytes_per_sec: u64,
+        current: String,
+        failures: impl ExactSizeIterator<Item = (&'a str, &'a str)>,
+    ) {
+        let Ok(mut status) = self.shared.status.lock() else {
+            return;
+        };
+        status.files_done = files_done;
+        status.files_total = files_total;
+        status.downloaded = downloaded;
+        status.total_bytes = total_bytes;
+        status.bytes_per_sec = bytes_per_sec;
+        status.current = current;
+        let known = status.failures.len();
+        if failures.len() > known {
+            let new: Vec<_> = failures
+                .skip(known)
+                .map(|(dest, reason)| (dest.to_string(), reason.to_string()))
+                .collect();
+            status.failures.extend(new);
+        }
+    }
+
+    pub fn finish(&self, stopped: bool) {
+        if let Ok(mut status) = self.shared.status.lock() {
+            status.state = if stopped { "stopped" } else { "finished" };
+            status.bytes_per_sec = 0;
+            status.current.clear();
+        }
+    }
+
+    fn snapshot(&self) -> Value {
+        let paused = self
+            .controls()
+            .is_some_and(|(control, _)| control.is_paused());
+        let Ok(status) = self.shared.status.lock() else {
+            return Value::Null;
+        };
+        let state = match status.state {
+            "running" if paused => "paused",
+            "" => "waiting",
+            state => state,
+        };
+        json!({
+            "state": state,
+            "files_done": status.files_done,
+            "files_total": status.files_total,
+            "downloaded": status.downloaded,
+            "total_bytes": status.total_bytes,
+            "bytes_per_sec": status.bytes_per_sec,
+            "current": status.current,
+            "failures": status
+                .failures
+                .iter()
+                .map(|(dest, reason)| json!({ "dest": dest, "reason": reason }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn controls(&self) -> Option<(DownloadControl, Arc<AtomicBool>)> {
+        self.shared.controls.lock().ok()?.clone()
+    }
+
+    /// Applies a button press; false when no session is attached yet.
+    fn command(&self, action: &str) -> bool {
+        let Some((control, should_stop)) = self.controls() else {
+            return false;
+        };
+        match action {
+            "pause" => control.pause(),
+            "resume" => control.resume(),
+            _ => should_stop.store(true, Ordering::SeqCst),
+        }
+        true
+    }
+}
+
+fn serve_on(listener: TcpListener, web_ui: WebUi) -> io::Result<()> {
+    thread::Builder::new()
+        .name("web-ui".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let web_ui = web_ui.clone();
+                // Event streams stay open, so each connection gets a thread.
+                let _ = thread::Builder::new()
+                    .name("web-ui-client".to_string())
+                    .spawn(move || respond(stream, &web_ui));
+            }
+        })?;
+    Ok(())
+}
+
+fn respond(stream: TcpStream, web_ui: &WebUi) -> io::Result<()> {
+    let (method, path, mut stream) = read_request(stream)?;
+    match (method.as_str(), path.as_str()) {
+        ("GET", "/") => write_response(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE),
+        ("GET", "/status") => write_response(
+            &mut stream,
+            "200 OK",
+            "application/json",
+            &web_ui.snapshot().to_string(),
+        ),
+        ("GET", "/events") => stream_events(stream, web_ui),
+        ("POST", "/pause" | "/resume" | "/cancel") => {
+            if web_ui.command(&path[1..]) {
+                write_response(&mut stream, "200 OK", "text/plain", "ok\n")
+            } else {
+                write_response(
+                    &mut stream,
+                    "409 Conflict",
+                    "text/plain",
+                    "No download to control yet\n",
+                )
+            }
+        }
+        _ => write_response(&mut stream, "404 Not Found", "text/plain", "Not found\n"),
+    }
+}
+
+/// Server-sent events: the status every [`PUSH_INTERVAL`] until the page is
+/// closed.
+fn stream_events(mut stream: TcpStream, web_ui: &WebUi) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n"
+    )?;
+    loop {
+        write!(stream, "data: {}\n\n", web_ui.snapshot())?;
+        stream.flush()?;
+        thread::sleep(PUSH_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WebUi, serve_on};
+    use crate::download::control::DownloadControl;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    fn request(addr: std::net::SocketAddr, method: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            method, path
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn streams_status_and_applies_buttons() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let web_ui = WebUi::default();
+        serve_on(listener, web_ui.clone()).unwrap();
+
+        assert!(request(addr, "POST", "/pause").starts_with("HTTP/1.1 409"));
+        let control = DownloadControl::default();
+        let should_stop = Arc::new(AtomicBool::new(false));
+        web_ui.attach(&control, &should_stop);
+        let failures = [("a.pak", "HTTP error: 404")];
+        web_ui.set_progress(
+            2,
+            5,
+            100,
+            400,
+            10,
+            "b.pak".to_string(),
+            failures.into_iter(),
+        );
+
+        assert!(request(addr, "GET", "/").contains("<html"));
+        assert!(request(addr, "POST", "/pause").starts_with("HTTP/1.1 200"));
+        assert!(control.is_paused());
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /events HTTP/1.1\r\n\r\n").unwrap();
+        let data = BufReader::new(stream)
+            .lines()
+            .map(Result::unwrap)
+            .find(|line| line.starts_with("data: "))
+            .unwrap();
+        let status: serde_json::Value = serde_json::from_str(&data["data: ".len()..]).unwrap();
+        assert_eq!(status["state"], "paused");
+        assert_eq!(status["files_done"], 2);
+        assert_eq!(status["current"], "b.pak");
+        assert_eq!(status["failures"][0]["dest"], "a.pak");
+
+        assert!(request(addr, "POST", "/cancel").starts_with("HTTP/1.1 200"));
+        assert!(should_stop.load(Ordering::SeqCst));
+        assert!(request(addr, "POST", "/explode").starts_with("HTTP/1.1 404"));
+    }
+}
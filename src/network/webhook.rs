@@ -0,0 +1,94 @@
+use indicatif::{HumanBytes, HumanDuration};
+use reqwest::Client;
+use serde_json::{Value, json};
+use std::time::Duration;
+
+use crate::io::logging::{SharedLogFile, log_warn};
+
+const POST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Chat webhook that receives periodic progress and the final summary.
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    pub url: String,
+    pub interval: Duration,
+}
+
+impl Webhook {
+    /// Posts `text` to the webhook. Failures are logged and otherwise ignored;
+    /// a chat outage must never stop the download.
+    pub async fn post(&self, client: &Client, text: &str, log_file: &SharedLogFile) {
+        let sent = client
+            .post(&self.url)
+            .timeout(POST_TIMEOUT)
+            .json(&payload(text))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = sent {
+            log_warn(log_file, &format!("Webhook post failed: {}", e));
+        }
+    }
+}
+
+/// Discord reads `content`; Telegram's `sendMessage` and Slack read `text`.
+fn payload(text: &str) -> Value {
+    json!({ "content": text, "text": text })
+}
+
+/// One-line progress update for a running session.
+pub fn progress_message(
+    files_done: usize,
+    files_total: usize,
+    failed: usize,
+    downloaded: u64,
+    total_bytes: u64,
+    bytes_per_sec: u64,
+) -> String {
+    let percent = (downloaded.min(total_bytes) * 100)
+        .checked_div(total_bytes)
+        .unwrap_or(0);
+    let eta = match total_bytes
+        .saturating_sub(downloaded)
+        .checked_div(bytes_per_sec)
+    {
+        Some(secs) => HumanDuration(Duration::from_secs(secs)).to_string(),
+        None => "--".to_string(),
+    };
+    let mut message = format!(
+        "Downloading: {}/{} files, {} of {} ({}%), {}/s, ETA {}",
+        files_done,
+        files_total,
+        HumanBytes(downloaded),
+        HumanBytes(total_bytes),
+        percent,
+        HumanBytes(bytes_per_sec),
+        eta
+    );
+    if failed > 0 {
+        message.push_str(&format!(", {} failed", failed));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{payload, progress_message};
+
+    #[test]
+    fn progress_message_reports_counts_and_eta() {
+        assert_eq!(
+            progress_message(
+                10,
+                40,
+                0,
+                512 * 1024 * 1024,
+                1024 * 1024 * 1024,
+                1024 * 1024
+            ),
+            "Downloading: 10/40 files, 512.00 MiB of 1.00 GiB (50%), 1.00 MiB/s, ETA 9 minutes"
+        );
+        assert!(progress_message(1, 2, 1, 0, 0, 0).ends_with("ETA --, 1 failed"));
+        assert_eq!(payload("hi")["content"], "hi");
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    sync::{Mutex, OnceLock, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Disk bytes per second that hashing, extraction and writes share under `--background`.
+const BACKGROUND_DISK_RATE: f64 = 64.0 * 1024.0 * 1024.0;
+
+static THROTTLE: OnceLock<Throttle> = OnceLock::new();
+
+/// Spreads disk work out so it averages `rate` bytes per second.
+#[derive(Debug)]
+struct Throttle {
+    rate: f64,
+    next: Mutex<Option<Instant>>,
+}
+
+impl Throttle {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Books `bytes` and returns how long the caller waits before its turn.
+    fn reserve(&self, bytes: usize, now: Instant) -> Duration {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.rate);
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+        let slot = next.map_or(now, |next| next.max(now));
+        *next = Some(slot + cost);
+        slot - now
+    }
+}
+
+/// `--background`: lowers the CPU and disk priority of the process, which
+/// threads started afterwards inherit, and caps disk throughput. Call it
+/// before the async runtime starts its threads. The throttle applies even
+/// when the priority cannot be changed.
+pub fn enter_background() -> Result<(), String> {
+    let _ = THROTTLE.set(Throttle::new(BACKGROUND_DISK_RATE));
+    lower_priority()
+}
+
+/// Waits, on a blocking thread, until `bytes` more fit the `--background` rate.
+pub fn throttle_blocking(bytes: usize) {
+    if let Some(throttle) = THROTTLE.get() {
+        thread::sleep(throttle.reserve(bytes, Instant::now()));
+    }
+}
+
+/// Waits until `bytes` more fit the `--background` rate.
+pub async fn throttle(bytes: usize) {
+    if let Some(throttle) = THROTTLE.get() {
+        tokio::time::sleep(throttle.reserve(bytes, Instant::now())).await;
+    }
+}
+
+/// `--keep-awake`: holds off system sleep until dropped, or until the process
+/// exits without dropping it.
+pub struct KeepAwake {
+    #[cfg(unix)]
+    inhibitor: std::process::Child,
+}
+
+/// Keeps the system from sleeping. On Windows call it on a thread that lives
+/// as long as the guard, as the request is per thread.
+#[cfg(windows)]
+pub fn keep_awake() -> Result<KeepAwake, String> {
+    use windows_sys::Win32::System::Power::{
+        ES_CONTINUOUS, ES_SYSTEM_REQUIRED, SetThreadExecutionState,
+    };
+
+    if unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED) } == 0 {
+        return Err(format!(
+            "Failed to keep the system awake: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(KeepAwake {})
+}
+
+#[cfg(windows)]
+impl Drop for KeepAwake {
+    fn drop(&mut self) {
+        use windows_sys::Win32::System::Power::{ES_CONTINUOUS, SetThreadExecutionState};
+
+        unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+    }
+}
+
+/// Runs an inhibitor that exits along with this process, however it ends:
+/// `systemd-inhibit` around a loop that waits for our PID on Linux,
+/// `caffeinate -w` on macOS.
+#[cfg(unix)]
+pub fn keep_awake() -> Result<KeepAwake, String> {
+    use std::process::{Command, Stdio};
+
+    let pid = std::process::id().to_string();
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("caffeinate");
+        command.args(["-i", "-w", &pid]);
+        command
+    } else {
+        let mut command = Command::new("systemd-inhibit");
+        command.args([
+            "--what=sleep:idle",
+            "--who=wuwa-downloader",
+            "--why=Downloading game files",
+            "--mode=block",
+            "sh",
+            "-c",
+            "while kill -0 \"$0\" 2>/dev/null; do sleep 5; done",
+            &pid,
+        ]);
+        command
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut inhibitor = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to keep the system awake: {}: {}", program, e))?;
+    // An inhibitor that cannot take the lock (no logind, say) exits right away.
+    thread::sleep(Duration::from_millis(200));
+    if let Ok(Some(status)) = inhibitor.try_wait() {
+        return Err(format!(
+            "Failed to keep the system awake: {} exited with {}",
+            program, status
+        ));
+    }
+    Ok(KeepAwake { inhibitor })
+}
+
+#[cfg(unix)]
+impl Drop for KeepAwake {
+    fn drop(&mut self) {
+        let _ = self.inhibitor.kill();
+        let _ = self.inhibitor.wait();
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn keep_awake() -> Result<KeepAwake, String> {
+    Err("--keep-awake is not supported on this platform".to_string())
+}
+
+/// What `--shutdown-on-complete` or `--hibernate-on-complete` does at the end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerAction {
+    Shutdown,
+    Hibernate,
+}
+
+impl PowerAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            PowerAction::Shutdown => "Shutting down",
+            PowerAction::Hibernate => "Hibernating",
+        }
+    }
+
+    fn command(self) -> Option<(&'static str, &'static [&'static str])> {
+        if cfg!(windows) {
+            Some(match self {
+                PowerAction::Shutdown => ("shutdown", &["/s", "/t", "0"]),
+                PowerAction::Hibernate => ("shutdown", &["/h"]),
+            })
+        } else if cfg!(target_os = "macos") {
+            match self {
+                PowerAction::Shutdown => Some((
+                    "osascript",
+                    &["-e", "tell application \"System Events\" to shut down"],
+                )),
+                PowerAction::Hibernate => None,
+            }
+        } else {
+            Some(match self {
+                PowerAction::Shutdown => ("systemctl", &["poweroff"]),
+                PowerAction::Hibernate => ("systemctl", &["hibernate"]),
+            })
+        }
+    }
+}
+
+/// Asks the system to power down; returns once the request was accepted.
+pub fn power_down(action: PowerAction) -> Result<(), String> {
+    let Some((program, args)) = action.command() else {
+        return Err(format!(
+            "{} is not supported on this platform",
+            action.label()
+        ));
+    };
+    let status = std::process::Command::new(program)
+        .args(args)
+        .status()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", program, status))
+    }
+}
+
+/// Whether a process with `pid` is running on this machine.
+#[cfg(unix)]
+pub fn process_alive(pid: u32) -> bool {
+    // 0 and values past i32::MAX would address process groups.
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if pid == 0 {
+        return false;
+    }
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub fn process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{CloseHandle, ERROR_ACCESS_DENIED, STILL_ACTIVE};
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process.is_null() {
+        return std::io::Error::last_os_error().raw_os_error() == Some(ERROR_ACCESS_DENIED as i32);
+    }
+    let mut code = 0;
+    let alive =
+        unsafe { GetExitCodeProcess(process, &mut code) } != 0 && code == STILL_ACTIVE as u32;
+    unsafe { CloseHandle(process) };
+    alive
+}
+
+/// Assumes the process runs, where there is no way to ask.
+#[cfg(not(any(unix, windows)))]
+pub fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Name of this machine, or an empty string when it has none.
+#[cfg(unix)]
+pub fn hostname() -> String {
+    let mut name = [0_u8; 256];
+    if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } != 0 {
+        return String::new();
+    }
+    let len = name
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
+
+#[cfg(not(unix))]
+pub fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn lower_priority() -> Result<(), String> {
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 10) } != 0 {
+        return Err(format!(
+            "Failed to lower CPU priority: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    lower_io_priority()
+}
+
+/// Lowest best-effort I/O class, which still makes progress under a busy game.
+#[cfg(target_os = "linux")]
+fn lower_io_priority() -> Result<(), String> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    let priority = (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | 7;
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) } != 0 {
+        return Err(format!(
+            "Failed to lower disk priority: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn lower_io_priority() -> Result<(), String> {
+    Ok(())
+}
+
+/// Background processing mode lowers CPU, disk and memory priority at once.
+#[cfg(windows)]
+fn lower_priority() -> Result<(), String> {
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, PROCESS_MODE_BACKGROUND_BEGIN, SetPriorityClass,
+    };
+
+    if unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) } == 0 {
+        return Err(format!(
+            "Failed to enter background mode: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lower_priority() -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Throttle;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn spaces_disk_work_by_rate() {
+        let throttle = Throttle::new(1000.0);
+        let start = Instant::now();
+        assert_eq!(throttle.reserve(500, start), Duration::ZERO);
+        assert_eq!(throttle.reserve(500, start), Duration::from_millis(500));
+        // Time that passed counts towards the wait.
+        let later = start + Duration::from_millis(800);
+        assert_eq!(throttle.reserve(100, later), Duration::from_millis(200));
+        // An idle throttle does not bank a burst.
+        let idle = start + Duration::from_secs(10);
+        assert_eq!(throttle.reserve(100, idle), Duration::ZERO);
+    }
+}
@@ -0,0 +1,419 @@
+//! Local mock CDN for the integration tests: serves a synthetic index and
+//! files with known hashes, and can be told to misbehave per request. A mock
+//! aria2 RPC stands in for the daemon behind `--backend aria2`.
+
+use md5::{Digest, Md5};
+use serde_json::{Value, json};
+use std::collections::{HashMap, VecDeque};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tiny_http::{Header, Request, Response, Server};
+
+use wuwa_downloader::config::cfg::{Config, DownloadOptions, ResourceItem};
+use wuwa_downloader::download::{
+    control::DownloadControl,
+    events::EventSink,
+    pipeline::{PipelineResult, run_pipeline},
+};
+use wuwa_downloader::io::logging::setup_logging;
+use wuwa_downloader::network::{
+    http::{HttpOptions, build_client},
+    retry::RetryPolicy,
+};
+
+pub const INDEX_PATH: &str = "/index.json";
+/// Short enough that a stalled response fails the attempt quickly.
+pub const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// One way to answer a single request wrongly. Faults queued for a path are
+/// used up in order, one per request; later requests are served normally.
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    /// Reply with this status and no body.
+    Status(u16),
+    /// Wait this long before answering, to trip the client's read timeout.
+    Stall(Duration),
+    /// Announce the full length but close the connection after this many bytes.
+    Truncate(usize),
+    /// Serve the right length with the first byte flipped.
+    Corrupt,
+}
+
+/// A request the CDN received, for asserting on retries and resumes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hit {
+    pub path: String,
+    /// Offset of a `Range: bytes=N-` request.
+    pub range_start: Option<u64>,
+}
+
+#[derive(Default)]
+struct State {
+    files: HashMap<String, Vec<u8>>,
+    faults: HashMap<String, VecDeque<Fault>>,
+    hits: Vec<Hit>,
+}
+
+impl State {
+    /// The launcher index for every file served, with MD5 checksums.
+    fn index(&self) -> Value {
+        let mut resources: Vec<(&String, &Vec<u8>)> = self.files.iter().collect();
+        resources.sort();
+        let resources: Vec<Value> = resources
+            .into_iter()
+            .map(|(path, body)| {
+                json!({
+                    "dest": path.trim_start_matches('/'),
+                    "md5": md5_hex(body),
+                    "size": body.len(),
+                })
+            })
+            .collect();
+        json!({ "resource": resources })
+    }
+}
+
+pub struct MockCdn {
+    pub url: String,
+    server: Arc<Server>,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockCdn {
+    pub fn start() -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").expect("bind mock CDN"));
+        let url = format!("http://{}", server.server_addr());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let (listener, shared) = (server.clone(), state.clone());
+        thread::spawn(move || {
+            for request in listener.incoming_requests() {
+                let state = shared.clone();
+                // A stalled response must not hold up the other downloads.
+                thread::spawn(move || respond(&state, request));
+            }
+        });
+
+        Self { url, server, state }
+    }
+
+    /// Serves `body` at `/<dest>` and lists it in the index.
+    pub fn add_file(&self, dest: &str, body: &[u8]) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .files
+            .insert(format!("/{}", dest), body.to_vec());
+        self
+    }
+
+    /// Queues faults for the next requests of `/<dest>`.
+    pub fn fail(&self, dest: &str, faults: &[Fault]) -> &Self {
+        self.state
+            .lock()
+            .unwrap()
+            .faults
+            .entry(format!("/{}", dest))
+            .or_default()
+            .extend(faults.iter().copied());
+        self
+    }
+
+    pub fn hits(&self, dest: &str) -> Vec<Hit> {
+        let path = format!("/{}", dest);
+        self.state
+            .lock()
+            .unwrap()
+            .hits
+            .iter()
+            .filter(|hit| hit.path == path)
+            .cloned()
+            .collect()
+    }
+
+    pub fn index_url(&self) -> String {
+        format!("{}{}", self.url, INDEX_PATH)
+    }
+}
+
+impl Drop for MockCdn {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+fn respond(state: &Mutex<State>, request: Request) {
+    let path = request.url().to_string();
+    let range: Option<(u64, Option<u64>)> = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Range"))
+        .and_then(|header| {
+            let (start, end) = header
+                .value
+                .as_str()
+                .strip_prefix("bytes=")?
+                .split_once('-')?;
+            Some((start.parse().ok()?, end.parse().ok()))
+        });
+    let range_start = range.map(|(start, _)| start);
+
+    let (body, fault) = {
+        let mut state = state.lock().unwrap();
+        state.hits.push(Hit {
+            path: path.clone(),
+            range_start,
+        });
+        let fault = state.faults.get_mut(&path).and_then(VecDeque::pop_front);
+        let body = if path == INDEX_PATH {
+            Some(state.index().to_string().into_bytes())
+        } else {
+            state.files.get(&path).cloned()
+        };
+        (body, fault)
+    };
+
+    let Some(mut body) = body else {
+        let _ = request.respond(Response::empty(404));
+        return;
+    };
+    let mut status = 200;
+    let mut headers = vec![header("Accept-Ranges", "bytes")];
+    if let Some((start, end)) = range {
+        let total = body.len();
+        let start = start as usize;
+        if start >= total {
+            let _ = request.respond(Response::empty(416));
+            return;
+        }
+        let end = end.map_or(total - 1, |end| (end as usize).min(total - 1));
+        body.truncate(end + 1);
+        body.drain(..start);
+        status = 206;
+        headers.push(header(
+            "Content-Range",
+            &format!("bytes {}-{}/{}", start, end, total),
+        ));
+    }
+
+    let mut sent = body.len();
+    match fault {
+        Some(Fault::Status(code)) => {
+            let _ = request.respond(Response::empty(code));
+            return;
+        }
+        Some(Fault::Stall(delay)) => thread::sleep(delay),
+        Some(Fault::Truncate(bytes)) => {
+            sent = bytes.min(sent);
+            headers.push(header("Connection", "close"));
+        }
+        Some(Fault::Corrupt) => {
+            if let Some(first) = body.first_mut() {
+                *first ^= 0xff;
+            }
+        }
+        None => {}
+    }
+    let length = body.len();
+    body.truncate(sent);
+    // Chunked encoding would end a truncated body cleanly.
+    let response = Response::new(
+        status.into(),
+        headers,
+        Cursor::new(body),
+        Some(length),
+        None,
+    )
+    .with_chunked_threshold(usize::MAX);
+    let _ = request.respond(response);
+}
+
+/// How the mock aria2 answers one `aria2.tellStatus` poll.
+#[derive(Clone, Copy, Debug)]
+pub enum Poll {
+    /// An RPC error, as from a daemon that timed out.
+    Fail,
+    /// The download is done.
+    Complete,
+}
+
+#[derive(Default)]
+struct Aria2State {
+    body: Vec<u8>,
+    polls: VecDeque<Poll>,
+    calls: Vec<(String, String)>,
+}
+
+/// `addUri` writes the whole body to the requested file at once; polls are
+/// answered from the queue, then fail.
+pub struct MockAria2 {
+    pub url: String,
+    server: Arc<Server>,
+    state: Arc<Mutex<Aria2State>>,
+}
+
+impl MockAria2 {
+    pub fn start(body: &[u8], polls: &[Poll]) -> Self {
+        let server = Arc::new(Server::http("127.0.0.1:0").expect("bind mock aria2"));
+        let url = format!("http://{}/jsonrpc", server.server_addr());
+        let state = Arc::new(Mutex::new(Aria2State {
+            body: body.to_vec(),
+            polls: polls.iter().copied().collect(),
+            calls: Vec::new(),
+        }));
+
+        let (listener, shared) = (server.clone(), state.clone());
+        thread::spawn(move || {
+            for request in listener.incoming_requests() {
+                respond_rpc(&shared, request);
+            }
+        });
+
+        Self { url, server, state }
+    }
+
+    /// Every RPC method called, with the download id it was about.
+    pub fn calls(&self) -> Vec<(String, String)> {
+        self.state.lock().unwrap().calls.clone()
+    }
+}
+
+impl Drop for MockAria2 {
+    fn drop(&mut self) {
+        self.server.unblock();
+    }
+}
+
+fn respond_rpc(state: &Mutex<Aria2State>, mut request: Request) {
+    let mut text = String::new();
+    let _ = request.as_reader().read_to_string(&mut text);
+    let call: Value = serde_json::from_str(&text).unwrap_or_default();
+    let method = call["method"].as_str().unwrap_or_default().to_string();
+    let params = &call["params"];
+
+    let mut state = state.lock().unwrap();
+    let (gid, reply) = match method.as_str() {
+        "aria2.addUri" => {
+            let gid = format!("gid{}", state.calls.len() + 1);
+            let path = Path::new(params[1]["dir"].as_str().unwrap_or_default())
+                .join(params[1]["out"].as_str().unwrap_or_default());
+            std::fs::write(path, &state.body).unwrap();
+            (gid.clone(), Ok(json!(gid)))
+        }
+        "aria2.tellStatus" => {
+            let length = state.body.len().to_string();
+            let reply = match state.polls.pop_front().unwrap_or(Poll::Fail) {
+                Poll::Fail => Err("timed out"),
+                Poll::Complete => Ok(json!({
+                    "status": "complete",
+                    "totalLength": length,
+                    "completedLength": length,
+                })),
+            };
+            (params[0].as_str().unwrap_or_default().to_string(), reply)
+        }
+        _ => {
+            let gid = params[0].as_str().unwrap_or_default().to_string();
+            (gid.clone(), Ok(json!(gid)))
+        }
+    };
+    state.calls.push((method, gid));
+    drop(state);
+
+    let body = match reply {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": call["id"], "result": result }),
+        Err(message) => {
+            json!({ "jsonrpc": "2.0", "id": call["id"], "error": { "code": 1, "message": message } })
+        }
+    };
+    let _ = request.respond(Response::from_string(body.to_string()));
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+pub fn md5_hex(data: &[u8]) -> String {
+    Md5::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    std::env::temp_dir().join(format!("wuwa-downloader-it-{name}-{nanos}"))
+}
+
+/// Deterministic, incompressible-looking file contents.
+pub fn body(len: usize, seed: u8) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+        .collect()
+}
+
+pub fn client() -> reqwest::Client {
+    build_client(&HttpOptions {
+        read_timeout: READ_TIMEOUT,
+        ..HttpOptions::default()
+    })
+    .unwrap()
+}
+
+/// Config that tries `cdns` in order of preference.
+pub fn config(cdns: &[&MockCdn]) -> Config {
+    Config {
+        index_url: cdns[0].index_url(),
+        zip_bases: cdns.iter().map(|cdn| cdn.url.clone()).collect(),
+    }
+}
+
+/// Fast retries and no progress bars.
+pub fn options() -> DownloadOptions {
+    DownloadOptions {
+        show_progress: false,
+        retry: RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+        },
+        ..DownloadOptions::default()
+    }
+}
+
+/// Downloads `resources` from `cdns`, in order of preference, into `folder`.
+pub async fn download(
+    cdns: &[&MockCdn],
+    resources: Vec<ResourceItem>,
+    folder: &Path,
+) -> PipelineResult {
+    download_with(cdns, resources, folder, options()).await
+}
+
+/// [`download`] with options other than the test defaults.
+pub async fn download_with(
+    cdns: &[&MockCdn],
+    resources: Vec<ResourceItem>,
+    folder: &Path,
+    options: DownloadOptions,
+) -> PipelineResult {
+    run_pipeline(
+        Arc::new(client()),
+        Arc::new(config(cdns)),
+        resources,
+        folder.to_path_buf(),
+        setup_logging(&folder.join("logs")).unwrap(),
+        Arc::new(AtomicBool::new(false)),
+        options,
+        EventSink::disabled(),
+        DownloadControl::default(),
+    )
+    .await
+}
@@ -0,0 +1,367 @@
+mod common;
+
+use common::{
+    Fault, Hit, MockAria2, MockCdn, Poll, body, client, config, download, download_with, options,
+    scratch_dir,
+};
+use std::fs;
+use std::sync::Arc;
+use wuwa_downloader::config::cfg::{Config, DownloadOptions, ResourceItem};
+use wuwa_downloader::download::{
+    events::Event,
+    session::{DownloadSession, SessionCommand, Setup, SetupCommand, SetupEvent},
+};
+use wuwa_downloader::io::{
+    logging::setup_logging, quarantine::QUARANTINE_DIR, util::parse_resources,
+};
+use wuwa_downloader::network::{aria2::Aria2Backend, client::fetch_index};
+
+const PAK: &str = "Client/Content/Paks/pakchunk0.pak";
+
+/// Fetches the CDN's index the way a session does.
+async fn resources(cdn: &MockCdn) -> Vec<ResourceItem> {
+    let config = Config {
+        index_url: cdn.index_url(),
+        zip_bases: vec![cdn.url.clone()],
+    };
+    let log_file = setup_logging(&scratch_dir("index-logs")).unwrap();
+    let index = fetch_index(&client(), &config, &log_file).await.unwrap();
+    parse_resources(&index).unwrap()
+}
+
+#[tokio::test]
+async fn downloads_every_file_in_the_index() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(200_000, 1))
+        .add_file("Client/Binaries/Client.exe", &body(1_000, 2));
+    let folder = scratch_dir("all");
+
+    let result = download(&[&cdn], resources(&cdn).await, &folder).await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (2, 0));
+    assert_eq!(fs::read(folder.join(PAK)).unwrap(), body(200_000, 1));
+    assert_eq!(
+        fs::read(folder.join("Client/Binaries/Client.exe")).unwrap(),
+        body(1_000, 2)
+    );
+}
+
+#[tokio::test]
+async fn retries_server_errors_on_the_same_cdn() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 3))
+        .fail(PAK, &[Fault::Status(500), Fault::Status(503)]);
+    let folder = scratch_dir("retry");
+
+    let result = download(&[&cdn], resources(&cdn).await, &folder).await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (1, 0));
+    assert_eq!(cdn.hits(PAK).len(), 3);
+}
+
+#[tokio::test]
+async fn fails_over_to_the_next_cdn() {
+    let broken = MockCdn::start();
+    let healthy = MockCdn::start();
+    healthy.add_file(PAK, &body(10_000, 4));
+    let folder = scratch_dir("failover");
+
+    let result = download(&[&broken, &healthy], resources(&healthy).await, &folder).await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (1, 0));
+    assert_eq!(broken.hits(PAK).len(), 1, "a 404 is not retried");
+    assert_eq!(healthy.hits(PAK).len(), 1);
+    let stats = result.completed[0].stats.as_ref().unwrap();
+    assert_eq!((stats.requests, stats.bytes), (2, 10_000));
+    assert!(healthy.url.ends_with(stats.cdn.as_deref().unwrap()));
+}
+
+#[tokio::test]
+async fn resumes_a_truncated_body_with_a_range_request() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(100_000, 5))
+        .fail(PAK, &[Fault::Truncate(40_000)]);
+    let folder = scratch_dir("resume");
+
+    let result = download(&[&cdn], resources(&cdn).await, &folder).await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (1, 0));
+    assert_eq!(fs::read(folder.join(PAK)).unwrap(), body(100_000, 5));
+    let hits = cdn.hits(PAK);
+    assert_eq!(hits.len(), 2);
+    assert!(
+        matches!(hits[1], Hit { range_start: Some(start), .. } if start > 0),
+        "second request should resume: {:?}",
+        hits
+    );
+}
+
+#[tokio::test]
+async fn retries_a_stalled_response_after_the_read_timeout() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 6))
+        .fail(PAK, &[Fault::Stall(common::READ_TIMEOUT * 4)]);
+    let folder = scratch_dir("stall");
+
+    let result = download(&[&cdn], resources(&cdn).await, &folder).await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (1, 0));
+    assert_eq!(cdn.hits(PAK).len(), 2);
+}
+
+#[tokio::test]
+async fn redownloads_a_file_with_the_wrong_hash() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 7))
+        .fail(PAK, &[Fault::Corrupt]);
+    let folder = scratch_dir("corrupt-once");
+
+    let result = download(&[&cdn], resources(&cdn).await, &folder).await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (1, 0));
+    assert_eq!(fs::read(folder.join(PAK)).unwrap(), body(10_000, 7));
+    assert!(folder.join(QUARANTINE_DIR).join(PAK).exists());
+}
+
+#[tokio::test]
+async fn gives_up_on_a_file_that_never_matches() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 8))
+        .fail(PAK, &[Fault::Corrupt; 8]);
+    let folder = scratch_dir("corrupt-always");
+
+    let result = download(&[&cdn], resources(&cdn).await, &folder).await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (0, 1));
+    assert!(!folder.join(PAK).exists());
+}
+
+#[tokio::test]
+async fn keeps_valid_files_without_downloading_them() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 9));
+    let folder = scratch_dir("existing");
+    fs::create_dir_all(folder.join(PAK).parent().unwrap()).unwrap();
+    fs::write(folder.join(PAK), body(10_000, 9)).unwrap();
+
+    let result = download(&[&cdn], resources(&cdn).await, &folder).await;
+
+    assert_eq!((result.verified_ok, result.downloaded_ok), (1, 0));
+    assert!(cdn.hits(PAK).is_empty());
+}
+
+#[tokio::test]
+async fn repairs_a_damaged_existing_file() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 10));
+    let folder = scratch_dir("damaged");
+    let mut damaged = body(10_000, 10);
+    damaged[5_000] ^= 0xff;
+    fs::create_dir_all(folder.join(PAK).parent().unwrap()).unwrap();
+    fs::write(folder.join(PAK), damaged).unwrap();
+
+    let result = download(&[&cdn], resources(&cdn).await, &folder).await;
+
+    assert_eq!((result.verified_ok, result.downloaded_ok), (0, 1));
+    assert_eq!(fs::read(folder.join(PAK)).unwrap(), body(10_000, 10));
+}
+
+#[tokio::test]
+async fn restarts_a_partial_whose_tail_differs_from_the_cdn() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(100_000, 14));
+    let folder = scratch_dir("verify-partials");
+    let mut partial = body(60_000, 14);
+    partial[59_000] ^= 0xff;
+    fs::create_dir_all(folder.join(PAK).parent().unwrap()).unwrap();
+    fs::write(folder.join(format!("{PAK}.part")), partial).unwrap();
+    let options = DownloadOptions {
+        verify_partials: Some(4_096),
+        ..options()
+    };
+
+    let result = download_with(&[&cdn], resources(&cdn).await, &folder, options).await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (1, 0));
+    assert_eq!(fs::read(folder.join(PAK)).unwrap(), body(100_000, 14));
+    let starts: Vec<_> = cdn.hits(PAK).iter().map(|hit| hit.range_start).collect();
+    assert_eq!(starts, [Some(60_000 - 4_096), None]);
+    assert!(!folder.join(QUARANTINE_DIR).join(PAK).exists());
+}
+
+#[tokio::test]
+async fn session_reports_events_on_its_channel() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 11));
+    let folder = scratch_dir("session");
+
+    let session = DownloadSession::start(
+        client(),
+        config(&[&cdn]),
+        resources(&cdn).await,
+        folder.clone(),
+        setup_logging(&folder.join("logs")).unwrap(),
+        options(),
+    );
+    let events = session.events();
+    let result = session.finish().await;
+
+    assert_eq!((result.downloaded_ok, result.failed), (1, 0));
+    let received: Vec<Event> = std::iter::from_fn(|| events.try_recv().ok()).collect();
+    assert!(received.contains(&Event::FileDone {
+        dest: PAK.to_string(),
+        outcome: "downloaded".to_string(),
+    }));
+    assert!(matches!(
+        received.last(),
+        Some(Event::Summary {
+            downloaded: 1,
+            interrupted: false,
+            ..
+        })
+    ));
+    assert!(events.is_closed());
+}
+
+#[tokio::test]
+async fn session_cancel_stops_before_downloading() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 12));
+    let folder = scratch_dir("session-cancel");
+
+    let session = DownloadSession::start(
+        client(),
+        config(&[&cdn]),
+        resources(&cdn).await,
+        folder.clone(),
+        setup_logging(&folder.join("logs")).unwrap(),
+        options(),
+    );
+    session.send(SessionCommand::Pause);
+    session.send(SessionCommand::Cancel);
+    let result = session.finish().await;
+
+    assert_eq!(result.downloaded_ok, 0);
+    assert!(!folder.join(PAK).exists());
+}
+
+#[tokio::test]
+async fn setup_lists_releases_and_starts_the_picked_one() {
+    let cdn = MockCdn::start();
+    cdn.add_file(PAK, &body(10_000, 13));
+    let meta = MockCdn::start();
+    let version = serde_json::json!({
+        "default": {
+            "config": { "baseUrl": "/", "indexFile": "index.json", "version": "2.0.0" },
+            "cdnList": [{ "url": cdn.url }],
+        },
+    });
+    let version_url = format!("{}/version.json", meta.url);
+    let channels = serde_json::json!({ "os": version_url, "cn": version_url });
+    let versions = serde_json::json!({ "live": channels, "beta": channels });
+    meta.add_file("version.json", version.to_string().as_bytes())
+        .add_file("versions.json", versions.to_string().as_bytes());
+    let folder = scratch_dir("setup");
+
+    let setup = Setup::start(
+        client(),
+        Some(format!("{}/versions.json", meta.url)),
+        setup_logging(&folder.join("logs")).unwrap(),
+        options(),
+    );
+    let events = setup.events();
+    setup.send(SetupCommand::ListReleases);
+    let Ok(SetupEvent::Releases(Ok(releases))) = events.recv().await else {
+        panic!("expected the release list");
+    };
+    assert_eq!(releases.len(), 4);
+    assert_eq!(releases[0].label, "Live - OS");
+    assert_eq!(releases[0].version, "2.0.0");
+
+    let not_a_folder = folder.join("logs").join("logs.log");
+    setup.send(SetupCommand::Start {
+        release: releases[0].clone(),
+        folder: not_a_folder,
+    });
+    assert!(matches!(
+        events.recv().await,
+        Ok(SetupEvent::Started(Err(_)))
+    ));
+
+    setup.send(SetupCommand::Start {
+        release: releases[0].clone(),
+        folder: folder.join("game"),
+    });
+    let Ok(SetupEvent::Started(Ok(session))) = events.recv().await else {
+        panic!("expected a started session");
+    };
+    let result = session.finish().await;
+    assert_eq!((result.downloaded_ok, result.failed), (1, 0));
+    assert_eq!(
+        fs::read(folder.join("game").join(PAK)).unwrap(),
+        body(10_000, 13)
+    );
+}
+
+/// Downloads one 1000-byte file through the mock aria2 at `aria2`.
+async fn download_with_aria2(aria2: &MockAria2, folder: &std::path::Path) -> (usize, usize) {
+    let cdn = MockCdn::start();
+    let item = ResourceItem {
+        dest: PAK.to_string(),
+        checksum: None,
+        size: Some(1_000),
+        chunks: Vec::new(),
+        unpacked_size: None,
+    };
+    let options = DownloadOptions {
+        backend: Arc::new(Aria2Backend {
+            rpc_url: aria2.url.clone(),
+            secret: None,
+        }),
+        ..options()
+    };
+    let result = download_with(&[&cdn], vec![item], folder, options).await;
+    (result.downloaded_ok, result.failed)
+}
+
+#[tokio::test]
+async fn aria2_rides_out_a_failed_status_poll() {
+    let aria2 = MockAria2::start(&body(1_000, 8), &[Poll::Fail, Poll::Complete]);
+    let folder = scratch_dir("aria2-blip");
+
+    assert_eq!(download_with_aria2(&aria2, &folder).await, (1, 0));
+    assert_eq!(fs::read(folder.join(PAK)).unwrap(), body(1_000, 8));
+    let calls = aria2.calls();
+    assert_eq!(
+        calls
+            .iter()
+            .filter(|(method, _)| method == "aria2.addUri")
+            .count(),
+        1
+    );
+    assert!(
+        !calls
+            .iter()
+            .any(|(method, _)| method == "aria2.forceRemove")
+    );
+}
+
+#[tokio::test]
+async fn aria2_removes_a_download_it_stopped_polling() {
+    let aria2 = MockAria2::start(&body(1_000, 9), &[]);
+    let folder = scratch_dir("aria2-unreachable");
+
+    assert_eq!(download_with_aria2(&aria2, &folder).await, (0, 1));
+    let mut active: Option<String> = None;
+    for (method, gid) in aria2.calls() {
+        match method.as_str() {
+            "aria2.addUri" => {
+                assert_eq!(active, None, "{gid} added while another is active");
+                active = Some(gid);
+            }
+            "aria2.forceRemove" => assert_eq!(active.take(), Some(gid)),
+            _ => {}
+        }
+    }
+    assert_eq!(active, None, "left running in aria2");
+}